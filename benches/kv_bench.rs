@@ -1,8 +1,11 @@
 use std::path::PathBuf;
 
-use bitcask::{db::Engine, option::Options};
+use bitcask::{
+    db::Engine,
+    option::{Checksum, Options},
+};
 use bytes::Bytes;
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 use rand::Rng;
 
 #[allow(dead_code)]
@@ -35,6 +38,95 @@ fn benchmark_put(c: &mut Criterion) {
     });
 }
 
+fn benchmark_small_put(c: &mut Criterion) {
+    let value = Bytes::from("bitcask-rs-small-value");
+
+    // 分别测试不使用写缓冲和使用 64KB 写缓冲时小数据的写入性能
+    for write_buffer_size in [0, 64 * 1024] {
+        let mut options = Options::default();
+        options.dir_path = PathBuf::from("/tmp/bitcask-rs-bench-small-put");
+        options.write_buffer_size = write_buffer_size;
+        let engine = Engine::open(options.clone()).unwrap();
+
+        let mut rnd: rand::rngs::ThreadRng = rand::thread_rng();
+
+        let name = format!("bitcask-small-put-bench-buffer-{}", write_buffer_size);
+        c.bench_function(&name, |b| {
+            b.iter(|| {
+                let i = rnd.gen_range(0..std::u32::MAX);
+                let res = engine.put(get_test_key(i), value.clone());
+                assert!(res.is_ok());
+            })
+        });
+
+        std::mem::drop(engine);
+        std::fs::remove_dir_all(options.dir_path).unwrap();
+    }
+}
+
+fn benchmark_large_put(c: &mut Criterion) {
+    let value = Bytes::from(vec![b'v'; 64 * 1024]);
+
+    // 分别测试使用 crc32、xxhash64 校验以及关闭校验时较大数据的写入性能
+    for checksum in [Checksum::Crc32, Checksum::XxHash64, Checksum::None] {
+        let mut options = Options::default();
+        options.dir_path = PathBuf::from("/tmp/bitcask-rs-bench-large-put");
+        options.checksum = checksum;
+        options.checksum_enabled = checksum != Checksum::None;
+        let engine = Engine::open(options.clone()).unwrap();
+
+        let mut rnd: rand::rngs::ThreadRng = rand::thread_rng();
+
+        let name = format!("bitcask-large-put-bench-{:?}", checksum);
+        c.bench_function(&name, |b| {
+            b.iter(|| {
+                let i = rnd.gen_range(0..std::u32::MAX);
+                let res = engine.put(get_test_key(i), value.clone());
+                assert!(res.is_ok());
+            })
+        });
+
+        std::mem::drop(engine);
+        std::fs::remove_dir_all(options.dir_path).unwrap();
+    }
+}
+
+fn benchmark_put_batch_sync(c: &mut Criterion) {
+    // 开启 sync_writes 时，对比循环调用 put 和使用 put_batch_sync 写入 100 条数据的性能
+    let mut options = Options::default();
+    options.dir_path = PathBuf::from("/tmp/bitcask-rs-bench-put-batch-sync");
+    options.sync_writes = true;
+    let engine = Engine::open(options.clone()).unwrap();
+
+    let mut rnd: rand::rngs::ThreadRng = rand::thread_rng();
+
+    c.bench_function("bitcask-sync-put-loop-100-bench", |b| {
+        b.iter(|| {
+            for _ in 0..100 {
+                let i = rnd.gen_range(0..std::u32::MAX);
+                let res = engine.put(get_test_key(i), get_test_value(i));
+                assert!(res.is_ok());
+            }
+        })
+    });
+
+    c.bench_function("bitcask-put-batch-sync-100-bench", |b| {
+        b.iter(|| {
+            let pairs: Vec<_> = (0..100)
+                .map(|_| {
+                    let i = rnd.gen_range(0..std::u32::MAX);
+                    (get_test_key(i), get_test_value(i))
+                })
+                .collect();
+            let res = engine.put_batch_sync(&pairs);
+            assert!(res.is_ok());
+        })
+    });
+
+    std::mem::drop(engine);
+    std::fs::remove_dir_all(options.dir_path).unwrap();
+}
+
 fn benchmark_get(c: &mut Criterion) {
     // 打开存储引擎
     let mut options = Options::default();
@@ -56,6 +148,79 @@ fn benchmark_get(c: &mut Criterion) {
     });
 }
 
+fn benchmark_cached_get(c: &mut Criterion) {
+    // 分别测试不使用缓存和使用 64MB 缓存时重复读取同一个 key 的性能
+    for value_cache_size in [0, 64 * 1024 * 1024] {
+        let mut options = Options::default();
+        options.dir_path = PathBuf::from("/tmp/bitcask-rs-bench-cached-get");
+        options.value_cache_size = value_cache_size;
+        let engine = Engine::open(options.clone()).unwrap();
+
+        let res = engine.put(get_test_key(1), get_test_value(1));
+        assert!(res.is_ok());
+
+        let name = format!("bitcask-cached-get-bench-cache-{}", value_cache_size);
+        c.bench_function(&name, |b| {
+            b.iter(|| {
+                let res = engine.get(get_test_key(1));
+                assert!(res.is_ok());
+            })
+        });
+
+        std::mem::drop(engine);
+        std::fs::remove_dir_all(options.dir_path).unwrap();
+    }
+}
+
+fn benchmark_concurrent_get(c: &mut Criterion) {
+    // 打开存储引擎，数据分布在多个数据文件中
+    let mut options = Options::default();
+    options.dir_path = PathBuf::from("/tmp/bitcask-rs-bench-concurrent-get");
+    options.data_file_size = 16 * 1024 * 1024;
+    let engine = Engine::open(options.clone()).unwrap();
+
+    let key_count = 50000;
+    for i in 0..key_count {
+        let res = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res.is_ok());
+    }
+
+    // 分别使用不同数量的线程并发读取，每个线程读取 iters 次，吞吐量随着线程数增长
+    let max_threads = std::thread::available_parallelism().map_or(4, |n| n.get());
+    let mut group = c.benchmark_group("bitcask-concurrent-get-bench");
+    let mut threads = 1;
+    while threads <= max_threads {
+        group.throughput(Throughput::Elements(threads as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    let start = std::time::Instant::now();
+                    std::thread::scope(|s| {
+                        for _ in 0..threads {
+                            s.spawn(|| {
+                                let mut rnd = rand::thread_rng();
+                                for _ in 0..iters {
+                                    let i = rnd.gen_range(0..key_count);
+                                    let res = engine.get(get_test_key(i));
+                                    assert!(res.is_ok());
+                                }
+                            });
+                        }
+                    });
+                    start.elapsed()
+                })
+            },
+        );
+        threads *= 2;
+    }
+    group.finish();
+
+    std::mem::drop(engine);
+    std::fs::remove_dir_all(options.dir_path).unwrap();
+}
+
 fn benchmark_delete(c: &mut Criterion) {
     // 打开存储引擎
     let mut options = Options::default();
@@ -78,5 +243,15 @@ fn benchmark_delete(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, benchmark_put, benchmark_get, benchmark_delete);
+criterion_group!(
+    benches,
+    benchmark_put,
+    benchmark_small_put,
+    benchmark_large_put,
+    benchmark_put_batch_sync,
+    benchmark_get,
+    benchmark_cached_get,
+    benchmark_concurrent_get,
+    benchmark_delete
+);
 criterion_main!(benches);
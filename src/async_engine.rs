@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+
+use crate::{db::Engine, error::Result, option::Options};
+
+/// 异步的存储引擎接口，阻塞的读写操作放到 tokio 的阻塞线程池中执行
+/// 克隆之后共享同一个存储引擎实例，可以在多个任务中同时使用
+#[derive(Clone)]
+pub struct AsyncEngine {
+    inner: Arc<Engine>,
+}
+
+impl AsyncEngine {
+    /// 使用已经打开的存储引擎实例构造异步接口
+    pub fn new(engine: Engine) -> Self {
+        AsyncEngine {
+            inner: Arc::new(engine),
+        }
+    }
+
+    /// 打开 bitcask 存储引擎实例
+    pub async fn open(opts: Options) -> Result<Self> {
+        let engine = spawn_blocking(move || Engine::open(opts)).await?;
+        Ok(Self::new(engine))
+    }
+
+    /// 获取内部的存储引擎实例，用于调用其他同步的接口
+    pub fn engine(&self) -> &Engine {
+        &self.inner
+    }
+
+    /// 存储 key/value 数据，key 不能为空，返回分配的写入序列号
+    pub async fn put(&self, key: Bytes, value: Bytes) -> Result<u64> {
+        let engine = self.inner.clone();
+        spawn_blocking(move || engine.put(key, value)).await
+    }
+
+    /// 根据 key 获取对应的数据
+    pub async fn get(&self, key: Bytes) -> Result<Bytes> {
+        let engine = self.inner.clone();
+        spawn_blocking(move || engine.get(key)).await
+    }
+
+    /// 根据 key 删除对应的数据，返回删除之前的 value，key 不存在时返回 None
+    pub async fn delete(&self, key: Bytes) -> Result<Option<Bytes>> {
+        let engine = self.inner.clone();
+        spawn_blocking(move || engine.delete(key)).await
+    }
+
+    /// merge 数据目录，处理无效数据，并生成 hint 索引文件
+    pub async fn merge(&self) -> Result<()> {
+        let engine = self.inner.clone();
+        spawn_blocking(move || engine.merge()).await
+    }
+}
+
+// 在阻塞线程池中执行 f，f 中发生 panic 时在当前任务中继续 panic
+async fn spawn_blocking<F, R>(f: F) -> Result<R>
+where
+    F: FnOnce() -> Result<R> + Send + 'static,
+    R: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(res) => res,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{
+        error::Errors,
+        merge::get_merge_path,
+        util::rand_kv::{get_test_key, get_test_value},
+    };
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_async_engine() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-async-engine");
+        opts.data_file_size = 64 * 1024 * 1024;
+        opts.data_file_merge_ratio = 0 as f32;
+        let engine = AsyncEngine::open(opts.clone())
+            .await
+            .expect("failed to open engine");
+
+        // 在多个任务中同时写入
+        let mut handles = Vec::new();
+        for t in 0..4 {
+            let engine = engine.clone();
+            handles.push(tokio::spawn(async move {
+                for i in t * 100..(t + 1) * 100 {
+                    let res = engine.put(get_test_key(i), get_test_value(i)).await;
+                    assert!(res.is_ok());
+                }
+            }));
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(400, engine.engine().list_keys().unwrap().len());
+
+        let res1 = engine.get(get_test_key(10)).await;
+        assert_eq!(get_test_value(10), res1.unwrap());
+        let res2 = engine.delete(get_test_key(10)).await;
+        assert_eq!(Some(get_test_value(10)), res2.unwrap());
+        let res3 = engine.get(get_test_key(10)).await;
+        assert_eq!(Errors::KeyNotFound, res3.err().unwrap());
+        let res4 = engine.get(Bytes::new()).await;
+        assert_eq!(Errors::KeyIsEmpty, res4.err().unwrap());
+        let res5 = engine.merge().await;
+        assert!(res5.is_ok());
+
+        // 删除测试的文件夹
+        std::mem::drop(engine);
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+        std::fs::remove_dir_all(get_merge_path(opts.dir_path)).expect("failed to remove path");
+    }
+}
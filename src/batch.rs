@@ -1,6 +1,9 @@
 use std::{
     collections::HashMap,
-    sync::{atomic::Ordering, Arc},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
 };
 
 use bytes::{BufMut, Bytes, BytesMut};
@@ -11,7 +14,8 @@ use crate::{
     data::log_record::{LogRecord, LogRecordType},
     db::Engine,
     error::{Errors, Result},
-    option::WriteBatchOptions,
+    index::prefix_upper_bound,
+    option::{Compression, IteratorOptions, WriteBatchOptions},
 };
 
 const TXN_FIN_KEY: &[u8] = "txn-fin".as_bytes();
@@ -19,23 +23,86 @@ const TXN_FIN_KEY: &[u8] = "txn-fin".as_bytes();
 pub(crate) const NON_TRANSACTION_SEQ_NO: usize = 0;
 
 pub struct WriteBatch<'a> {
-    pending_writes: Arc<Mutex<HashMap<Vec<u8>, LogRecord>>>, // 暂存用户写入的数据
+    pending_writes: Arc<Mutex<HashMap<Vec<u8>, PendingWrite>>>, // 暂存用户写入的数据
+    next_order: AtomicUsize,                                    // 下一次写入的顺序
     engine: &'a Engine,
     options: WriteBatchOptions,
 }
 
+// 暂存的写入操作，同一个 key 只保留最后一次操作
+struct PendingWrite {
+    order: usize, // 最后一次写入的顺序
+    record: LogRecord,
+}
+
+/// 提交时每个暂存操作的结果，用于记录哪些操作实际改变了数据
+#[derive(Debug, Clone, PartialEq)]
+pub enum OpOutcome {
+    // 写入 key，overwritten 表示提交前 key 是否已经存在
+    Put { key: Bytes, overwritten: bool },
+    // 删除 key，found 表示提交前 key 是否存在
+    Delete { key: Bytes, found: bool },
+}
+
 impl Engine {
     // 初始化 WriteBatch
     pub fn new_write_batch(&self, options: WriteBatchOptions) -> Result<WriteBatch> {
+        self.check_writable()?;
+        self.wait_index_ready()?;
         if !self.seq_file_exists && !self.is_initial {
             return Err(Errors::UnableToUseWriteBatch);
         }
         Ok(WriteBatch {
             pending_writes: Arc::new(Mutex::new(HashMap::new())),
+            next_order: AtomicUsize::new(0),
             engine: self,
             options,
         })
     }
+
+    /// 删除所有以 prefix 开头的 key，返回删除的 key 的数量
+    /// 所有的删除标记在同一个批次中原子提交，遍历开始之后新写入的 key 不会被删除
+    pub fn delete_prefix(&self, prefix: &[u8]) -> Result<usize> {
+        self.check_writable()?;
+        self.wait_index_ready()?;
+        // 避免误删除所有的数据
+        if prefix.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+
+        // 从索引中取出匹配的 key
+        let options = IteratorOptions {
+            prefix: prefix.to_vec(),
+            start: Some(prefix.to_vec()),
+            end: prefix_upper_bound(prefix),
+            ..Default::default()
+        };
+        let mut keys = Vec::new();
+        let mut index_iter = self.index.iterator(options);
+        while let Some((key, _)) = index_iter.next() {
+            keys.push(key.clone());
+        }
+        if keys.is_empty() {
+            return Ok(0);
+        }
+
+        // 删除操作不依赖事务序列号文件，直接构造 WriteBatch，匹配的 key 全部放在同一个批次中
+        let wb = WriteBatch {
+            pending_writes: Arc::new(Mutex::new(HashMap::new())),
+            next_order: AtomicUsize::new(0),
+            engine: self,
+            options: WriteBatchOptions {
+                max_batch_num: keys.len(),
+                sync_writes: self.options.sync_writes,
+            },
+        };
+        for key in keys {
+            wb.delete(Bytes::from(key))?;
+        }
+        let count = wb.len();
+        wb.commit()?;
+        Ok(count)
+    }
 }
 
 impl WriteBatch<'_> {
@@ -43,15 +110,22 @@ impl WriteBatch<'_> {
         if key.is_empty() {
             return Err(Errors::KeyIsEmpty);
         }
+        self.engine.check_key_value_size(&key, &value)?;
 
         let record = LogRecord {
             key: key.to_vec(),
             value: value.to_vec(),
             rec_type: LogRecordType::NORMAL,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
         };
 
         let mut pending_writes = self.pending_writes.lock();
-        pending_writes.insert(key.to_vec(), record);
+        pending_writes.insert(key.to_vec(), self.pending_write(record));
         Ok(())
     }
 
@@ -73,15 +147,79 @@ impl WriteBatch<'_> {
             key: key.to_vec(),
             value: Default::default(),
             rec_type: LogRecordType::DELETED,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
         };
-        pending_writes.insert(key.to_vec(), record);
+        pending_writes.insert(key.to_vec(), self.pending_write(record));
         Ok(())
     }
 
+    /// 暂存的写入操作的数量，同一个 key 的多次操作只计算一次
+    pub fn len(&self) -> usize {
+        self.pending_writes.lock().len()
+    }
+
+    /// 是否没有暂存的写入操作
+    pub fn is_empty(&self) -> bool {
+        self.pending_writes.lock().is_empty()
+    }
+
+    /// 按照写入的顺序遍历暂存的操作，删除操作的 value 为 None
+    /// 同一个 key 只保留最后一次操作，位于最后一次操作的位置；删除不存在的 key 会撤销之前暂存的写入
+    pub fn iter(&self) -> impl Iterator<Item = (Bytes, Option<Bytes>)> {
+        let pending_writes = self.pending_writes.lock();
+        let mut writes: Vec<&PendingWrite> = pending_writes.values().collect();
+        writes.sort_by_key(|write| write.order);
+        let items: Vec<(Bytes, Option<Bytes>)> = writes
+            .into_iter()
+            .map(|write| {
+                let record = &write.record;
+                let value = match record.rec_type {
+                    LogRecordType::DELETED => None,
+                    _ => Some(Bytes::copy_from_slice(&record.value)),
+                };
+                (Bytes::copy_from_slice(&record.key), value)
+            })
+            .collect();
+        items.into_iter()
+    }
+
+    /// 清空暂存的写入操作，保留已经分配的内存，可以继续用于下一个批次
+    pub fn clear(&mut self) {
+        self.pending_writes.lock().clear();
+        *self.next_order.get_mut() = 0;
+    }
+
+    /// 放弃暂存的写入操作，暂存的数据只在 commit 时写入数据文件，没有提交的批次不会留下任何数据
+    /// 直接 drop 没有提交的批次效果相同
+    pub fn rollback(mut self) {
+        self.clear();
+    }
+
+    // 记录写入的顺序，调用方需要持有 pending_writes 的锁
+    fn pending_write(&self, record: LogRecord) -> PendingWrite {
+        PendingWrite {
+            order: self.next_order.fetch_add(1, Ordering::SeqCst),
+            record,
+        }
+    }
+
+    /// 提交暂存的数据，put 和 delete 只暂存在内存中，提交之前不会写入数据文件，也不会更新索引
     pub fn commit(&self) -> Result<()> {
+        self.commit_with_outcomes()?;
+        Ok(())
+    }
+
+    /// 提交暂存的数据，并按照写入的顺序返回每个操作的结果，顺序和 iter 一致
+    /// 提交时根据内存索引判断 key 之前是否存在，已经过期但还没有被清理的 key 视为存在
+    pub fn commit_with_outcomes(&self) -> Result<Vec<OpOutcome>> {
         let mut pending_writes = self.pending_writes.lock();
         if pending_writes.len() == 0 {
-            return Ok(());
+            return Ok(Vec::new());
         }
         if pending_writes.len() > self.options.max_batch_num {
             return Err(Errors::ExceedMaxBatchNum);
@@ -89,16 +227,25 @@ impl WriteBatch<'_> {
 
         // 加锁保证事务提交串行化
         let _lock = self.engine.batch_commit_lock.lock();
+        let _write_lock = self.engine.write_lock.read();
 
         let seq_no = self.engine.seq_no.fetch_add(1, Ordering::SeqCst);
 
         let mut positions = HashMap::new();
         // 开始写数据到数据文件当中
-        for (_, item) in pending_writes.iter() {
+        for item in pending_writes.values().map(|write| &write.record) {
+            let (value, compression) = self.engine.compress_value(&item.value)?;
+            let (value, encrypted) = self.engine.encrypt_value(&item.key, value)?;
             let mut record = LogRecord {
                 key: log_record_key_with_seq(item.key.clone(), seq_no),
-                value: item.value.clone(),
+                value,
                 rec_type: item.rec_type,
+                expire: item.expire,
+                compression,
+                encrypted,
+                sequence: 0,
+                timestamp: 0,
+                keyspace: 0,
             };
 
             let pos = self.engine.append_log_record(&mut record)?;
@@ -110,36 +257,63 @@ impl WriteBatch<'_> {
             key: log_record_key_with_seq(TXN_FIN_KEY.to_vec(), seq_no),
             value: Default::default(),
             rec_type: LogRecordType::TXNFINISHED,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
         };
-        self.engine.append_log_record(&mut finish_record);
+        self.engine.append_log_record(&mut finish_record)?;
 
         if self.options.sync_writes {
-            self.engine.sync();
+            self.engine.sync()?;
         }
 
-        // 数据全部写完之后更新内存索引
-        for (_, item) in pending_writes.iter() {
+        // 数据全部写完之后按照写入的顺序更新内存索引
+        let mut writes: Vec<&PendingWrite> = pending_writes.values().collect();
+        writes.sort_by_key(|write| write.order);
+        let mut outcomes = Vec::with_capacity(writes.len());
+        for item in writes.into_iter().map(|write| &write.record) {
             if item.rec_type == LogRecordType::NORMAL {
                 let record_pos = positions.get(&item.key).unwrap();
-                if let Some(old_pos) = self.engine.index.put(item.key.clone(), *record_pos) {
+                let old_pos = self.engine.index_put(item.key.clone(), *record_pos);
+                if let Some(old_pos) = old_pos {
                     self.engine
                         .reclaim_size
                         .fetch_add(old_pos.size as usize, Ordering::SeqCst);
                 }
+                self.engine.invalidate_cache(&item.key);
+                self.engine.set_tombstone(&item.key, None);
+                self.engine.update_prefix_index(&item.key, false);
+                outcomes.push(OpOutcome::Put {
+                    key: Bytes::copy_from_slice(&item.key),
+                    overwritten: old_pos.is_some(),
+                });
             }
             if item.rec_type == LogRecordType::DELETED {
-                if let Some(old_pos) = self.engine.index.delete(item.key.clone()) {
+                let record_pos = positions.get(&item.key).unwrap();
+                let old_pos = self.engine.index_delete(item.key.clone());
+                if let Some(old_pos) = old_pos {
                     self.engine
                         .reclaim_size
                         .fetch_add(old_pos.size as usize, Ordering::SeqCst);
                 }
+                self.engine.invalidate_cache(&item.key);
+                self.engine
+                    .set_tombstone(&item.key, Some(record_pos.file_id));
+                self.engine.update_prefix_index(&item.key, true);
+                outcomes.push(OpOutcome::Delete {
+                    key: Bytes::copy_from_slice(&item.key),
+                    found: old_pos.is_some(),
+                });
             }
         }
 
         // 清空暂存数据
         pending_writes.clear();
 
-        Ok(())
+        Ok(outcomes)
     }
 }
 
@@ -241,7 +415,6 @@ mod tests {
 
         // 重启之后进行校验
         engine.close().expect("failed to close");
-        std::mem::drop(engine);
 
         let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
         let keys = engine2.list_keys();
@@ -276,4 +449,349 @@ mod tests {
 
     //     // wb.commit();
     // }
+
+    #[test]
+    fn test_write_batch_crash_recovery() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-batch-crash");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        let put_res1 = engine.put(
+            util::rand_kv::get_test_key(1),
+            util::rand_kv::get_test_value(1),
+        );
+        assert!(put_res1.is_ok());
+
+        let wb = engine
+            .new_write_batch(WriteBatchOptions::default())
+            .expect("failed to create write batch");
+        // 空的批次提交直接返回
+        assert!(wb.commit().is_ok());
+
+        let put_res2 = wb.put(
+            util::rand_kv::get_test_key(2),
+            util::rand_kv::get_test_value(2),
+        );
+        assert!(put_res2.is_ok());
+        let put_res3 = wb.put(
+            util::rand_kv::get_test_key(3),
+            util::rand_kv::get_test_value(3),
+        );
+        assert!(put_res3.is_ok());
+        let del_res = wb.delete(util::rand_kv::get_test_key(1));
+        assert!(del_res.is_ok());
+
+        let seq_no = engine.seq_no.load(Ordering::SeqCst);
+        let commit_res = wb.commit();
+        assert!(commit_res.is_ok());
+        assert!(engine.get(util::rand_kv::get_test_key(2)).is_ok());
+        engine.close().expect("failed to close");
+
+        // 截断数据文件中标识事务完成的记录，模拟提交过程中崩溃
+        let finish_record = LogRecord {
+            key: log_record_key_with_seq(TXN_FIN_KEY.to_vec(), seq_no),
+            value: Default::default(),
+            rec_type: LogRecordType::TXNFINISHED,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
+        };
+        let data_file = opts.dir_path.join("000000000.data");
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(data_file)
+            .unwrap();
+        let file_size = file.metadata().unwrap().len();
+        file.set_len(file_size - finish_record.encode().len() as u64)
+            .unwrap();
+        std::mem::drop(file);
+
+        // 重启之后批次中的数据都不可见
+        let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+        let keys = engine2.list_keys().unwrap();
+        assert_eq!(1, keys.len());
+        let get_res1 = engine2.get(util::rand_kv::get_test_key(1));
+        assert_eq!(util::rand_kv::get_test_value(1), get_res1.unwrap());
+        let get_res2 = engine2.get(util::rand_kv::get_test_key(2));
+        assert_eq!(Errors::KeyNotFound, get_res2.err().unwrap());
+        let get_res3 = engine2.get(util::rand_kv::get_test_key(3));
+        assert_eq!(Errors::KeyNotFound, get_res3.err().unwrap());
+        std::mem::drop(engine2);
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+
+    #[test]
+    fn test_write_batch_exceed_max_batch_num() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-batch-max-num");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        let mut wb_opts = WriteBatchOptions::default();
+        wb_opts.max_batch_num = 10;
+        let wb = engine
+            .new_write_batch(wb_opts)
+            .expect("failed to create write batch");
+        for i in 0..11 {
+            let put_res = wb.put(
+                util::rand_kv::get_test_key(i),
+                util::rand_kv::get_test_value(i),
+            );
+            assert!(put_res.is_ok());
+        }
+        let commit_res = wb.commit();
+        assert_eq!(Errors::ExceedMaxBatchNum, commit_res.err().unwrap());
+        assert_eq!(0, engine.list_keys().unwrap().len());
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+
+    #[test]
+    fn test_delete_prefix() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-delete-prefix");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for i in 0..20 {
+            let put_res1 = engine.put(
+                Bytes::from(format!("tenant:1:{}", i)),
+                util::rand_kv::get_test_value(i),
+            );
+            assert!(put_res1.is_ok());
+            let put_res2 = engine.put(
+                Bytes::from(format!("tenant:2:{}", i)),
+                util::rand_kv::get_test_value(i),
+            );
+            assert!(put_res2.is_ok());
+        }
+
+        // 1.前缀为空
+        let del_res1 = engine.delete_prefix(b"");
+        assert_eq!(Errors::KeyIsEmpty, del_res1.err().unwrap());
+
+        // 2.删除一个前缀下的所有 key，使用一个事务提交
+        let seq_no = engine.seq_no.load(Ordering::SeqCst);
+        let del_res2 = engine.delete_prefix(b"tenant:1:");
+        assert_eq!(20, del_res2.unwrap());
+        assert_eq!(seq_no + 1, engine.seq_no.load(Ordering::SeqCst));
+        assert_eq!(0, engine.count_prefix(b"tenant:1:").unwrap());
+        assert_eq!(20, engine.count_prefix(b"tenant:2:").unwrap());
+
+        // 3.没有匹配的 key
+        let del_res3 = engine.delete_prefix(b"tenant:3:");
+        assert_eq!(0, del_res3.unwrap());
+
+        // 重启之后删除的数据仍然不可见，并且可以继续删除
+        engine.close().expect("failed to close");
+        let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(20, engine2.list_keys().unwrap().len());
+        let get_res1 = engine2.get(Bytes::from("tenant:1:0"));
+        assert_eq!(Errors::KeyNotFound, get_res1.err().unwrap());
+        let del_res4 = engine2.delete_prefix(b"tenant:");
+        assert_eq!(20, del_res4.unwrap());
+        assert_eq!(0, engine2.list_keys().unwrap().len());
+        std::mem::drop(engine2);
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+
+    #[test]
+    fn test_write_batch_iter() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-batch-iter");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let put_res1 = engine.put(Bytes::from("a"), Bytes::from("value-a"));
+        assert!(put_res1.is_ok());
+
+        let wb = engine
+            .new_write_batch(WriteBatchOptions::default())
+            .expect("failed to create write batch");
+        assert!(wb.is_empty());
+        assert_eq!(0, wb.len());
+        assert_eq!(0, wb.iter().count());
+
+        // 同一个 key 的多次操作只保留最后一次，并且位于最后一次操作的位置
+        let res1 = wb.put(Bytes::from("c"), Bytes::from("value-c"));
+        assert!(res1.is_ok());
+        let res2 = wb.put(Bytes::from("a"), Bytes::from("value-a1"));
+        assert!(res2.is_ok());
+        let res3 = wb.put(Bytes::from("b"), Bytes::from("value-b"));
+        assert!(res3.is_ok());
+        let res4 = wb.delete(Bytes::from("a"));
+        assert!(res4.is_ok());
+        let res5 = wb.put(Bytes::from("c"), Bytes::from("value-c1"));
+        assert!(res5.is_ok());
+        assert!(!wb.is_empty());
+        assert_eq!(3, wb.len());
+        let items: Vec<_> = wb.iter().collect();
+        assert_eq!(
+            vec![
+                (Bytes::from("b"), Some(Bytes::from("value-b"))),
+                (Bytes::from("a"), None),
+                (Bytes::from("c"), Some(Bytes::from("value-c1"))),
+            ],
+            items
+        );
+
+        // 删除不存在的 key 撤销之前暂存的写入
+        let res6 = wb.delete(Bytes::from("b"));
+        assert!(res6.is_ok());
+        assert_eq!(2, wb.len());
+        let keys: Vec<_> = wb.iter().map(|(key, _)| key).collect();
+        assert_eq!(vec![Bytes::from("a"), Bytes::from("c")], keys);
+
+        // 遍历不会消耗暂存的数据，提交之后清空
+        let commit_res = wb.commit();
+        assert!(commit_res.is_ok());
+        assert!(wb.is_empty());
+        let get_res1 = engine.get(Bytes::from("a"));
+        assert_eq!(Errors::KeyNotFound, get_res1.err().unwrap());
+        let get_res2 = engine.get(Bytes::from("c"));
+        assert_eq!(Bytes::from("value-c1"), get_res2.unwrap());
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+
+    #[test]
+    fn test_write_batch_commit_with_outcomes() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-batch-outcomes");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let put_res1 = engine.put(Bytes::from("a"), Bytes::from("value-a"));
+        assert!(put_res1.is_ok());
+        let put_res2 = engine.put(Bytes::from("b"), Bytes::from("value-b"));
+        assert!(put_res2.is_ok());
+
+        let wb = engine
+            .new_write_batch(WriteBatchOptions::default())
+            .expect("failed to create write batch");
+        assert!(wb.commit_with_outcomes().unwrap().is_empty());
+
+        let res1 = wb.put(Bytes::from("c"), Bytes::from("value-c"));
+        assert!(res1.is_ok());
+        let res2 = wb.put(Bytes::from("a"), Bytes::from("value-a1"));
+        assert!(res2.is_ok());
+        let res3 = wb.delete(Bytes::from("a"));
+        assert!(res3.is_ok());
+        let res4 = wb.delete(Bytes::from("b"));
+        assert!(res4.is_ok());
+        // 暂存之后、提交之前 key 被删除，提交时删除操作找不到 key
+        let del_res = engine.delete(Bytes::from("b"));
+        assert!(del_res.is_ok());
+
+        let outcomes = wb.commit_with_outcomes().unwrap();
+        assert_eq!(
+            vec![
+                OpOutcome::Put {
+                    key: Bytes::from("c"),
+                    overwritten: false,
+                },
+                OpOutcome::Delete {
+                    key: Bytes::from("a"),
+                    found: true,
+                },
+                OpOutcome::Delete {
+                    key: Bytes::from("b"),
+                    found: false,
+                },
+            ],
+            outcomes
+        );
+
+        // 覆盖已经存在的 key
+        let res5 = wb.put(Bytes::from("c"), Bytes::from("value-c1"));
+        assert!(res5.is_ok());
+        let outcomes = wb.commit_with_outcomes().unwrap();
+        assert_eq!(
+            vec![OpOutcome::Put {
+                key: Bytes::from("c"),
+                overwritten: true,
+            }],
+            outcomes
+        );
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+
+    #[test]
+    fn test_write_batch_rollback() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-batch-rollback");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let put_res1 = engine.put(Bytes::from("a"), Bytes::from("value-a"));
+        assert!(put_res1.is_ok());
+        let write_off = engine.active_file.read().get_write_off();
+
+        // 没有提交的批次直接 drop，暂存的操作不会生效，也不会写入数据文件
+        let wb1 = engine
+            .new_write_batch(WriteBatchOptions::default())
+            .expect("failed to create write batch");
+        assert!(wb1.put(Bytes::from("b"), Bytes::from("value-b")).is_ok());
+        assert!(wb1.delete(Bytes::from("a")).is_ok());
+        std::mem::drop(wb1);
+        assert_eq!(
+            Bytes::from("value-a"),
+            engine.get(Bytes::from("a")).unwrap()
+        );
+        assert_eq!(
+            Errors::KeyNotFound,
+            engine.get(Bytes::from("b")).err().unwrap()
+        );
+
+        // rollback 和 drop 的效果相同
+        let wb2 = engine
+            .new_write_batch(WriteBatchOptions::default())
+            .expect("failed to create write batch");
+        assert!(wb2.put(Bytes::from("c"), Bytes::from("value-c")).is_ok());
+        wb2.rollback();
+        assert_eq!(
+            Errors::KeyNotFound,
+            engine.get(Bytes::from("c")).err().unwrap()
+        );
+        assert_eq!(write_off, engine.active_file.read().get_write_off());
+
+        // 清空之后继续使用同一个批次，只提交清空之后的操作
+        let mut wb3 = engine
+            .new_write_batch(WriteBatchOptions::default())
+            .expect("failed to create write batch");
+        assert!(wb3.put(Bytes::from("d"), Bytes::from("value-d")).is_ok());
+        wb3.clear();
+        assert!(wb3.is_empty());
+        assert!(wb3.put(Bytes::from("e"), Bytes::from("value-e")).is_ok());
+        assert!(wb3.commit().is_ok());
+        assert_eq!(
+            Bytes::from("value-e"),
+            engine.get(Bytes::from("e")).unwrap()
+        );
+        assert_eq!(
+            Errors::KeyNotFound,
+            engine.get(Bytes::from("d")).err().unwrap()
+        );
+
+        // 重启之后放弃的操作仍然不可见
+        std::mem::drop(wb3);
+        engine.close().expect("failed to close engine");
+        let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(
+            vec![Bytes::from("a"), Bytes::from("e")],
+            engine2.list_keys().unwrap()
+        );
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
 }
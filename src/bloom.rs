@@ -0,0 +1,301 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bytes::{Buf, BufMut, BytesMut};
+use log::warn;
+use parking_lot::{Mutex, RwLock};
+use prost::decode_length_delimiter;
+
+use crate::{
+    data::{data_file::DataFile, log_record::LogRecordType},
+    error::{Errors, Result},
+    option::Options,
+};
+
+// 布隆过滤器文件的扩展名，和数据文件使用相同的文件 id
+pub(crate) const BLOOM_FILE_EXT: &str = "bloom";
+
+// 计算 key 的哈希值，由同一个哈希值派生出多个位置
+fn hash_key(key: &[u8]) -> u64 {
+    xxhash_rust::xxh64::xxh64(key, 0)
+}
+
+// 数据文件中存储的 key 以事务序列号开头，去掉序列号得到用户写入的 key
+fn user_key(stored_key: &[u8]) -> &[u8] {
+    let mut buf = stored_key;
+    match decode_length_delimiter(&mut buf) {
+        Ok(_) => buf,
+        Err(_) => stored_key,
+    }
+}
+
+pub(crate) fn get_bloom_file_name(options: &Options, file_id: u32) -> PathBuf {
+    let name = std::format!(
+        "{:0width$}.{}",
+        file_id,
+        BLOOM_FILE_EXT,
+        width = options.data_file_id_width
+    );
+    options.dir_path.join(name)
+}
+
+// 删除数据文件对应的过滤器文件，不存在时不做处理
+pub(crate) fn remove_bloom_file(options: &Options, file_id: u32) -> Result<()> {
+    let file_name = get_bloom_file_name(options, file_id);
+    if file_name.is_file() {
+        fs::remove_file(file_name)?;
+    }
+    Ok(())
+}
+
+// 单个数据文件的布隆过滤器，判断不存在的 key 一定不在文件中，判断存在时可能误判
+pub(crate) struct BloomFilter {
+    bits: Vec<u8>,
+    num_hashes: u32,
+}
+
+impl BloomFilter {
+    // 根据 key 的哈希值构建过滤器，fp_rate 为期望的误判率
+    fn from_hashes(hashes: &[u64], fp_rate: f64) -> Self {
+        // 每个 key 需要的位数为 -ln(p) / ln(2)^2，哈希函数的个数为 位数 * ln(2)
+        let bits_per_key = -fp_rate.ln() / (std::f64::consts::LN_2 * std::f64::consts::LN_2);
+        let num_hashes = ((bits_per_key * std::f64::consts::LN_2).round() as u32).clamp(1, 30);
+        let num_bits = ((hashes.len() as f64 * bits_per_key).ceil() as usize).max(64);
+
+        let mut filter = BloomFilter {
+            bits: vec![0; num_bits.div_ceil(8)],
+            num_hashes,
+        };
+        for hash in hashes {
+            for bit in filter.bit_positions(*hash) {
+                filter.bits[bit / 8] |= 1 << (bit % 8);
+            }
+        }
+        filter
+    }
+
+    // 使用两个哈希值的线性组合模拟多个哈希函数
+    fn bit_positions(&self, hash: u64) -> impl Iterator<Item = usize> {
+        let num_bits = (self.bits.len() * 8) as u64;
+        let (h1, h2) = (hash & 0xffff_ffff, hash >> 32);
+        (0..self.num_hashes as u64)
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % num_bits) as usize)
+    }
+
+    pub(crate) fn may_contain(&self, key: &[u8]) -> bool {
+        self.bit_positions(hash_key(key))
+            .all(|bit| self.bits[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+
+    // 文件格式：哈希函数的个数、构建时数据文件的大小、过滤器的位数组，最后 4 个字节是之前所有内容的 crc32
+    fn encode(&self, file_size: u64) -> Vec<u8> {
+        let mut buf = BytesMut::new();
+        buf.put_u32_le(self.num_hashes);
+        buf.put_u64_le(file_size);
+        buf.extend_from_slice(&self.bits);
+        let crc = crc32fast::hash(&buf);
+        buf.put_u32_le(crc);
+        buf.to_vec()
+    }
+
+    // 解码过滤器以及构建时数据文件的大小，数据损坏时返回 None
+    fn decode(buf: &[u8]) -> Option<(Self, u64)> {
+        if buf.len() < 16 {
+            return None;
+        }
+        let (content, mut crc_buf) = buf.split_at(buf.len() - 4);
+        if crc32fast::hash(content) != crc_buf.get_u32_le() {
+            return None;
+        }
+        let mut content = content;
+        let num_hashes = content.get_u32_le();
+        let file_size = content.get_u64_le();
+        if num_hashes == 0 || content.is_empty() {
+            return None;
+        }
+        let filter = BloomFilter {
+            bits: content.to_vec(),
+            num_hashes,
+        };
+        Some((filter, file_size))
+    }
+}
+
+// 所有数据文件的布隆过滤器，开启 Options::enable_bloom_filters 时使用
+// 旧的数据文件的过滤器保存在和数据文件同名的 .bloom 文件中，活跃文件的过滤器只保存在内存中
+pub(crate) struct BloomFilters {
+    filters: RwLock<HashMap<u32, BloomFilter>>,
+    active_hashes: Mutex<Vec<u64>>, // 活跃文件中的 key 的哈希值，文件写满之后用于构建过滤器
+    fp_rate: f64,
+}
+
+impl BloomFilters {
+    pub(crate) fn new(fp_rate: f64) -> Self {
+        BloomFilters {
+            filters: RwLock::new(HashMap::new()),
+            active_hashes: Mutex::new(Vec::new()),
+            fp_rate,
+        }
+    }
+
+    // 打开数据库时加载旧的数据文件的过滤器，不存在或者已经失效时遍历数据文件重新构建
+    // 活跃文件总是遍历构建，只保存在内存中
+    pub(crate) fn load(
+        &self,
+        options: &Options,
+        older_files: &HashMap<u32, DataFile>,
+        active_file: &DataFile,
+    ) -> Result<()> {
+        let mut filters = self.filters.write();
+        for (file_id, data_file) in older_files.iter() {
+            let file_name = get_bloom_file_name(options, *file_id);
+            if let Ok(buf) = fs::read(&file_name) {
+                match BloomFilter::decode(&buf) {
+                    // 数据文件的大小发生了变化，例如被修复截断，过滤器已经失效
+                    Some((filter, file_size)) if file_size == data_file.file_size() => {
+                        filters.insert(*file_id, filter);
+                        continue;
+                    }
+                    _ => warn!(
+                        "bloom filter of data file {} is invalid, rebuild it",
+                        file_id
+                    ),
+                }
+            }
+            let hashes = read_key_hashes(data_file)?;
+            let filter = BloomFilter::from_hashes(&hashes, self.fp_rate);
+            if !options.read_only {
+                write_bloom_file(&file_name, &filter.encode(data_file.file_size()))?;
+            }
+            filters.insert(*file_id, filter);
+        }
+
+        let hashes = read_key_hashes(active_file)?;
+        filters.insert(
+            active_file.get_file_id(),
+            BloomFilter::from_hashes(&hashes, self.fp_rate),
+        );
+        *self.active_hashes.lock() = hashes;
+        Ok(())
+    }
+
+    // 记录写入活跃文件的 key
+    pub(crate) fn record(&self, stored_key: &[u8]) {
+        self.active_hashes
+            .lock()
+            .push(hash_key(user_key(stored_key)));
+    }
+
+    // 活跃文件写满之后构建并保存它的过滤器
+    pub(crate) fn seal(&self, options: &Options, data_file: &DataFile) -> Result<()> {
+        let hashes = std::mem::take(&mut *self.active_hashes.lock());
+        let filter = BloomFilter::from_hashes(&hashes, self.fp_rate);
+        let file_id = data_file.get_file_id();
+        write_bloom_file(
+            &get_bloom_file_name(options, file_id),
+            &filter.encode(data_file.file_size()),
+        )?;
+        self.filters.write().insert(file_id, filter);
+        Ok(())
+    }
+
+    // 清空所有的过滤器以及活跃文件中记录的 key，用于清空数据库之后
+    pub(crate) fn clear(&self) {
+        self.filters.write().clear();
+        self.active_hashes.lock().clear();
+    }
+
+    // 已经构建的过滤器的数量，用于测试
+    #[cfg(test)]
+    pub(crate) fn filter_count(&self) -> usize {
+        self.filters.read().len()
+    }
+
+    // 判断 key 是否可能存在于数据库中，返回 false 时 key 一定不在任何数据文件中
+    // 数据文件没有过滤器时（例如打开之后新建的活跃文件）无法判断，返回 true
+    pub(crate) fn may_contain(&self, key: &[u8], file_ids: &[u32]) -> bool {
+        let filters = self.filters.read();
+        file_ids.iter().any(|file_id| match filters.get(file_id) {
+            Some(filter) => filter.may_contain(key),
+            None => true,
+        })
+    }
+}
+
+// 遍历数据文件，获取所有 key 的哈希值
+fn read_key_hashes(data_file: &DataFile) -> Result<Vec<u64>> {
+    let mut hashes = Vec::new();
+    let mut offset = data_file.header_size();
+    loop {
+        let read_record = match data_file.read_log_record(offset) {
+            Ok(read_record) => read_record,
+            Err(Errors::ReadDataFileEOF) => break,
+            Err(e) => return Err(e),
+        };
+        offset += read_record.size as u64;
+        // 事务完成的标识不是用户写入的 key
+        if read_record.record.rec_type != LogRecordType::TXNFINISHED {
+            hashes.push(hash_key(user_key(&read_record.record.key)));
+        }
+    }
+    Ok(hashes)
+}
+
+// 先写入临时文件再重命名，避免留下不完整的过滤器文件
+fn write_bloom_file(file_name: &Path, buf: &[u8]) -> Result<()> {
+    let tmp_file_name = file_name.with_extension("bloom.tmp");
+    fs::write(&tmp_file_name, buf)?;
+    fs::rename(&tmp_file_name, file_name)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bloom_filter_no_false_negative() {
+        let keys: Vec<Vec<u8>> = (0..10000)
+            .map(|i| format!("bitcask-key-{:09}", i).into_bytes())
+            .collect();
+        let hashes: Vec<u64> = keys.iter().map(|key| hash_key(key)).collect();
+        let filter = BloomFilter::from_hashes(&hashes, 0.01);
+
+        // 写入的 key 一定不会被排除
+        for key in keys.iter() {
+            assert!(filter.may_contain(key));
+        }
+
+        // 不存在的 key 的误判率接近配置的值
+        let false_positives = (10000..20000)
+            .filter(|i| filter.may_contain(format!("bitcask-key-{:09}", i).as_bytes()))
+            .count();
+        assert!(
+            false_positives < 300,
+            "false positives: {}",
+            false_positives
+        );
+
+        // 编码之后再解码，结果不变
+        let (filter2, file_size) = BloomFilter::decode(&filter.encode(1234)).unwrap();
+        assert_eq!(1234, file_size);
+        for key in keys.iter() {
+            assert!(filter2.may_contain(key));
+        }
+
+        // 数据损坏
+        let mut buf = filter.encode(1234);
+        buf[20] ^= 0xff;
+        assert!(BloomFilter::decode(&buf).is_none());
+        assert!(BloomFilter::decode(&[]).is_none());
+    }
+
+    #[test]
+    fn test_bloom_filter_empty() {
+        let filter = BloomFilter::from_hashes(&[], 0.01);
+        assert!(!filter.may_contain(b"key"));
+    }
+}
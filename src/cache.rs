@@ -0,0 +1,159 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use bytes::Bytes;
+use lru::LruCache;
+use parking_lot::Mutex;
+
+use crate::data::log_record::LogRecordPos;
+
+// 分片的数量，不同分片各自加锁，减少并发读取时的锁竞争
+const CACHE_SHARD_NUM: usize = 16;
+
+// 缓存的 value 以及读取时的位置信息
+#[derive(Clone)]
+pub(crate) struct CachedValue {
+    pub(crate) pos: LogRecordPos, // 读取 value 时索引中的位置，和当前索引不一致时缓存失效
+    pub(crate) value: Bytes,
+    pub(crate) expire: u64, // 过期时间，为 0 时表示不过期
+}
+
+// 分片的 LRU value 缓存，按照 key 缓存最近读取过的 value
+pub(crate) struct ValueCache {
+    shards: Vec<Mutex<CacheShard>>,
+}
+
+struct CacheShard {
+    lru: LruCache<Vec<u8>, CachedValue>,
+    size: usize,     // 当前缓存的字节数
+    capacity: usize, // 分片可以缓存的字节数
+}
+
+impl ValueCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        let shard_capacity = capacity.div_ceil(CACHE_SHARD_NUM);
+        let shards = (0..CACHE_SHARD_NUM)
+            .map(|_| {
+                Mutex::new(CacheShard {
+                    lru: LruCache::unbounded(),
+                    size: 0,
+                    capacity: shard_capacity,
+                })
+            })
+            .collect();
+        ValueCache { shards }
+    }
+
+    pub(crate) fn get(&self, key: &[u8]) -> Option<CachedValue> {
+        let mut shard = self.shard(key).lock();
+        shard.lru.get(key).cloned()
+    }
+
+    pub(crate) fn put(&self, key: Vec<u8>, value: CachedValue) {
+        let charge = entry_size(&key, &value);
+        let mut shard = self.shard(&key).lock();
+        // 超过分片大小的数据不缓存
+        if charge > shard.capacity {
+            shard.remove(&key);
+            return;
+        }
+        if let Some(old) = shard.lru.put(key.clone(), value) {
+            shard.size -= entry_size(&key, &old);
+        }
+        shard.size += charge;
+
+        // 淘汰最久没有访问的数据，直到不超过分片的大小
+        while shard.size > shard.capacity {
+            match shard.lru.pop_lru() {
+                Some((k, v)) => shard.size -= entry_size(&k, &v),
+                None => break,
+            }
+        }
+    }
+
+    pub(crate) fn remove(&self, key: &[u8]) {
+        self.shard(key).lock().remove(key);
+    }
+
+    pub(crate) fn clear(&self) {
+        for shard in self.shards.iter() {
+            let mut shard = shard.lock();
+            shard.lru.clear();
+            shard.size = 0;
+        }
+    }
+
+    // 根据 key 的哈希值找到对应的分片
+    fn shard(&self, key: &[u8]) -> &Mutex<CacheShard> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+}
+
+impl CacheShard {
+    fn remove(&mut self, key: &[u8]) {
+        if let Some(old) = self.lru.pop(key) {
+            self.size -= entry_size(key, &old);
+        }
+    }
+}
+
+// 缓存项占用的字节数
+fn entry_size(key: &[u8], value: &CachedValue) -> usize {
+    key.len() + value.value.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cached_value(offset: u64, value: &'static str) -> CachedValue {
+        CachedValue {
+            pos: LogRecordPos {
+                file_id: 1,
+                offset,
+                size: 10,
+            },
+            value: Bytes::from(value),
+            expire: 0,
+        }
+    }
+
+    #[test]
+    fn test_value_cache_put_get() {
+        let cache = ValueCache::new(1024 * CACHE_SHARD_NUM);
+        cache.put(b"aa".to_vec(), cached_value(0, "value-1"));
+        let res1 = cache.get(b"aa");
+        assert_eq!(Bytes::from("value-1"), res1.unwrap().value);
+
+        // 覆盖之后读取到新的 value
+        cache.put(b"aa".to_vec(), cached_value(20, "value-2"));
+        let res2 = cache.get(b"aa").unwrap();
+        assert_eq!(Bytes::from("value-2"), res2.value);
+        assert_eq!(20, res2.pos.offset);
+
+        cache.remove(b"aa");
+        assert!(cache.get(b"aa").is_none());
+        assert!(cache.get(b"bb").is_none());
+    }
+
+    #[test]
+    fn test_value_cache_evict() {
+        let cache = ValueCache::new(CACHE_SHARD_NUM * 10);
+        // 超过分片大小的数据不缓存
+        cache.put(b"aa".to_vec(), cached_value(0, "value-value"));
+        assert!(cache.get(b"aa").is_none());
+
+        // 同一个分片中只保留最近访问的数据
+        cache.put(b"aa".to_vec(), cached_value(0, "value"));
+        assert!(cache.get(b"aa").is_some());
+        for i in 0..100u8 {
+            cache.put(vec![b'b', i], cached_value(0, "value"));
+        }
+        for shard in cache.shards.iter() {
+            let shard = shard.lock();
+            assert!(shard.size <= shard.capacity);
+            assert!(shard.lru.len() <= 1);
+        }
+    }
+}
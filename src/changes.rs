@@ -0,0 +1,87 @@
+use bytes::Bytes;
+
+use crate::{
+    data::log_record::{LogRecord, LogRecordType, ReadLogRecord},
+    db::Engine,
+    error::{Errors, Result},
+    scan::LogCursor,
+};
+
+/// 一次成功的写入，value 为 None 表示删除
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub sequence: u64,
+    pub key: Bytes,
+    pub value: Option<Bytes>,
+}
+
+impl Engine {
+    /// 按照写入序列号的顺序返回序列号大于 sequence 的写入，用于增量同步数据
+    /// 只能返回数据文件中仍然保留的记录：merge 会清理被覆盖和删除的数据，merge 之后只保留每个 key 最后一次写入；
+    /// 没有提交的事务以及旧版本写入的没有序列号的数据不会返回；compact_key 重写的数据保留原有的序列号，同一个序列号只返回一次
+    /// 需要遍历所有的数据文件，结果全部保存在内存中
+    pub fn changes_since(&self, sequence: u64) -> Result<impl Iterator<Item = Change>> {
+        self.wait_index_ready()?;
+        let first_file_id = {
+            let active_file = self.active_file.read();
+            let older_files = self.older_files.read();
+            older_files
+                .keys()
+                .copied()
+                .min()
+                .unwrap_or(active_file.get_file_id())
+        };
+
+        let mut changes = Vec::new();
+        for res in LogCursor::new(self, first_file_id, 0).committed() {
+            let (_, log_record) = res?;
+            // 只返回默认 keyspace 中的变更
+            if log_record.keyspace == 0 && log_record.sequence > sequence {
+                changes.push(self.to_change(log_record)?);
+            }
+        }
+
+        // merge 之后的数据按照 key 的顺序重写，需要重新排序
+        // compact_key 重写的数据和原来的数据在 merge 之前同时存在，序列号相同，只保留一条
+        changes.sort_by_key(|change| change.sequence);
+        changes.dedup_by_key(|change| change.sequence);
+        Ok(changes.into_iter())
+    }
+
+    /// 当前的写入位置，即活跃文件的 id 以及下一条数据写入的偏移
+    /// 可以作为复制数据时的游标，之后写入的数据都在这个位置之后
+    pub fn current_position(&self) -> Result<(u32, u64)> {
+        self.wait_index_ready()?;
+        let active_file = self.active_file.read();
+        Ok((active_file.get_file_id(), active_file.get_write_off()))
+    }
+
+    /// 读取数据文件中给定位置的一条记录，offset 必须是一条记录的起始位置，文件中的第一条记录在文件头部之后
+    /// 返回的是数据文件中存储的原始记录，key 可能带有事务序列号，value 可能经过压缩和加密，
+    /// 读取的位置加上记录的大小即为下一条记录的位置
+    /// 始终校验记录的校验值；读到写入位置时返回 ReadDataFileEOF，记录没有完整写入时返回 CorruptedRecord
+    pub fn read_at(&self, file_id: u32, offset: u64) -> Result<ReadLogRecord> {
+        self.wait_index_ready()?;
+        let active_file = self.active_file.read();
+        let older_files = self.older_files.read();
+        let data_file = match active_file.get_file_id() == file_id {
+            true => &*active_file,
+            false => older_files.get(&file_id).ok_or(Errors::DataFileNotFound)?,
+        };
+        data_file.read_log_record_with_crc(offset, true)
+    }
+
+    fn to_change(&self, log_record: LogRecord) -> Result<Change> {
+        let sequence = log_record.sequence;
+        let key = Bytes::copy_from_slice(&log_record.key);
+        let value = match log_record.rec_type {
+            LogRecordType::DELETED => None,
+            _ => Some(self.decode_value(log_record)?),
+        };
+        Ok(Change {
+            sequence,
+            key,
+            value,
+        })
+    }
+}
@@ -0,0 +1,71 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::util;
+
+/// 获取当前时间的接口，过期时间和写入时间都通过它获取
+/// 测试中可以使用 MockClock 控制时间，不需要等待数据真正过期
+pub trait Clock: Send + Sync {
+    // 当前的 unix 毫秒时间戳
+    fn now(&self) -> u64;
+}
+
+/// 使用系统时间的时钟，默认的实现
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        util::time::now_millis()
+    }
+}
+
+/// 手动控制的时钟，只有调用 set 或者 advance 时时间才会变化
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now: AtomicU64,
+}
+
+impl MockClock {
+    pub fn new(now: u64) -> Self {
+        Self {
+            now: AtomicU64::new(now),
+        }
+    }
+
+    // 设置当前的 unix 毫秒时间戳
+    pub fn set(&self, now: u64) {
+        self.now.store(now, Ordering::SeqCst);
+    }
+
+    // 将时间向前推进
+    pub fn advance(&self, duration: Duration) {
+        self.now
+            .fetch_add(duration.as_millis() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::new(1000);
+        assert_eq!(1000, clock.now());
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(3000, clock.now());
+        clock.set(10);
+        assert_eq!(10, clock.now());
+
+        assert!(SystemClock.now() > 0);
+    }
+}
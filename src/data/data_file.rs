@@ -1,23 +1,50 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use bytes::{Buf, BytesMut};
 use parking_lot::RwLock;
-use prost::{decode_length_delimiter, length_delimiter_len};
+use prost::{
+    decode_length_delimiter,
+    encoding::{decode_varint, encoded_len_varint},
+    length_delimiter_len,
+};
 
+#[cfg(feature = "mmap")]
+use crate::fileio::mmap::MappedSlice;
 use crate::{
     error::{Errors, Result},
-    fileio::{self, new_io_manager},
-    option::IOType,
+    fileio::{self, new_io_manager, new_io_manager_with_sync_mode, new_read_only_io_manager},
+    option::{Checksum, Compression, IOType, Options},
 };
 
 use super::log_record::{
-    max_log_record_header_size, LogRecord, LogRecordPos, LogRecordType, ReadLogRecord,
+    checksum_from_u8, checksum_len, compression_from_flag, compute_checksum,
+    max_log_record_header_size, LogRecord, LogRecordHeader, LogRecordPos, LogRecordType,
+    ReadLogRecord, LOG_RECORD_ENCRYPTION_FLAG, LOG_RECORD_EXPIRE_FLAG, LOG_RECORD_KEYSPACE_MARKER,
+    LOG_RECORD_SEQUENCE_FLAG, LOG_RECORD_TIMESTAMP_FLAG, LOG_RECORD_TYPE_MASK,
 };
 
-pub const DATA_FILE_NAME_SUFFIX: &str = ".data";
 pub const HINT_FILE_NAME: &str = "hint-index";
 pub const MERGE_FINISHED_FILE_NAME: &str = "merge-finished";
+pub const MERGE_CHECKPOINT_FILE_NAME: &str = "merge-checkpoint";
 pub const SEQ_NO_FILE_NAME: &str = "seq-no";
+pub const KEYSPACES_FILE_NAME: &str = "keyspaces";
+
+// 数据文件头部的魔数，第一个字节的压缩标识位是非法值，不会和没有头部的旧版本数据文件混淆
+pub const DATA_FILE_MAGIC: [u8; 4] = [0xBF, b'B', b'C', b'K'];
+// 数据文件的格式版本，没有头部的旧版本数据文件视为版本 0
+// 版本 1 的头部只有魔数和版本号，使用 crc32 校验；版本 2 在版本号之后增加了一个字节的校验算法
+// 版本 3 的头部和版本 2 相同，记录中可以带有写入序列号，旧版本的程序无法解析
+pub const DATA_FILE_VERSION: u32 = 3;
+// 数据文件头部的长度，魔数、版本号加上校验算法
+pub const DATA_FILE_HEADER_SIZE: u64 = 9;
+// 版本 1 的数据文件头部的长度
+const DATA_FILE_HEADER_SIZE_V1: u64 = 8;
 
 pub struct DataFile {
     // 数据文件id
@@ -28,60 +55,258 @@ pub struct DataFile {
 
     // IO 管理接口
     io_manager: Box<dyn fileio::IOManager>,
+
+    // 上一次持久化之后累计写入的字节数
+    bytes_since_sync: Arc<AtomicUsize>,
+
+    // 文件头部的长度，第一条数据从这个位置开始，旧版本的数据文件为 0
+    header_size: u64,
+
+    // 当前使用的 IO 类型
+    io_type: IOType,
+
+    // 数据使用的校验算法，记录在文件头部中
+    checksum: Checksum,
+
+    // 是否预分配了磁盘空间，预分配之后文件的大小不代表写入的位置，从 write_off 开始写入
+    preallocated: bool,
 }
 
-// 获取文件名称
-pub fn get_data_file_name(dir_path: PathBuf, file_id: u32) -> PathBuf {
-    let name = std::format!("{:09}", file_id) + DATA_FILE_NAME_SUFFIX;
-    dir_path.join(name)
+// 获取文件名称，文件 id 按照配置的宽度补 0，再加上配置的扩展名
+pub fn get_data_file_name(options: &Options, file_id: u32) -> PathBuf {
+    let name = std::format!(
+        "{:0width$}.{}",
+        file_id,
+        options.data_file_ext,
+        width = options.data_file_id_width
+    );
+    options.dir_path.join(name)
+}
+
+// 从数据文件的名称中解析出文件 id，不是数据文件时返回 None
+pub fn parse_data_file_id(options: &Options, file_name: &str) -> Option<Result<u32>> {
+    let suffix = std::format!(".{}", options.data_file_ext);
+    let id = file_name.strip_suffix(&suffix)?;
+    Some(
+        id.parse::<u32>()
+            .map_err(|_| Errors::DataDirectoryCorrupted),
+    )
+}
+
+// 打开 hint、merge 标识等辅助文件，设置了 io_factory 时通过它打开
+// read_only 为 true 时不会创建新的文件
+fn open_aux_io_manager(
+    options: &Options,
+    file_name: PathBuf,
+    read_only: bool,
+) -> Result<Box<dyn fileio::IOManager>> {
+    match (&options.io_factory, read_only) {
+        (Some(io_factory), _) => Ok(io_factory.open(file_name, IOType::StandardFIO)),
+        (None, true) => new_read_only_io_manager(file_name, IOType::StandardFIO),
+        (None, false) => Ok(new_io_manager(file_name, IOType::StandardFIO)),
+    }
 }
 
 impl DataFile {
-    pub fn new(dir_path: PathBuf, file_id: u32, io_type: IOType) -> Result<DataFile> {
-        let file_name = get_data_file_name(dir_path, file_id);
+    pub fn new(options: &Options, file_id: u32, io_type: IOType) -> Result<DataFile> {
+        let file_name = get_data_file_name(options, file_id);
 
-        let io_manager = new_io_manager(file_name, io_type);
+        // 只读模式下不创建和修改文件，数据库可以位于只读的文件系统上
+        let io_manager = match (&options.io_factory, options.read_only) {
+            (Some(io_factory), _) => io_factory.open(file_name, io_type),
+            (None, true) => new_read_only_io_manager(file_name, io_type)?,
+            (None, false) => new_io_manager_with_sync_mode(file_name, io_type, options.sync_mode),
+        };
+        let io_manager = fileio::with_retry(io_manager, options.io_retry_count);
 
-        Ok(DataFile {
+        let mut data_file = DataFile {
             file_id: Arc::new(RwLock::new(file_id)),
             write_off: Arc::new(RwLock::new(0)),
             io_manager,
-        })
+            bytes_since_sync: Arc::new(AtomicUsize::new(0)),
+            header_size: 0,
+            io_type,
+            checksum: Checksum::Crc32,
+            preallocated: false,
+        };
+        // 关闭校验时新建的数据文件不存储校验值
+        let checksum = match options.checksum_enabled {
+            true => options.checksum,
+            false => Checksum::None,
+        };
+        (data_file.header_size, data_file.checksum) =
+            data_file.init_header(checksum, options.read_only)?;
+        data_file.set_write_off(data_file.header_size);
+        Ok(data_file)
+    }
+
+    // 新文件写入头部，已有的文件校验头部，返回头部的长度以及文件使用的校验算法
+    // 只读模式下不为空文件写入头部
+    fn init_header(&self, checksum: Checksum, read_only: bool) -> Result<(u64, Checksum)> {
+        if self.io_manager.size() == 0 {
+            if read_only {
+                return Ok((0, checksum));
+            }
+            let mut header = Vec::with_capacity(DATA_FILE_HEADER_SIZE as usize);
+            header.extend_from_slice(&DATA_FILE_MAGIC);
+            header.extend_from_slice(&DATA_FILE_VERSION.to_be_bytes());
+            header.push(checksum as u8);
+            self.io_manager.write(&header)?;
+            return Ok((DATA_FILE_HEADER_SIZE, checksum));
+        }
+
+        let mut header = [0u8; DATA_FILE_HEADER_SIZE_V1 as usize];
+        let n = match self.io_manager.read(&mut header, 0) {
+            Ok(n) => n,
+            Err(Errors::ReadDataFileEOF) => 0,
+            Err(e) => return Err(e),
+        };
+        if n >= DATA_FILE_MAGIC.len() && header[..DATA_FILE_MAGIC.len()] == DATA_FILE_MAGIC {
+            if n < header.len() {
+                return Err(Errors::UnknownFileFormat);
+            }
+            let version = u32::from_be_bytes(header[4..].try_into().unwrap());
+            return match version {
+                1 => Ok((DATA_FILE_HEADER_SIZE_V1, Checksum::Crc32)),
+                2 | DATA_FILE_VERSION => {
+                    let mut checksum_buf = [0u8; 1];
+                    match self
+                        .io_manager
+                        .read(&mut checksum_buf, DATA_FILE_HEADER_SIZE_V1)
+                    {
+                        Ok(1) => Ok((DATA_FILE_HEADER_SIZE, checksum_from_u8(checksum_buf[0])?)),
+                        Ok(_) | Err(Errors::ReadDataFileEOF) => Err(Errors::UnknownFileFormat),
+                        Err(e) => Err(e),
+                    }
+                }
+                _ => Err(Errors::UnknownFileFormat),
+            };
+        }
+
+        // 没有头部的旧版本数据文件使用 crc32 校验，第一条数据必须能够正常解析，否则不是数据文件
+        match self.read_log_record(0) {
+            Ok(_) | Err(Errors::ReadDataFileEOF) => Ok((0, Checksum::Crc32)),
+            Err(_) => Err(Errors::UnknownFileFormat),
+        }
+    }
+
+    // 使用指定的 IO 管理接口创建数据文件，用于测试中替换底层的 IO 实现
+    #[cfg(test)]
+    pub(crate) fn with_io_manager(
+        file_id: u32,
+        io_manager: Box<dyn fileio::IOManager>,
+    ) -> DataFile {
+        DataFile {
+            file_id: Arc::new(RwLock::new(file_id)),
+            write_off: Arc::new(RwLock::new(0)),
+            io_manager,
+            bytes_since_sync: Arc::new(AtomicUsize::new(0)),
+            header_size: 0,
+            io_type: IOType::StandardFIO,
+            checksum: Checksum::Crc32,
+            preallocated: false,
+        }
     }
 
     // 新建或打开 hint 索引文件
-    pub fn new_hint_file(dir_path: PathBuf) -> Result<DataFile> {
+    pub fn new_hint_file(options: &Options, dir_path: PathBuf) -> Result<DataFile> {
+        let file_name = dir_path.join(HINT_FILE_NAME);
+        let io_manager = open_aux_io_manager(options, file_name, false)?;
+
+        Ok(DataFile {
+            file_id: Arc::new(RwLock::new(0)),
+            write_off: Arc::new(RwLock::new(0)),
+            io_manager,
+            bytes_since_sync: Arc::new(AtomicUsize::new(0)),
+            header_size: 0,
+            io_type: IOType::StandardFIO,
+            checksum: Checksum::Crc32,
+            preallocated: false,
+        })
+    }
+
+    // 以只读的方式打开 hint 索引文件
+    pub fn open_hint_file_read_only(options: &Options, dir_path: PathBuf) -> Result<DataFile> {
         let file_name = dir_path.join(HINT_FILE_NAME);
-        let io_manager = new_io_manager(file_name, IOType::StandardFIO);
+        let io_manager = open_aux_io_manager(options, file_name, true)?;
 
         Ok(DataFile {
             file_id: Arc::new(RwLock::new(0)),
             write_off: Arc::new(RwLock::new(0)),
             io_manager,
+            bytes_since_sync: Arc::new(AtomicUsize::new(0)),
+            header_size: 0,
+            io_type: IOType::StandardFIO,
+            checksum: Checksum::Crc32,
+            preallocated: false,
         })
     }
 
     // 新建或打开标识 merge 完成的文件
-    pub fn new_merge_fin_file(dir_path: PathBuf) -> Result<DataFile> {
+    pub fn new_merge_fin_file(options: &Options, dir_path: PathBuf) -> Result<DataFile> {
         let file_name = dir_path.join(MERGE_FINISHED_FILE_NAME);
-        let io_manager = new_io_manager(file_name, IOType::StandardFIO);
+        let io_manager = open_aux_io_manager(options, file_name, false)?;
+
+        Ok(DataFile {
+            file_id: Arc::new(RwLock::new(0)),
+            write_off: Arc::new(RwLock::new(0)),
+            io_manager,
+            bytes_since_sync: Arc::new(AtomicUsize::new(0)),
+            header_size: 0,
+            io_type: IOType::StandardFIO,
+            checksum: Checksum::Crc32,
+            preallocated: false,
+        })
+    }
+
+    // 新建或打开分批 merge 的检查点文件
+    pub fn new_merge_checkpoint_file(options: &Options, dir_path: PathBuf) -> Result<DataFile> {
+        let file_name = dir_path.join(MERGE_CHECKPOINT_FILE_NAME);
+        let io_manager = open_aux_io_manager(options, file_name, false)?;
 
         Ok(DataFile {
             file_id: Arc::new(RwLock::new(0)),
             write_off: Arc::new(RwLock::new(0)),
             io_manager,
+            bytes_since_sync: Arc::new(AtomicUsize::new(0)),
+            header_size: 0,
+            io_type: IOType::StandardFIO,
+            checksum: Checksum::Crc32,
+            preallocated: false,
         })
     }
 
     // 新建或打开存储事务序列号的文件
-    pub fn new_seq_no_file(dir_path: PathBuf) -> Result<DataFile> {
+    pub fn new_seq_no_file(options: &Options, dir_path: PathBuf) -> Result<DataFile> {
         let file_name = dir_path.join(SEQ_NO_FILE_NAME);
-        let io_manager = new_io_manager(file_name, IOType::StandardFIO);
+        let io_manager = open_aux_io_manager(options, file_name, false)?;
+
+        Ok(DataFile {
+            file_id: Arc::new(RwLock::new(0)),
+            write_off: Arc::new(RwLock::new(0)),
+            io_manager,
+            bytes_since_sync: Arc::new(AtomicUsize::new(0)),
+            header_size: 0,
+            io_type: IOType::StandardFIO,
+            checksum: Checksum::Crc32,
+            preallocated: false,
+        })
+    }
+
+    // 打开存储 keyspace 名称和 id 的文件，read_only 为 true 时不会创建新的文件
+    pub fn new_keyspaces_file(options: &Options, read_only: bool) -> Result<DataFile> {
+        let file_name = options.dir_path.join(KEYSPACES_FILE_NAME);
+        let io_manager = open_aux_io_manager(options, file_name, read_only)?;
 
         Ok(DataFile {
             file_id: Arc::new(RwLock::new(0)),
             write_off: Arc::new(RwLock::new(0)),
             io_manager,
+            bytes_since_sync: Arc::new(AtomicUsize::new(0)),
+            header_size: 0,
+            io_type: IOType::StandardFIO,
+            checksum: Checksum::Crc32,
+            preallocated: false,
         })
     }
 
@@ -89,6 +314,16 @@ impl DataFile {
         self.io_manager.size()
     }
 
+    // 获取文件头部的长度，即第一条数据的位置
+    pub fn header_size(&self) -> u64 {
+        self.header_size
+    }
+
+    // 获取数据使用的校验算法
+    pub fn checksum(&self) -> Checksum {
+        self.checksum
+    }
+
     pub fn get_write_off(&self) -> u64 {
         let read_guard = self.write_off.read();
         *read_guard
@@ -104,26 +339,93 @@ impl DataFile {
         *read_guard
     }
     pub fn sync(&self) -> Result<()> {
-        self.io_manager.sync()
+        self.io_manager.sync()?;
+        self.bytes_since_sync.store(0, Ordering::SeqCst);
+        Ok(())
     }
 
-    pub fn set_io_manager(&mut self, dir_path: PathBuf, io_type: IOType) {
-        self.io_manager = new_io_manager(get_data_file_name(dir_path, self.get_file_id()), io_type);
+    // 获取上一次持久化之后累计写入的字节数
+    pub fn bytes_since_sync(&self) -> usize {
+        self.bytes_since_sync.load(Ordering::SeqCst)
     }
 
-    pub fn write(&self, buf: &[u8]) -> Result<usize> {
-        let write_bytes = self.io_manager.write(buf)?;
+    pub fn set_io_manager(&mut self, options: &Options, io_type: IOType) {
+        let file_name = get_data_file_name(options, self.get_file_id());
+        let io_manager = match &options.io_factory {
+            Some(io_factory) => io_factory.open(file_name, io_type),
+            None => new_io_manager_with_sync_mode(file_name, io_type, options.sync_mode),
+        };
+        self.io_manager = fileio::with_retry(io_manager, options.io_retry_count);
+        self.io_type = io_type;
+    }
+
+    // 按照配置为活跃文件预先分配 data_file_size 大小的磁盘空间，需要在确定写入位置之后调用
+    // 只对标准文件 IO 生效，使用写缓冲、只读模式或者自定义的 io_factory 时不预分配
+    pub fn preallocate(&mut self, options: &Options) -> Result<()> {
+        if !options.preallocate
+            || options.read_only
+            || options.io_factory.is_some()
+            || self.io_type != IOType::StandardFIO
+        {
+            return Ok(());
+        }
+        let file_name = get_data_file_name(options, self.get_file_id());
+        let io_manager = fileio::new_preallocated_io_manager(
+            file_name,
+            options.data_file_size,
+            options.sync_mode,
+        )?;
+        self.io_manager = fileio::with_retry(io_manager, options.io_retry_count);
+        self.preallocated = true;
+        Ok(())
+    }
+
+    // 截断预分配之后没有使用的空间，没有预分配时不做处理
+    pub fn truncate_unused_space(&self) -> Result<()> {
+        match self.preallocated {
+            true => self.truncate_to_write_off(),
+            false => Ok(()),
+        }
+    }
 
+    // 截断写入位置之后的数据，只能在确定了写入位置之后调用，例如加载索引之后截断崩溃前预分配的空间
+    pub fn truncate_to_write_off(&self) -> Result<()> {
+        let write_off = self.get_write_off();
+        if self.io_manager.size() > write_off {
+            self.io_manager.truncate(write_off)?;
+        }
+        Ok(())
+    }
+
+    // 获取当前使用的 IO 类型
+    pub fn io_type(&self) -> IOType {
+        self.io_type
+    }
+
+    pub fn write(&self, buf: &[u8]) -> Result<usize> {
         let mut write_off = self.write_off.write();
+        let write_bytes = match self.preallocated {
+            true => self.io_manager.write_at(buf, *write_off)?,
+            false => self.io_manager.write(buf)?,
+        };
         *write_off += write_bytes as u64;
+        self.bytes_since_sync
+            .fetch_add(write_bytes, Ordering::SeqCst);
         Ok(write_bytes)
     }
 
-    pub fn write_hint_record(&self, key: Vec<u8>, pos: LogRecordPos) -> Result<()> {
+    // 写入 hint 索引，keyspace 为数据所属的 keyspace id
+    pub fn write_hint_record(&self, key: Vec<u8>, keyspace: u32, pos: LogRecordPos) -> Result<()> {
         let hint_record = LogRecord {
             key,
             value: pos.encode(),
             rec_type: LogRecordType::NORMAL,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace,
         };
         let enc_record = hint_record.encode();
         self.write(&enc_record)?;
@@ -131,49 +433,288 @@ impl DataFile {
     }
 
     pub fn read_log_record(&self, offset: u64) -> Result<ReadLogRecord> {
-        let mut header_buf = BytesMut::zeroed(max_log_record_header_size());
-        self.io_manager.read(&mut header_buf, offset)?;
-
-        let rec_type = header_buf.get_u8();
-        let key_size = decode_length_delimiter(&mut header_buf).unwrap();
-        let value_size = decode_length_delimiter(&mut header_buf).unwrap();
-
-        if key_size == 0 && value_size == 0 {
-            return Err(Errors::ReadDataFileEOF);
-        }
+        self.read_log_record_with_crc(offset, true)
+    }
 
-        let actual_header_size = length_delimiter_len(key_size)
-            + length_delimiter_len(value_size)
-            + std::mem::size_of::<u8>();
+    // 根据 offset 读取 LogRecord，verify_crc 为 false 时跳过 crc 校验
+    pub fn read_log_record_with_crc(&self, offset: u64, verify_crc: bool) -> Result<ReadLogRecord> {
+        let header = self.read_log_record_header(offset)?;
+        let (key_size, value_size) = (header.key_size, header.value_size);
 
-        let mut kv_buf = BytesMut::zeroed(key_size + value_size + std::mem::size_of::<u32>());
+        let checksum_size = checksum_len(self.checksum);
+        self.check_record_size(offset, &header, checksum_size)?;
+        let mut kv_buf = BytesMut::zeroed(key_size + value_size + checksum_size);
 
-        self.io_manager
-            .read(&mut kv_buf, offset + actual_header_size as u64)?;
+        // 检查之后文件仍然可能被修复截断
+        match self
+            .io_manager
+            .read_exact(&mut kv_buf, offset + header.header_size as u64)
+        {
+            Ok(()) => {}
+            Err(Errors::ReadDataFileEOF) => return Err(self.corrupted_record(offset)),
+            Err(e) => return Err(e),
+        }
 
         // 构造 LogRecord
         let log_record = LogRecord {
             key: kv_buf.get(..key_size).unwrap().to_vec(),
             value: kv_buf
-                .get(key_size..kv_buf.len() - std::mem::size_of::<u32>())
+                .get(key_size..kv_buf.len() - checksum_size)
                 .unwrap()
                 .to_vec(),
-            rec_type: rec_type.into(),
+            rec_type: header.rec_type,
+            expire: header.expire,
+            compression: header.compression,
+            encrypted: header.encrypted,
+            sequence: header.sequence,
+            timestamp: header.timestamp,
+            keyspace: header.keyspace,
         };
 
-        // 向前移动到最后的 4 个字节，就是 crc 的值
+        // 向前移动到最后的校验值
         kv_buf.advance(key_size + value_size);
+        let checksum_value = match self.checksum {
+            Checksum::Crc32 => kv_buf.get_u32() as u64,
+            Checksum::XxHash64 => kv_buf.get_u64(),
+            Checksum::None => 0,
+        };
 
-        if kv_buf.get_u32() != log_record.get_crc() {
-            return Err(Errors::InvalidLogRecordCrc);
+        // 数据文件没有存储校验值时无法校验
+        let verify_crc = verify_crc && self.checksum != Checksum::None;
+        if verify_crc && checksum_value != log_record.get_checksum(self.checksum) {
+            return Err(Errors::InvalidCrc {
+                key: log_record.key,
+            });
         }
 
         // 构造结果并返回
         Ok(ReadLogRecord {
             record: log_record,
-            size: actual_header_size + key_size + value_size + std::mem::size_of::<u32>(),
+            size: header.header_size + key_size + value_size + checksum_size,
         })
     }
+
+    // 不拷贝数据，直接返回映射区域中 LogRecord 的 value
+    // 文件没有使用 mmap，或者 value 经过压缩、加密需要解码时返回 None
+    #[cfg(feature = "mmap")]
+    pub(crate) fn read_mapped_value(
+        &self,
+        offset: u64,
+        verify_crc: bool,
+    ) -> Result<Option<(LogRecordHeader, MappedSlice)>> {
+        let header = self.read_log_record_header(offset)?;
+        if header.compression != Compression::None || header.encrypted {
+            return Ok(None);
+        }
+
+        let checksum_size = checksum_len(self.checksum);
+        self.check_record_size(offset, &header, checksum_size)?;
+        let data_size = header.header_size + header.key_size + header.value_size;
+        let Some(record) = self
+            .io_manager
+            .read_mapped(offset, data_size + checksum_size)?
+        else {
+            return Ok(None);
+        };
+
+        // 直接在映射区域上计算校验值
+        if verify_crc && self.checksum != Checksum::None {
+            let mut checksum_buf = &record[data_size..];
+            let checksum_value = match self.checksum {
+                Checksum::Crc32 => checksum_buf.get_u32() as u64,
+                Checksum::XxHash64 => checksum_buf.get_u64(),
+                Checksum::None => 0,
+            };
+            if checksum_value != compute_checksum(self.checksum, &record[..data_size]) {
+                let key_start = header.header_size;
+                return Err(Errors::InvalidCrc {
+                    key: record[key_start..key_start + header.key_size].to_vec(),
+                });
+            }
+        }
+
+        let value_start = header.header_size + header.key_size;
+        let value = record.slice(value_start..value_start + header.value_size);
+        Ok(Some((header, value)))
+    }
+
+    // header 中记录的长度超过了文件末尾，说明这条数据没有完整写入，通常是崩溃时最后一次写入被中断
+    // 在分配内存之前检查，避免按照损坏的长度读取或者分配过大的内存
+    fn check_record_size(
+        &self,
+        offset: u64,
+        header: &LogRecordHeader,
+        checksum_size: usize,
+    ) -> Result<()> {
+        let end = [
+            header.header_size,
+            header.key_size,
+            header.value_size,
+            checksum_size,
+        ]
+        .iter()
+        .try_fold(offset, |end, size| end.checked_add(*size as u64));
+        match end {
+            Some(end) if end <= self.io_manager.size() => Ok(()),
+            _ => Err(self.corrupted_record(offset)),
+        }
+    }
+
+    fn corrupted_record(&self, offset: u64) -> Errors {
+        Errors::CorruptedRecord {
+            file_id: self.get_file_id(),
+            offset,
+        }
+    }
+
+    // 将 offset 处长度为 size 的整条 LogRecord 读取到 buf 中，校验之后只保留 value，返回 LogRecord 的 header
+    // buf 中原有的数据会被清空，容量足够时不需要重新分配内存
+    pub(crate) fn read_value_into(
+        &self,
+        offset: u64,
+        size: usize,
+        verify_crc: bool,
+        buf: &mut Vec<u8>,
+    ) -> Result<LogRecordHeader> {
+        buf.clear();
+        buf.resize(size, 0);
+        match self.io_manager.read_exact(buf, offset) {
+            Ok(()) => {}
+            Err(Errors::ReadDataFileEOF) => return Err(self.corrupted_record(offset)),
+            Err(e) => return Err(e),
+        }
+        if buf.is_empty() {
+            return Err(self.corrupted_record(offset));
+        }
+
+        // 索引中记录的大小和 header 中记录的长度不一致，说明数据已经损坏
+        let header = decode_log_record_header(buf)?;
+        let checksum_size = checksum_len(self.checksum);
+        let data_size = [header.header_size, header.key_size, header.value_size]
+            .iter()
+            .try_fold(0usize, |total, len| total.checked_add(*len));
+        let data_size = match data_size {
+            Some(data_size) if size.checked_sub(checksum_size) == Some(data_size) => data_size,
+            _ => return Err(self.corrupted_record(offset)),
+        };
+
+        if verify_crc && self.checksum != Checksum::None {
+            let mut checksum_buf = &buf[data_size..];
+            let checksum_value = match self.checksum {
+                Checksum::Crc32 => checksum_buf.get_u32() as u64,
+                Checksum::XxHash64 => checksum_buf.get_u64(),
+                Checksum::None => 0,
+            };
+            if checksum_value != compute_checksum(self.checksum, &buf[..data_size]) {
+                let key_start = header.header_size;
+                return Err(Errors::InvalidCrc {
+                    key: buf[key_start..key_start + header.key_size].to_vec(),
+                });
+            }
+        }
+
+        // 去掉 header、key 以及校验值，只保留 value
+        buf.truncate(data_size);
+        buf.drain(..header.header_size + header.key_size);
+        Ok(header)
+    }
+
+    // 根据 offset 只读取 LogRecord 的 header，不读取 key 和 value
+    pub(crate) fn read_log_record_header(&self, offset: u64) -> Result<LogRecordHeader> {
+        let mut header_buf = BytesMut::zeroed(max_log_record_header_size());
+        self.io_manager.read(&mut header_buf, offset)?;
+        decode_log_record_header(&header_buf)
+    }
+}
+
+// 从 buf 中解码 LogRecord 的 header，buf 从 header 的第一个字节开始
+fn decode_log_record_header(mut header_buf: &[u8]) -> Result<LogRecordHeader> {
+    // 以 keyspace 标识开头时，先解码 keyspace id，id 为 0 说明读取到了文件末尾填充的 0
+    let mut keyspace = 0;
+    let mut keyspace_size = 0;
+    if header_buf.first() == Some(&LOG_RECORD_KEYSPACE_MARKER) {
+        header_buf.advance(1);
+        keyspace = match decode_varint(&mut header_buf) {
+            Ok(0) => return Err(Errors::ReadDataFileEOF),
+            Ok(keyspace) if keyspace <= u32::MAX as u64 => keyspace as u32,
+            _ => return Err(Errors::InvalidLogRecordCrc),
+        };
+        keyspace_size = std::mem::size_of::<u8>() + encoded_len_varint(keyspace as u64);
+    }
+    let type_byte = header_buf.get_u8();
+    // 设置了过期标识，则 type 之后存储了过期时间
+    let mut expire = 0;
+    if type_byte & LOG_RECORD_EXPIRE_FLAG != 0 {
+        expire = match decode_varint(&mut header_buf) {
+            Ok(expire) => expire,
+            Err(_) => return Err(Errors::InvalidLogRecordCrc),
+        };
+    }
+    // 设置了序列号标识，则之后存储了写入序列号
+    let mut sequence = 0;
+    if type_byte & LOG_RECORD_SEQUENCE_FLAG != 0 {
+        sequence = match decode_varint(&mut header_buf) {
+            Ok(sequence) => sequence,
+            Err(_) => return Err(Errors::InvalidLogRecordCrc),
+        };
+    }
+    // 设置了写入时间标识，则之后存储了写入时间
+    let mut timestamp = 0;
+    if type_byte & LOG_RECORD_TIMESTAMP_FLAG != 0 {
+        timestamp = match decode_varint(&mut header_buf) {
+            Ok(timestamp) => timestamp,
+            Err(_) => return Err(Errors::InvalidLogRecordCrc),
+        };
+    }
+    let key_size = match decode_length_delimiter(&mut header_buf) {
+        Ok(key_size) => key_size,
+        Err(_) => return Err(Errors::InvalidLogRecordCrc),
+    };
+    let value_size = match decode_length_delimiter(&mut header_buf) {
+        Ok(value_size) => value_size,
+        Err(_) => return Err(Errors::InvalidLogRecordCrc),
+    };
+
+    if key_size == 0 && value_size == 0 {
+        return Err(Errors::ReadDataFileEOF);
+    }
+
+    // 校验记录类型，避免损坏的数据导致程序崩溃
+    let rec_type = LogRecordType::try_from(type_byte & LOG_RECORD_TYPE_MASK)?;
+    let compression = compression_from_flag(type_byte)?;
+
+    let expire_size = match type_byte & LOG_RECORD_EXPIRE_FLAG != 0 {
+        true => encoded_len_varint(expire),
+        false => 0,
+    };
+    let sequence_size = match type_byte & LOG_RECORD_SEQUENCE_FLAG != 0 {
+        true => encoded_len_varint(sequence),
+        false => 0,
+    };
+    let timestamp_size = match type_byte & LOG_RECORD_TIMESTAMP_FLAG != 0 {
+        true => encoded_len_varint(timestamp),
+        false => 0,
+    };
+    let header_size = keyspace_size
+        + expire_size
+        + sequence_size
+        + timestamp_size
+        + length_delimiter_len(key_size)
+        + length_delimiter_len(value_size)
+        + std::mem::size_of::<u8>();
+
+    Ok(LogRecordHeader {
+        rec_type,
+        expire,
+        compression,
+        encrypted: type_byte & LOG_RECORD_ENCRYPTION_FLAG != 0,
+        sequence,
+        timestamp,
+        keyspace,
+        key_size,
+        value_size,
+        header_size,
+    })
 }
 
 #[cfg(test)]
@@ -182,18 +723,19 @@ mod tests {
 
     #[test]
     fn test_new_data_file() {
-        let dir_path = std::env::temp_dir();
-        let data_file_res1 = DataFile::new(dir_path.clone(), 0, IOType::StandardFIO);
+        let mut opts = Options::default();
+        opts.dir_path = std::env::temp_dir();
+        let data_file_res1 = DataFile::new(&opts, 0, IOType::StandardFIO);
         assert!(data_file_res1.is_ok());
         let data_file1 = data_file_res1.unwrap();
         assert_eq!(data_file1.get_file_id(), 0);
 
-        let data_file_res2 = DataFile::new(dir_path.clone(), 0, IOType::StandardFIO);
+        let data_file_res2 = DataFile::new(&opts, 0, IOType::StandardFIO);
         assert!(data_file_res2.is_ok());
         let data_file2 = data_file_res2.unwrap();
         assert_eq!(data_file2.get_file_id(), 0);
 
-        let data_file_res3 = DataFile::new(dir_path.clone(), 660, IOType::StandardFIO);
+        let data_file_res3 = DataFile::new(&opts, 660, IOType::StandardFIO);
         assert!(data_file_res3.is_ok());
         let data_file3 = data_file_res3.unwrap();
         assert_eq!(data_file3.get_file_id(), 660);
@@ -201,8 +743,9 @@ mod tests {
 
     #[test]
     fn test_data_file_write() {
-        let dir_path = std::env::temp_dir();
-        let data_file_res1 = DataFile::new(dir_path.clone(), 100, IOType::StandardFIO);
+        let mut opts = Options::default();
+        opts.dir_path = std::env::temp_dir();
+        let data_file_res1 = DataFile::new(&opts, 100, IOType::StandardFIO);
         assert!(data_file_res1.is_ok());
         let data_file1 = data_file_res1.unwrap();
         assert_eq!(data_file1.get_file_id(), 100);
@@ -222,8 +765,9 @@ mod tests {
 
     #[test]
     fn test_data_file_sync() {
-        let dir_path = std::env::temp_dir();
-        let data_file_res1 = DataFile::new(dir_path.clone(), 200, IOType::StandardFIO);
+        let mut opts = Options::default();
+        opts.dir_path = std::env::temp_dir();
+        let data_file_res1 = DataFile::new(&opts, 200, IOType::StandardFIO);
         assert!(data_file_res1.is_ok());
         let data_file1 = data_file_res1.unwrap();
         assert_eq!(data_file1.get_file_id(), 200);
@@ -234,8 +778,9 @@ mod tests {
 
     #[test]
     fn test_data_file_read_log_record() {
-        let dir_path = std::env::temp_dir();
-        let data_file_res1 = DataFile::new(dir_path.clone(), 700, IOType::StandardFIO);
+        let mut opts = Options::default();
+        opts.dir_path = std::env::temp_dir();
+        let data_file_res1 = DataFile::new(&opts, 700, IOType::StandardFIO);
         assert!(data_file_res1.is_ok());
         let data_file1 = data_file_res1.unwrap();
         assert_eq!(data_file1.get_file_id(), 700);
@@ -244,12 +789,19 @@ mod tests {
             key: "name".as_bytes().to_vec(),
             value: "bitcask-rs-kv".as_bytes().to_vec(),
             rec_type: LogRecordType::NORMAL,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
         };
         let write_res1 = data_file1.write(&enc1.encode());
         assert!(write_res1.is_ok());
 
-        // 从起始位置读取
-        let read_res1 = data_file1.read_log_record(0);
+        // 从第一条数据的位置读取
+        let start = data_file1.header_size();
+        let read_res1 = data_file1.read_log_record(start);
         assert!(read_res1.is_ok());
         let read_enc1 = read_res1.ok().unwrap().record;
         assert_eq!(enc1.key, read_enc1.key);
@@ -261,11 +813,17 @@ mod tests {
             key: "name".as_bytes().to_vec(),
             value: "new-value".as_bytes().to_vec(),
             rec_type: LogRecordType::NORMAL,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
         };
         let write_res2 = data_file1.write(&enc2.encode());
         assert!(write_res2.is_ok());
 
-        let read_res2 = data_file1.read_log_record(24);
+        let read_res2 = data_file1.read_log_record(start + 24);
         assert!(read_res2.is_ok());
         let read_enc2 = read_res2.ok().unwrap().record;
         assert_eq!(enc2.key, read_enc2.key);
@@ -277,15 +835,318 @@ mod tests {
             key: "name".as_bytes().to_vec(),
             value: Default::default(),
             rec_type: LogRecordType::DELETED,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
         };
         let write_res3 = data_file1.write(&enc3.encode());
         assert!(write_res3.is_ok());
 
-        let read_res3 = data_file1.read_log_record(44);
+        let read_res3 = data_file1.read_log_record(start + 44);
         assert!(read_res3.is_ok());
         let read_enc3 = read_res3.ok().unwrap().record;
         assert_eq!(enc3.key, read_enc3.key);
         assert_eq!(enc3.value, read_enc3.value);
         assert_eq!(enc3.rec_type, read_enc3.rec_type);
     }
+
+    #[test]
+    fn test_data_file_read_log_record_with_expire() {
+        let mut opts = Options::default();
+        opts.dir_path = std::env::temp_dir();
+        let data_file_res1 = DataFile::new(&opts, 800, IOType::StandardFIO);
+        assert!(data_file_res1.is_ok());
+        let data_file1 = data_file_res1.unwrap();
+        assert_eq!(data_file1.get_file_id(), 800);
+
+        let enc1 = LogRecord {
+            key: "name".as_bytes().to_vec(),
+            value: "bitcask-rs-kv".as_bytes().to_vec(),
+            rec_type: LogRecordType::NORMAL,
+            expire: 1718000000000,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
+        };
+        let write_res1 = data_file1.write(&enc1.encode());
+        assert!(write_res1.is_ok());
+
+        // 没有过期时间的数据和带有过期时间的数据混合存储
+        let enc2 = LogRecord {
+            key: "name".as_bytes().to_vec(),
+            value: "new-value".as_bytes().to_vec(),
+            rec_type: LogRecordType::NORMAL,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
+        };
+        let write_res2 = data_file1.write(&enc2.encode());
+        assert!(write_res2.is_ok());
+
+        let start = data_file1.header_size();
+        let read_res1 = data_file1.read_log_record(start);
+        assert!(read_res1.is_ok());
+        let read_rec1 = read_res1.ok().unwrap();
+        assert_eq!(enc1.key, read_rec1.record.key);
+        assert_eq!(enc1.value, read_rec1.record.value);
+        assert_eq!(enc1.rec_type, read_rec1.record.rec_type);
+        assert_eq!(enc1.expire, read_rec1.record.expire);
+        assert_eq!(enc1.encode().len(), read_rec1.size);
+
+        let read_res2 = data_file1.read_log_record(start + read_rec1.size as u64);
+        assert!(read_res2.is_ok());
+        let read_enc2 = read_res2.ok().unwrap().record;
+        assert_eq!(enc2.value, read_enc2.value);
+        assert_eq!(0, read_enc2.expire);
+
+        std::fs::remove_file(get_data_file_name(&opts, 800)).expect("failed to remove file");
+    }
+
+    #[test]
+    fn test_data_file_read_log_record_with_sequence() {
+        let mut opts = Options::default();
+        opts.dir_path = std::env::temp_dir();
+        let data_file1 = DataFile::new(&opts, 801, IOType::StandardFIO).unwrap();
+
+        let enc1 = LogRecord {
+            key: "name".as_bytes().to_vec(),
+            value: "bitcask-rs-kv".as_bytes().to_vec(),
+            rec_type: LogRecordType::NORMAL,
+            expire: 1718000000000,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 42,
+            timestamp: 1717000000000,
+            keyspace: 300,
+        };
+        let write_res1 = data_file1.write(&enc1.encode());
+        assert!(write_res1.is_ok());
+
+        // 没有序列号的数据和带有序列号的数据混合存储
+        let enc2 = LogRecord {
+            key: "name".as_bytes().to_vec(),
+            value: "new-value".as_bytes().to_vec(),
+            rec_type: LogRecordType::DELETED,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
+        };
+        let write_res2 = data_file1.write(&enc2.encode());
+        assert!(write_res2.is_ok());
+
+        let start = data_file1.header_size();
+        let read_rec1 = data_file1.read_log_record(start).unwrap();
+        assert_eq!(enc1.value, read_rec1.record.value);
+        assert_eq!(enc1.expire, read_rec1.record.expire);
+        assert_eq!(42, read_rec1.record.sequence);
+        assert_eq!(1717000000000, read_rec1.record.timestamp);
+        assert_eq!(enc1.encode().len(), read_rec1.size);
+        let header1 = data_file1.read_log_record_header(start).unwrap();
+        assert_eq!(1717000000000, header1.timestamp);
+        assert_eq!(300, read_rec1.record.keyspace);
+        assert_eq!(300, header1.keyspace);
+        assert_eq!(enc1.encoded_length(Checksum::Crc32), read_rec1.size);
+
+        let read_rec2 = data_file1
+            .read_log_record(start + read_rec1.size as u64)
+            .unwrap();
+        assert_eq!(LogRecordType::DELETED, read_rec2.record.rec_type);
+        assert_eq!(0, read_rec2.record.sequence);
+        assert_eq!(0, read_rec2.record.timestamp);
+        assert_eq!(0, read_rec2.record.keyspace);
+
+        std::fs::remove_file(get_data_file_name(&opts, 801)).expect("failed to remove file");
+    }
+
+    #[test]
+    fn test_data_file_header() {
+        let mut opts = Options::default();
+        opts.dir_path = std::env::temp_dir();
+        let file_name = get_data_file_name(&opts, 900);
+        let _ = std::fs::remove_file(&file_name);
+
+        // 新建的数据文件写入头部，数据从头部之后开始写入
+        let data_file1 = DataFile::new(&opts, 900, IOType::StandardFIO).unwrap();
+        assert_eq!(data_file1.header_size(), DATA_FILE_HEADER_SIZE);
+        assert_eq!(data_file1.get_write_off(), DATA_FILE_HEADER_SIZE);
+        assert_eq!(data_file1.file_size(), DATA_FILE_HEADER_SIZE);
+        let header = std::fs::read(&file_name).unwrap();
+        assert_eq!(header[..4], DATA_FILE_MAGIC);
+        assert_eq!(header[4..8], DATA_FILE_VERSION.to_be_bytes());
+        assert_eq!(header[8], Checksum::Crc32 as u8);
+
+        // 重新打开时校验头部
+        let data_file2 = DataFile::new(&opts, 900, IOType::StandardFIO).unwrap();
+        assert_eq!(data_file2.header_size(), DATA_FILE_HEADER_SIZE);
+
+        // 不支持的版本号
+        let mut header = DATA_FILE_MAGIC.to_vec();
+        header.extend_from_slice(&(DATA_FILE_VERSION + 1).to_be_bytes());
+        std::fs::write(&file_name, &header).unwrap();
+        let res1 = DataFile::new(&opts, 900, IOType::StandardFIO);
+        assert_eq!(res1.err().unwrap(), Errors::UnknownFileFormat);
+
+        // 不是数据文件
+        std::fs::write(&file_name, "not a bitcask data file").unwrap();
+        let res2 = DataFile::new(&opts, 900, IOType::StandardFIO);
+        assert_eq!(res2.err().unwrap(), Errors::UnknownFileFormat);
+
+        std::fs::remove_file(&file_name).expect("failed to remove file");
+    }
+
+    #[test]
+    fn test_data_file_without_header() {
+        let mut opts = Options::default();
+        opts.dir_path = std::env::temp_dir();
+        let file_name = get_data_file_name(&opts, 901);
+
+        // 旧版本的数据文件没有头部，视为版本 0，数据从起始位置开始
+        let rec1 = LogRecord {
+            key: "name".as_bytes().to_vec(),
+            value: "bitcask-rs-kv".as_bytes().to_vec(),
+            rec_type: LogRecordType::NORMAL,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
+        };
+        std::fs::write(&file_name, rec1.encode()).unwrap();
+
+        let data_file1 = DataFile::new(&opts, 901, IOType::StandardFIO).unwrap();
+        assert_eq!(data_file1.header_size(), 0);
+        let read_res1 = data_file1.read_log_record(0);
+        assert!(read_res1.is_ok());
+        assert_eq!(rec1.value, read_res1.unwrap().record.value);
+
+        std::fs::remove_file(&file_name).expect("failed to remove file");
+    }
+
+    #[test]
+    fn test_data_file_checksum() {
+        let mut opts = Options::default();
+        opts.dir_path = std::env::temp_dir();
+        opts.checksum = Checksum::XxHash64;
+        let file_name = get_data_file_name(&opts, 902);
+        let _ = std::fs::remove_file(&file_name);
+
+        // 新建的数据文件在头部记录校验算法
+        let data_file1 = DataFile::new(&opts, 902, IOType::StandardFIO).unwrap();
+        assert_eq!(Checksum::XxHash64, data_file1.checksum());
+        let rec1 = LogRecord {
+            key: "name".as_bytes().to_vec(),
+            value: "bitcask-rs-kv".as_bytes().to_vec(),
+            rec_type: LogRecordType::NORMAL,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
+        };
+        let enc1 = rec1.encode_with_checksum(data_file1.checksum());
+        let write_res1 = data_file1.write(&enc1);
+        assert!(write_res1.is_ok());
+
+        // 重新打开时使用头部记录的校验算法，和配置项无关
+        opts.checksum = Checksum::Crc32;
+        let data_file2 = DataFile::new(&opts, 902, IOType::StandardFIO).unwrap();
+        assert_eq!(Checksum::XxHash64, data_file2.checksum());
+        let read_res1 = data_file2.read_log_record(DATA_FILE_HEADER_SIZE).unwrap();
+        assert_eq!(rec1.value, read_res1.record.value);
+        assert_eq!(enc1.len(), read_res1.size);
+
+        // 版本 1 的数据文件使用 crc32 校验
+        let mut header = DATA_FILE_MAGIC.to_vec();
+        header.extend_from_slice(&1u32.to_be_bytes());
+        header.extend_from_slice(&rec1.encode());
+        std::fs::write(&file_name, &header).unwrap();
+        let data_file3 = DataFile::new(&opts, 902, IOType::StandardFIO).unwrap();
+        assert_eq!(Checksum::Crc32, data_file3.checksum());
+        assert_eq!(DATA_FILE_HEADER_SIZE_V1, data_file3.header_size());
+        let read_res2 = data_file3
+            .read_log_record(DATA_FILE_HEADER_SIZE_V1)
+            .unwrap();
+        assert_eq!(rec1.value, read_res2.record.value);
+
+        // 未知的校验算法
+        let mut header = DATA_FILE_MAGIC.to_vec();
+        header.extend_from_slice(&DATA_FILE_VERSION.to_be_bytes());
+        header.push(9);
+        std::fs::write(&file_name, &header).unwrap();
+        let res1 = DataFile::new(&opts, 902, IOType::StandardFIO);
+        assert_eq!(res1.err().unwrap(), Errors::UnknownFileFormat);
+
+        std::fs::remove_file(&file_name).expect("failed to remove file");
+    }
+
+    #[test]
+    fn test_data_file_corrupted_record() {
+        let mut opts = Options::default();
+        opts.dir_path = std::env::temp_dir();
+        let file_name = get_data_file_name(&opts, 903);
+        let _ = std::fs::remove_file(&file_name);
+
+        let data_file1 = DataFile::new(&opts, 903, IOType::StandardFIO).unwrap();
+        let rec1 = LogRecord {
+            key: "name".as_bytes().to_vec(),
+            value: "bitcask-rs-kv".as_bytes().to_vec(),
+            rec_type: LogRecordType::NORMAL,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
+        };
+        let enc1 = rec1.encode();
+        let write_res1 = data_file1.write(&enc1);
+        assert!(write_res1.is_ok());
+
+        // 追加一个记录了巨大 value 长度的 header，模拟崩溃时没有完整写入的数据
+        let mut header = BytesMut::new();
+        header.extend_from_slice(&[LogRecordType::NORMAL as u8]);
+        prost::encode_length_delimiter(4, &mut header).unwrap();
+        prost::encode_length_delimiter(u32::MAX as usize, &mut header).unwrap();
+        header.extend_from_slice(b"name");
+        let write_res2 = data_file1.write(&header);
+        assert!(write_res2.is_ok());
+
+        // 完整的数据可以正常读取，之后的数据返回错误而不是读取到无效的数据
+        let start = data_file1.header_size();
+        let read_res1 = data_file1.read_log_record(start).unwrap();
+        assert_eq!(rec1.value, read_res1.record.value);
+        let offset = start + enc1.len() as u64;
+        assert_eq!(
+            Errors::CorruptedRecord {
+                file_id: 903,
+                offset
+            },
+            data_file1.read_log_record(offset).err().unwrap()
+        );
+        assert_eq!(
+            Errors::CorruptedRecord {
+                file_id: 903,
+                offset
+            },
+            data_file1
+                .read_log_record_with_crc(offset, false)
+                .err()
+                .unwrap()
+        );
+
+        std::fs::remove_file(&file_name).expect("failed to remove file");
+    }
 }
@@ -1,10 +1,40 @@
 use bytes::{BufMut, BytesMut};
+use log::error;
 use prost::{
     encode_length_delimiter,
-    encoding::{decode_varint, encode_varint},
+    encoding::{decode_varint, encode_varint, encoded_len_varint},
     length_delimiter_len,
 };
 
+use crate::{
+    batch::parse_log_record_key,
+    error::{Errors, Result},
+    option::{Checksum, Compression},
+};
+
+// type 字段低 2 位存储记录类型，其余的位作为标识位
+pub(crate) const LOG_RECORD_TYPE_MASK: u8 = 0x03;
+
+// 标识位：记录带有写入时间，旧版本的记录类型不超过 3，这一位始终为 0
+pub(crate) const LOG_RECORD_TIMESTAMP_FLAG: u8 = 0x04;
+
+// 标识位：记录带有写入序列号
+pub(crate) const LOG_RECORD_SEQUENCE_FLAG: u8 = 0x08;
+
+// keyspace 标识：header 以 0 开头时，之后是变长编码的 keyspace id，再之后才是 type
+// 记录类型不会为 0，默认的 keyspace 不存储这部分，旧版本的数据可以直接兼容读取
+pub(crate) const LOG_RECORD_KEYSPACE_MARKER: u8 = 0x00;
+
+// 标识位：记录带有过期时间
+pub(crate) const LOG_RECORD_EXPIRE_FLAG: u8 = 0x80;
+
+// 标识位：value 使用的压缩算法，占用两位
+pub(crate) const LOG_RECORD_COMPRESSION_MASK: u8 = 0x30;
+const LOG_RECORD_COMPRESSION_SHIFT: u8 = 4;
+
+// 标识位：value 经过加密
+pub(crate) const LOG_RECORD_ENCRYPTION_FLAG: u8 = 0x40;
+
 #[derive(Clone, Copy, Debug)]
 pub struct LogRecordPos {
     pub(crate) file_id: u32, // 文件 id，表示将数据存储到了哪个文件当中
@@ -28,6 +58,12 @@ pub struct LogRecord {
     pub(crate) key: Vec<u8>,
     pub(crate) value: Vec<u8>,
     pub(crate) rec_type: LogRecordType,
+    pub(crate) expire: u64, // 过期时间，unix 毫秒时间戳，0 表示永不过期
+    pub(crate) compression: Compression, // value 的压缩算法，value 中存储的是压缩后的数据
+    pub(crate) encrypted: bool, // value 是否经过加密，先压缩再加密，加密后的 value 以 nonce 开头
+    pub(crate) sequence: u64, // 写入序列号，每次成功的写入单调递增，0 表示没有序列号
+    pub(crate) timestamp: u64, // 写入时间，unix 毫秒时间戳，0 表示没有记录写入时间
+    pub(crate) keyspace: u32, // 所属的 keyspace id，0 表示默认的 keyspace
 }
 
 // 从数据文件中读取的 log_record 信息，包含其 size
@@ -37,33 +73,137 @@ pub struct ReadLogRecord {
     pub(crate) size: usize,
 }
 
+// 从数据文件中读取的 LogRecord header 信息，不包含 key 和 value
+pub(crate) struct LogRecordHeader {
+    pub(crate) rec_type: LogRecordType,
+    pub(crate) expire: u64,
+    pub(crate) compression: Compression,
+    pub(crate) encrypted: bool,
+    pub(crate) key_size: usize,
+    pub(crate) value_size: usize,
+    pub(crate) sequence: u64,
+    pub(crate) timestamp: u64,
+    pub(crate) keyspace: u32,
+    pub(crate) header_size: usize, // header 实际占据的长度
+}
+
 // 暂存事务数据信息
 pub struct TransactionRecord {
     pub(crate) record: LogRecord,
     pub(crate) pos: LogRecordPos,
 }
 
-//	+----------+-------------------------+----------------------+--------------+--------------+--------+
-//	|  type    |    key size             |   value size         |       key    |      value   |  crc32   |
-//	+----------+-------------------------+----------------------+--------------+--------------+--------+
-//	  1byte       varint（max size 5）       varint（max size 5）     key len      value len      4byte
+//	+------------------------+----------+--------------------+-------------------------+----------------------+--------------+--------------+--------+
+//	|  keyspace（可选）         |  type    |   expire（可选）     |    key size             |   value size         |       key    |      value   |  crc   |
+//	+------------------------+----------+--------------------+-------------------------+----------------------+--------------+--------------+--------+
+//	  1byte + varint（max size 5） 1byte      varint（max size 10）   varint（max size 5）       varint（max size 5）     key len      value len   4byte/8byte
+//
+// type 的最高位标识是否存储了 expire 字段，第 7 位标识 value 是否加密，第 5、6 位标识 value 的压缩算法
+// 第 4 位标识是否存储了写入序列号，第 3 位标识是否存储了写入时间，两者都是 varint，依次存储在 expire 之后
+// 旧版本的数据没有这些标识，可以直接兼容读取
+// 不属于默认 keyspace 的记录在 type 之前存储一个 0 字节以及 keyspace id
+// 最后的校验值使用数据文件头部记录的算法，crc32 占用 4 个字节，xxhash64 占用 8 个字节
 impl LogRecord {
+    // 数据文件中存储的 key，事务中写入的 key 以事务序列号开头
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    // 解析出用户写入的 key 以及写入时的事务序列号，不在事务中写入的数据序列号为 0
+    pub fn parse_key(&self) -> (Vec<u8>, usize) {
+        parse_log_record_key(self.key.clone())
+    }
+
+    // 数据文件中存储的 value，可能经过压缩和加密
+    pub fn value(&self) -> &[u8] {
+        &self.value
+    }
+
+    // 记录类型
+    pub fn rec_type(&self) -> LogRecordType {
+        self.rec_type
+    }
+
+    // 过期时间，unix 毫秒时间戳，0 表示永不过期
+    pub fn expire(&self) -> u64 {
+        self.expire
+    }
+
+    // value 的压缩算法
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    // value 是否经过加密
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    // 写入序列号，0 表示没有序列号
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    // 写入时间，unix 毫秒时间戳，0 表示没有记录写入时间
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    // 所属的 keyspace id，0 表示默认的 keyspace
+    pub fn keyspace(&self) -> u32 {
+        self.keyspace
+    }
+
     pub fn encode(&self) -> Vec<u8> {
-        let (enc_buf, _) = self.encode_and_get_crc();
+        self.encode_with_checksum(Checksum::Crc32)
+    }
+
+    // 使用指定的校验算法编码
+    pub fn encode_with_checksum(&self, checksum: Checksum) -> Vec<u8> {
+        let (enc_buf, _) = self.encode_and_get_checksum(checksum);
         enc_buf
     }
 
-    pub fn get_crc(&self) -> u32 {
-        let (_, crc_value) = self.encode_and_get_crc();
-        crc_value
+    // 使用指定的校验算法计算校验值
+    pub fn get_checksum(&self, checksum: Checksum) -> u64 {
+        let (_, checksum_value) = self.encode_and_get_checksum(checksum);
+        checksum_value
     }
 
-    fn encode_and_get_crc(&self) -> (Vec<u8>, u32) {
+    fn encode_and_get_checksum(&self, checksum: Checksum) -> (Vec<u8>, u64) {
         let mut buf = BytesMut::new();
-        buf.reserve(self.encoded_length());
+        buf.reserve(self.encoded_length(checksum));
 
-        // 先存入type
-        buf.put_u8(self.rec_type as u8);
+        // 不属于默认 keyspace 时先存入 keyspace 标识和 id
+        if self.keyspace > 0 {
+            buf.put_u8(LOG_RECORD_KEYSPACE_MARKER);
+            encode_varint(self.keyspace as u64, &mut buf);
+        }
+
+        // 先存入type，有过期时间则设置标识位并存储过期时间，之后是写入序列号
+        let mut type_byte = self.rec_type as u8 | compression_to_flag(self.compression);
+        if self.expire > 0 {
+            type_byte |= LOG_RECORD_EXPIRE_FLAG;
+        }
+        if self.encrypted {
+            type_byte |= LOG_RECORD_ENCRYPTION_FLAG;
+        }
+        if self.sequence > 0 {
+            type_byte |= LOG_RECORD_SEQUENCE_FLAG;
+        }
+        if self.timestamp > 0 {
+            type_byte |= LOG_RECORD_TIMESTAMP_FLAG;
+        }
+        buf.put_u8(type_byte);
+        if self.expire > 0 {
+            encode_varint(self.expire, &mut buf);
+        }
+        if self.sequence > 0 {
+            encode_varint(self.sequence, &mut buf);
+        }
+        if self.timestamp > 0 {
+            encode_varint(self.timestamp, &mut buf);
+        }
 
         // 再存入变长的key和value长度
         encode_length_delimiter(self.key.len(), &mut buf).expect("encode key len error");
@@ -73,37 +213,109 @@ impl LogRecord {
         buf.extend_from_slice(&self.key);
         buf.extend_from_slice(&self.value);
 
-        // 最后存储crc校验值
-        let mut hasher = crc32fast::Hasher::new();
-        hasher.update(&buf);
-        let crc = hasher.finalize();
-        buf.put_u32(crc);
+        // 最后存储校验值
+        let checksum_value = compute_checksum(checksum, &buf);
+        match checksum {
+            Checksum::Crc32 => buf.put_u32(checksum_value as u32),
+            Checksum::XxHash64 => buf.put_u64(checksum_value),
+            Checksum::None => {}
+        }
 
-        (buf.to_vec(), crc)
+        (buf.to_vec(), checksum_value)
     }
 
-    fn encoded_length(&self) -> usize {
+    // 使用指定的校验算法编码之后的长度
+    pub(crate) fn encoded_length(&self, checksum: Checksum) -> usize {
+        let expire_len = match self.expire > 0 {
+            true => encoded_len_varint(self.expire),
+            false => 0,
+        };
+        let sequence_len = match self.sequence > 0 {
+            true => encoded_len_varint(self.sequence),
+            false => 0,
+        };
+        let timestamp_len = match self.timestamp > 0 {
+            true => encoded_len_varint(self.timestamp),
+            false => 0,
+        };
+        let keyspace_len = match self.keyspace > 0 {
+            true => std::mem::size_of::<u8>() + encoded_len_varint(self.keyspace as u64),
+            false => 0,
+        };
         std::mem::size_of::<u8>()
+            + keyspace_len
+            + expire_len
+            + sequence_len
+            + timestamp_len
             + length_delimiter_len(self.key.len())
             + length_delimiter_len(self.value.len())
             + self.key.len()
             + self.value.len()
-            + std::mem::size_of::<u32>()
+            + checksum_len(checksum)
+    }
+
+    // 判断数据是否已经过期
+    pub(crate) fn is_expired(&self, now: u64) -> bool {
+        self.expire > 0 && self.expire <= now
     }
 }
 
-impl From<u8> for LogRecordType {
-    fn from(value: u8) -> Self {
+impl ReadLogRecord {
+    // 读取到的 LogRecord
+    pub fn record(&self) -> &LogRecord {
+        &self.record
+    }
+
+    // LogRecord 在数据文件中占据的大小，加上读取的位置即为下一条数据的位置
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl TryFrom<u8> for LogRecordType {
+    type Error = Errors;
+
+    fn try_from(value: u8) -> Result<Self> {
         match value {
-            1 => LogRecordType::NORMAL,
-            2 => LogRecordType::DELETED,
-            3 => LogRecordType::TXNFINISHED,
-            _ => panic!("unknown log record type"),
+            1 => Ok(LogRecordType::NORMAL),
+            2 => Ok(LogRecordType::DELETED),
+            3 => Ok(LogRecordType::TXNFINISHED),
+            _ => Err(Errors::InvalidLogRecordType(value)),
         }
     }
 }
 
+// 将压缩算法转换为 type 中的标识位
+fn compression_to_flag(compression: Compression) -> u8 {
+    (compression as u8) << LOG_RECORD_COMPRESSION_SHIFT
+}
+
+// 从 type 中的标识位解析压缩算法
+pub(crate) fn compression_from_flag(type_byte: u8) -> Result<Compression> {
+    match (type_byte & LOG_RECORD_COMPRESSION_MASK) >> LOG_RECORD_COMPRESSION_SHIFT {
+        0 => Ok(Compression::None),
+        1 => Ok(Compression::Lz4),
+        2 => Ok(Compression::Zstd),
+        _ => Err(Errors::InvalidLogRecordType(type_byte)),
+    }
+}
+
 impl LogRecordPos {
+    // 数据所在的文件 id
+    pub fn file_id(&self) -> u32 {
+        self.file_id
+    }
+
+    // 数据在文件中的偏移
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    // 数据在磁盘上占据的空间大小
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
     pub fn encode(&self) -> Vec<u8> {
         let mut buf = BytesMut::new();
         encode_varint(self.file_id as u64, &mut buf);
@@ -137,33 +349,75 @@ impl LogRecordPos {
 }
 
 // 解码 LogRecordPos
-pub fn decode_log_record_pos(pos: Vec<u8>) -> LogRecordPos {
+pub fn decode_log_record_pos(pos: Vec<u8>) -> Result<LogRecordPos> {
     let mut buf = BytesMut::new();
     buf.put_slice(&pos);
 
     let fid = match decode_varint(&mut buf) {
         Ok(fid) => fid,
-        Err(e) => panic!("decode log record pos err: {}", e),
+        Err(e) => {
+            error!("decode log record pos err: {}", e);
+            return Err(Errors::FailedToDecodeLogRecordPos);
+        }
     };
     let offset = match decode_varint(&mut buf) {
         Ok(offset) => offset,
-        Err(e) => panic!("decode log record pos err: {}", e),
+        Err(e) => {
+            error!("decode log record pos err: {}", e);
+            return Err(Errors::FailedToDecodeLogRecordPos);
+        }
     };
     let size = match decode_varint(&mut buf) {
         Ok(size) => size,
-        Err(e) => panic!("decode log record pos err: {}", e),
+        Err(e) => {
+            error!("decode log record pos err: {}", e);
+            return Err(Errors::FailedToDecodeLogRecordPos);
+        }
     };
-    LogRecordPos {
+    Ok(LogRecordPos {
         file_id: fid as u32,
         offset,
         size: size as u32,
+    })
+}
+
+// 校验值占用的字节数
+pub(crate) fn checksum_len(checksum: Checksum) -> usize {
+    match checksum {
+        Checksum::Crc32 => std::mem::size_of::<u32>(),
+        Checksum::XxHash64 => std::mem::size_of::<u64>(),
+        Checksum::None => 0,
+    }
+}
+
+// 使用指定的校验算法计算数据的校验值
+pub(crate) fn compute_checksum(checksum: Checksum, buf: &[u8]) -> u64 {
+    match checksum {
+        Checksum::Crc32 => crc32fast::hash(buf) as u64,
+        Checksum::XxHash64 => xxhash_rust::xxh64::xxh64(buf, 0),
+        Checksum::None => 0,
+    }
+}
+
+// 从数据文件头部存储的值中解析校验算法
+pub(crate) fn checksum_from_u8(value: u8) -> Result<Checksum> {
+    match value {
+        0 => Ok(Checksum::Crc32),
+        1 => Ok(Checksum::XxHash64),
+        2 => Ok(Checksum::None),
+        _ => Err(Errors::UnknownFileFormat),
     }
 }
 
 // 获取 LogRecord header 部分的最大长度
 pub fn max_log_record_header_size() -> usize {
-    // 1byte + 5byte + 5byte
+    // 1byte + 5byte + 1byte + 10byte + 10byte + 10byte + 5byte + 5byte
     std::mem::size_of::<u8>()
+        + encoded_len_varint(u32::MAX as u64)
+        + std::mem::size_of::<u8>()
+        + encoded_len_varint(u64::MAX)
+        + encoded_len_varint(u64::MAX)
+        + encoded_len_varint(u64::MAX)
         + length_delimiter_len(std::u32::MAX as usize)
         + length_delimiter_len(std::u32::MAX as usize)
 }
@@ -179,29 +433,203 @@ mod tests {
             key: "name".as_bytes().to_vec(),
             value: "bitcask-rs".as_bytes().to_vec(),
             rec_type: LogRecordType::NORMAL,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
         };
         let enc1 = rec1.encode();
         assert!(enc1.len() > 5);
-        assert_eq!(1020360578, rec1.get_crc());
+        assert_eq!(1020360578, rec1.get_checksum(Checksum::Crc32));
 
         // LogRecord 的 value 为空
         let rec2 = LogRecord {
             key: "name".as_bytes().to_vec(),
             value: Default::default(),
             rec_type: LogRecordType::NORMAL,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
         };
         let enc2 = rec2.encode();
         assert!(enc2.len() > 5);
-        assert_eq!(3756865478, rec2.get_crc());
+        assert_eq!(3756865478, rec2.get_checksum(Checksum::Crc32));
 
         // 类型为 Deleted 的情况
         let rec3 = LogRecord {
             key: "name".as_bytes().to_vec(),
             value: "bitcask-rs".as_bytes().to_vec(),
             rec_type: LogRecordType::DELETED,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
         };
         let enc3 = rec3.encode();
         assert!(enc3.len() > 5);
-        assert_eq!(1867197446, rec3.get_crc());
+        assert_eq!(1867197446, rec3.get_checksum(Checksum::Crc32));
+    }
+
+    #[test]
+    fn test_log_record_pos_encode_and_decode() {
+        // 正常编解码
+        let pos1 = LogRecordPos {
+            file_id: 12,
+            offset: 1024 * 1024,
+            size: 87,
+        };
+        let dec_res1 = decode_log_record_pos(pos1.encode());
+        assert!(dec_res1.is_ok());
+        let dec_pos1 = dec_res1.unwrap();
+        assert_eq!(pos1.file_id, dec_pos1.file_id);
+        assert_eq!(pos1.offset, dec_pos1.offset);
+        assert_eq!(pos1.size, dec_pos1.size);
+
+        // 数据为空
+        let dec_res2 = decode_log_record_pos(Vec::new());
+        assert_eq!(Errors::FailedToDecodeLogRecordPos, dec_res2.err().unwrap());
+
+        // 数据被截断
+        let mut enc2 = pos1.encode();
+        enc2.truncate(2);
+        let dec_res3 = decode_log_record_pos(enc2);
+        assert_eq!(Errors::FailedToDecodeLogRecordPos, dec_res3.err().unwrap());
+
+        // 无效的数据
+        let dec_res4 = decode_log_record_pos(vec![0xff; 11]);
+        assert_eq!(Errors::FailedToDecodeLogRecordPos, dec_res4.err().unwrap());
+    }
+
+    #[test]
+    fn test_log_record_type_try_from() {
+        assert_eq!(LogRecordType::NORMAL, LogRecordType::try_from(1u8).unwrap());
+        assert_eq!(
+            LogRecordType::DELETED,
+            LogRecordType::try_from(2u8).unwrap()
+        );
+        assert_eq!(
+            LogRecordType::TXNFINISHED,
+            LogRecordType::try_from(3u8).unwrap()
+        );
+
+        // 未知的类型
+        let res1 = LogRecordType::try_from(4u8);
+        assert_eq!(Errors::InvalidLogRecordType(4), res1.err().unwrap());
+        let res2 = LogRecordType::try_from(0u8);
+        assert_eq!(Errors::InvalidLogRecordType(0), res2.err().unwrap());
+    }
+
+    #[test]
+    fn test_log_record_encode_with_expire() {
+        let rec1 = LogRecord {
+            key: "name".as_bytes().to_vec(),
+            value: "bitcask-rs".as_bytes().to_vec(),
+            rec_type: LogRecordType::NORMAL,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
+        };
+        let rec2 = LogRecord {
+            key: "name".as_bytes().to_vec(),
+            value: "bitcask-rs".as_bytes().to_vec(),
+            rec_type: LogRecordType::NORMAL,
+            expire: 1718000000000,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
+        };
+        let enc1 = rec1.encode();
+        let enc2 = rec2.encode();
+
+        // 设置了过期时间的数据带有标识位，并且多存储了过期时间
+        assert_eq!(enc1[0], LogRecordType::NORMAL as u8);
+        assert_eq!(
+            enc2[0],
+            LogRecordType::NORMAL as u8 | LOG_RECORD_EXPIRE_FLAG
+        );
+        assert_eq!(enc2.len(), enc1.len() + encoded_len_varint(rec2.expire));
+        assert_eq!(enc2.len(), rec2.encoded_length(Checksum::Crc32));
+        assert_ne!(
+            rec1.get_checksum(Checksum::Crc32),
+            rec2.get_checksum(Checksum::Crc32)
+        );
+
+        assert!(!rec1.is_expired(u64::MAX));
+        assert!(!rec2.is_expired(rec2.expire - 1));
+        assert!(rec2.is_expired(rec2.expire));
+    }
+
+    #[test]
+    fn test_log_record_compression_flag() {
+        for compression in [Compression::None, Compression::Lz4, Compression::Zstd] {
+            for encrypted in [false, true] {
+                let rec = LogRecord {
+                    key: "name".as_bytes().to_vec(),
+                    value: "bitcask-rs".as_bytes().to_vec(),
+                    rec_type: LogRecordType::DELETED,
+                    expire: 1718000000000,
+                    compression,
+                    encrypted,
+                    sequence: 0,
+                    timestamp: 0,
+                    keyspace: 0,
+                };
+                let enc = rec.encode();
+                assert_eq!(LOG_RECORD_EXPIRE_FLAG, enc[0] & LOG_RECORD_EXPIRE_FLAG);
+                assert_eq!(LogRecordType::DELETED as u8, enc[0] & LOG_RECORD_TYPE_MASK);
+                assert_eq!(compression, compression_from_flag(enc[0]).unwrap());
+                assert_eq!(encrypted, enc[0] & LOG_RECORD_ENCRYPTION_FLAG != 0);
+            }
+        }
+
+        // 未知的压缩算法
+        let res = compression_from_flag(LOG_RECORD_COMPRESSION_MASK | 1);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_log_record_encode_with_checksum() {
+        let rec = LogRecord {
+            key: "name".as_bytes().to_vec(),
+            value: "bitcask-rs".as_bytes().to_vec(),
+            rec_type: LogRecordType::NORMAL,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
+        };
+        let enc1 = rec.encode_with_checksum(Checksum::Crc32);
+        let enc2 = rec.encode_with_checksum(Checksum::XxHash64);
+        assert_eq!(rec.encode(), enc1);
+        assert_eq!(enc1.len(), rec.encoded_length(Checksum::Crc32));
+        assert_eq!(enc2.len(), rec.encoded_length(Checksum::XxHash64));
+
+        // 校验值之前的数据相同，只有末尾的校验值不同
+        assert_eq!(enc1.len() + 4, enc2.len());
+        assert_eq!(enc1[..enc1.len() - 4], enc2[..enc1.len() - 4]);
+        let hash = rec.get_checksum(Checksum::XxHash64);
+        assert_eq!(hash.to_be_bytes(), enc2[enc2.len() - 8..]);
+
+        // 未知的校验算法
+        assert_eq!(Checksum::XxHash64, checksum_from_u8(1).unwrap());
+        assert_eq!(Checksum::None, checksum_from_u8(2).unwrap());
+        assert_eq!(
+            Errors::UnknownFileFormat,
+            checksum_from_u8(3).err().unwrap()
+        );
     }
 }
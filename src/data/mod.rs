@@ -3,10 +3,17 @@ pub mod log_record;
 
 pub trait LogPosition {
     fn get_size(&self) -> u32;
+
+    // 编码位置信息，用于持久化索引
+    fn encode(&self) -> Vec<u8>;
 }
 
 impl LogPosition for log_record::LogRecordPos {
     fn get_size(&self) -> u32 {
         self.size
     }
+
+    fn encode(&self) -> Vec<u8> {
+        log_record::LogRecordPos::encode(self)
+    }
 }
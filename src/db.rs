@@ -1,32 +1,46 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs::{self, File},
-    path::PathBuf,
+    ops::Deref,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
     sync::{
-        atomic::{AtomicUsize, Ordering},
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
         Arc,
     },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use bytes::Bytes;
 use fs2::FileExt;
-use log::warn;
-use parking_lot::{Mutex, RwLock};
+use log::{info, warn};
+use parking_lot::{Condvar, Mutex, RwLock};
+use prost::decode_length_delimiter;
 
+#[cfg(feature = "mmap")]
+use crate::fileio::mmap::MappedSlice;
 use crate::{
     batch::{log_record_key_with_seq, parse_log_record_key, NON_TRANSACTION_SEQ_NO},
+    bloom::{remove_bloom_file, BloomFilters},
+    cache::{CachedValue, ValueCache},
     data::{
-        data_file::{DataFile, DATA_FILE_NAME_SUFFIX, MERGE_FINISHED_FILE_NAME, SEQ_NO_FILE_NAME},
+        data_file::{
+            get_data_file_name, parse_data_file_id, DataFile, HINT_FILE_NAME,
+            MERGE_FINISHED_FILE_NAME, SEQ_NO_FILE_NAME,
+        },
         log_record::{LogRecord, LogRecordPos, LogRecordType, TransactionRecord},
     },
     error::{Errors, Result},
-    index,
-    merge::load_merge_files,
-    option::{IOType, IndexType, Options},
+    index::{self, prefix::PrefixIndex},
+    keyspace::Keyspaces,
+    merge::{get_merge_path, load_merge_files},
+    metrics::OpKind,
+    option::{Compression, IOType, IndexType, Options, SyncMode},
+    snapshot::SnapshotOverlay,
     util,
 };
 
-const INITIAL_FILE_ID: u32 = 0;
 const SEQ_NO_KEY: &str = "seq.no";
 pub(crate) const FILE_LOCK_NAME: &str = "flock";
 
@@ -35,16 +49,88 @@ pub struct Engine {
     pub(crate) options: Arc<Options>,
     pub(crate) active_file: Arc<RwLock<DataFile>>, // 当前活跃数据文件
     pub(crate) older_files: Arc<RwLock<HashMap<u32, DataFile>>>, // 旧的数据文件
-    pub(crate) index: Box<dyn index::Index<LogRecordPos>>, // 数据内存索引
-    file_ids: Vec<u32>, // 数据库启动时的文件 id，只用于加载索引时使用，不能在其他的地方更新或使用
-    pub(crate) batch_commit_lock: Mutex<()>, // 事务提交保证串行化
+    pub(crate) index: Arc<dyn index::Index<LogRecordPos>>, // 数据内存索引
+    pub(crate) batch_commit_lock: Mutex<()>,       // 事务提交保证串行化
+    pub(crate) write_lock: RwLock<()>, // 普通写入持有读锁，先读后写的操作持有写锁保证原子性
     pub(crate) seq_no: Arc<AtomicUsize>, // 事务序列号，全局递增
-    pub(crate) merging_lock: Mutex<()>, // 防止多个线程同时 merge
-    pub(crate) seq_file_exists: bool, // 事务序列号文件是否存在
-    pub(crate) is_initial: bool, // 是否是第一次初始化该目录
-    lock_file: File,    // 文件锁，保证只能在数据目录上打开一个实例
-    bytes_write: Arc<AtomicUsize>, // 累计写入了多少字节
+    pub(crate) sequence: Arc<AtomicU64>, // 已经分配的最大的写入序列号
+    pub(crate) is_merging: AtomicBool, // 是否正在 merge，防止多个线程同时 merge
+    pub(crate) seq_file_exists: bool,  // 事务序列号文件是否存在
+    pub(crate) is_initial: bool,       // 是否是第一次初始化该目录
+    lock_file: File,                   // 文件锁，保证只能在数据目录上打开一个实例
     pub(crate) reclaim_size: Arc<AtomicUsize>, // 累计有多少空间可以 merge
+    pub(crate) value_cache: Option<ValueCache>, // 最近读取过的 value 缓存，为 None 时不使用缓存
+    pub(crate) bloom_filters: Option<BloomFilters>, // 每个数据文件的布隆过滤器，为 None 时不使用
+    pub(crate) tombstones: Option<Tombstones>, // 被删除的 key，用于区分被删除和从未写入的 key，为 None 时不记录
+    pub(crate) snapshots: RwLock<Vec<SnapshotOverlay>>, // 还没有释放的快照，修改索引之前在快照中记录旧的位置
+    pub(crate) prefix_index: Option<Arc<PrefixIndex>>, // 按照 key 前缀分组的二级索引，为 None 时不使用
+    pub(crate) keyspaces: Arc<Keyspaces>, // 除默认 keyspace 之外的所有 keyspace 以及它们的索引
+    index_state: Arc<IndexState>,         // 索引的加载状态
+    is_closed: bool,                      // 是否已经调用 close 关闭
+}
+
+// 被删除的 key 以及删除标记所在的数据文件 id
+pub(crate) type Tombstones = Arc<RwLock<HashMap<Vec<u8>, u32>>>;
+
+/// key 的状态
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyStatus {
+    // key 存在，以及对应的 value
+    Present(Bytes),
+    // key 被删除了
+    Deleted,
+    // key 从未写入过，或者已经过期
+    Absent,
+}
+
+/// get_ref 返回的 value，数据在 mmap 读取的旧数据文件中时直接引用映射区域，不拷贝数据
+/// 引用映射区域时持有映射区域的读锁，不要长期持有
+pub struct ValueRef {
+    inner: ValueRefInner,
+}
+
+enum ValueRefInner {
+    #[cfg(feature = "mmap")]
+    Mapped(MappedSlice),
+    Owned(Bytes),
+}
+
+impl ValueRef {
+    // 是否直接引用了映射区域中的数据
+    pub fn is_mapped(&self) -> bool {
+        match self.inner {
+            #[cfg(feature = "mmap")]
+            ValueRefInner::Mapped(_) => true,
+            ValueRefInner::Owned(_) => false,
+        }
+    }
+
+    // 转换为 Bytes，引用映射区域时会拷贝数据
+    pub fn to_bytes(&self) -> Bytes {
+        match &self.inner {
+            #[cfg(feature = "mmap")]
+            ValueRefInner::Mapped(value) => Bytes::copy_from_slice(value),
+            ValueRefInner::Owned(value) => value.clone(),
+        }
+    }
+}
+
+impl Deref for ValueRef {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.inner {
+            #[cfg(feature = "mmap")]
+            ValueRefInner::Mapped(value) => value,
+            ValueRefInner::Owned(value) => value,
+        }
+    }
+}
+
+impl AsRef<[u8]> for ValueRef {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
 }
 
 /// 存储引擎相关统计信息
@@ -55,8 +141,8 @@ pub struct Stat {
     // 数据文件的数量
     pub data_file_num: usize,
     // 可以回收的数据量
-    pub reclaim_size: usize,
-    // 数据目录占据的磁盘空间大小
+    pub reclaimable_size: u64,
+    // 数据文件占据的磁盘空间大小
     pub disk_size: u64,
 }
 
@@ -72,35 +158,52 @@ impl Engine {
         let options = opts.clone();
         // 判断数据目录是否存在，如果不存在的话则创建这个目录
         let dir_path = options.dir_path.clone();
+        // 路径已经存在但不是目录，例如指向了一个普通文件
+        if dir_path.exists() && !dir_path.is_dir() {
+            return Err(Errors::DataDirectoryNotDir);
+        }
         if !dir_path.is_dir() {
+            // 只读模式下只能打开已经存在的数据库
+            if options.read_only {
+                return Err(Errors::FailedToReadDatabaseDir);
+            }
             is_initial = true;
             if let Err(e) = fs::create_dir_all(dir_path.as_path()) {
                 warn!("create database directory err: {}", e);
-                return Err(Errors::FailedToCreateDatabaseDir);
+                return Err(Errors::FailedCreateDatabaseDir { kind: e.kind() });
             }
         }
 
-        // 判断数据目录是否已经被使用了
-        let lock_file = fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(dir_path.join(FILE_LOCK_NAME))
-            .unwrap();
-        if let Err(_) = lock_file.try_lock_exclusive() {
-            return Err(Errors::DatabaseIsUsing);
+        // 判断数据目录是否已经被使用了，只读模式下使用共享锁，允许多个只读实例同时打开
+        let lock_file = match open_lock_file(&dir_path, options.read_only) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("open database lock file err: {}", e);
+                return Err(e.into());
+            }
+        };
+        let lock_res = match options.read_only {
+            true => FileExt::try_lock_shared(&lock_file),
+            false => lock_file.try_lock_exclusive(),
+        };
+        if lock_res.is_err() {
+            return Err(Errors::DatabaseIsInUse);
         }
 
-        let entries = fs::read_dir(dir_path.clone()).unwrap();
-        if entries.count() == 0 {
+        if util::file::list_file_names(&options, &dir_path)?.is_empty() {
             is_initial = true;
         }
 
-        // 加载 merge 数据目录
-        load_merge_files(dir_path.clone())?;
+        // 加载 merge 数据目录，只读模式下不修改数据目录
+        if !options.read_only {
+            load_merge_files(&options)?;
+        }
 
         // 加载数据文件
-        let mut data_files = load_data_files(dir_path.clone(), options.mmap_at_startup)?;
+        let mut data_files = load_data_files(&options)?;
+
+        // 加载 keyspace，之后加载索引时才能区分数据属于哪个 keyspace
+        let keyspaces = Keyspaces::load(&options)?;
 
         // 设置 file id 信息
         let mut file_ids = Vec::new();
@@ -122,42 +225,98 @@ impl Engine {
         // 拿到当前活跃文件，即列表中最后一个文件
         let active_file = match data_files.pop() {
             Some(v) => v,
-            None => DataFile::new(dir_path.clone(), INITIAL_FILE_ID, IOType::StandardFIO)?,
+            // 只读模式下不创建新的活跃文件
+            None if options.read_only => return Err(Errors::DataFileNotFound),
+            None => {
+                let data_file =
+                    DataFile::new(&options, options.initial_file_id, IOType::StandardFIO)?;
+                util::file::sync_dir(&options.dir_path)?;
+                data_file
+            }
         };
 
         // 构造存储引擎实例
-        let mut engine = Self {
+        let engine = Self {
             options: Arc::new(opts),
             active_file: Arc::new(RwLock::new(active_file)),
             older_files: Arc::new(RwLock::new(older_files)),
-            index: index::new_indexer(options.index_type, options.dir_path),
-            file_ids,
+            index: Arc::from(index::new_indexer(options.index_type, options.dir_path)),
             batch_commit_lock: Mutex::new(()),
+            write_lock: RwLock::new(()),
             seq_no: Arc::new(AtomicUsize::new(1)),
-            merging_lock: Mutex::new(()),
+            sequence: Arc::new(AtomicU64::new(0)),
+            is_merging: AtomicBool::new(false),
             seq_file_exists: false,
             is_initial,
             lock_file,
-            bytes_write: Arc::new(AtomicUsize::new(0)),
             reclaim_size: Arc::new(AtomicUsize::new(0)),
+            value_cache: match options.value_cache_size {
+                0 => None,
+                size => Some(ValueCache::new(size)),
+            },
+            bloom_filters: match options.enable_bloom_filters {
+                true => Some(BloomFilters::new(options.bloom_false_positive_rate)),
+                false => None,
+            },
+            tombstones: match options.track_tombstones {
+                true => Some(Arc::new(RwLock::new(HashMap::new()))),
+                false => None,
+            },
+            snapshots: RwLock::new(Vec::new()),
+            prefix_index: options
+                .prefix_index_len
+                .map(|len| Arc::new(PrefixIndex::new(len))),
+            keyspaces: Arc::new(keyspaces),
+            index_state: Arc::new(IndexState::new()),
+            is_closed: false,
         };
 
-        // B+ 树则不需要从数据文件中加载索引
-        // if engine.options.index_type != IndexType::BPlusTree {
-        // 从 hint 文件中加载索引
-        engine.load_index_from_hint_file()?;
-
-        // 从数据文件中加载索引
-        let current_seq_no = engine.load_index_from_data_files()?;
-
-        // 更新当前事务序列号
-        if current_seq_no > 0 {
-            engine.seq_no.store(current_seq_no + 1, Ordering::SeqCst);
+        // 布隆过滤器在加载索引之前准备好，索引在后台加载时可以直接排除不存在的 key
+        if let Some(bloom_filters) = &engine.bloom_filters {
+            let active_file = engine.active_file.read();
+            let older_files = engine.older_files.read();
+            bloom_filters.load(&engine.options, &older_files, &active_file)?;
         }
 
-        // 重置 IO 类型
-        if engine.options.mmap_at_startup {
-            engine.reset_io_type();
+        // B+ 树则不需要从数据文件中加载索引
+        // if engine.options.index_type != IndexType::BPlusTree {
+        let loader = IndexLoader {
+            options: engine.options.clone(),
+            active_file: engine.active_file.clone(),
+            older_files: engine.older_files.clone(),
+            index: engine.index.clone(),
+            file_ids,
+            tombstones: engine.tombstones.clone(),
+            reclaim_size: engine.reclaim_size.clone(),
+            prefix_index: engine.prefix_index.clone(),
+            keyspaces: engine.keyspaces.clone(),
+            seq_no: engine.seq_no.clone(),
+            sequence: engine.sequence.clone(),
+        };
+        match engine.options.lazy_index {
+            // 在后台线程中加载索引，读写操作等待加载完成
+            true => {
+                let index_state = engine.index_state.clone();
+                let spawn_res = thread::Builder::new()
+                    .name("bitcask-index-loader".to_string())
+                    .spawn(move || {
+                        let res = panic::catch_unwind(AssertUnwindSafe(|| loader.load()))
+                            .unwrap_or(Err(Errors::IndexLoadFailed));
+                        if let Err(e) = &res {
+                            log::error!("failed to load index in background: {}", e);
+                        }
+                        index_state.finish(res);
+                    });
+                if let Err(e) = spawn_res {
+                    engine.index_state.finish(Err(Errors::IndexLoadFailed));
+                    return Err(e.into());
+                }
+            }
+            false => {
+                let res = loader.load();
+                engine.index_state.finish(res.clone());
+                res?;
+            }
         }
         // }
 
@@ -178,52 +337,119 @@ impl Engine {
     }
 
     /// 关闭数据库，释放相关资源
-    pub fn close(&self) -> Result<()> {
+    /// close 返回 Ok 之后，之前所有写入成功的数据都已经持久化到磁盘中
+    pub fn close(mut self) -> Result<()> {
+        // 标记为已关闭，Drop 时不会重复关闭
+        self.is_closed = true;
+        self.close_engine()
+    }
+
+    // 记录事务序列号，持久化活跃文件并释放文件锁
+    fn close_engine(&self) -> Result<()> {
         // 如果数据目录不存在则返回
         if !self.options.dir_path.is_dir() {
             return Ok(());
         }
 
+        // 等待后台加载索引结束，之后不会再有线程读取数据文件
+        let _ = self.index_state.wait();
+
+        // 只读模式下没有写入数据，释放文件锁即可
+        if self.options.read_only {
+            self.lock_file.unlock()?;
+            return Ok(());
+        }
+
+        // 先截断预分配之后没有使用的空间并持久化活跃文件，后续步骤失败时最近写入的数据也不会丢失
+        let read_guard = self.active_file.read();
+        read_guard.truncate_unused_space()?;
+        read_guard.sync()?;
+
         // 记录当前的事务序列号
-        let seq_no_file = DataFile::new_seq_no_file(self.options.dir_path.clone())?;
+        let seq_no_file = DataFile::new_seq_no_file(&self.options, self.options.dir_path.clone())?;
         let seq_no = self.seq_no.load(Ordering::SeqCst);
         let record = LogRecord {
             key: SEQ_NO_KEY.as_bytes().to_vec(),
             value: seq_no.to_string().into_bytes(),
             rec_type: LogRecordType::NORMAL,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
         };
         seq_no_file.write(&record.encode())?;
         seq_no_file.sync()?;
 
-        let read_guard = self.active_file.read();
-        read_guard.sync()?;
-
         // 释放文件锁
-        self.lock_file.unlock().unwrap();
+        self.lock_file.unlock()?;
 
         Ok(())
     }
 
     /// 持久化当前活跃文件
     pub fn sync(&self) -> Result<()> {
+        // 只读模式下没有写入数据，无需持久化
+        if self.options.read_only {
+            return Ok(());
+        }
+        // 写入时持有活跃文件的写锁，这里持有读锁即可保证不会和正在进行的追加写并发
         let read_guard = self.active_file.read();
         read_guard.sync()
     }
 
+    /// 持久化当前活跃文件以及数据目录
+    /// 返回 Ok 之后，即使断电，所有数据文件以及其中写入成功的数据都不会丢失
+    pub fn flush_all_and_fsync_directory(&self) -> Result<()> {
+        // 只读模式下没有写入数据，数据目录可能位于只读的文件系统上
+        if self.options.read_only {
+            return Ok(());
+        }
+        let read_guard = self.active_file.read();
+        read_guard.sync()?;
+        util::file::sync_dir(&self.options.dir_path)?;
+        Ok(())
+    }
+
     /// 获取数据库统计信息
     pub fn stat(&self) -> Result<Stat> {
+        self.wait_index_ready()?;
         let keys = self.list_keys()?;
+        let active_file = self.active_file.read();
         let older_files = self.older_files.read();
+
+        // 累加所有数据文件的大小
+        let mut disk_size = active_file.file_size();
+        for file in older_files.values() {
+            disk_size += file.file_size();
+        }
+
         Ok(Stat {
             key_num: keys.len(),
             data_file_num: older_files.len() + 1,
-            reclaim_size: self.reclaim_size.load(Ordering::SeqCst),
-            disk_size: util::file::dir_disk_size(self.options.dir_path.clone()),
+            reclaimable_size: self.reclaimable_size()?,
+            disk_size,
         })
     }
 
-    /// 备份数据目录
+    /// 获取 merge 之后可以回收的数据量，包括被覆盖、删除的数据以及删除标记本身
+    pub fn reclaimable_size(&self) -> Result<u64> {
+        self.wait_index_ready()?;
+        Ok(self.reclaim_size.load(Ordering::SeqCst) as u64)
+    }
+
+    /// 备份数据目录，备份之后在目标目录上打开可以得到相同的数据
+    /// 只能拷贝本地的数据文件，设置了 io_factory 时返回 IOFactoryUnsupported
     pub fn backup(&self, dir_path: PathBuf) -> Result<()> {
+        if self.options.io_factory.is_some() {
+            return Err(Errors::IOFactoryUnsupported);
+        }
+        // 持有活跃文件的锁，备份期间不会有新的写入和文件切换，保证拷贝的文件是一致的
+        let active_file = self.active_file.read();
+        // 持久化活跃文件，避免备份缺少最近写入的数据
+        active_file.sync()?;
+
         let exclude = [FILE_LOCK_NAME];
         if let Err(e) = util::file::copy_dir(self.options.dir_path.clone(), dir_path, &exclude) {
             log::error!("failed to copy dir: {}", e);
@@ -232,50 +458,366 @@ impl Engine {
         Ok(())
     }
 
-    /// 存储 key/value 数据，key 不能为空
-    pub fn put(&self, key: Bytes, value: Bytes) -> Result<()> {
+    /// 清空数据库，删除所有的数据文件以及 merge 的输出，并清空内存索引
+    /// 快照引用的数据文件不能删除，存在还没有释放的快照时返回 SnapshotInUse
+    /// 按照文件 id 从小到大删除数据文件，中途崩溃时剩余的文件中仍然是每个 key 最新的数据
+    pub fn clear(&self) -> Result<()> {
+        self.check_writable()?;
+        self.wait_index_ready()?;
+
+        // 清空时不能 merge，避免 merge 的输出在下一次启动时恢复被清空的数据
+        let _merge_guard = self.start_merge()?;
+        // 持有写锁，清空的过程中没有其他的读写
+        let _lock = self.write_lock.write();
+        if !self.snapshots.read().is_empty() {
+            return Err(Errors::SnapshotInUse);
+        }
+        let mut active_file = self.active_file.write();
+        let mut older_files = self.older_files.write();
+
+        // 先删除 merge 的输出以及 hint 文件，避免引用被删除的数据文件
+        let merge_path = get_merge_path(self.options.dir_path.clone());
+        util::file::remove_dir(&self.options, &merge_path)?;
+        for file_name in [HINT_FILE_NAME, MERGE_FINISHED_FILE_NAME] {
+            util::file::remove_file(&self.options, &self.options.dir_path.join(file_name))?;
+        }
+
+        // 删除所有的旧数据文件以及它们的布隆过滤器
+        let mut file_ids: Vec<u32> = older_files.keys().copied().collect();
+        file_ids.sort();
+        for file_id in file_ids {
+            older_files.remove(&file_id);
+            util::file::remove_file(&self.options, &get_data_file_name(&self.options, file_id))?;
+            remove_bloom_file(&self.options, file_id)?;
+        }
+
+        // 使用相同的文件 id 重新创建空的活跃文件
+        let active_file_id = active_file.get_file_id();
+        util::file::remove_file(
+            &self.options,
+            &get_data_file_name(&self.options, active_file_id),
+        )?;
+        remove_bloom_file(&self.options, active_file_id)?;
+        let mut new_file = DataFile::new(&self.options, active_file_id, self.active_io_type())?;
+        new_file.preallocate(&self.options)?;
+        *active_file = new_file;
+        util::file::sync_dir(&self.options.dir_path)?;
+
+        // 清空内存中的状态
+        self.index.clear();
+        self.keyspaces.clear_indexes();
+        if let Some(tombstones) = &self.tombstones {
+            tombstones.write().clear();
+        }
+        if let Some(prefix_index) = &self.prefix_index {
+            prefix_index.clear();
+        }
+        if let Some(bloom_filters) = &self.bloom_filters {
+            bloom_filters.clear();
+        }
+        if let Some(cache) = &self.value_cache {
+            cache.clear();
+        }
+        self.reclaim_size.store(0, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// 存储 key/value 数据，key 不能为空，返回分配的写入序列号
+    pub fn put(&self, key: Bytes, value: Bytes) -> Result<u64> {
+        self.with_metrics(OpKind::Put, || self.put_with_expire(key, value, 0))
+    }
+
+    /// 存储带有过期时间的 key/value 数据，超过 ttl 之后数据不可见，返回分配的写入序列号
+    pub fn put_with_ttl(&self, key: Bytes, value: Bytes, ttl: Duration) -> Result<u64> {
+        let expire = self.now_millis() + ttl.as_millis() as u64;
+        self.with_metrics(OpKind::Put, || self.put_with_expire(key, value, expire))
+    }
+
+    fn put_with_expire(&self, key: Bytes, value: Bytes, expire: u64) -> Result<u64> {
+        self.check_writable()?;
+        self.wait_index_ready()?;
+
+        // 判断 key 的有效性
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+        self.check_key_value_size(&key, &value)?;
+
+        let _lock = self.write_lock.read();
+        self.write_value(key, value, expire)
+    }
+
+    /// 存储 key/value 数据，返回被覆盖的旧的 value，key 不存在（或者已被删除、已过期）时返回 None
+    /// 需要额外从磁盘中读取旧的 value，不需要旧的 value 时使用 put
+    pub fn put_and_get_old(&self, key: Bytes, value: Bytes) -> Result<Option<Bytes>> {
+        self.with_metrics(OpKind::Put, || self.put_with_old(key, value))
+    }
+
+    fn put_with_old(&self, key: Bytes, value: Bytes) -> Result<Option<Bytes>> {
+        self.check_writable()?;
+        self.wait_index_ready()?;
+
+        // 判断 key 的有效性
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+        self.check_key_value_size(&key, &value)?;
+
+        // 读取旧的 value 和写入新的数据之间不能有其他的写入，需要持有写锁
+        let _lock = self.write_lock.write();
+
+        // 先从磁盘中读取旧的 value，已经过期的数据视为不存在
+        let old_value = match self.index.get(key.to_vec()) {
+            Some(pos) => match self.get_value_by_position(&pos) {
+                Ok(value) => Some(value),
+                Err(Errors::KeyNotFound) => None,
+                Err(e) => return Err(e),
+            },
+            None => None,
+        };
+
+        self.write_value(key, value, 0)?;
+        Ok(old_value)
+    }
+
+    /// 只有 key 不存在（或者已被删除、已过期）时才写入数据
+    /// 写入成功返回 true，key 已经存在则返回 false，判断和写入对其他的写操作是原子的
+    pub fn put_if_absent(&self, key: Bytes, value: Bytes) -> Result<bool> {
+        self.check_writable()?;
+        self.wait_index_ready()?;
+
+        // 判断 key 的有效性
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+        self.check_key_value_size(&key, &value)?;
+
+        // 持有写锁，判断期间不会有其他的写入
+        let _lock = self.write_lock.write();
+        if let Some(pos) = self.index.get(key.to_vec()) {
+            match self.get_value_by_position(&pos) {
+                Ok(_) => return Ok(false),
+                // 数据已经过期，视为不存在
+                Err(Errors::KeyNotFound) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.write_value(key, value, 0)?;
+        Ok(true)
+    }
+
+    /// 将 key 对应的值加上 delta 并返回新的值，delta 为负数时即为减少
+    /// 值按照 8 字节大端序的 i64 存储，key 不存在时视为 0，溢出时按照补码回绕
+    pub fn increment(&self, key: Bytes, delta: i64) -> Result<i64> {
+        self.check_writable()?;
+        self.wait_index_ready()?;
+
         // 判断 key 的有效性
         if key.is_empty() {
             return Err(Errors::KeyIsEmpty);
         }
 
+        // 持有写锁，读取和写入之间不会有其他的写入
+        let _lock = self.write_lock.write();
+        let mut current = 0;
+        if let Some(pos) = self.index.get(key.to_vec()) {
+            match self.get_value_by_position(&pos) {
+                Ok(value) => {
+                    let buf: [u8; 8] = match value.as_ref().try_into() {
+                        Ok(buf) => buf,
+                        Err(_) => return Err(Errors::ValueNotInteger),
+                    };
+                    current = i64::from_be_bytes(buf);
+                }
+                // 数据已经过期，视为不存在
+                Err(Errors::KeyNotFound) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let new_value = current.wrapping_add(delta);
+        let value = Bytes::copy_from_slice(&new_value.to_be_bytes());
+        self.check_key_value_size(&key, &value)?;
+        self.write_value(key, value, 0)?;
+        Ok(new_value)
+    }
+
+    /// 在 key 对应的值后面追加 suffix，返回追加之后的总长度，key 不存在时视为空值
+    /// 数据文件中的记录不能原地修改，追加时会重写完整的 value，读取和写入对其他的写操作是原子的
+    pub fn append(&self, key: Bytes, suffix: Bytes) -> Result<usize> {
+        self.with_metrics(OpKind::Put, || self.append_value(key, suffix))
+    }
+
+    fn append_value(&self, key: Bytes, suffix: Bytes) -> Result<usize> {
+        self.check_writable()?;
+        self.wait_index_ready()?;
+
+        // 判断 key 的有效性
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+
+        // 持有写锁，读取和写入之间不会有其他的写入
+        let _lock = self.write_lock.write();
+        let current = match self.index.get(key.to_vec()) {
+            Some(pos) => match self.get_value_by_position(&pos) {
+                Ok(value) => value,
+                // 数据已经过期，视为不存在
+                Err(Errors::KeyNotFound) => Bytes::new(),
+                Err(e) => return Err(e),
+            },
+            None => Bytes::new(),
+        };
+
+        let mut value = Vec::with_capacity(current.len() + suffix.len());
+        value.extend_from_slice(&current);
+        value.extend_from_slice(&suffix);
+        let value = Bytes::from(value);
+        self.check_key_value_size(&key, &value)?;
+        let len = value.len();
+        self.write_value(key, value, 0)?;
+        Ok(len)
+    }
+
+    /// 批量写入 key/value 数据，全部追加写入之后只持久化一次，写入成功返回时所有的数据都已经持久化
+    /// 和 WriteBatch 不同，写入的数据不带有事务标识，不保证原子性，
+    /// 写入过程中崩溃时，还没有持久化的数据可能部分保留，和普通写入中没有持久化的数据一样处理
+    pub fn put_batch_sync(&self, pairs: &[(Bytes, Bytes)]) -> Result<()> {
+        self.check_writable()?;
+        self.wait_index_ready()?;
+
+        // 写入之前先校验所有的数据，避免只写入了一部分
+        for (key, value) in pairs.iter() {
+            if key.is_empty() {
+                return Err(Errors::KeyIsEmpty);
+            }
+            self.check_key_value_size(key, value)?;
+        }
+
+        let _lock = self.write_lock.read();
+        for (key, value) in pairs.iter() {
+            self.write_value_with_sync(key.clone(), value.clone(), 0, false)?;
+        }
+
+        // 切换活跃文件时已经持久化了旧的文件，只需要持久化当前的活跃文件
+        self.active_file.read().sync()
+    }
+
+    // 写入数据并更新内存索引，返回分配的写入序列号，调用方需要持有 write_lock
+    fn write_value(&self, key: Bytes, value: Bytes, expire: u64) -> Result<u64> {
+        self.write_value_with_sync(key, value, expire, true)
+    }
+
+    // 写入数据并更新内存索引，allow_sync 为 false 时写入之后不持久化，由调用方统一持久化
+    fn write_value_with_sync(
+        &self,
+        key: Bytes,
+        value: Bytes,
+        expire: u64,
+        allow_sync: bool,
+    ) -> Result<u64> {
         // 构造 LogRecord
+        let (value, compression) = self.compress_value(&value)?;
+        let (value, encrypted) = self.encrypt_value(&key, value)?;
         let mut record = LogRecord {
             key: log_record_key_with_seq(key.to_vec(), NON_TRANSACTION_SEQ_NO),
-            value: value.to_vec(),
+            value,
             rec_type: LogRecordType::NORMAL,
+            expire,
+            compression,
+            encrypted,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
         };
 
         // 追加写到活跃数据文件中
-        let log_record_pos = self.append_log_record(&mut record)?;
+        let log_record_pos = self.append_log_record_with_sequence(&mut record, true, allow_sync)?;
 
         // 更新内存索引
-        if let Some(old_pos) = self.index.put(key.to_vec(), log_record_pos) {
+        if let Some(old_pos) = self.index_put(key.to_vec(), log_record_pos) {
             self.reclaim_size
                 .fetch_add(old_pos.size as usize, Ordering::SeqCst);
         }
+        self.invalidate_cache(&key);
+        self.set_tombstone(&key, None);
+        self.update_prefix_index(&key, false);
 
-        Ok(())
+        Ok(record.sequence)
     }
 
-    /// 根据 key 删除对应的数据
-    pub fn delete(&self, key: Bytes) -> Result<()> {
+    /// 根据 key 删除对应的数据，返回删除之前的 value，key 不存在时返回 None
+    pub fn delete(&self, key: Bytes) -> Result<Option<Bytes>> {
+        self.with_metrics(OpKind::Delete, || self.delete_and_get_old(key))
+    }
+
+    fn delete_and_get_old(&self, key: Bytes) -> Result<Option<Bytes>> {
+        self.check_writable()?;
+        self.wait_index_ready()?;
+
         // 判断 key 的有效性
         if key.is_empty() {
             return Err(Errors::KeyIsEmpty);
         }
 
+        // 读取旧的 value 和写入删除标记之间不能有其他的写入，需要持有写锁
+        let _lock = self.write_lock.write();
+
         // 从内存索引当中取出对应的数据，不存在的话直接返回
-        let pos = self.index.get(key.to_vec());
-        if pos.is_none() {
-            return Ok(());
+        let Some(pos) = self.index.get(key.to_vec()) else {
+            return Ok(None);
+        };
+
+        // 先从磁盘中读取旧的 value，已经过期的数据视为不存在
+        let old_value = match self.get_value_by_position(&pos) {
+            Ok(value) => Some(value),
+            Err(Errors::KeyNotFound) => None,
+            Err(e) => return Err(e),
+        };
+
+        self.delete_value(&key)?;
+        Ok(old_value)
+    }
+
+    /// 根据 key 删除对应的数据，不读取旧的 value
+    /// 返回删除标记的写入序列号，key 不存在时不写入删除标记，返回 None
+    pub fn delete_fast(&self, key: Bytes) -> Result<Option<u64>> {
+        self.with_metrics(OpKind::Delete, || self.delete_without_old(key))
+    }
+
+    fn delete_without_old(&self, key: Bytes) -> Result<Option<u64>> {
+        self.check_writable()?;
+        self.wait_index_ready()?;
+
+        // 判断 key 的有效性
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+
+        let _lock = self.write_lock.read();
+
+        // 从内存索引当中取出对应的数据，不存在的话直接返回
+        if self.index.get(key.to_vec()).is_none() {
+            return Ok(None);
         }
 
+        Ok(Some(self.delete_value(&key)?))
+    }
+
+    // 写入删除标记并删除内存索引，返回分配的写入序列号，调用方需要持有 write_lock
+    fn delete_value(&self, key: &Bytes) -> Result<u64> {
         // 构造 LogRecord，标识其是被删除的
         let mut record = LogRecord {
             key: log_record_key_with_seq(key.to_vec(), NON_TRANSACTION_SEQ_NO),
             value: Default::default(),
             rec_type: LogRecordType::DELETED,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
         };
 
         // 写入到数据文件当中
@@ -284,16 +826,67 @@ impl Engine {
             .fetch_add(pos.size as usize, Ordering::SeqCst);
 
         // 删除内存索引中对应的 key
-        if let Some(old_pos) = self.index.delete(key.to_vec()) {
+        if let Some(old_pos) = self.index_delete(key.to_vec()) {
             self.reclaim_size
                 .fetch_add(old_pos.size as usize, Ordering::SeqCst);
         }
+        self.invalidate_cache(key);
+        self.set_tombstone(key, Some(pos.file_id));
+        self.update_prefix_index(key, true);
+
+        Ok(record.sequence)
+    }
+
+    /// 将 key 当前的 value 重新追加写入到活跃文件中并更新内存索引，key 不存在（或者已被删除、已过期）时不做任何操作
+    /// 频繁覆盖写入的 key 的旧版本分散在多个数据文件中，重写之后这些版本都变成了可以回收的数据，之后 merge 时全部丢弃
+    /// 重写的数据和 merge 一样保留原有的写入序列号、写入时间以及过期时间，value 不需要重新压缩和加密
+    pub fn compact_key(&self, key: Bytes) -> Result<()> {
+        self.check_writable()?;
+        self.wait_index_ready()?;
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
 
+        // 读取和重写之间不能有其他的写入，需要持有写锁
+        let _lock = self.write_lock.write();
+        let Some(pos) = self.index.get(key.to_vec()) else {
+            return Ok(());
+        };
+        let mut record = {
+            let active_file = self.active_file.read();
+            let older_files = self.older_files.read();
+            let data_file = find_data_file(&active_file, &older_files, pos.file_id)?;
+            data_file
+                .read_log_record(pos.offset)
+                .map_err(strip_invalid_crc_key)?
+                .record
+        };
+        if record.rec_type == LogRecordType::DELETED || record.is_expired(self.now_millis()) {
+            return Ok(());
+        }
+
+        // 事务中写入的数据已经提交，去除事务的标识
+        record.key = log_record_key_with_seq(key.to_vec(), NON_TRANSACTION_SEQ_NO);
+        let new_pos = self.append_rewritten_log_record(&mut record)?;
+        if let Some(old_pos) = self.index_put(key.to_vec(), new_pos) {
+            self.reclaim_size
+                .fetch_add(old_pos.size as usize, Ordering::SeqCst);
+        }
+        self.invalidate_cache(&key);
         Ok(())
     }
 
     /// 根据 key 获取对应的数据
     pub fn get(&self, key: Bytes) -> Result<Bytes> {
+        self.with_metrics(OpKind::Get, || self.get_value(key))
+    }
+
+    fn get_value(&self, key: Bytes) -> Result<Bytes> {
+        // 索引还在后台加载时，布隆过滤器排除的 key 不需要等待加载完成
+        if self.bloom_filters_exclude(&key) {
+            return Err(Errors::KeyNotFound);
+        }
+        self.wait_index_ready()?;
         // 判断 key 的有效性
         if key.is_empty() {
             return Err(Errors::KeyIsEmpty);
@@ -307,86 +900,622 @@ impl Engine {
         }
 
         let log_reord_pos = pos.unwrap();
+        // 优先从缓存中读取 value
+        if let Some(value) = self.get_cached_value(&key, &log_reord_pos) {
+            return Ok(value);
+        }
         // 根据索引获取数据文件中的 value
-        self.get_value_by_position(&log_reord_pos)
+        match self.read_value_and_cache(&key, &log_reord_pos) {
+            Err(Errors::KeyNotFound) => {
+                // 数据已经过期，惰性删除内存索引
+                self.remove_expired_index(key.to_vec(), &log_reord_pos);
+                Err(Errors::KeyNotFound)
+            }
+            res => res,
+        }
+    }
+
+    /// 根据 key 获取数据并写入调用方提供的 buf，返回 value 的长度，key 不存在或者已经过期时返回 None
+    /// buf 中原有的数据会被清空，循环读取时重复使用同一个 buf，可以避免每次读取都分配内存；
+    /// 经过压缩或者加密的 value 解码时仍然需要分配内存
+    pub fn get_into(&self, key: Bytes, buf: &mut Vec<u8>) -> Result<Option<usize>> {
+        self.with_metrics(OpKind::Get, || self.get_value_into(key, buf))
+    }
+
+    fn get_value_into(&self, key: Bytes, buf: &mut Vec<u8>) -> Result<Option<usize>> {
+        buf.clear();
+        if self.bloom_filters_exclude(&key) {
+            return Ok(None);
+        }
+        self.wait_index_ready()?;
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+        let Some(pos) = self.index.get(key.to_vec()) else {
+            return Ok(None);
+        };
+
+        // 优先从缓存中读取 value
+        if let Some(value) = self.get_cached_value(&key, &pos) {
+            buf.extend_from_slice(&value);
+            return Ok(Some(value.len()));
+        }
+        match self.read_value_into(&key, &pos, buf) {
+            Err(Errors::KeyNotFound) => {
+                // 数据已经过期，惰性删除内存索引
+                self.remove_expired_index(key.to_vec(), &pos);
+                Ok(None)
+            }
+            res => res.map(Some),
+        }
+    }
+
+    // 将索引位置对应的 value 读取到 buf 中，返回 value 的长度
+    fn read_value_into(
+        &self,
+        key: &[u8],
+        log_record_pos: &LogRecordPos,
+        buf: &mut Vec<u8>,
+    ) -> Result<usize> {
+        let verify_crc = self.options.verify_checksum_on_read;
+        let header = self
+            .with_data_file(log_record_pos.file_id, |data_file| {
+                data_file.read_value_into(
+                    log_record_pos.offset,
+                    log_record_pos.size as usize,
+                    verify_crc,
+                    buf,
+                )
+            })
+            .map_err(strip_invalid_crc_key)?;
+
+        if header.rec_type == LogRecordType::DELETED
+            || (header.expire > 0 && header.expire <= self.now_millis())
+        {
+            buf.clear();
+            return Err(Errors::KeyNotFound);
+        }
+
+        // 经过压缩或者加密的 value 需要解码之后再放回 buf
+        if header.compression != Compression::None || header.encrypted {
+            let value =
+                self.decode_stored_value(key, buf.clone(), header.compression, header.encrypted)?;
+            buf.clear();
+            buf.extend_from_slice(&value);
+        }
+        Ok(buf.len())
+    }
+
+    /// 根据 key 获取数据，不存在或者已经过期时返回 None
+    /// 配置了 mmap_at_startup 时旧的数据文件使用 mmap 读取，如果 value 在旧的数据文件中且没有压缩和加密，
+    /// 直接返回映射区域中的数据，不拷贝；活跃文件仍然会被写入，和其他情况一样拷贝一份数据返回
+    pub fn get_ref(&self, key: Bytes) -> Result<Option<ValueRef>> {
+        self.with_metrics(OpKind::Get, || self.get_value_ref(key))
+    }
+
+    fn get_value_ref(&self, key: Bytes) -> Result<Option<ValueRef>> {
+        #[cfg(feature = "mmap")]
+        if let Some(value) = self.get_mapped_value(&key)? {
+            return Ok(Some(ValueRef {
+                inner: ValueRefInner::Mapped(value),
+            }));
+        }
+        match self.get_value(key) {
+            Ok(value) => Ok(Some(ValueRef {
+                inner: ValueRefInner::Owned(value),
+            })),
+            Err(Errors::KeyNotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    // 从旧的数据文件的映射区域中读取 value，无法直接引用时返回 None，由调用方拷贝数据
+    #[cfg(feature = "mmap")]
+    fn get_mapped_value(&self, key: &Bytes) -> Result<Option<MappedSlice>> {
+        self.wait_index_ready()?;
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+        let Some(pos) = self.index.get(key.to_vec()) else {
+            return Ok(None);
+        };
+
+        let older_files = self.older_files.read();
+        let Some(data_file) = older_files.get(&pos.file_id) else {
+            return Ok(None);
+        };
+        let verify_crc = self.options.verify_checksum_on_read;
+        match data_file.read_mapped_value(pos.offset, verify_crc) {
+            // 删除和过期的数据由 get 处理
+            Ok(Some((header, value)))
+                if header.rec_type != LogRecordType::DELETED
+                    && (header.expire == 0 || header.expire > self.now_millis()) =>
+            {
+                Ok(Some(value))
+            }
+            Ok(_) => Ok(None),
+            // 校验失败时由 get 返回去掉事务序列号之后的 key
+            Err(Errors::InvalidCrc { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// 获取 key 的状态，区分存在、被删除以及从未写入的 key，需要开启 Options::track_tombstones
+    /// 删除标记只记录在内存中，启动时从数据文件中加载；merge 会清理删除标记，
+    /// 因此 merge 完成之后，删除标记被清理的 key 视为从未写入
+    pub fn get_status(&self, key: Bytes) -> Result<KeyStatus> {
+        let Some(tombstones) = &self.tombstones else {
+            return Err(Errors::TombstonesNotTracked);
+        };
+        match self.get(key.clone()) {
+            Ok(value) => Ok(KeyStatus::Present(value)),
+            Err(Errors::KeyNotFound) => match tombstones.read().contains_key(key.as_ref()) {
+                true => Ok(KeyStatus::Deleted),
+                false => Ok(KeyStatus::Absent),
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    /// 获取 key 对应的数据在磁盘上的位置，key 不存在时返回 None
+    pub fn locate(&self, key: Bytes) -> Result<Option<LogRecordPos>> {
+        self.wait_index_ready()?;
+        Ok(self.index.get(key.to_vec()))
+    }
+
+    /// 获取 key 对应的 value 的长度，key 不存在或者已经过期时返回 None
+    /// 返回的是写入时 value 的实际长度，而不是数据在磁盘上占据的大小。
+    /// 索引中只记录了整条数据的大小，过期时间和事务序列号都是变长编码的，无法据此推算出
+    /// value 的长度，因此需要读取数据的 header，但不会读取 value 本身；
+    /// 压缩或者加密过的数据在磁盘上的长度和实际长度不同，仍然需要读取完整的数据
+    pub fn value_len(&self, key: Bytes) -> Result<Option<usize>> {
+        self.wait_index_ready()?;
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+        let Some(pos) = self.index.get(key.to_vec()) else {
+            return Ok(None);
+        };
+        if let Some(value) = self.get_cached_value(&key, &pos) {
+            return Ok(Some(value.len()));
+        }
+
+        let header = {
+            let active_file = self.active_file.read();
+            let older_files = self.older_files.read();
+            find_data_file(&active_file, &older_files, pos.file_id)?
+                .read_log_record_header(pos.offset)?
+        };
+        if header.rec_type == LogRecordType::DELETED
+            || (header.expire > 0 && header.expire <= self.now_millis())
+        {
+            return Ok(None);
+        }
+        if header.compression != Compression::None || header.encrypted {
+            return match self.get_value(key) {
+                Ok(value) => Ok(Some(value.len())),
+                Err(Errors::KeyNotFound) => Ok(None),
+                Err(e) => Err(e),
+            };
+        }
+        Ok(Some(header.value_size))
+    }
+
+    /// 获取 key 最后一次写入的时间，key 不存在或者已经过期时返回 KeyNotFound
+    /// 写入时间存储在数据的 header 中，只读取 header，不读取 value；
+    /// 只有开启了 Options::write_timestamp 之后写入的数据才有写入时间，其他数据返回 None。
+    /// merge 重写数据时保留原有的写入时间
+    pub fn modified_at(&self, key: Bytes) -> Result<Option<SystemTime>> {
+        self.wait_index_ready()?;
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+        let Some(pos) = self.index.get(key.to_vec()) else {
+            return Err(Errors::KeyNotFound);
+        };
+
+        let header = {
+            let active_file = self.active_file.read();
+            let older_files = self.older_files.read();
+            find_data_file(&active_file, &older_files, pos.file_id)?
+                .read_log_record_header(pos.offset)?
+        };
+        if header.rec_type == LogRecordType::DELETED
+            || (header.expire > 0 && header.expire <= self.now_millis())
+        {
+            return Err(Errors::KeyNotFound);
+        }
+        Ok(match header.timestamp {
+            0 => None,
+            timestamp => Some(UNIX_EPOCH + Duration::from_millis(timestamp)),
+        })
+    }
+
+    // 删除已过期数据的内存索引，如果索引已经被更新则不做处理
+    fn remove_expired_index(&self, key: Vec<u8>, pos: &LogRecordPos) {
+        if let Some(curr_pos) = self.index.get(key.clone()) {
+            if curr_pos.file_id != pos.file_id || curr_pos.offset != pos.offset {
+                return;
+            }
+            if let Some(old_pos) = self.index_delete(key.clone()) {
+                self.reclaim_size
+                    .fetch_add(old_pos.size as usize, Ordering::SeqCst);
+            }
+            self.invalidate_cache(&key);
+            self.update_prefix_index(&key, true);
+        }
+    }
+
+    // 从缓存中读取 value，缓存时的位置和当前索引不一致或者数据已经过期时视为没有缓存
+    fn get_cached_value(&self, key: &[u8], pos: &LogRecordPos) -> Option<Bytes> {
+        let cached = self.value_cache.as_ref()?.get(key)?;
+        if cached.pos.file_id != pos.file_id || cached.pos.offset != pos.offset {
+            return None;
+        }
+        if cached.expire > 0 && cached.expire <= self.now_millis() {
+            return None;
+        }
+        Some(cached.value)
+    }
+
+    // 从数据文件中读取 value，并放到缓存中
+    fn read_value_and_cache(&self, key: &[u8], pos: &LogRecordPos) -> Result<Bytes> {
+        let (value, expire) = self.with_data_file(pos.file_id, |data_file| {
+            self.read_value_with_expire(data_file, pos)
+        })?;
+        if let Some(cache) = &self.value_cache {
+            let cached = CachedValue {
+                pos: *pos,
+                value: value.clone(),
+                expire,
+            };
+            cache.put(key.to_vec(), cached);
+        }
+        Ok(value)
+    }
+
+    // 写入或者删除 key 之后更新删除标记，deleted_in 为删除标记所在的数据文件 id，写入时为 None
+    pub(crate) fn set_tombstone(&self, key: &[u8], deleted_in: Option<u32>) {
+        if let Some(tombstones) = &self.tombstones {
+            update_tombstone(tombstones, key, deleted_in);
+        }
+    }
+
+    // merge 完成之后，non_merge_file_id 之前的数据文件中的删除标记都会被丢弃，同时清理内存中对应的记录
+    pub(crate) fn prune_tombstones(&self, non_merge_file_id: u32) {
+        if let Some(tombstones) = &self.tombstones {
+            tombstones
+                .write()
+                .retain(|_, file_id| *file_id >= non_merge_file_id);
+        }
+    }
+
+    // 写入或者删除 key 之后更新前缀索引
+    pub(crate) fn update_prefix_index(&self, key: &[u8], deleted: bool) {
+        if let Some(prefix_index) = &self.prefix_index {
+            match deleted {
+                true => prefix_index.delete(key),
+                false => prefix_index.put(key),
+            }
+        }
+    }
+
+    // 删除 key 对应的缓存，写入或者删除数据之后调用
+    pub(crate) fn invalidate_cache(&self, key: &[u8]) {
+        if let Some(cache) = &self.value_cache {
+            cache.remove(key);
+        }
+    }
+
+    /// 判断 key 是否存在，只查询内存索引，不读取数据文件
+    pub fn contains_key(&self, key: Bytes) -> Result<bool> {
+        self.wait_index_ready()?;
+        // 判断 key 的有效性
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+
+        Ok(self.index.get(key.to_vec()).is_some())
+    }
+
+    /// 批量获取多个 key 对应的数据，返回结果和传入的 key 顺序一致，不存在的 key 对应 None
+    pub fn get_many(&self, keys: &[Bytes]) -> Result<Vec<Option<Bytes>>> {
+        self.wait_index_ready()?;
+        // 先从内存索引中取出所有 key 的位置信息
+        let mut positions = Vec::with_capacity(keys.len());
+        for (i, key) in keys.iter().enumerate() {
+            if key.is_empty() {
+                return Err(Errors::KeyIsEmpty);
+            }
+            if let Some(pos) = self.index.get(key.to_vec()) {
+                positions.push((i, pos));
+            }
+        }
+
+        // 按照文件 id 和偏移排序，尽量顺序读取数据文件
+        positions.sort_by_key(|(_, pos)| (pos.file_id, pos.offset));
+
+        let mut values = vec![None; keys.len()];
+        let active_file = self.active_file.read();
+        let older_files = self.older_files.read();
+        for (i, pos) in positions {
+            let data_file = find_data_file(&active_file, &older_files, pos.file_id)?;
+            match self.read_value(data_file, &pos) {
+                Ok(value) => values[i] = Some(value),
+                Err(Errors::KeyNotFound) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(values)
     }
 
     /// 根据索引信息获取 value
     pub(crate) fn get_value_by_position(&self, log_record_pos: &LogRecordPos) -> Result<Bytes> {
-        // 从对应的数据文件中获取对应的 LogRecord
-        let active_file = self.active_file.read();
-        let oldre_files = self.older_files.read();
-        let log_record = match active_file.get_file_id() == log_record_pos.file_id {
-            true => active_file.read_log_record(log_record_pos.offset)?.record,
-            false => {
-                let data_file = oldre_files.get(&log_record_pos.file_id);
-                if data_file.is_none() {
-                    // 找不到对应的数据文件，返回错误
-                    return Err(Errors::DataFileNotFound);
-                }
-                data_file
-                    .unwrap()
-                    .read_log_record(log_record_pos.offset)?
-                    .record
+        self.with_data_file(log_record_pos.file_id, |data_file| {
+            self.read_value(data_file, log_record_pos)
+        })
+    }
+
+    // 在 file_id 对应的数据文件上执行读取操作
+    // 写入数据时会一直持有活跃文件的写锁，包括持久化的过程，因此旧的数据文件只持有 older_files 的读锁，
+    // 不会被并发的写入阻塞，只有读取活跃文件时才需要活跃文件的读锁
+    fn with_data_file<R>(&self, file_id: u32, f: impl FnOnce(&DataFile) -> Result<R>) -> Result<R> {
+        {
+            let older_files = self.older_files.read();
+            if let Some(data_file) = older_files.get(&file_id) {
+                return f(data_file);
             }
-        };
+        }
+        // 和写入时的加锁顺序一致，先锁活跃文件再锁旧的数据文件
+        // 期间活跃文件可能已经被切换为旧的数据文件，需要重新查找
+        let active_file = self.active_file.read();
+        let older_files = self.older_files.read();
+        f(find_data_file(&active_file, &older_files, file_id)?)
+    }
+
+    // 从数据文件中读取索引位置对应的 value
+    fn read_value(&self, data_file: &DataFile, log_record_pos: &LogRecordPos) -> Result<Bytes> {
+        let (value, _) = self.read_value_with_expire(data_file, log_record_pos)?;
+        Ok(value)
+    }
+
+    // 从数据文件中读取索引位置对应的 value 以及过期时间
+    fn read_value_with_expire(
+        &self,
+        data_file: &DataFile,
+        log_record_pos: &LogRecordPos,
+    ) -> Result<(Bytes, u64)> {
+        // 从对应的数据文件中获取对应的 LogRecord
+        let verify_crc = self.options.verify_checksum_on_read;
+        let log_record = data_file
+            .read_log_record_with_crc(log_record_pos.offset, verify_crc)
+            .map_err(strip_invalid_crc_key)?
+            .record;
 
         // 判断 LogRecord 的类型
         if log_record.rec_type == LogRecordType::DELETED {
             return Err(Errors::KeyNotFound);
         }
 
-        // 返回对应的 value 信息
-        Ok(log_record.value.into())
+        // 判断数据是否已经过期
+        if log_record.is_expired(self.now_millis()) {
+            return Err(Errors::KeyNotFound);
+        }
+
+        // 返回对应的 value 信息，数据文件中存储的 key 带有事务序列号
+        let expire = log_record.expire;
+        let (real_key, _) = log_record.parse_key();
+        let value = self.decode_stored_value(
+            &real_key,
+            log_record.value,
+            log_record.compression,
+            log_record.encrypted,
+        )?;
+        Ok((value.into(), expire))
+    }
+
+    // 解码数据文件中存储的 value，先解密再解压，log_record 的 key 需要是用户写入的 key
+    pub(crate) fn decode_value(&self, log_record: LogRecord) -> Result<Bytes> {
+        let value = self.decode_stored_value(
+            &log_record.key,
+            log_record.value,
+            log_record.compression,
+            log_record.encrypted,
+        )?;
+        Ok(value.into())
+    }
+
+    fn decode_stored_value(
+        &self,
+        key: &[u8],
+        value: Vec<u8>,
+        compression: Compression,
+        encrypted: bool,
+    ) -> Result<Vec<u8>> {
+        let value = match encrypted {
+            true => match &self.options.encryption {
+                Some(config) => util::crypto::decrypt(&config.key, &value, key)?,
+                None => return Err(Errors::DecryptionFailed),
+            },
+            false => value,
+        };
+        util::compress::decompress(compression, value)
+    }
+
+    // 统计操作的耗时和错误，操作完成之后再调用 recorder，此时不持有引擎内部的锁
+    pub(crate) fn with_metrics<R>(&self, op: OpKind, f: impl FnOnce() -> Result<R>) -> Result<R> {
+        let Some(metrics) = &self.options.metrics else {
+            return f();
+        };
+        let start = Instant::now();
+        let res = f();
+        metrics.record_op(op, start.elapsed());
+        if let Err(e) = &res {
+            if *e != Errors::KeyNotFound {
+                metrics.incr_error(op, e);
+            }
+        }
+        res
+    }
+
+    /// 已经分配的最大的写入序列号，还没有写入过数据时为 0
+    pub fn last_sequence(&self) -> Result<u64> {
+        self.wait_index_ready()?;
+        Ok(self.sequence.load(Ordering::SeqCst))
+    }
+
+    /// 索引是否已经加载完成，配置了 lazy_index 时索引在后台线程中加载
+    pub fn index_ready(&self) -> bool {
+        self.index_state.ready.load(Ordering::SeqCst)
+    }
+
+    // 等待索引加载完成，索引加载失败时返回对应的错误，读写操作之前需要调用
+    pub(crate) fn wait_index_ready(&self) -> Result<()> {
+        self.index_state.wait()
+    }
+
+    // 索引加载完成之前，根据布隆过滤器判断 key 是否一定不存在，加载完成之后直接使用索引
+    fn bloom_filters_exclude(&self, key: &[u8]) -> bool {
+        let Some(bloom_filters) = &self.bloom_filters else {
+            return false;
+        };
+        if key.is_empty() || self.index_state.is_ready() {
+            return false;
+        }
+        let mut file_ids = vec![self.active_file.read().get_file_id()];
+        file_ids.extend(self.older_files.read().keys());
+        !bloom_filters.may_contain(key, &file_ids)
+    }
+
+    // 只读模式下不允许写入数据
+    pub(crate) fn check_writable(&self) -> Result<()> {
+        if self.options.read_only {
+            return Err(Errors::ReadOnlyDatabase);
+        }
+        Ok(())
+    }
+
+    // 校验 key 和 value 的长度，需要在编码之前进行，避免写入无法索引的数据
+    pub(crate) fn check_key_value_size(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        if key.len() > self.options.max_key_size || key.len() > u32::MAX as usize {
+            return Err(Errors::KeyTooLarge);
+        }
+        if value.len() > self.options.max_value_size || value.len() > u32::MAX as usize {
+            return Err(Errors::ValueTooLarge);
+        }
+        Ok(())
+    }
+
+    // 按照配置项压缩 value，返回压缩后的数据和实际使用的压缩算法
+    // 空的以及小于 compression_min_size 的 value 不压缩，压缩带来的额外开销可能超过节省的空间
+    pub(crate) fn compress_value(&self, value: &[u8]) -> Result<(Vec<u8>, Compression)> {
+        let compression = match value.is_empty() || value.len() < self.options.compression_min_size
+        {
+            true => Compression::None,
+            false => self.options.compression,
+        };
+        let value = util::compress::compress(compression, value)?;
+        Ok((value, compression))
+    }
+
+    // 配置了加密时加密 value，返回加密后的数据以及是否经过加密
+    // 用户写入的 key 作为附加认证数据，value 被复制到其他 key 的记录中时无法解密
+    pub(crate) fn encrypt_value(&self, key: &[u8], value: Vec<u8>) -> Result<(Vec<u8>, bool)> {
+        match &self.options.encryption {
+            Some(config) if !value.is_empty() => {
+                Ok((util::crypto::encrypt(&config.key, &value, key)?, true))
+            }
+            _ => Ok((value, false)),
+        }
+    }
+
+    // 从配置的时钟获取当前的 unix 毫秒时间戳
+    pub(crate) fn now_millis(&self) -> u64 {
+        self.options.clock.now()
     }
 
     // 追加写数据到当前活跃文件中
+    // 普通的数据和删除标记会分配新的写入序列号，并回填到 log_record 中
     pub(crate) fn append_log_record(&self, log_record: &mut LogRecord) -> Result<LogRecordPos> {
-        let dir_path = self.options.dir_path.clone();
+        let assign_sequence = log_record.rec_type != LogRecordType::TXNFINISHED;
+        self.append_log_record_with_sequence(log_record, assign_sequence, true)
+    }
 
-        // 输入数据进行编码
-        let enc_record = log_record.encode();
-        let record_len = enc_record.len() as u64;
+    // 追加写入 merge 重写的数据，保留数据原有的写入序列号
+    pub(crate) fn append_rewritten_log_record(
+        &self,
+        log_record: &mut LogRecord,
+    ) -> Result<LogRecordPos> {
+        self.append_log_record_with_sequence(log_record, false, true)
+    }
 
-        // 获取到当前活跃文件
+    // allow_sync 为 false 时不按照配置项持久化，切换活跃文件时仍然会持久化旧的文件
+    fn append_log_record_with_sequence(
+        &self,
+        log_record: &mut LogRecord,
+        assign_sequence: bool,
+        allow_sync: bool,
+    ) -> Result<LogRecordPos> {
+        // 获取到当前活跃文件，数据使用活跃文件的校验算法编码
         let mut active_file = self.active_file.write();
+        // 持有活跃文件的锁分配序列号，数据在文件中的顺序和序列号的顺序一致
+        // 写入失败时序列号不会回退，只会产生空洞
+        if assign_sequence {
+            log_record.sequence = self.sequence.fetch_add(1, Ordering::SeqCst) + 1;
+            // 新写入的数据记录写入时间，merge 重写的数据保留原有的写入时间
+            if self.options.write_timestamp {
+                log_record.timestamp = self.now_millis();
+            }
+        }
+        let record_len = log_record.encoded_length(active_file.checksum()) as u64;
 
-        // 判断当前活跃文件是否达到了阈值
-        if active_file.get_write_off() + record_len > self.options.data_file_size {
-            // 将当前活跃文件进行持久化
+        // 判断当前活跃文件是否达到了阈值，空的活跃文件直接写入，避免产生空的数据文件
+        // 超过数据文件大小的数据最多切换一次文件，单独占用一个新的数据文件，不会跨文件存储
+        let write_off = active_file.get_write_off();
+        if write_off > active_file.header_size()
+            && write_off + record_len > self.options.data_file_size
+        {
+            // 截断预分配之后没有使用的空间，将当前活跃文件进行持久化
+            active_file.truncate_unused_space()?;
             active_file.sync()?;
+            if let Some(bloom_filters) = &self.bloom_filters {
+                bloom_filters.seal(&self.options, &active_file)?;
+            }
 
             let current_fid = active_file.get_file_id();
             // 旧的数据文件存储到 map 中
             let mut older_files = self.older_files.write();
-            let old_file = DataFile::new(dir_path.clone(), current_fid, IOType::StandardFIO)?;
+            let old_file = DataFile::new(&self.options, current_fid, older_io_type(&self.options))?;
             older_files.insert(current_fid, old_file);
 
-            // 打开新的数据文件
-            let new_file = DataFile::new(dir_path.clone(), current_fid + 1, IOType::StandardFIO)?;
+            // 打开新的数据文件，并持久化目录，保证断电之后新的数据文件仍然存在
+            let mut new_file =
+                DataFile::new(&self.options, current_fid + 1, self.active_io_type())?;
+            new_file.preallocate(&self.options)?;
+            util::file::sync_dir(&self.options.dir_path)?;
             *active_file = new_file;
         }
 
-        // 追加写数据到当前活跃文件中
+        // 输入数据进行编码，追加写数据到当前活跃文件中
+        let enc_record = log_record.encode_with_checksum(active_file.checksum());
         let write_off = active_file.get_write_off();
         active_file.write(&enc_record)?;
+        if let Some(bloom_filters) = &self.bloom_filters {
+            bloom_filters.record(&log_record.key);
+        }
 
-        let previous = self
-            .bytes_write
-            .fetch_add(enc_record.len(), Ordering::SeqCst);
-        // 根据配置项决定是否持久化
+        // 根据配置项决定是否持久化，累计写入的字节数在持久化之后清空
+        // SyncMode::None 表示写入时不持久化，交给操作系统决定何时落盘
         let mut need_sync = self.options.sync_writes;
         if !need_sync
             && self.options.bytes_per_sync > 0
-            && previous + enc_record.len() >= self.options.bytes_per_sync
+            && active_file.bytes_since_sync() >= self.options.bytes_per_sync
         {
             need_sync = true;
         }
 
-        if need_sync {
+        if need_sync && allow_sync && self.options.sync_mode != SyncMode::None {
             active_file.sync()?;
-            // 清空累计值
-            self.bytes_write.store(0, Ordering::SeqCst);
         }
 
         // 构造数据索引信息
@@ -397,9 +1526,149 @@ impl Engine {
         })
     }
 
+    // B+树索引模式下加载事务序列号
+    fn load_seq_no(&self) -> (bool, usize) {
+        let file_name = self.options.dir_path.join(SEQ_NO_FILE_NAME);
+        if !util::file::is_file(&self.options, &file_name) {
+            return (false, 0);
+        }
+
+        let seq_no_file =
+            DataFile::new_seq_no_file(&self.options, self.options.dir_path.clone()).unwrap();
+        let record = match seq_no_file.read_log_record(0) {
+            Ok(res) => res.record,
+            Err(e) => panic!("failed to read seq no: {}", e),
+        };
+        let v = String::from_utf8(record.value).unwrap();
+        let seq_no = v.parse::<usize>().unwrap();
+
+        // 加载后删除掉，避免追加写入
+        util::file::remove_file(&self.options, &file_name).unwrap();
+
+        (true, seq_no)
+    }
+
+    // 活跃文件的 IO 类型，需要追加写入，不使用 mmap
+    pub(crate) fn active_io_type(&self) -> IOType {
+        active_io_type(&self.options)
+    }
+}
+
+// 活跃文件的 IO 类型，配置了写缓冲时使用带缓冲的文件 IO
+fn active_io_type(options: &Options) -> IOType {
+    if options.write_buffer_size > 0 && !options.read_only {
+        IOType::BufferedFIO(options.write_buffer_size)
+    } else {
+        IOType::StandardFIO
+    }
+}
+
+// 索引的加载状态，后台加载索引时读写操作需要等待加载完成
+pub(crate) struct IndexState {
+    ready: AtomicBool,                 // 索引是否已经加载成功
+    result: Mutex<Option<Result<()>>>, // 加载的结果，为 None 时表示还在加载
+    finished: Condvar,
+}
+
+impl IndexState {
+    fn new() -> Self {
+        IndexState {
+            ready: AtomicBool::new(false),
+            result: Mutex::new(None),
+            finished: Condvar::new(),
+        }
+    }
+
+    // 记录加载的结果，并唤醒所有等待的线程
+    fn finish(&self, res: Result<()>) {
+        let mut result = self.result.lock();
+        self.ready.store(res.is_ok(), Ordering::SeqCst);
+        *result = Some(res);
+        self.finished.notify_all();
+    }
+
+    fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::SeqCst)
+    }
+
+    // 等待加载结束，返回加载的结果
+    fn wait(&self) -> Result<()> {
+        if self.ready.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let mut result = self.result.lock();
+        loop {
+            match result.as_ref() {
+                Some(res) => return res.clone(),
+                None => self.finished.wait(&mut result),
+            }
+        }
+    }
+}
+
+// 加载索引需要用到的数据，和 Engine 共享，后台加载索引时在单独的线程中使用
+pub(crate) struct IndexLoader {
+    pub(crate) options: Arc<Options>,
+    pub(crate) active_file: Arc<RwLock<DataFile>>,
+    older_files: Arc<RwLock<HashMap<u32, DataFile>>>,
+    pub(crate) index: Arc<dyn index::Index<LogRecordPos>>,
+    pub(crate) file_ids: Vec<u32>, // 数据库启动时的文件 id，只用于加载索引时使用，不能在其他的地方更新或使用
+    pub(crate) tombstones: Option<Tombstones>,
+    pub(crate) reclaim_size: Arc<AtomicUsize>,
+    prefix_index: Option<Arc<PrefixIndex>>,
+    pub(crate) keyspaces: Arc<Keyspaces>,
+    pub(crate) seq_no: Arc<AtomicUsize>,
+    pub(crate) sequence: Arc<AtomicU64>,
+}
+
+impl IndexLoader {
+    // 加载索引，之后更新事务序列号并切换活跃文件的 IO 类型
+    fn load(&self) -> Result<()> {
+        let start = Instant::now();
+
+        // 优先从索引快照中加载，快照不存在或者已经失效时再从 hint 文件和数据文件中加载
+        // 快照中只有默认 keyspace 的索引，存在其他 keyspace 时不使用快照
+        let snapshot_loaded = self.keyspaces.is_empty() && self.load_index_from_snapshot()?;
+        if !snapshot_loaded {
+            // 从 hint 文件中加载索引
+            let hint_loaded = self.load_index_from_hint_file()?;
+
+            // 从数据文件中加载索引
+            let current_seq_no = self.load_index_from_data_files(hint_loaded)?;
+
+            // 更新当前事务序列号
+            if current_seq_no > 0 {
+                self.seq_no.store(current_seq_no + 1, Ordering::SeqCst);
+            }
+        }
+
+        // 根据加载好的索引建立前缀索引
+        self.load_prefix_index()?;
+
+        // 活跃文件按照配置切换 IO 类型，例如使用带写缓冲的 IO
+        // 崩溃之前预分配的空间没有被截断，先截断再写入，否则之后追加的数据在重启时无法读取
+        let mut active_file = self.active_file.write();
+        if !self.options.read_only {
+            active_file.truncate_to_write_off()?;
+        }
+        let io_type = active_io_type(&self.options);
+        if active_file.io_type() != io_type {
+            active_file.set_io_manager(&self.options, io_type);
+        }
+        active_file.preallocate(&self.options)?;
+
+        info!(
+            "loaded index of {} in {:?}",
+            self.options.dir_path.display(),
+            start.elapsed()
+        );
+        Ok(())
+    }
+
     /// 从数据文件中加载内存索引
     /// 遍历数据文件中的内容，并依次处理其中的记录
-    fn load_index_from_data_files(&self) -> Result<usize> {
+    /// hint_loaded 为 false 时，merge 后的数据文件也需要重新加载
+    fn load_index_from_data_files(&self, hint_loaded: bool) -> Result<usize> {
         let mut current_seq_no = NON_TRANSACTION_SEQ_NO;
 
         // 数据文件为空，直接返回
@@ -411,17 +1680,21 @@ impl Engine {
         let mut has_merge = false;
         let mut non_merge_fid = 0;
         let merge_fin_file = self.options.dir_path.join(MERGE_FINISHED_FILE_NAME);
-        if merge_fin_file.is_file() {
-            let merge_fin_file = DataFile::new_merge_fin_file(self.options.dir_path.clone())?;
+        if util::file::is_file(&self.options, &merge_fin_file) {
+            let merge_fin_file =
+                DataFile::new_merge_fin_file(&self.options, self.options.dir_path.clone())?;
             let merge_fin_record = merge_fin_file.read_log_record(0)?;
+            // merge 完成时记录了当时最大的写入序列号，merge 之后的数据文件不再重新遍历
+            self.sequence
+                .fetch_max(merge_fin_record.record.sequence, Ordering::SeqCst);
             let v = String::from_utf8(merge_fin_record.record.value).unwrap();
 
             non_merge_fid = v.parse::<u32>().unwrap();
-            has_merge = true;
+            has_merge = hint_loaded;
         }
 
         // 暂存事务相关的数据
-        let mut transaction_records = HashMap::new();
+        let mut transaction_records: HashMap<usize, Vec<TransactionRecord>> = HashMap::new();
 
         let active_file = self.active_file.read();
         let older_files = self.older_files.read();
@@ -433,7 +1706,11 @@ impl Engine {
                 continue;
             }
 
-            let mut offset = 0;
+            // 跳过文件头部，从第一条数据开始读取
+            let mut offset = match *file_id == active_file.get_file_id() {
+                true => active_file.header_size(),
+                false => older_files.get(file_id).unwrap().header_size(),
+            };
             loop {
                 let log_record_res = match *file_id == active_file.get_file_id() {
                     true => active_file.read_log_record(offset),
@@ -460,29 +1737,45 @@ impl Engine {
                     size: size as u32,
                 };
 
+                // 恢复最大的写入序列号，包括未提交的事务中的数据
+                self.sequence
+                    .fetch_max(log_record.sequence, Ordering::SeqCst);
+
                 // 解析 key，拿到实际的 key 和 seq no
                 let (real_key, seq_no) = parse_log_record_key(log_record.key.clone());
                 // 非事务提交的情况，直接更新内存索引
                 if seq_no == NON_TRANSACTION_SEQ_NO {
-                    self.update_index(real_key, log_record.rec_type, log_record_pos);
+                    self.update_index(
+                        real_key,
+                        log_record.rec_type,
+                        log_record_pos,
+                        log_record.keyspace,
+                    );
                 } else {
                     // 事务有提交的标识，更新内存索引
                     if log_record.rec_type == LogRecordType::TXNFINISHED {
-                        let records: &Vec<TransactionRecord> =
-                            transaction_records.get(&seq_no).unwrap();
-                        for txn_record in records.iter() {
-                            self.update_index(
-                                txn_record.record.key.clone(),
-                                txn_record.record.rec_type,
-                                txn_record.pos,
-                            );
+                        // 事务的数据可能已经从 hint 文件中加载，找不到对应的数据时跳过提交标识
+                        match transaction_records.remove(&seq_no) {
+                            Some(records) => {
+                                for txn_record in records.iter() {
+                                    self.update_index(
+                                        txn_record.record.key.clone(),
+                                        txn_record.record.rec_type,
+                                        txn_record.pos,
+                                        txn_record.record.keyspace,
+                                    );
+                                }
+                            }
+                            None => warn!(
+                                "transaction {} has no records before its finished marker, skip it",
+                                seq_no
+                            ),
                         }
-                        transaction_records.remove(&seq_no);
                     } else {
                         log_record.key = real_key;
                         transaction_records
                             .entry(seq_no)
-                            .or_insert(Vec::new())
+                            .or_default()
                             .push(TransactionRecord {
                                 record: log_record,
                                 pos: log_record_pos,
@@ -508,14 +1801,37 @@ impl Engine {
     }
 
     // 加载索引时更新内存数据
-    fn update_index(&self, key: Vec<u8>, rec_type: LogRecordType, pos: LogRecordPos) {
+    fn update_index(
+        &self,
+        key: Vec<u8>,
+        rec_type: LogRecordType,
+        pos: LogRecordPos,
+        keyspace: u32,
+    ) {
+        // 不属于默认 keyspace 的数据只更新对应 keyspace 的索引，不记录删除标记和前缀索引
+        if keyspace != 0 {
+            match self.keyspaces.get(keyspace) {
+                Some(keyspace) => {
+                    let size = keyspace.update_index(key, rec_type, pos);
+                    self.reclaim_size.fetch_add(size, Ordering::SeqCst);
+                }
+                None => warn!("skip record of unknown keyspace {}", keyspace),
+            }
+            return;
+        }
         if rec_type == LogRecordType::NORMAL {
+            if let Some(tombstones) = &self.tombstones {
+                update_tombstone(tombstones, &key, None);
+            }
             if let Some(old_pos) = self.index.put(key.clone(), pos) {
                 self.reclaim_size
                     .fetch_add(old_pos.size as usize, Ordering::SeqCst);
             }
         }
         if rec_type == LogRecordType::DELETED {
+            if let Some(tombstones) = &self.tombstones {
+                update_tombstone(tombstones, &key, Some(pos.file_id));
+            }
             let mut size = pos.size;
             if let Some(old_pos) = self.index.delete(key) {
                 size += old_pos.size;
@@ -524,72 +1840,121 @@ impl Engine {
         }
     }
 
-    // B+树索引模式下加载事务序列号
-    fn load_seq_no(&self) -> (bool, usize) {
-        let file_name = self.options.dir_path.join(SEQ_NO_FILE_NAME);
-        if !file_name.is_file() {
-            return (false, 0);
+    // 根据内存索引中的 key 建立前缀索引，加载完索引之后调用
+    fn load_prefix_index(&self) -> Result<()> {
+        if let Some(prefix_index) = &self.prefix_index {
+            for key in self.index.list_keys()? {
+                prefix_index.put(&key);
+            }
         }
+        Ok(())
+    }
+}
 
-        let seq_no_file = DataFile::new_seq_no_file(self.options.dir_path.clone()).unwrap();
-        let record = match seq_no_file.read_log_record(0) {
-            Ok(res) => res.record,
-            Err(e) => panic!("failed to read seq no: {}", e),
-        };
-        let v = String::from_utf8(record.value).unwrap();
-        let seq_no = v.parse::<usize>().unwrap();
-
-        // 加载后删除掉，避免追加写入
-        fs::remove_file(file_name).unwrap();
+// 旧的数据文件的 IO 类型，旧的数据文件不会再写入，配置了 mmap_at_startup 时使用 mmap 读取
+pub(crate) fn older_io_type(options: &Options) -> IOType {
+    match options.mmap_at_startup {
+        true => IOType::MemoryMap,
+        false => IOType::StandardFIO,
+    }
+}
 
-        (true, seq_no)
+// 更新删除标记，deleted_in 为 None 时移除 key 的删除标记
+// 大部分写入的 key 没有删除标记，先持有读锁检查，避免每次写入都持有写锁
+fn update_tombstone(tombstones: &Tombstones, key: &[u8], deleted_in: Option<u32>) {
+    match deleted_in {
+        Some(file_id) => {
+            tombstones.write().insert(key.to_vec(), file_id);
+        }
+        None => {
+            if tombstones.read().contains_key(key) {
+                tombstones.write().remove(key);
+            }
+        }
     }
+}
 
-    fn reset_io_type(&self) {
-        let mut active_file = self.active_file.write();
-        active_file.set_io_manager(self.options.dir_path.clone(), IOType::StandardFIO);
-        let mut older_files = self.older_files.write();
-        for (_, file) in older_files.iter_mut() {
-            file.set_io_manager(self.options.dir_path.clone(), IOType::StandardFIO);
+// 校验失败时去掉事务序列号，返回实际的 key，key 本身损坏时返回原始数据
+fn strip_invalid_crc_key(e: Errors) -> Errors {
+    match e {
+        Errors::InvalidCrc { key } => {
+            let mut buf = key.as_slice();
+            let key = match decode_length_delimiter(&mut buf) {
+                Ok(_) => buf.to_vec(),
+                Err(_) => key,
+            };
+            Errors::InvalidCrc { key }
         }
+        e => e,
+    }
+}
+
+// 根据文件 id 找到对应的数据文件，找不到时返回错误
+fn find_data_file<'a>(
+    active_file: &'a DataFile,
+    older_files: &'a HashMap<u32, DataFile>,
+    file_id: u32,
+) -> Result<&'a DataFile> {
+    match active_file.get_file_id() == file_id {
+        true => Ok(active_file),
+        false => older_files.get(&file_id).ok_or(Errors::DataFileNotFound),
     }
 }
 
 impl Drop for Engine {
     fn drop(&mut self) {
-        if let Err(e) = self.close() {
-            log::error!("error whiling close engine: {}", e);
+        // 已经调用 close 关闭并持久化，不需要重复处理
+        if self.is_closed {
+            return;
+        }
+        // 没有调用 close 时尽量持久化活跃文件，失败时记录日志，避免静默丢失最近写入的数据
+        if let Err(e) = self.close_engine() {
+            log::error!(
+                "failed to close engine on drop, recent writes may be lost: {}",
+                e
+            );
         }
     }
 }
 
 // 从数据目录中加载数据文件
-fn load_data_files(dir_path: PathBuf, use_mmap: bool) -> Result<Vec<DataFile>> {
-    // 读取数据目录
-    let dir = fs::read_dir(dir_path.clone());
-    if dir.is_err() {
-        return Err(Errors::FailedToReadDatabaseDir);
+// 打开数据目录的文件锁，只读模式下不创建锁文件，数据目录可以位于只读的文件系统上
+// 只读的数据目录中没有锁文件时，对数据目录本身加锁
+fn open_lock_file(dir_path: &Path, read_only: bool) -> std::io::Result<File> {
+    let lock_path = dir_path.join(FILE_LOCK_NAME);
+    if !read_only {
+        return fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(lock_path);
+    }
+    match File::open(&lock_path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => File::open(dir_path),
+        res => res,
     }
+}
+
+pub(crate) fn load_data_files(options: &Options) -> Result<Vec<DataFile>> {
+    // 读取数据目录
+    let file_names = match util::file::list_file_names(options, &options.dir_path) {
+        Ok(file_names) => file_names,
+        Err(_) => return Err(Errors::FailedToReadDatabaseDir),
+    };
 
     let mut file_ids: Vec<u32> = Vec::new();
+    let mut empty_file_ids: HashSet<u32> = HashSet::new();
     let mut data_files: Vec<DataFile> = Vec::new();
-    for file in dir.unwrap() {
-        if let Ok(entry) = file {
-            // 拿到文件名
-            let file_os_str = entry.file_name();
-            let file_name = file_os_str.to_str().unwrap();
-
-            // 判断文件名称是否是以 .data 结尾
-            if file_name.ends_with(DATA_FILE_NAME_SUFFIX) {
-                let split_names: Vec<&str> = file_name.split(".").collect();
-                let file_id = match split_names[0].parse::<u32>() {
-                    Ok(fid) => fid,
-                    Err(_) => {
-                        return Err(Errors::DataDirectoryCorrupted);
-                    }
-                };
-                file_ids.push(file_id);
+    for file_name in file_names {
+        // 判断文件名称是否是以数据文件的扩展名结尾
+        if let Some(file_id) = parse_data_file_id(options, &file_name) {
+            let file_id = file_id?;
+            // 新建数据文件之后发生崩溃时，可能留下长度为 0 的文件
+            let file_path = options.dir_path.join(&file_name);
+            if util::file::file_size(options, &file_path).is_ok_and(|size| size == 0) {
+                empty_file_ids.insert(file_id);
             }
+            file_ids.push(file_id);
         }
     }
 
@@ -598,15 +1963,18 @@ fn load_data_files(dir_path: PathBuf, use_mmap: bool) -> Result<Vec<DataFile>> {
         return Ok(data_files);
     }
 
-    // 对文件 id 进行排序，从小到大进行加载
+    // 对文件 id 进行排序，从小到大进行加载，删除过部分数据文件时 id 可能不连续
     file_ids.sort();
-    // 遍历所有的文件id，依次打开对应的数据文件
-    for file_id in file_ids.iter() {
-        let mut io_type = IOType::StandardFIO;
-        if use_mmap {
-            io_type = IOType::MemoryMap;
-        }
-        let data_file = DataFile::new(dir_path.clone(), *file_id, io_type)?;
+    // 空文件中没有任何数据，不作为旧的数据文件加载，id 最大的空文件继续作为活跃文件使用
+    let max_file_id = file_ids[file_ids.len() - 1];
+    file_ids.retain(|file_id| *file_id == max_file_id || !empty_file_ids.contains(file_id));
+    // 遍历所有的文件id，依次打开对应的数据文件，最后一个文件是活跃文件，使用标准文件 IO
+    for (i, file_id) in file_ids.iter().enumerate() {
+        let io_type = match i == file_ids.len() - 1 {
+            true => IOType::StandardFIO,
+            false => older_io_type(options),
+        };
+        let data_file = DataFile::new(options, *file_id, io_type)?;
         data_files.push(data_file);
     }
 
@@ -627,5 +1995,24 @@ fn check_options(opts: &Options) -> Option<Errors> {
         return Some(Errors::InvalidMergeRatio);
     }
 
+    // 布隆过滤器和索引快照直接读写本地文件
+    if opts.io_factory.is_some()
+        && (opts.enable_bloom_filters || opts.index_snapshot_path.is_some())
+    {
+        return Some(Errors::IOFactoryUnsupported);
+    }
+
+    if opts.enable_bloom_filters
+        && !(opts.bloom_false_positive_rate > 0.0 && opts.bloom_false_positive_rate < 1.0)
+    {
+        return Some(Errors::InvalidBloomFalsePositiveRate);
+    }
+
+    // 扩展名为空或者包含路径分隔符时，无法和其他文件区分
+    let ext = &opts.data_file_ext;
+    if ext.is_empty() || ext.contains('.') || ext.contains('/') || ext.contains('\\') {
+        return Some(Errors::InvalidDataFileExt);
+    }
+
     None
 }
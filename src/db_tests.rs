@@ -1,11 +1,44 @@
 use bytes::Bytes;
-use std::path::PathBuf;
+use std::{
+    collections::HashMap,
+    io::{Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use crate::{
-    db::Engine,
+    batch::{log_record_key_with_seq, NON_TRANSACTION_SEQ_NO},
+    bloom::get_bloom_file_name,
+    clock::MockClock,
+    data::{
+        data_file::{
+            get_data_file_name, DataFile, DATA_FILE_HEADER_SIZE, HINT_FILE_NAME,
+            MERGE_FINISHED_FILE_NAME,
+        },
+        log_record::{LogRecord, LogRecordType},
+    },
+    db::{Engine, KeyStatus, FILE_LOCK_NAME},
     error::Errors,
-    option::Options,
-    util::rand_kv::{get_test_key, get_test_value},
+    fileio::{
+        file_io::FileIO,
+        testing::{FaultyIO, InMemoryFactory, InMemoryIO},
+        IOManager,
+    },
+    merge::get_merge_path,
+    metrics::{MetricsRecorder, OpKind},
+    option::{
+        Checksum, Compression, EncryptionConfig, IOType, IteratorOptions, Options, SyncMode,
+        WriteBatchOptions,
+    },
+    util::{
+        self,
+        rand_kv::{get_test_key, get_test_value},
+    },
 };
 
 #[test]
@@ -48,7 +81,8 @@ fn test_engine_put() {
     }
 
     // 6.重启后再 Put 数据
-    // 先关闭原数据库 todo
+    // 先关闭原数据库
+    engine.close().expect("failed to close engine");
     let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
     let res9 = engine2.put(get_test_key(55), get_test_value(55));
     assert!(res9.is_ok());
@@ -103,7 +137,8 @@ fn test_engine_get() {
     assert_eq!(get_test_value(505), res10.unwrap());
 
     // 6.重启后，前面写入的数据都能拿到
-    // 先关闭原数据库 todo
+    // 先关闭原数据库
+    engine.close().expect("failed to close engine");
     let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
     let res11 = engine2.get(get_test_key(111));
     assert_eq!(get_test_value(111), res11.unwrap());
@@ -127,13 +162,13 @@ fn test_engine_delete() {
     let res1 = engine.put(get_test_key(111), get_test_value(111));
     assert!(res1.is_ok());
     let res2 = engine.delete(get_test_key(111));
-    assert!(res2.is_ok());
+    assert_eq!(Some(get_test_value(111)), res2.unwrap());
     let res3 = engine.get(get_test_key(111));
     assert_eq!(Errors::KeyNotFound, res3.err().unwrap());
 
     // 2.删除一个不存在的 key
     let res4 = engine.delete(Bytes::from("not-existed-key"));
-    assert!(res4.is_ok());
+    assert_eq!(None, res4.unwrap());
 
     // 3.删除一个空的 key
     let res5 = engine.delete(Bytes::new());
@@ -149,13 +184,39 @@ fn test_engine_delete() {
     let res9 = engine.get(get_test_key(222));
     assert_eq!(Bytes::from("a new value"), res9.unwrap());
 
-    // 5.重启后再 Put 数据
-    // 先关闭原数据库 todo
+    // 5.不读取旧的 value 直接删除
+    let res10 = engine.put(get_test_key(333), get_test_value(333));
+    assert!(res10.is_ok());
+    let res11 = engine.delete_fast(get_test_key(333));
+    assert!(res11.is_ok());
+    let res12 = engine.get(get_test_key(333));
+    assert_eq!(Errors::KeyNotFound, res12.err().unwrap());
+    let res13 = engine.delete_fast(get_test_key(333));
+    assert!(res13.is_ok());
+    let res14 = engine.delete_fast(Bytes::new());
+    assert_eq!(Errors::KeyIsEmpty, res14.err().unwrap());
+
+    // 6.删除已经过期的 key
+    let res15 = engine.put_with_ttl(
+        get_test_key(444),
+        get_test_value(444),
+        Duration::from_millis(50),
+    );
+    assert!(res15.is_ok());
+    thread::sleep(Duration::from_millis(100));
+    let res16 = engine.delete(get_test_key(444));
+    assert_eq!(None, res16.unwrap());
+
+    // 7.重启后再 Put 数据
+    // 先关闭原数据库
+    engine.close().expect("failed to close engine");
     let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
-    let res10 = engine2.get(get_test_key(111));
-    assert_eq!(Errors::KeyNotFound, res10.err().unwrap());
-    let res11 = engine.get(get_test_key(222));
-    assert_eq!(Bytes::from("a new value"), res11.unwrap());
+    let res17 = engine2.get(get_test_key(111));
+    assert_eq!(Errors::KeyNotFound, res17.err().unwrap());
+    let res18 = engine2.get(get_test_key(222));
+    assert_eq!(Bytes::from("a new value"), res18.unwrap());
+    let res19 = engine2.get(get_test_key(333));
+    assert_eq!(Errors::KeyNotFound, res19.err().unwrap());
 
     // 删除测试的文件夹
     std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
@@ -174,6 +235,13 @@ fn test_engine_close() {
     let close_res = engine.close();
     assert!(close_res.is_ok());
 
+    // 关闭之后可以立即重新打开，之前写入的数据都能拿到
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    let res2 = engine2.get(get_test_key(222));
+    assert_eq!(get_test_value(222), res2.unwrap());
+    let close_res2 = engine2.close();
+    assert!(close_res2.is_ok());
+
     // 删除测试的文件夹
     std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
 }
@@ -191,6 +259,3110 @@ fn test_engine_sync() {
     let close_res = engine.sync();
     assert!(close_res.is_ok());
 
+    // 并发写入的同时持久化
+    let engine = Arc::new(engine);
+    let mut handles = vec![];
+    for t in 0..4 {
+        let eng = engine.clone();
+        let handle = thread::spawn(move || {
+            for i in t * 1000..(t + 1) * 1000 {
+                let put_res = eng.put(get_test_key(i), get_test_value(i));
+                assert!(put_res.is_ok());
+            }
+        });
+        handles.push(handle);
+    }
+    let eng = engine.clone();
+    handles.push(thread::spawn(move || {
+        for _ in 0..100 {
+            let sync_res = eng.sync();
+            assert!(sync_res.is_ok());
+        }
+    }));
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    for i in 0..4000 {
+        let get_res = engine.get(get_test_key(i));
+        assert!(get_res.is_ok());
+    }
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_contains_key() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-contains-key");
+    opts.data_file_size = 64 * 1024 * 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 1.key 存在
+    let res1 = engine.put(get_test_key(111), get_test_value(111));
+    assert!(res1.is_ok());
+    let res2 = engine.contains_key(get_test_key(111));
+    assert!(res2.unwrap());
+
+    // 2.key 不存在
+    let res3 = engine.contains_key(Bytes::from("not existed key"));
+    assert!(!res3.unwrap());
+
+    // 3.key 被删除
+    let res4 = engine.delete(get_test_key(111));
+    assert!(res4.is_ok());
+    let res5 = engine.contains_key(get_test_key(111));
+    assert!(!res5.unwrap());
+
+    // 4.key 为空
+    let res6 = engine.contains_key(Bytes::new());
+    assert_eq!(Errors::KeyIsEmpty, res6.err().unwrap());
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_put_if_absent() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-put-if-absent");
+    opts.data_file_size = 64 * 1024 * 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 1.key 不存在时写入
+    let res1 = engine.put_if_absent(get_test_key(1), get_test_value(1));
+    assert!(res1.unwrap());
+    assert_eq!(get_test_value(1), engine.get(get_test_key(1)).unwrap());
+
+    // 2.key 已经存在时不写入
+    let res2 = engine.put_if_absent(get_test_key(1), get_test_value(2));
+    assert!(!res2.unwrap());
+    assert_eq!(get_test_value(1), engine.get(get_test_key(1)).unwrap());
+
+    // 3.key 被删除之后可以再次写入
+    let res3 = engine.delete(get_test_key(1));
+    assert!(res3.is_ok());
+    let res4 = engine.put_if_absent(get_test_key(1), get_test_value(3));
+    assert!(res4.unwrap());
+    assert_eq!(get_test_value(3), engine.get(get_test_key(1)).unwrap());
+
+    // 4.key 过期之后可以再次写入
+    let res5 = engine.put_with_ttl(
+        get_test_key(2),
+        get_test_value(2),
+        Duration::from_millis(10),
+    );
+    assert!(res5.is_ok());
+    thread::sleep(Duration::from_millis(20));
+    let res6 = engine.put_if_absent(get_test_key(2), get_test_value(4));
+    assert!(res6.unwrap());
+    assert_eq!(get_test_value(4), engine.get(get_test_key(2)).unwrap());
+
+    // 5.key 为空
+    let res7 = engine.put_if_absent(Bytes::new(), get_test_value(1));
+    assert_eq!(Errors::KeyIsEmpty, res7.err().unwrap());
+
+    // 6.多个线程同时写入同一个 key，只有一个线程写入成功
+    for i in 100..150 {
+        let inserted = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for t in 0..2 {
+                let engine = &engine;
+                let inserted = &inserted;
+                s.spawn(move || {
+                    let res = engine.put_if_absent(get_test_key(i), get_test_value(t));
+                    if res.unwrap() {
+                        inserted.fetch_add(1, Ordering::SeqCst);
+                    }
+                });
+            }
+        });
+        assert_eq!(1, inserted.load(Ordering::SeqCst));
+    }
+
+    // 删除测试的文件夹
+    std::mem::drop(engine);
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_put_and_get_old() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-put-and-get-old");
+    opts.data_file_size = 64 * 1024 * 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 1.key 不存在时返回 None
+    let res1 = engine.put_and_get_old(get_test_key(1), get_test_value(1));
+    assert_eq!(None, res1.unwrap());
+
+    // 2.覆盖已有的 key，返回旧的 value
+    let res2 = engine.put_and_get_old(get_test_key(1), get_test_value(2));
+    assert_eq!(Some(get_test_value(1)), res2.unwrap());
+    assert_eq!(get_test_value(2), engine.get(get_test_key(1)).unwrap());
+
+    // 3.key 被删除之后返回 None
+    let res3 = engine.delete(get_test_key(1));
+    assert!(res3.is_ok());
+    let res4 = engine.put_and_get_old(get_test_key(1), get_test_value(3));
+    assert_eq!(None, res4.unwrap());
+
+    // 4.key 过期之后返回 None
+    let res5 = engine.put_with_ttl(
+        get_test_key(2),
+        get_test_value(2),
+        Duration::from_millis(10),
+    );
+    assert!(res5.is_ok());
+    thread::sleep(Duration::from_millis(20));
+    let res6 = engine.put_and_get_old(get_test_key(2), get_test_value(4));
+    assert_eq!(None, res6.unwrap());
+    assert_eq!(get_test_value(4), engine.get(get_test_key(2)).unwrap());
+
+    // 5.key 为空
+    let res7 = engine.put_and_get_old(Bytes::new(), get_test_value(1));
+    assert_eq!(Errors::KeyIsEmpty, res7.err().unwrap());
+
+    // 6.重启之后同样可以读取旧的 value
+    engine.close().expect("failed to close engine");
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    let res8 = engine2.put_and_get_old(get_test_key(1), get_test_value(5));
+    assert_eq!(Some(get_test_value(3)), res8.unwrap());
+
+    // 删除测试的文件夹
+    std::mem::drop(engine2);
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_increment() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-increment");
+    opts.data_file_size = 64 * 1024 * 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 1.key 不存在时从 0 开始累加
+    let res1 = engine.increment(Bytes::from("counter"), 5);
+    assert_eq!(5, res1.unwrap());
+    let res2 = engine.increment(Bytes::from("counter"), -8);
+    assert_eq!(-3, res2.unwrap());
+    let res3 = engine.get(Bytes::from("counter"));
+    assert_eq!((-3i64).to_be_bytes().as_slice(), res3.unwrap().as_ref());
+
+    // 2.溢出时按照补码回绕
+    let res4 = engine.put(
+        Bytes::from("max"),
+        Bytes::copy_from_slice(&i64::MAX.to_be_bytes()),
+    );
+    assert!(res4.is_ok());
+    let res5 = engine.increment(Bytes::from("max"), 1);
+    assert_eq!(i64::MIN, res5.unwrap());
+    let res6 = engine.increment(Bytes::from("max"), -1);
+    assert_eq!(i64::MAX, res6.unwrap());
+
+    // 3.已有的值不是 8 字节
+    let res7 = engine.put(Bytes::from("not-integer"), Bytes::from("abc"));
+    assert!(res7.is_ok());
+    let res8 = engine.increment(Bytes::from("not-integer"), 1);
+    assert_eq!(Errors::ValueNotInteger, res8.err().unwrap());
+    assert_eq!(
+        Bytes::from("abc"),
+        engine.get(Bytes::from("not-integer")).unwrap()
+    );
+
+    // 4.key 为空
+    let res9 = engine.increment(Bytes::new(), 1);
+    assert_eq!(Errors::KeyIsEmpty, res9.err().unwrap());
+
+    // 5.多个线程同时累加
+    thread::scope(|s| {
+        for _ in 0..4 {
+            let engine = &engine;
+            s.spawn(move || {
+                for _ in 0..100 {
+                    assert!(engine.increment(Bytes::from("concurrent"), 1).is_ok());
+                }
+            });
+        }
+    });
+    let res10 = engine.increment(Bytes::from("concurrent"), 0);
+    assert_eq!(400, res10.unwrap());
+
+    // 删除测试的文件夹
+    std::mem::drop(engine);
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_append() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-append");
+    opts.data_file_size = 64 * 1024 * 1024;
+    opts.max_value_size = 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 1.key 不存在时视为空值
+    let res1 = engine.append(Bytes::from("log"), Bytes::from("a"));
+    assert_eq!(1, res1.unwrap());
+    let res2 = engine.append(Bytes::from("log"), Bytes::from("bc"));
+    assert_eq!(3, res2.unwrap());
+    assert_eq!(Bytes::from("abc"), engine.get(Bytes::from("log")).unwrap());
+
+    // 2.key 被删除之后重新开始
+    let res3 = engine.delete(Bytes::from("log"));
+    assert!(res3.is_ok());
+    let res4 = engine.append(Bytes::from("log"), Bytes::from("d"));
+    assert_eq!(1, res4.unwrap());
+
+    // 3.追加之后超过 value 的大小限制，原来的值不变
+    let res5 = engine.append(Bytes::from("log"), Bytes::from(vec![b'v'; 1024]));
+    assert_eq!(Errors::ValueTooLarge, res5.err().unwrap());
+    assert_eq!(Bytes::from("d"), engine.get(Bytes::from("log")).unwrap());
+
+    // 4.key 为空
+    let res6 = engine.append(Bytes::new(), Bytes::from("a"));
+    assert_eq!(Errors::KeyIsEmpty, res6.err().unwrap());
+
+    // 5.两个线程同时追加，所有追加的数据都保留
+    thread::scope(|s| {
+        for c in [b'x', b'y'] {
+            let engine = &engine;
+            s.spawn(move || {
+                for _ in 0..100 {
+                    let res = engine.append(Bytes::from("concurrent"), Bytes::from(vec![c]));
+                    assert!(res.is_ok());
+                }
+            });
+        }
+    });
+    let value = engine.get(Bytes::from("concurrent")).unwrap();
+    assert_eq!(200, value.len());
+    assert_eq!(100, value.iter().filter(|c| **c == b'x').count());
+    assert_eq!(100, value.iter().filter(|c| **c == b'y').count());
+
+    // 删除测试的文件夹
+    std::mem::drop(engine);
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_get_many() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-get-many");
+    opts.data_file_size = 64 * 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 写入的数据分布在多个数据文件中
+    for i in 0..=2000 {
+        let res = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res.is_ok());
+    }
+    let res1 = engine.delete(get_test_key(100));
+    assert!(res1.is_ok());
+
+    // 1.返回结果和传入的 key 顺序一致，不存在的 key 为 None
+    let keys = vec![
+        get_test_key(1500),
+        get_test_key(3),
+        Bytes::from("not existed key"),
+        get_test_key(100),
+        get_test_key(2000),
+    ];
+    let res2 = engine.get_many(&keys);
+    assert!(res2.is_ok());
+    let values = res2.unwrap();
+    assert_eq!(values.len(), 5);
+    assert_eq!(values[0], Some(get_test_value(1500)));
+    assert_eq!(values[1], Some(get_test_value(3)));
+    assert_eq!(values[2], None);
+    assert_eq!(values[3], None);
+    assert_eq!(values[4], Some(get_test_value(2000)));
+
+    // 2.传入空的 key 列表
+    let res3 = engine.get_many(&[]);
+    assert_eq!(res3.unwrap().len(), 0);
+
+    // 3.包含空的 key
+    let res4 = engine.get_many(&[get_test_key(1), Bytes::new()]);
+    assert_eq!(Errors::KeyIsEmpty, res4.err().unwrap());
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_put_with_ttl() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-put-ttl");
+    opts.data_file_size = 64 * 1024 * 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 1.未过期的数据可以正常读取
+    let res1 = engine.put_with_ttl(
+        get_test_key(11),
+        get_test_value(11),
+        Duration::from_millis(200),
+    );
+    assert!(res1.is_ok());
+    let res2 = engine.put(get_test_key(22), get_test_value(22));
+    assert!(res2.is_ok());
+    let res3 = engine.get(get_test_key(11));
+    assert_eq!(get_test_value(11), res3.unwrap());
+
+    // 2.过期之后读取不到，并且索引被删除
+    thread::sleep(Duration::from_millis(300));
+    let res4 = engine.get(get_test_key(11));
+    assert_eq!(Errors::KeyNotFound, res4.err().unwrap());
+    assert_eq!(1, engine.list_keys().unwrap().len());
+    let res5 = engine.get(get_test_key(22));
+    assert_eq!(get_test_value(22), res5.unwrap());
+
+    // 3.过期的数据重新 Put
+    let res6 = engine.put(get_test_key(11), Bytes::from("a new value"));
+    assert!(res6.is_ok());
+    let res7 = engine.get(get_test_key(11));
+    assert_eq!(Bytes::from("a new value"), res7.unwrap());
+
+    // 4.重启之后过期时间仍然生效
+    let res8 = engine.put_with_ttl(
+        get_test_key(33),
+        get_test_value(33),
+        Duration::from_millis(200),
+    );
+    assert!(res8.is_ok());
+    std::mem::drop(engine);
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    let res9 = engine2.get(get_test_key(33));
+    assert_eq!(get_test_value(33), res9.unwrap());
+    thread::sleep(Duration::from_millis(300));
+    let res10 = engine2.get(get_test_key(33));
+    assert_eq!(Errors::KeyNotFound, res10.err().unwrap());
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_ttl_with_mock_clock() {
+    let clock = Arc::new(MockClock::new(1_000_000));
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-ttl-mock-clock");
+    opts.write_timestamp = true;
+    opts.clock = clock.clone();
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 1.过期时间和写入时间都使用配置的时钟
+    let res1 = engine.put_with_ttl(get_test_key(1), get_test_value(1), Duration::from_secs(10));
+    assert!(res1.is_ok());
+    let res2 = engine.put(get_test_key(2), get_test_value(2));
+    assert!(res2.is_ok());
+    assert_eq!(
+        Some(UNIX_EPOCH + Duration::from_millis(1_000_000)),
+        engine.modified_at(get_test_key(1)).unwrap()
+    );
+
+    // 2.时钟推进之前数据不会过期
+    clock.advance(Duration::from_millis(9999));
+    assert_eq!(get_test_value(1), engine.get(get_test_key(1)).unwrap());
+
+    // 3.时钟推进到过期时间之后读取不到
+    clock.advance(Duration::from_millis(1));
+    assert_eq!(
+        Errors::KeyNotFound,
+        engine.get(get_test_key(1)).err().unwrap()
+    );
+    assert_eq!(None, engine.value_len(get_test_key(1)).unwrap());
+    assert_eq!(get_test_value(2), engine.get(get_test_key(2)).unwrap());
+    engine.close().expect("failed to close engine");
+
+    // 4.时钟回拨之后重新打开，数据仍然在有效期内
+    clock.set(1_000_000);
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(get_test_value(1), engine2.get(get_test_key(1)).unwrap());
+    engine2.close().expect("failed to close engine");
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_get_into() {
+    let clock = Arc::new(MockClock::new(1_000_000));
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-get-into");
+    opts.data_file_size = 64 * 1024;
+    opts.compression = Compression::Lz4;
+    opts.compression_min_size = 512;
+    opts.clock = clock.clone();
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    for i in 0..1000 {
+        let res = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res.is_ok());
+    }
+    // 经过压缩的 value
+    let large_value = Bytes::from(vec![b'v'; 4096]);
+    let res1 = engine.put(get_test_key(1000), large_value.clone());
+    assert!(res1.is_ok());
+
+    // 1.重复使用同一个 buf 读取所有的数据
+    let mut buf = Vec::new();
+    for i in 0..1000 {
+        let len = engine.get_into(get_test_key(i), &mut buf).unwrap();
+        assert_eq!(Some(get_test_value(i).len()), len);
+        assert_eq!(get_test_value(i), buf);
+    }
+    let len = engine.get_into(get_test_key(1000), &mut buf).unwrap();
+    assert_eq!(Some(large_value.len()), len);
+    assert_eq!(large_value, buf);
+
+    // 2.不存在、被删除以及已经过期的 key 返回 None，buf 被清空
+    assert_eq!(None, engine.get_into(get_test_key(2000), &mut buf).unwrap());
+    assert!(buf.is_empty());
+    let res2 = engine.delete(get_test_key(10));
+    assert!(res2.is_ok());
+    assert_eq!(None, engine.get_into(get_test_key(10), &mut buf).unwrap());
+    let res3 = engine.put_with_ttl(
+        get_test_key(20),
+        get_test_value(20),
+        Duration::from_secs(10),
+    );
+    assert!(res3.is_ok());
+    let len = engine.get_into(get_test_key(20), &mut buf).unwrap();
+    assert_eq!(Some(get_test_value(20).len()), len);
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(None, engine.get_into(get_test_key(20), &mut buf).unwrap());
+    assert!(buf.is_empty());
+    assert_eq!(
+        Errors::KeyIsEmpty,
+        engine.get_into(Bytes::new(), &mut buf).err().unwrap()
+    );
+
+    // 3.数据损坏时返回校验错误
+    let pos = engine.locate(get_test_key(30)).unwrap().unwrap();
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .open(get_data_file_name(&opts, pos.file_id()))
+        .unwrap();
+    file.seek(SeekFrom::Start(pos.offset() + pos.size() as u64 - 6))
+        .unwrap();
+    file.write_all(b"#").unwrap();
+    drop(file);
+    let res4 = engine.get_into(get_test_key(30), &mut buf);
+    assert_eq!(
+        Errors::InvalidCrc {
+            key: get_test_key(30).to_vec()
+        },
+        res4.err().unwrap()
+    );
+    drop(engine);
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_get_ref() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-get-ref");
+    opts.data_file_size = 4 * 1024;
+    opts.mmap_at_startup = true;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    for i in 0..100 {
+        let res = engine.put(get_test_key(i), Bytes::from(vec![i as u8; 200]));
+        assert!(res.is_ok());
+    }
+    let res1 = engine.delete(get_test_key(1));
+    assert!(res1.is_ok());
+    engine.close().expect("failed to close engine");
+
+    // 1.重启之后旧的数据文件使用 mmap 读取，旧的数据文件中的 value 直接引用映射区域
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    let active_file_id = engine2.active_file.read().get_file_id();
+    for i in 0..100 {
+        let value_ref = engine2.get_ref(get_test_key(i)).unwrap();
+        if i == 1 {
+            assert!(value_ref.is_none());
+            continue;
+        }
+        let value_ref = value_ref.unwrap();
+        assert_eq!(engine2.get(get_test_key(i)).unwrap(), value_ref.as_ref());
+        assert_eq!(engine2.get(get_test_key(i)).unwrap(), value_ref.to_bytes());
+
+        let in_older_file =
+            engine2.locate(get_test_key(i)).unwrap().unwrap().file_id != active_file_id;
+        assert_eq!(
+            cfg!(feature = "mmap") && in_older_file,
+            value_ref.is_mapped()
+        );
+    }
+
+    // 2.活跃文件中的数据拷贝之后返回
+    let res2 = engine2.put(get_test_key(1), get_test_value(1));
+    assert!(res2.is_ok());
+    let value_ref = engine2.get_ref(get_test_key(1)).unwrap().unwrap();
+    assert!(!value_ref.is_mapped());
+    assert_eq!(get_test_value(1), value_ref.to_bytes());
+
+    // 3.不存在以及 key 为空的情况
+    assert!(engine2.get_ref(get_test_key(100)).unwrap().is_none());
+    assert_eq!(
+        Errors::KeyIsEmpty,
+        engine2.get_ref(Bytes::new()).err().unwrap()
+    );
+    engine2.close().expect("failed to close engine");
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_compression() {
+    for (n, compression) in [Compression::Lz4, Compression::Zstd].iter().enumerate() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from(format!("/tmp/bitcask-rs-compression-{}", n));
+        opts.data_file_size = 64 * 1024 * 1024;
+        opts.compression = *compression;
+
+        let mut raw_opts = opts.clone();
+        raw_opts.dir_path = PathBuf::from(format!("/tmp/bitcask-rs-compression-raw-{}", n));
+        raw_opts.compression = Compression::None;
+
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let raw_engine = Engine::open(raw_opts.clone()).expect("failed to open engine");
+
+        // 1.压缩之后可以正常读取
+        for i in 0..1000 {
+            let value = get_test_value(i).repeat(10);
+            let res1 = engine.put(get_test_key(i), Bytes::from(value.clone()));
+            assert!(res1.is_ok());
+            let res2 = raw_engine.put(get_test_key(i), Bytes::from(value));
+            assert!(res2.is_ok());
+        }
+        let res3 = engine.get(get_test_key(100));
+        assert_eq!(Bytes::from(get_test_value(100).repeat(10)), res3.unwrap());
+
+        // 2.空的 value
+        let res4 = engine.put(get_test_key(2000), Bytes::new());
+        assert!(res4.is_ok());
+        let res5 = engine.get(get_test_key(2000));
+        assert_eq!(0, res5.unwrap().len());
+
+        // 3.压缩之后数据文件更小
+        let size = engine.stat().unwrap().disk_size;
+        let raw_size = raw_engine.stat().unwrap().disk_size;
+        assert!(size < raw_size);
+
+        // 4.重启之后关闭压缩，压缩和未压缩的数据混合存储，都可以正常读取
+        std::mem::drop(engine);
+        let mut opts2 = opts.clone();
+        opts2.compression = Compression::None;
+        let engine2 = Engine::open(opts2.clone()).expect("failed to open engine");
+        let res6 = engine2.put(get_test_key(3000), get_test_value(3000));
+        assert!(res6.is_ok());
+        let res7 = engine2.get(get_test_key(100));
+        assert_eq!(Bytes::from(get_test_value(100).repeat(10)), res7.unwrap());
+        let res8 = engine2.get(get_test_key(3000));
+        assert_eq!(get_test_value(3000), res8.unwrap());
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+        std::fs::remove_dir_all(raw_opts.clone().dir_path).expect("failed to remove path");
+    }
+}
+
+#[test]
+fn test_engine_compression_min_size() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-compression-min-size");
+    opts.compression = Compression::Lz4;
+    opts.compression_min_size = 64;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    let small_value = Bytes::from("a".repeat(32));
+    let large_value = Bytes::from("a".repeat(1024));
+    let res1 = engine.put(Bytes::from("small"), small_value.clone());
+    assert!(res1.is_ok());
+    let res2 = engine.put(Bytes::from("large"), large_value.clone());
+    assert!(res2.is_ok());
+
+    // 1.只有不小于阈值的 value 被压缩，记录中的标识和实际存储的数据一致
+    let stored_compression = |engine: &Engine, key: &str| {
+        let pos = engine.index.get(key.as_bytes().to_vec()).unwrap();
+        let read_record = engine.read_at(pos.file_id, pos.offset).unwrap();
+        read_record.record().compression
+    };
+    assert_eq!(Compression::None, stored_compression(&engine, "small"));
+    assert_eq!(Compression::Lz4, stored_compression(&engine, "large"));
+    assert_eq!(small_value, engine.get(Bytes::from("small")).unwrap());
+    assert_eq!(large_value, engine.get(Bytes::from("large")).unwrap());
+    engine.close().expect("failed to close engine");
+
+    // 2.读取时根据记录中的标识解压，和当前的配置无关
+    opts.compression = Compression::None;
+    opts.compression_min_size = 0;
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(small_value, engine2.get(Bytes::from("small")).unwrap());
+    assert_eq!(large_value, engine2.get(Bytes::from("large")).unwrap());
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_encryption() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-encryption");
+    opts.data_file_size = 64 * 1024 * 1024;
+    opts.compression = Compression::Lz4;
+    opts.encryption = Some(EncryptionConfig { key: [1u8; 32] });
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 1.加密之后可以正常读取
+    for i in 0..100 {
+        let res1 = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res1.is_ok());
+    }
+    let res2 = engine.get(get_test_key(10));
+    assert_eq!(get_test_value(10), res2.unwrap());
+
+    // 2.数据文件中不包含明文的 value，key 仍然是明文
+    engine.sync().expect("failed to sync");
+    let content = std::fs::read(get_data_file_name(&opts, 0)).unwrap();
+    let contains = |data: &[u8]| content.windows(data.len()).any(|w| w == data);
+    assert!(!contains(&get_test_value(10)));
+    assert!(contains(&get_test_key(10)));
+
+    // 3.关闭加密之后，加密和未加密的数据混合存储
+    engine.close().expect("failed to close engine");
+    let mut opts2 = opts.clone();
+    opts2.encryption = None;
+    let engine2 = Engine::open(opts2.clone()).expect("failed to open engine");
+    let res3 = engine2.put(get_test_key(200), get_test_value(200));
+    assert!(res3.is_ok());
+    let res4 = engine2.get(get_test_key(200));
+    assert_eq!(get_test_value(200), res4.unwrap());
+    let res5 = engine2.get(get_test_key(10));
+    assert_eq!(Errors::DecryptionFailed, res5.err().unwrap());
+    engine2.close().expect("failed to close engine");
+
+    // 4.使用错误的密钥读取
+    let mut opts3 = opts.clone();
+    opts3.encryption = Some(EncryptionConfig { key: [2u8; 32] });
+    let engine3 = Engine::open(opts3.clone()).expect("failed to open engine");
+    let res6 = engine3.get(get_test_key(10));
+    assert_eq!(Errors::DecryptionFailed, res6.err().unwrap());
+    let res7 = engine3.get(get_test_key(200));
+    assert_eq!(get_test_value(200), res7.unwrap());
+    engine3.close().expect("failed to close engine");
+
+    // 5.使用正确的密钥重新打开
+    let engine4 = Engine::open(opts.clone()).expect("failed to open engine");
+    let res8 = engine4.get(get_test_key(10));
+    assert_eq!(get_test_value(10), res8.unwrap());
+    let res9 = engine4.get(get_test_key(200));
+    assert_eq!(get_test_value(200), res9.unwrap());
+
+    // 6.把 key 30 的密文写到 key 20 的记录中，key 参与校验，交换之后无法解密
+    let pos = engine4.index.get(get_test_key(30).to_vec()).unwrap();
+    let stolen = engine4.read_at(pos.file_id, pos.offset).unwrap().record;
+    assert!(stolen.encrypted);
+    let mut record = LogRecord {
+        key: log_record_key_with_seq(get_test_key(20).to_vec(), NON_TRANSACTION_SEQ_NO),
+        value: stolen.value,
+        rec_type: LogRecordType::NORMAL,
+        expire: 0,
+        compression: stolen.compression,
+        encrypted: true,
+        sequence: 0,
+        timestamp: 0,
+        keyspace: 0,
+    };
+    let swapped_pos = engine4.append_log_record(&mut record).unwrap();
+    engine4.index.put(get_test_key(20).to_vec(), swapped_pos);
+    let res10 = engine4.get(get_test_key(20));
+    assert_eq!(Errors::DecryptionFailed, res10.err().unwrap());
+    let mut buf = Vec::new();
+    let res11 = engine4.get_into(get_test_key(20), &mut buf);
+    assert_eq!(Errors::DecryptionFailed, res11.err().unwrap());
+    assert_eq!(get_test_value(30), engine4.get(get_test_key(30)).unwrap());
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_put_too_large() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-put-too-large");
+    opts.data_file_size = 64 * 1024 * 1024;
+    opts.max_key_size = 16;
+    opts.max_value_size = 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 1.key 超过最大长度
+    let res1 = engine.put(Bytes::from(vec![b'k'; 17]), get_test_value(1));
+    assert_eq!(Errors::KeyTooLarge, res1.err().unwrap());
+
+    // 2.value 超过最大长度
+    let res2 = engine.put(Bytes::from("key"), Bytes::from(vec![b'v'; 1025]));
+    assert_eq!(Errors::ValueTooLarge, res2.err().unwrap());
+    let res3 = engine.get(Bytes::from("key"));
+    assert_eq!(Errors::KeyNotFound, res3.err().unwrap());
+
+    // 3.刚好等于最大长度
+    let res4 = engine.put(Bytes::from(vec![b'k'; 16]), Bytes::from(vec![b'v'; 1024]));
+    assert!(res4.is_ok());
+    let res5 = engine.get(Bytes::from(vec![b'k'; 16]));
+    assert_eq!(1024, res5.unwrap().len());
+
+    // 4.批量写入同样校验
+    let wb = engine
+        .new_write_batch(WriteBatchOptions::default())
+        .expect("failed to create write batch");
+    let res6 = wb.put(Bytes::from(vec![b'k'; 17]), get_test_value(1));
+    assert_eq!(Errors::KeyTooLarge, res6.err().unwrap());
+    let res7 = wb.put(Bytes::from("key"), Bytes::from(vec![b'v'; 1025]));
+    assert_eq!(Errors::ValueTooLarge, res7.err().unwrap());
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_open_invalid_dir() {
+    // 1.路径指向一个普通文件
+    let file_path = PathBuf::from("/tmp/bitcask-rs-not-dir");
+    std::fs::write(&file_path, b"not a directory").expect("failed to write file");
+    let mut opts1 = Options::default();
+    opts1.dir_path = file_path.clone();
+    let res1 = Engine::open(opts1);
+    assert_eq!(Errors::DataDirectoryNotDir, res1.err().unwrap());
+    std::fs::remove_file(&file_path).expect("failed to remove file");
+}
+
+// root 用户不受目录权限的限制，CI 以 root 运行时无法构造没有权限的情况
+#[test]
+#[cfg(unix)]
+#[ignore = "root bypasses directory permissions, run as a non-root user with --ignored"]
+fn test_engine_open_dir_permission_denied() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // 没有权限在父目录中创建数据目录
+    let parent = PathBuf::from("/tmp/bitcask-rs-readonly-dir");
+    std::fs::create_dir_all(&parent).expect("failed to create dir");
+    std::fs::set_permissions(&parent, std::fs::Permissions::from_mode(0o555))
+        .expect("failed to set permissions");
+
+    let probe = parent.join("probe");
+    let probe_res = std::fs::create_dir(&probe);
+    let mut opts = Options::default();
+    opts.dir_path = parent.join("db");
+    let res = Engine::open(opts);
+
+    std::fs::set_permissions(&parent, std::fs::Permissions::from_mode(0o755))
+        .expect("failed to set permissions");
+    std::fs::remove_dir_all(&parent).expect("failed to remove path");
+
+    assert!(
+        probe_res.is_err(),
+        "the read-only dir is writable, this test must not run as root"
+    );
+    assert_eq!(
+        Errors::FailedCreateDatabaseDir {
+            kind: std::io::ErrorKind::PermissionDenied
+        },
+        res.err().unwrap()
+    );
+}
+
+#[test]
+fn test_engine_data_file_ext() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-data-file-ext");
+    opts.data_file_size = 64 * 1024;
+    opts.data_file_ext = "kv".to_string();
+    opts.data_file_id_width = 4;
+    opts.data_file_merge_ratio = 0 as f32;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    for i in 0..2000 {
+        let res = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res.is_ok());
+    }
+    for i in 0..1000 {
+        let res = engine.delete(get_test_key(i));
+        assert!(res.is_ok());
+    }
+    assert!(opts.dir_path.join("0000.kv").is_file());
+    assert!(opts.dir_path.join("0001.kv").is_file());
+
+    // 其他工具的 .data 文件不会被当作数据文件
+    std::fs::write(opts.dir_path.join("other.data"), b"other").expect("failed to write file");
+
+    // merge 之后的数据文件同样使用配置的扩展名
+    let res1 = engine.merge();
+    assert!(res1.is_ok());
+    engine.close().expect("failed to close engine");
+
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(1000, engine2.list_keys().unwrap().len());
+    for i in 1000..2000 {
+        let res = engine2.get(get_test_key(i));
+        assert_eq!(get_test_value(i), res.ok().unwrap());
+    }
+    for entry in std::fs::read_dir(&opts.dir_path).unwrap() {
+        let name = entry.unwrap().file_name().into_string().unwrap();
+        assert!(!name.ends_with(".data") || name == "other.data");
+    }
+    std::mem::drop(engine2);
+
+    // 扩展名不合法
+    let mut opts2 = opts.clone();
+    opts2.data_file_ext = "".to_string();
+    assert_eq!(
+        Errors::InvalidDataFileExt,
+        Engine::open(opts2).err().unwrap()
+    );
+    let mut opts3 = opts.clone();
+    opts3.data_file_ext = "a.b".to_string();
+    assert_eq!(
+        Errors::InvalidDataFileExt,
+        Engine::open(opts3).err().unwrap()
+    );
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_file_lock() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-file-lock");
+    opts.data_file_size = 64 * 1024 * 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 1.同一个目录不能同时打开多个实例
+    let res1 = Engine::open(opts.clone());
+    assert_eq!(Errors::DatabaseIsInUse, res1.err().unwrap());
+
+    // 2.drop 之后释放文件锁，可以重新打开
+    std::mem::drop(engine);
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    let res2 = Engine::open(opts.clone());
+    assert_eq!(Errors::DatabaseIsInUse, res2.err().unwrap());
+
+    // 3.close 之后释放文件锁，可以重新打开
+    let close_res = engine2.close();
+    assert!(close_res.is_ok());
+    let res3 = Engine::open(opts.clone());
+    assert!(res3.is_ok());
+    std::mem::drop(res3);
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_reclaimable_size() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-reclaimable-size");
+    opts.data_file_size = 64 * 1024 * 1024;
+    opts.data_file_merge_ratio = 0.5;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(0, engine.reclaimable_size().unwrap());
+
+    // 1.写入新的 key 不会产生可回收的数据
+    for i in 0..100 {
+        let res = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res.is_ok());
+    }
+    assert_eq!(0, engine.reclaimable_size().unwrap());
+
+    // 2.覆盖写入之后，旧的数据可以回收
+    let res1 = engine.put(get_test_key(1), get_test_value(1));
+    assert!(res1.is_ok());
+    let reclaim1 = engine.reclaimable_size().unwrap();
+    assert!(reclaim1 > 0);
+
+    // 3.删除之后，旧的数据和删除标记都可以回收
+    let res2 = engine.delete(get_test_key(2));
+    assert!(res2.is_ok());
+    let reclaim2 = engine.reclaimable_size().unwrap();
+    assert!(reclaim2 > reclaim1);
+    assert_eq!(reclaim2, engine.stat().unwrap().reclaimable_size);
+
+    // 4.没有达到 merge 的比例阈值
+    let res3 = engine.merge();
+    assert_eq!(Errors::MergeRatioUnreached, res3.err().unwrap());
+
+    // 5.重启之后重新统计可回收的数据量
+    engine.close().expect("failed to close engine");
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(reclaim2, engine2.reclaimable_size().unwrap());
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_verify_checksum_on_read() {
+    for verify in [true, false] {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from(format!("/tmp/bitcask-rs-verify-checksum-{}", verify));
+        opts.data_file_size = 64 * 1024 * 1024;
+        opts.verify_checksum_on_read = verify;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        let res1 = engine.put(get_test_key(1), get_test_value(1));
+        assert!(res1.is_ok());
+        let res2 = engine.put(get_test_key(2), get_test_value(2));
+        assert!(res2.is_ok());
+
+        // 修改最后一条数据 value 中的一个字节，模拟数据损坏
+        let data_file = opts.dir_path.join("000000000.data");
+        let mut content = std::fs::read(data_file.clone()).unwrap();
+        let idx = content.len() - 5;
+        content[idx] ^= 0xff;
+        std::fs::write(data_file, content).unwrap();
+
+        let res3 = engine.get(get_test_key(1));
+        assert_eq!(get_test_value(1), res3.unwrap());
+        let res4 = engine.get(get_test_key(2));
+        if verify {
+            // 1.校验 crc，读取时返回错误
+            assert_eq!(
+                Errors::InvalidCrc {
+                    key: get_test_key(2).to_vec()
+                },
+                res4.err().unwrap()
+            );
+        } else {
+            // 2.关闭 crc 校验，读到的是损坏的数据
+            assert_ne!(get_test_value(2), res4.unwrap());
+        }
+
+        // 删除测试的文件夹
+        std::mem::drop(engine);
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+}
+
+#[test]
+fn test_engine_read_only() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-read-only");
+    opts.data_file_size = 64 * 1024 * 1024;
+
+    // 1.只读模式下不能打开不存在的数据库
+    let mut ro_opts = opts.clone();
+    ro_opts.read_only = true;
+    let res1 = Engine::open(ro_opts.clone());
+    assert_eq!(Errors::FailedToReadDatabaseDir, res1.err().unwrap());
+
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    for i in 0..100 {
+        let res = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res.is_ok());
+    }
+    // 写入实例打开时不能以只读模式打开
+    let res2 = Engine::open(ro_opts.clone());
+    assert_eq!(Errors::DatabaseIsInUse, res2.err().unwrap());
+    engine.close().expect("failed to close engine");
+
+    // 2.可以同时打开多个只读实例
+    let engine1 = Engine::open(ro_opts.clone()).expect("failed to open engine");
+    ro_opts.mmap_at_startup = true;
+    let engine2 = Engine::open(ro_opts.clone()).expect("failed to open engine");
+    for i in 0..100 {
+        assert_eq!(get_test_value(i), engine1.get(get_test_key(i)).unwrap());
+        assert_eq!(get_test_value(i), engine2.get(get_test_key(i)).unwrap());
+    }
+
+    // 3.只读模式下不能写入数据
+    let res3 = engine1.put(get_test_key(1), get_test_value(1));
+    assert_eq!(Errors::ReadOnlyDatabase, res3.err().unwrap());
+    let res4 = engine1.delete(get_test_key(1));
+    assert_eq!(Errors::ReadOnlyDatabase, res4.err().unwrap());
+    let res5 = engine1.merge();
+    assert_eq!(Errors::ReadOnlyDatabase, res5.err().unwrap());
+    let res6 = engine1.new_write_batch(WriteBatchOptions::default());
+    assert_eq!(Errors::ReadOnlyDatabase, res6.err().unwrap());
+
+    // 持久化时不做任何操作，数据目录可能位于只读的文件系统上
+    let dir_sync_count = util::file::dir_sync_count();
+    assert!(engine1.sync().is_ok());
+    assert!(engine1.flush_all_and_fsync_directory().is_ok());
+    assert_eq!(dir_sync_count, util::file::dir_sync_count());
+
+    // 4.只读实例打开时不能以写入模式打开
+    let res7 = Engine::open(opts.clone());
+    assert_eq!(Errors::DatabaseIsInUse, res7.err().unwrap());
+    engine1.close().expect("failed to close engine");
+    engine2.close().expect("failed to close engine");
+    let engine3 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(100, engine3.list_keys().unwrap().len());
+    std::mem::drop(engine3);
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_stat() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-stat");
+    opts.data_file_size = 32 * 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    for i in 0..1000 {
+        let res = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res.is_ok());
+    }
+    for i in 0..100 {
+        let res = engine.delete(get_test_key(i));
+        assert!(res.is_ok());
+    }
+
+    let stat = engine.stat().unwrap();
+    assert_eq!(900, stat.key_num);
+    assert!(stat.data_file_num > 1);
+    assert!(stat.reclaimable_size > 0);
+
+    // 数据文件大小之和
+    let mut data_size = 0;
+    for entry in std::fs::read_dir(opts.dir_path.clone()).unwrap() {
+        let entry = entry.unwrap();
+        if entry.file_name().to_str().unwrap().ends_with(".data") {
+            data_size += entry.metadata().unwrap().len();
+        }
+    }
+    assert_eq!(data_size, stat.disk_size);
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_backup() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-backup");
+    opts.data_file_size = 32 * 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    for i in 0..1000 {
+        let res = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res.is_ok());
+    }
+    for i in 0..100 {
+        let res = engine.delete(get_test_key(i));
+        assert!(res.is_ok());
+    }
+
+    // 备份时原数据库仍然打开，活跃文件中最近写入的数据也需要备份
+    let backup_dir = PathBuf::from("/tmp/bitcask-rs-backup-dest");
+    let res1 = engine.backup(backup_dir.clone());
+    assert!(res1.is_ok());
+
+    let mut backup_opts = opts.clone();
+    backup_opts.dir_path = backup_dir.clone();
+    let engine2 = Engine::open(backup_opts).expect("failed to open engine");
+    assert_eq!(900, engine2.list_keys().unwrap().len());
+    for i in 100..1000 {
+        assert_eq!(get_test_value(i), engine2.get(get_test_key(i)).unwrap());
+    }
+    std::mem::drop(engine2);
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(backup_dir).expect("failed to remove path");
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+// 统计 sync 次数的 IO 实现，读写操作交给标准文件 IO
+struct SyncCountIO {
+    inner: FileIO,
+    sync_count: Arc<AtomicUsize>,
+}
+
+impl IOManager for SyncCountIO {
+    fn read(&self, buf: &mut [u8], offset: u64) -> crate::error::Result<usize> {
+        self.inner.read(buf, offset)
+    }
+
+    fn write(&self, buf: &[u8]) -> crate::error::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn sync(&self) -> crate::error::Result<()> {
+        self.sync_count.fetch_add(1, Ordering::SeqCst);
+        self.inner.sync()
+    }
+
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+}
+
+#[test]
+fn test_engine_bytes_per_sync() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-bytes-per-sync");
+    opts.data_file_size = 64 * 1024 * 1024;
+    opts.bytes_per_sync = 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 替换活跃文件的 IO 实现，统计 sync 的次数
+    let sync_count = Arc::new(AtomicUsize::new(0));
+    let io = SyncCountIO {
+        inner: FileIO::new(get_data_file_name(&opts, 0)).unwrap(),
+        sync_count: sync_count.clone(),
+    };
+    *engine.active_file.write() = DataFile::with_io_manager(0, Box::new(io));
+
+    // 每条数据 100 字节，写入 11 条之后累计超过 1024 字节
+    // type 和 key/value 的长度各占 1 字节，带上事务序列号的 key 占 9 字节，crc 占 4 字节
+    let value = Bytes::from(vec![b'v'; 84]);
+    for i in 0..10 {
+        let res = engine.put(Bytes::from(format!("key-{:04}", i)), value.clone());
+        assert!(res.is_ok());
+        assert_eq!(0, sync_count.load(Ordering::SeqCst));
+    }
+    let res1 = engine.put(Bytes::from("key-0010"), value.clone());
+    assert!(res1.is_ok());
+    assert_eq!(1, sync_count.load(Ordering::SeqCst));
+    assert_eq!(0, engine.active_file.read().bytes_since_sync());
+
+    // 持久化之后重新开始累计
+    for i in 11..21 {
+        let res = engine.put(Bytes::from(format!("key-{:04}", i)), value.clone());
+        assert!(res.is_ok());
+    }
+    assert_eq!(1, sync_count.load(Ordering::SeqCst));
+    let res2 = engine.put(Bytes::from("key-0021"), value.clone());
+    assert!(res2.is_ok());
+    assert_eq!(2, sync_count.load(Ordering::SeqCst));
+
+    // 手动持久化同样会清空累计值
+    let res3 = engine.put(Bytes::from("key-0022"), value.clone());
+    assert!(res3.is_ok());
+    assert!(engine.active_file.read().bytes_since_sync() > 0);
+    assert!(engine.sync().is_ok());
+    assert_eq!(3, sync_count.load(Ordering::SeqCst));
+    assert_eq!(0, engine.active_file.read().bytes_since_sync());
+
+    // 删除测试的文件夹
+    std::mem::drop(engine);
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_put_batch_sync() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-put-batch-sync");
+    opts.data_file_size = 64 * 1024 * 1024;
+    opts.sync_writes = true;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 替换活跃文件的 IO 实现，统计 sync 的次数
+    let sync_count = Arc::new(AtomicUsize::new(0));
+    let io = SyncCountIO {
+        inner: FileIO::new(get_data_file_name(&opts, 0)).unwrap(),
+        sync_count: sync_count.clone(),
+    };
+    *engine.active_file.write() = DataFile::with_io_manager(0, Box::new(io));
+
+    // 写入 100 条数据只持久化一次
+    let pairs: Vec<_> = (0..100)
+        .map(|i| (get_test_key(i), get_test_value(i)))
+        .collect();
+    let res1 = engine.put_batch_sync(&pairs);
+    assert!(res1.is_ok());
+    assert_eq!(1, sync_count.load(Ordering::SeqCst));
+    assert_eq!(0, engine.active_file.read().bytes_since_sync());
+
+    // 有非法的数据时不写入任何数据
+    let pairs2 = vec![
+        (get_test_key(200), get_test_value(200)),
+        (Bytes::new(), get_test_value(201)),
+    ];
+    let res2 = engine.put_batch_sync(&pairs2);
+    assert_eq!(Errors::KeyIsEmpty, res2.err().unwrap());
+    assert_eq!(
+        Errors::KeyNotFound,
+        engine.get(get_test_key(200)).err().unwrap()
+    );
+    assert_eq!(1, sync_count.load(Ordering::SeqCst));
+
+    // 重启之后数据仍然存在
+    engine.close().expect("failed to close engine");
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(100, engine2.list_keys().unwrap().len());
+    for i in 0..100 {
+        assert_eq!(get_test_value(i), engine2.get(get_test_key(i)).unwrap());
+    }
+
+    // 删除测试的文件夹
+    std::mem::drop(engine2);
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_write_buffer() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-write-buffer");
+    opts.data_file_size = 64 * 1024;
+    opts.write_buffer_size = 4 * 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 写入的数据还在缓冲中时也可以读取
+    let res1 = engine.put(get_test_key(1), get_test_value(1));
+    assert!(res1.is_ok());
+    let file_name = get_data_file_name(&opts, 0);
+    assert_eq!(
+        DATA_FILE_HEADER_SIZE,
+        std::fs::metadata(&file_name).unwrap().len()
+    );
+    let res2 = engine.get(get_test_key(1));
+    assert_eq!(get_test_value(1), res2.ok().unwrap());
+
+    // sync 之后数据写入到文件中
+    assert!(engine.sync().is_ok());
+    assert!(std::fs::metadata(&file_name).unwrap().len() > DATA_FILE_HEADER_SIZE);
+
+    // 写入足够多的数据，触发缓冲写满以及数据文件的切换
+    for i in 0..1000 {
+        let res = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res.is_ok());
+    }
+    assert!(engine.older_files.read().len() > 0);
+    for i in 0..1000 {
+        let res = engine.get(get_test_key(i));
+        assert_eq!(get_test_value(i), res.ok().unwrap());
+    }
+
+    // 关闭之后重新打开，数据都可以读取到
+    engine.close().expect("failed to close engine");
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    for i in 0..1000 {
+        let res = engine2.get(get_test_key(i));
+        assert_eq!(get_test_value(i), res.ok().unwrap());
+    }
+
+    // 删除测试的文件夹
+    std::mem::drop(engine2);
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_io_failure() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-io-failure");
+    opts.data_file_size = 64 * 1024 * 1024;
+    opts.sync_writes = true;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 第二次 sync 和第三次 write 失败
+    let io = FaultyIO::new(Box::new(InMemoryIO::new()))
+        .fail_sync_at(2)
+        .fail_write_at(3);
+    *engine.active_file.write() = DataFile::with_io_manager(0, Box::new(io));
+
+    // 1.sync 失败时返回错误，数据不会更新到索引中
+    let res1 = engine.put(get_test_key(1), get_test_value(1));
+    assert!(res1.is_ok());
+    let res2 = engine.put(get_test_key(2), get_test_value(2));
+    assert_eq!(Errors::FailedSyncDataFile, res2.err().unwrap());
+    assert!(!engine.contains_key(get_test_key(2)).unwrap());
+
+    // 2.write 失败时返回错误
+    let res3 = engine.put(get_test_key(3), get_test_value(3));
+    assert_eq!(Errors::FailedWriteToDataFile, res3.err().unwrap());
+    let res4 = engine.get(get_test_key(3));
+    assert_eq!(Errors::KeyNotFound, res4.err().unwrap());
+
+    // 3.之后的写入恢复正常
+    let res5 = engine.put(get_test_key(4), get_test_value(4));
+    assert!(res5.is_ok());
+    assert_eq!(get_test_value(1), engine.get(get_test_key(1)).unwrap());
+    assert_eq!(get_test_value(4), engine.get(get_test_key(4)).unwrap());
+
+    // 删除测试的文件夹
+    std::mem::drop(engine);
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_rollover_data_file() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-rollover");
+    opts.data_file_size = 64;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 每条数据 40 字节，一个数据文件只能存放一条数据
+    let value = Bytes::from(vec![b'v'; 24]);
+    for i in 0..5 {
+        let res = engine.put(Bytes::from(format!("key-{:04}", i)), value.clone());
+        assert!(res.is_ok());
+
+        // 新的数据写入到新的数据文件头部之后的位置
+        let pos = engine
+            .index
+            .get(format!("key-{:04}", i).into_bytes())
+            .unwrap();
+        assert_eq!(i, pos.file_id);
+        assert_eq!(DATA_FILE_HEADER_SIZE, pos.offset);
+    }
+
+    let data_files = std::fs::read_dir(opts.dir_path.clone())
+        .unwrap()
+        .filter(|entry| {
+            let name = entry.as_ref().unwrap().file_name();
+            name.to_str().unwrap().ends_with(".data")
+        })
+        .count();
+    assert_eq!(5, data_files);
+
+    // 超过数据文件大小的数据写入到空的数据文件中，不会产生空的数据文件
+    let res1 = engine.put(Bytes::from("big-key"), Bytes::from(vec![b'v'; 100]));
+    assert!(res1.is_ok());
+    let res2 = engine.put(Bytes::from("big-key2"), Bytes::from(vec![b'v'; 100]));
+    assert!(res2.is_ok());
+    assert_eq!(6, engine.index.get(b"big-key2".to_vec()).unwrap().file_id);
+
+    // 重启之后数据都能读取
+    engine.close().expect("failed to close engine");
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    for i in 0..5 {
+        let res = engine2.get(Bytes::from(format!("key-{:04}", i)));
+        assert_eq!(value, res.unwrap());
+    }
+    assert_eq!(100, engine2.get(Bytes::from("big-key")).unwrap().len());
+    std::mem::drop(engine2);
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_locate() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-locate");
+    opts.data_file_size = 64 * 1024 * 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 1.key 不存在
+    assert!(engine.locate(get_test_key(1)).unwrap().is_none());
+
+    // 2.key 存在，返回数据所在的位置
+    let res1 = engine.put(get_test_key(1), get_test_value(1));
+    assert!(res1.is_ok());
+    let pos1 = engine.locate(get_test_key(1)).unwrap().unwrap();
+    assert_eq!(0, pos1.file_id());
+    assert_eq!(DATA_FILE_HEADER_SIZE, pos1.offset());
+    assert!(pos1.size() > 0);
+
+    let res2 = engine.put(get_test_key(2), get_test_value(2));
+    assert!(res2.is_ok());
+    let pos2 = engine.locate(get_test_key(2)).unwrap().unwrap();
+    assert_eq!(pos1.offset() + pos1.size() as u64, pos2.offset());
+
+    // 3.key 被删除
+    let res3 = engine.delete(get_test_key(1));
+    assert!(res3.is_ok());
+    assert!(engine.locate(get_test_key(1)).unwrap().is_none());
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_io_type_per_file() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-io-type-per-file");
+    opts.data_file_size = 4 * 1024;
+    opts.mmap_at_startup = true;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    for i in 0..200 {
+        let res = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res.is_ok());
+    }
+    engine.close().expect("failed to close engine");
+
+    // 重启之后活跃文件使用标准文件 IO，旧的数据文件使用 mmap
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(IOType::StandardFIO, engine2.active_file.read().io_type());
+    assert!(engine2.older_files.read().len() > 0);
+    for file in engine2.older_files.read().values() {
+        assert_eq!(IOType::MemoryMap, file.io_type());
+    }
+
+    // 活跃文件可以继续写入，切换之后的旧数据文件同样使用 mmap
+    let older_count = engine2.older_files.read().len();
+    for i in 200..400 {
+        let res1 = engine2.put(get_test_key(i), get_test_value(i));
+        assert!(res1.is_ok());
+    }
+    assert!(engine2.older_files.read().len() > older_count);
+    assert_eq!(IOType::StandardFIO, engine2.active_file.read().io_type());
+    for file in engine2.older_files.read().values() {
+        assert_eq!(IOType::MemoryMap, file.io_type());
+    }
+    for i in 0..400 {
+        let res2 = engine2.get(get_test_key(i));
+        assert_eq!(get_test_value(i), res2.unwrap());
+    }
+
+    // 没有配置 mmap_at_startup 时都使用标准文件 IO
+    engine2.close().expect("failed to close engine");
+    opts.mmap_at_startup = false;
+    let engine3 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(IOType::StandardFIO, engine3.active_file.read().io_type());
+    for file in engine3.older_files.read().values() {
+        assert_eq!(IOType::StandardFIO, file.io_type());
+    }
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_sync_dir_after_rollover() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-sync-dir");
+    opts.data_file_size = 64;
+
+    // 创建第一个数据文件时持久化目录
+    let count1 = util::file::dir_sync_count();
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(count1 + 1, util::file::dir_sync_count());
+
+    // 每次切换数据文件都持久化目录
+    let value = Bytes::from(vec![b'v'; 24]);
+    for i in 0..5 {
+        let res = engine.put(Bytes::from(format!("key-{:04}", i)), value.clone());
+        assert!(res.is_ok());
+    }
+    assert_eq!(4, engine.older_files.read().len());
+    assert_eq!(count1 + 5, util::file::dir_sync_count());
+
+    // 不切换数据文件时也可以主动持久化目录
+    let res1 = engine.flush_all_and_fsync_directory();
+    assert!(res1.is_ok());
+    assert_eq!(count1 + 6, util::file::dir_sync_count());
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_open_without_file_header() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-without-header");
+    opts.data_file_size = 64 * 1024 * 1024;
+    std::fs::create_dir_all(&opts.dir_path).expect("failed to create dir");
+
+    // 旧版本写入的数据文件没有头部
+    let mut buf = Vec::new();
+    for i in 0..10 {
+        let record = LogRecord {
+            key: log_record_key_with_seq(get_test_key(i).to_vec(), NON_TRANSACTION_SEQ_NO),
+            value: get_test_value(i).to_vec(),
+            rec_type: LogRecordType::NORMAL,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
+        };
+        buf.extend_from_slice(&record.encode());
+    }
+    std::fs::write(get_data_file_name(&opts, 0), &buf).expect("failed to write file");
+
+    // 可以读取旧的数据，并继续写入新的数据
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    for i in 0..10 {
+        let res1 = engine.get(get_test_key(i));
+        assert_eq!(get_test_value(i), res1.ok().unwrap());
+    }
+    let res2 = engine.put(get_test_key(10), get_test_value(10));
+    assert!(res2.is_ok());
+    engine.close().expect("failed to close engine");
+
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    for i in 0..=10 {
+        let res3 = engine2.get(get_test_key(i));
+        assert_eq!(get_test_value(i), res3.ok().unwrap());
+    }
+    engine2.close().expect("failed to close engine");
+
+    // 不是数据文件的情况
+    std::fs::write(get_data_file_name(&opts, 1), "not a data file").expect("failed to write file");
+    let res4 = Engine::open(opts.clone());
+    assert_eq!(Errors::UnknownFileFormat, res4.err().unwrap());
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_value_cache() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-value-cache");
+    opts.data_file_size = 64 * 1024 * 1024;
+    opts.value_cache_size = 64 * 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    let cache = engine.value_cache.as_ref().unwrap();
+
+    // 1.读取之后放到缓存中，再次读取命中缓存
+    let res1 = engine.put(get_test_key(1), get_test_value(1));
+    assert!(res1.is_ok());
+    assert!(cache.get(&get_test_key(1)).is_none());
+    let res2 = engine.get(get_test_key(1));
+    assert_eq!(get_test_value(1), res2.unwrap());
+    assert!(cache.get(&get_test_key(1)).is_some());
+    let res3 = engine.get(get_test_key(1));
+    assert_eq!(get_test_value(1), res3.unwrap());
+
+    // 2.覆盖写入之后缓存失效
+    let res4 = engine.put(get_test_key(1), get_test_value(2));
+    assert!(res4.is_ok());
+    assert!(cache.get(&get_test_key(1)).is_none());
+    let res5 = engine.get(get_test_key(1));
+    assert_eq!(get_test_value(2), res5.unwrap());
+
+    // 3.删除之后缓存失效
+    let res6 = engine.delete_fast(get_test_key(1));
+    assert!(res6.is_ok());
+    assert!(cache.get(&get_test_key(1)).is_none());
+    let res7 = engine.get(get_test_key(1));
+    assert_eq!(Errors::KeyNotFound, res7.err().unwrap());
+
+    // 4.批量写入之后缓存失效
+    let res8 = engine.put(get_test_key(3), get_test_value(3));
+    assert!(res8.is_ok());
+    let res9 = engine.get(get_test_key(3));
+    assert_eq!(get_test_value(3), res9.unwrap());
+    let wb = engine
+        .new_write_batch(WriteBatchOptions::default())
+        .expect("failed to create write batch");
+    let res10 = wb.put(get_test_key(3), get_test_value(4));
+    assert!(res10.is_ok());
+    let res11 = wb.commit();
+    assert!(res11.is_ok());
+    let res12 = engine.get(get_test_key(3));
+    assert_eq!(get_test_value(4), res12.unwrap());
+
+    // 5.缓存中的数据过期之后读取不到
+    let res13 = engine.put_with_ttl(
+        get_test_key(5),
+        get_test_value(5),
+        Duration::from_millis(100),
+    );
+    assert!(res13.is_ok());
+    let res14 = engine.get(get_test_key(5));
+    assert_eq!(get_test_value(5), res14.unwrap());
+    thread::sleep(Duration::from_millis(200));
+    let res15 = engine.get(get_test_key(5));
+    assert_eq!(Errors::KeyNotFound, res15.err().unwrap());
+    assert!(cache.get(&get_test_key(5)).is_none());
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_get_status() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-get-status");
+    opts.data_file_size = 64 * 1024 * 1024;
+    opts.data_file_merge_ratio = 0 as f32;
+    opts.track_tombstones = true;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 1.存在、被删除以及从未写入的 key
+    let res1 = engine.put(get_test_key(1), get_test_value(1));
+    assert!(res1.is_ok());
+    let res2 = engine.put(get_test_key(2), get_test_value(2));
+    assert!(res2.is_ok());
+    let res3 = engine.delete(get_test_key(2));
+    assert!(res3.is_ok());
+    assert_eq!(
+        KeyStatus::Present(get_test_value(1)),
+        engine.get_status(get_test_key(1)).unwrap()
+    );
+    assert_eq!(
+        KeyStatus::Deleted,
+        engine.get_status(get_test_key(2)).unwrap()
+    );
+    assert_eq!(
+        KeyStatus::Absent,
+        engine.get_status(get_test_key(3)).unwrap()
+    );
+    let res4 = engine.get_status(Bytes::new());
+    assert_eq!(Errors::KeyIsEmpty, res4.err().unwrap());
+
+    // 2.删除不存在的 key 不会产生删除标记
+    let res5 = engine.delete_fast(get_test_key(3));
+    assert!(res5.is_ok());
+    assert_eq!(
+        KeyStatus::Absent,
+        engine.get_status(get_test_key(3)).unwrap()
+    );
+
+    // 3.删除之后重新写入
+    let res6 = engine.put(get_test_key(2), get_test_value(22));
+    assert!(res6.is_ok());
+    assert_eq!(
+        KeyStatus::Present(get_test_value(22)),
+        engine.get_status(get_test_key(2)).unwrap()
+    );
+
+    // 4.批量删除
+    let wb = engine
+        .new_write_batch(WriteBatchOptions::default())
+        .expect("failed to create write batch");
+    let res7 = wb.delete(get_test_key(1));
+    assert!(res7.is_ok());
+    let res8 = wb.commit();
+    assert!(res8.is_ok());
+    assert_eq!(
+        KeyStatus::Deleted,
+        engine.get_status(get_test_key(1)).unwrap()
+    );
+
+    // 5.重启之后从数据文件中恢复删除标记
+    engine.close().expect("failed to close engine");
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(
+        KeyStatus::Deleted,
+        engine2.get_status(get_test_key(1)).unwrap()
+    );
+    assert_eq!(
+        KeyStatus::Present(get_test_value(22)),
+        engine2.get_status(get_test_key(2)).unwrap()
+    );
+    assert_eq!(
+        KeyStatus::Absent,
+        engine2.get_status(get_test_key(3)).unwrap()
+    );
+
+    // 6.merge 之后清理参与 merge 的数据文件中的删除标记，之后的删除标记仍然保留
+    let res9 = engine2.put(get_test_key(4), get_test_value(4));
+    assert!(res9.is_ok());
+    let res10 = engine2.delete(get_test_key(4));
+    assert!(res10.is_ok());
+    engine2.merge().expect("failed to merge");
+    let res11 = engine2.delete(get_test_key(2));
+    assert!(res11.is_ok());
+    assert_eq!(
+        KeyStatus::Absent,
+        engine2.get_status(get_test_key(1)).unwrap()
+    );
+    assert_eq!(
+        KeyStatus::Deleted,
+        engine2.get_status(get_test_key(2)).unwrap()
+    );
+    assert_eq!(1, engine2.tombstones.as_ref().unwrap().read().len());
+    engine2.close().expect("failed to close engine");
+
+    // 7.重启之后和 merge 完成时的删除标记一致
+    let engine3 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(
+        KeyStatus::Absent,
+        engine3.get_status(get_test_key(1)).unwrap()
+    );
+    assert_eq!(
+        KeyStatus::Absent,
+        engine3.get_status(get_test_key(4)).unwrap()
+    );
+    assert_eq!(
+        KeyStatus::Deleted,
+        engine3.get_status(get_test_key(2)).unwrap()
+    );
+    engine3.close().expect("failed to close engine");
+
+    // 8.没有开启 track_tombstones 时不记录删除标记，无法区分被删除和从未写入的 key
+    opts.track_tombstones = false;
+    let engine4 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert!(engine4.tombstones.is_none());
+    let res12 = engine4.get_status(get_test_key(2));
+    assert_eq!(Errors::TombstonesNotTracked, res12.err().unwrap());
+    std::mem::drop(engine4);
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_checksum() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-checksum");
+    opts.data_file_size = 32 * 1024;
+    opts.checksum = Checksum::XxHash64;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 1.使用 xxhash64 校验写入和读取
+    for i in 0..100 {
+        let res1 = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res1.is_ok());
+    }
+    let res2 = engine.get(get_test_key(10));
+    assert_eq!(get_test_value(10), res2.unwrap());
+    engine.close().expect("failed to close engine");
+
+    // 2.修改配置之后重新打开，已有的数据文件仍然使用 xxhash64 校验，新的数据文件使用 crc32 校验
+    let mut opts2 = opts.clone();
+    opts2.checksum = Checksum::Crc32;
+    let engine2 = Engine::open(opts2.clone()).expect("failed to open engine");
+    assert_eq!(Checksum::XxHash64, engine2.active_file.read().checksum());
+    for i in 100..1000 {
+        let res3 = engine2.put(get_test_key(i), get_test_value(i));
+        assert!(res3.is_ok());
+    }
+    assert_eq!(Checksum::Crc32, engine2.active_file.read().checksum());
+    engine2.close().expect("failed to close engine");
+
+    // 3.重启之后两种校验算法的数据都可以读取
+    let engine3 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(1000, engine3.list_keys().unwrap().len());
+    for i in 0..1000 {
+        let res4 = engine3.get(get_test_key(i));
+        assert_eq!(get_test_value(i), res4.unwrap());
+    }
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_checksum_disabled() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-checksum-disabled");
+    opts.data_file_size = 32 * 1024;
+    opts.checksum_enabled = false;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 1.关闭校验之后数据记录中不存储校验值
+    assert_eq!(Checksum::None, engine.active_file.read().checksum());
+    let res1 = engine.put(get_test_key(0), get_test_value(0));
+    assert!(res1.is_ok());
+    let pos = engine.locate(get_test_key(0)).unwrap().unwrap();
+    let record = engine.read_at(pos.file_id(), pos.offset()).unwrap();
+    assert_eq!(
+        record.record().encoded_length(Checksum::Crc32) - 4,
+        pos.size() as usize
+    );
+    for i in 1..100 {
+        let res2 = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res2.is_ok());
+    }
+    assert_eq!(get_test_value(10), engine.get(get_test_key(10)).unwrap());
+    engine.close().expect("failed to close engine");
+
+    // 2.重新开启校验，已有的数据文件仍然没有校验值，新的数据文件使用 crc32 校验
+    let mut opts2 = opts.clone();
+    opts2.checksum_enabled = true;
+    let engine2 = Engine::open(opts2.clone()).expect("failed to open engine");
+    assert_eq!(Checksum::None, engine2.active_file.read().checksum());
+    for i in 100..1000 {
+        let res3 = engine2.put(get_test_key(i), get_test_value(i));
+        assert!(res3.is_ok());
+    }
+    assert_eq!(Checksum::Crc32, engine2.active_file.read().checksum());
+    engine2.close().expect("failed to close engine");
+
+    // 3.重启之后两种数据文件混合存储，都可以正常读取
+    let engine3 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(1000, engine3.list_keys().unwrap().len());
+    for i in 0..1000 {
+        let res4 = engine3.get(get_test_key(i));
+        assert_eq!(get_test_value(i), res4.unwrap());
+    }
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_clear() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-clear");
+    opts.data_file_size = 32 * 1024;
+    opts.data_file_merge_ratio = 0 as f32;
+    opts.value_cache_size = 64 * 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    for i in 0..1000 {
+        let res1 = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res1.is_ok());
+    }
+    let res2 = engine.get(get_test_key(1));
+    assert_eq!(get_test_value(1), res2.unwrap());
+    let res3 = engine.merge();
+    assert!(res3.is_ok());
+
+    // 1.存在快照时不能清空
+    let snapshot = engine.snapshot().unwrap();
+    let res4 = engine.clear();
+    assert_eq!(Errors::SnapshotInUse, res4.err().unwrap());
+    drop(snapshot);
+
+    // 2.清空之后读取不到任何数据，只剩下一个空的数据文件
+    let res5 = engine.clear();
+    assert!(res5.is_ok());
+    assert!(engine.list_keys().unwrap().is_empty());
+    let res6 = engine.get(get_test_key(1));
+    assert_eq!(Errors::KeyNotFound, res6.err().unwrap());
+    let stat = engine.stat().unwrap();
+    assert_eq!(1, stat.data_file_num);
+    assert_eq!(0, stat.reclaimable_size);
+    assert!(!crate::merge::get_merge_path(opts.dir_path.clone()).exists());
+
+    // 3.清空之后可以继续写入，重启之后只有新写入的数据
+    let res7 = engine.put(get_test_key(2000), get_test_value(2000));
+    assert!(res7.is_ok());
+    engine.close().expect("failed to close engine");
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(vec![get_test_key(2000)], engine2.list_keys().unwrap());
+    engine2.close().expect("failed to close engine");
+
+    // 4.只读模式下不能清空
+    let mut opts2 = opts.clone();
+    opts2.read_only = true;
+    let engine3 = Engine::open(opts2).expect("failed to open engine");
+    let res8 = engine3.clear();
+    assert_eq!(Errors::ReadOnlyDatabase, res8.err().unwrap());
+    drop(engine3);
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_sync_on_drop() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-sync-on-drop");
+    opts.data_file_size = 64 * 1024 * 1024;
+
+    // 1.没有调用 close 时，Drop 持久化活跃文件
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    let sync_count = Arc::new(AtomicUsize::new(0));
+    let io = SyncCountIO {
+        inner: FileIO::new(get_data_file_name(&opts, 0)).unwrap(),
+        sync_count: sync_count.clone(),
+    };
+    *engine.active_file.write() = DataFile::with_io_manager(0, Box::new(io));
+    let res1 = engine.put(get_test_key(1), get_test_value(1));
+    assert!(res1.is_ok());
+    assert_eq!(0, sync_count.load(Ordering::SeqCst));
+    drop(engine);
+    assert_eq!(1, sync_count.load(Ordering::SeqCst));
+
+    // 2.调用 close 之后 Drop 不会重复持久化
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    let res2 = engine2.get(get_test_key(1));
+    assert_eq!(get_test_value(1), res2.unwrap());
+    let sync_count2 = Arc::new(AtomicUsize::new(0));
+    let io2 = SyncCountIO {
+        inner: FileIO::new(get_data_file_name(&opts, 0)).unwrap(),
+        sync_count: sync_count2.clone(),
+    };
+    *engine2.active_file.write() = DataFile::with_io_manager(0, Box::new(io2));
+    engine2.close().expect("failed to close engine");
+    assert_eq!(1, sync_count2.load(Ordering::SeqCst));
+
+    // 3.持久化失败时只记录日志，不会 panic
+    let engine3 = Engine::open(opts.clone()).expect("failed to open engine");
+    let io3 =
+        FaultyIO::new(Box::new(FileIO::new(get_data_file_name(&opts, 0)).unwrap())).fail_sync_at(1);
+    *engine3.active_file.write() = DataFile::with_io_manager(0, Box::new(io3));
+    drop(engine3);
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_record_larger_than_data_file() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-large-record");
+    opts.data_file_size = 1024;
+    opts.data_file_merge_ratio = 0 as f32;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    let large_value = Bytes::from(vec![b'v'; 4096]);
+
+    // 1.空的活跃文件直接写入超过数据文件大小的数据
+    let res1 = engine.put(get_test_key(1), large_value.clone());
+    assert!(res1.is_ok());
+    assert_eq!(
+        0,
+        engine.locate(get_test_key(1)).unwrap().unwrap().file_id()
+    );
+
+    // 2.之后的数据写入新的数据文件
+    let res2 = engine.put(get_test_key(2), get_test_value(2));
+    assert!(res2.is_ok());
+    assert_eq!(
+        1,
+        engine.locate(get_test_key(2)).unwrap().unwrap().file_id()
+    );
+
+    // 3.活跃文件不为空时切换一次文件，超过大小的数据单独占用一个数据文件
+    let res3 = engine.put(get_test_key(3), large_value.clone());
+    assert!(res3.is_ok());
+    let pos3 = engine.locate(get_test_key(3)).unwrap().unwrap();
+    assert_eq!(2, pos3.file_id());
+    assert_eq!(DATA_FILE_HEADER_SIZE, pos3.offset());
+    let res4 = engine.put(get_test_key(4), get_test_value(4));
+    assert!(res4.is_ok());
+    assert_eq!(
+        3,
+        engine.locate(get_test_key(4)).unwrap().unwrap().file_id()
+    );
+
+    // 4.merge 以及重启之后都可以正常读取
+    let res5 = engine.merge();
+    assert!(res5.is_ok());
+    engine.close().expect("failed to close engine");
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(large_value, engine2.get(get_test_key(1)).unwrap());
+    assert_eq!(get_test_value(2), engine2.get(get_test_key(2)).unwrap());
+    assert_eq!(large_value, engine2.get(get_test_key(3)).unwrap());
+    assert_eq!(get_test_value(4), engine2.get(get_test_key(4)).unwrap());
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+// 记录每种操作的次数和错误次数
+#[derive(Default)]
+struct CountingRecorder {
+    ops: Mutex<HashMap<OpKind, usize>>,
+    errors: Mutex<HashMap<OpKind, usize>>,
+}
+
+impl CountingRecorder {
+    fn ops(&self, op: OpKind) -> usize {
+        *self.ops.lock().unwrap().get(&op).unwrap_or(&0)
+    }
+
+    fn errors(&self, op: OpKind) -> usize {
+        *self.errors.lock().unwrap().get(&op).unwrap_or(&0)
+    }
+}
+
+impl MetricsRecorder for CountingRecorder {
+    fn record_op(&self, op: OpKind, _latency: Duration) {
+        *self.ops.lock().unwrap().entry(op).or_default() += 1;
+    }
+
+    fn incr_error(&self, op: OpKind, _error: &Errors) {
+        *self.errors.lock().unwrap().entry(op).or_default() += 1;
+    }
+}
+
+#[test]
+fn test_engine_metrics() {
+    let recorder = Arc::new(CountingRecorder::default());
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-metrics");
+    opts.data_file_merge_ratio = 0 as f32;
+    opts.metrics = Some(recorder.clone());
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    let res1 = engine.put(get_test_key(1), get_test_value(1));
+    assert!(res1.is_ok());
+    let res2 = engine.put_with_ttl(get_test_key(2), get_test_value(2), Duration::from_secs(60));
+    assert!(res2.is_ok());
+    assert_eq!(2, recorder.ops(OpKind::Put));
+
+    // key 不存在不算作错误，key 为空算作错误
+    let res3 = engine.get(get_test_key(1));
+    assert!(res3.is_ok());
+    let res4 = engine.get(get_test_key(3));
+    assert_eq!(Errors::KeyNotFound, res4.err().unwrap());
+    let res5 = engine.get(Bytes::new());
+    assert_eq!(Errors::KeyIsEmpty, res5.err().unwrap());
+    assert_eq!(3, recorder.ops(OpKind::Get));
+    assert_eq!(1, recorder.errors(OpKind::Get));
+
+    let res6 = engine.delete(get_test_key(1));
+    assert!(res6.is_ok());
+    let res7 = engine.delete_fast(get_test_key(2));
+    assert!(res7.is_ok());
+    assert_eq!(2, recorder.ops(OpKind::Delete));
+
+    let res8 = engine.merge();
+    assert!(res8.is_ok());
+    assert_eq!(1, recorder.ops(OpKind::Merge));
+    assert_eq!(0, recorder.errors(OpKind::Put));
+    assert_eq!(0, recorder.errors(OpKind::Delete));
+    assert_eq!(0, recorder.errors(OpKind::Merge));
+
+    // keyspace 中的读写同样会记录
+    let users = engine.keyspace("users").unwrap();
+    assert!(users.put(get_test_key(1), get_test_value(1)).is_ok());
+    assert!(users.get(get_test_key(1)).is_ok());
+    assert_eq!(Errors::KeyIsEmpty, users.get(Bytes::new()).err().unwrap());
+    assert!(users.delete(get_test_key(1)).is_ok());
+    assert_eq!(3, recorder.ops(OpKind::Put));
+    assert_eq!(5, recorder.ops(OpKind::Get));
+    assert_eq!(2, recorder.errors(OpKind::Get));
+    assert_eq!(3, recorder.ops(OpKind::Delete));
+    drop(users);
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    let _ = std::fs::remove_dir_all(get_merge_path(opts.dir_path));
+}
+
+#[test]
+fn test_engine_modified_at() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-modified-at");
+    opts.data_file_merge_ratio = 0 as f32;
+    opts.write_timestamp = true;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 1.开启 write_timestamp 之后普通写入和事务写入都记录写入时间
+    let before = SystemTime::now() - Duration::from_millis(1);
+    let res1 = engine.put(get_test_key(1), get_test_value(1));
+    assert!(res1.is_ok());
+    let wb = engine
+        .new_write_batch(WriteBatchOptions::default())
+        .expect("failed to create write batch");
+    let res2 = wb.put(get_test_key(2), get_test_value(2));
+    assert!(res2.is_ok());
+    let res3 = wb.commit();
+    assert!(res3.is_ok());
+    let after = SystemTime::now();
+    let modified1 = engine.modified_at(get_test_key(1)).unwrap().unwrap();
+    assert!(before <= modified1 && modified1 <= after);
+    let modified2 = engine.modified_at(get_test_key(2)).unwrap().unwrap();
+    assert!(before <= modified2 && modified2 <= after);
+    engine.close().expect("failed to close engine");
+
+    // 2.没有开启 write_timestamp 时写入的数据没有写入时间
+    opts.write_timestamp = false;
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    let res4 = engine2.put(get_test_key(3), get_test_value(3));
+    assert!(res4.is_ok());
+    assert_eq!(None, engine2.modified_at(get_test_key(3)).unwrap());
+    assert_eq!(
+        Some(modified1),
+        engine2.modified_at(get_test_key(1)).unwrap()
+    );
+
+    // 3.不存在、被删除以及 key 为空的情况
+    assert_eq!(
+        Errors::KeyNotFound,
+        engine2.modified_at(get_test_key(4)).err().unwrap()
+    );
+    let res5 = engine2.delete(get_test_key(3));
+    assert!(res5.is_ok());
+    assert_eq!(
+        Errors::KeyNotFound,
+        engine2.modified_at(get_test_key(3)).err().unwrap()
+    );
+    assert_eq!(
+        Errors::KeyIsEmpty,
+        engine2.modified_at(Bytes::new()).err().unwrap()
+    );
+
+    // 4.merge 重写数据之后写入时间不变
+    let res6 = engine2.merge();
+    assert!(res6.is_ok());
+    engine2.close().expect("failed to close engine");
+    let engine3 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(
+        Some(modified1),
+        engine3.modified_at(get_test_key(1)).unwrap()
+    );
+    assert_eq!(
+        Some(modified2),
+        engine3.modified_at(get_test_key(2)).unwrap()
+    );
+    engine3.close().expect("failed to close engine");
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_value_len() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-value-len");
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 1.不同长度的 value、带有过期时间的数据以及事务写入的数据
+    let res1 = engine.put(get_test_key(1), Bytes::new());
+    assert!(res1.is_ok());
+    let res2 = engine.put(get_test_key(2), Bytes::from(vec![b'v'; 300]));
+    assert!(res2.is_ok());
+    let res3 = engine.put_with_ttl(get_test_key(3), get_test_value(3), Duration::from_secs(60));
+    assert!(res3.is_ok());
+    let wb = engine
+        .new_write_batch(WriteBatchOptions::default())
+        .expect("failed to create write batch");
+    let res4 = wb.put(get_test_key(4), Bytes::from(vec![b'v'; 20000]));
+    assert!(res4.is_ok());
+    let res5 = wb.commit();
+    assert!(res5.is_ok());
+    for i in 1..=4 {
+        let value = engine.get(get_test_key(i)).unwrap();
+        assert_eq!(
+            Some(value.len()),
+            engine.value_len(get_test_key(i)).unwrap()
+        );
+    }
+
+    // 2.不存在、被删除以及 key 为空的情况
+    assert_eq!(None, engine.value_len(get_test_key(5)).unwrap());
+    let res6 = engine.delete(get_test_key(1));
+    assert!(res6.is_ok());
+    assert_eq!(None, engine.value_len(get_test_key(1)).unwrap());
+    assert_eq!(
+        Errors::KeyIsEmpty,
+        engine.value_len(Bytes::new()).err().unwrap()
+    );
+    engine.close().expect("failed to close engine");
+
+    // 3.开启压缩之后返回的是压缩前的长度
+    opts.compression = Compression::Zstd;
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    let res7 = engine2.put(get_test_key(6), Bytes::from(vec![b'v'; 4096]));
+    assert!(res7.is_ok());
+    assert_eq!(Some(4096), engine2.value_len(get_test_key(6)).unwrap());
+    assert_eq!(Some(300), engine2.value_len(get_test_key(2)).unwrap());
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_list_suffixes() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-list-suffixes");
+    opts.data_file_merge_ratio = 0 as f32;
+    opts.prefix_index_len = Some(4);
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 1.普通写入、事务写入以及删除都会更新前缀索引
+    for key in ["t01:b", "t01:a", "t02:c", "t03:d", "t0"] {
+        let res = engine.put(Bytes::from(key), get_test_value(1));
+        assert!(res.is_ok());
+    }
+    let wb = engine
+        .new_write_batch(WriteBatchOptions::default())
+        .expect("failed to create write batch");
+    let res1 = wb.put(Bytes::from("t01:e"), get_test_value(2));
+    assert!(res1.is_ok());
+    let res2 = wb.delete(Bytes::from("t03:d"));
+    assert!(res2.is_ok());
+    let res3 = wb.commit();
+    assert!(res3.is_ok());
+    let res4 = engine.delete(Bytes::from("t01:b"));
+    assert!(res4.is_ok());
+
+    let expected = vec![Bytes::from("a"), Bytes::from("e")];
+    assert_eq!(expected, engine.list_suffixes(b"t01:").unwrap());
+    assert_eq!(
+        vec![Bytes::from("c")],
+        engine.list_suffixes(b"t02:").unwrap()
+    );
+    assert!(engine.list_suffixes(b"t03:").unwrap().is_empty());
+    // 前缀的长度和配置不一致
+    assert_eq!(
+        Errors::InvalidPrefix,
+        engine.list_suffixes(b"t01").err().unwrap()
+    );
+
+    // 2.重启以及 merge 之后重新建立前缀索引
+    engine.close().expect("failed to close engine");
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(expected, engine2.list_suffixes(b"t01:").unwrap());
+    let res5 = engine2.merge();
+    assert!(res5.is_ok());
+    engine2.close().expect("failed to close engine");
+    let engine3 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(expected, engine3.list_suffixes(b"t01:").unwrap());
+
+    // 3.清空数据之后前缀索引为空
+    let res6 = engine3.clear();
+    assert!(res6.is_ok());
+    assert!(engine3.list_suffixes(b"t01:").unwrap().is_empty());
+    engine3.close().expect("failed to close engine");
+
+    // 4.没有配置前缀索引时返回错误
+    opts.prefix_index_len = None;
+    let engine4 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(
+        Errors::InvalidPrefix,
+        engine4.list_suffixes(b"t01:").err().unwrap()
+    );
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    let _ = std::fs::remove_dir_all(get_merge_path(opts.dir_path));
+}
+
+#[test]
+fn test_engine_lazy_index() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-lazy-index");
+    opts.data_file_size = 64 * 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    for i in 0..2000 {
+        let res = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res.is_ok());
+    }
+    engine.close().expect("failed to close engine");
+
+    // 1.后台加载索引，读写操作等待加载完成
+    opts.lazy_index = true;
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(get_test_value(10), engine2.get(get_test_key(10)).unwrap());
+    assert!(engine2.index_ready());
+    assert_eq!(2000, engine2.list_keys().unwrap().len());
+    let res1 = engine2.put(get_test_key(2000), get_test_value(2000));
+    assert!(res1.is_ok());
+    engine2.close().expect("failed to close engine");
+
+    // 2.加载完成之前写入的数据不会覆盖已有的数据
+    opts.lazy_index = false;
+    let engine3 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert!(engine3.index_ready());
+    assert_eq!(2001, engine3.list_keys().unwrap().len());
+    assert_eq!(get_test_value(0), engine3.get(get_test_key(0)).unwrap());
+    assert_eq!(
+        get_test_value(2000),
+        engine3.get(get_test_key(2000)).unwrap()
+    );
+    engine3.close().expect("failed to close engine");
+
+    // 3.后台加载失败时读写操作返回对应的错误
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .open(get_data_file_name(&opts, 0))
+        .unwrap();
+    file.seek(SeekFrom::Start(DATA_FILE_HEADER_SIZE + 10))
+        .unwrap();
+    file.write_all(b"#").unwrap();
+    drop(file);
+    opts.lazy_index = true;
+    let engine4 = Engine::open(opts.clone()).expect("failed to open engine");
+    let res2 = engine4.get(get_test_key(10));
+    assert!(matches!(res2, Err(Errors::InvalidCrc { .. })));
+    assert!(!engine4.index_ready());
+    let res3 = engine4.put(get_test_key(1), get_test_value(1));
+    assert!(matches!(res3, Err(Errors::InvalidCrc { .. })));
+    let res4 = engine4.iter(IteratorOptions::default());
+    assert!(matches!(res4.err(), Some(Errors::InvalidCrc { .. })));
+    let res5 = engine4.entries(IteratorOptions::default());
+    assert!(matches!(res5.err(), Some(Errors::InvalidCrc { .. })));
+    let res6 = engine4.locate(get_test_key(10));
+    assert!(matches!(res6, Err(Errors::InvalidCrc { .. })));
+    let res7 = engine4.snapshot();
+    assert!(matches!(res7.err(), Some(Errors::InvalidCrc { .. })));
+    assert!(matches!(
+        engine4.last_sequence(),
+        Err(Errors::InvalidCrc { .. })
+    ));
+    assert!(matches!(
+        engine4.current_position(),
+        Err(Errors::InvalidCrc { .. })
+    ));
+    drop(engine4);
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_sequence() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-sequence");
+    opts.data_file_size = 64 * 1024 * 1024;
+    opts.data_file_merge_ratio = 0 as f32;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(engine.last_sequence().unwrap(), 0);
+
+    // 每次写入都会得到递增的序列号
+    let res1 = engine.put(get_test_key(1), get_test_value(1)).unwrap();
+    let res2 = engine.put(get_test_key(2), get_test_value(2)).unwrap();
+    assert!(res2 > res1);
+    let res3 = engine.delete_fast(get_test_key(1)).unwrap();
+    assert!(res3.unwrap() > res2);
+    // 删除不存在的 key 不会写入数据
+    let res4 = engine.delete_fast(get_test_key(100)).unwrap();
+    assert!(res4.is_none());
+    assert_eq!(engine.last_sequence().unwrap(), res3.unwrap());
+
+    // 事务中的数据同样分配序列号
+    let wb = engine
+        .new_write_batch(WriteBatchOptions::default())
+        .expect("failed to create write batch");
+    wb.put(get_test_key(3), get_test_value(3)).unwrap();
+    wb.put(get_test_key(4), get_test_value(4)).unwrap();
+    wb.commit().unwrap();
+    assert_eq!(engine.last_sequence().unwrap(), res3.unwrap() + 2);
+
+    let changes1: Vec<_> = engine.changes_since(0).unwrap().collect();
+    assert_eq!(changes1.len(), 5);
+    assert_eq!(changes1[0].sequence, res1);
+    assert_eq!(changes1[0].key, get_test_key(1));
+    assert_eq!(changes1[0].value, Some(get_test_value(1)));
+    assert_eq!(changes1[2].key, get_test_key(1));
+    assert!(changes1[2].value.is_none());
+    // 事务中的数据写入的顺序不固定
+    let mut txn_keys = vec![changes1[3].key.clone(), changes1[4].key.clone()];
+    txn_keys.sort();
+    assert_eq!(txn_keys, vec![get_test_key(3), get_test_key(4)]);
+    assert!(changes1.windows(2).all(|w| w[0].sequence < w[1].sequence));
+
+    // 只返回给定序列号之后的写入
+    let changes2: Vec<_> = engine.changes_since(res2).unwrap().collect();
+    assert_eq!(changes2.len(), 3);
+    assert_eq!(changes2[0].sequence, res3.unwrap());
+    assert_eq!(
+        engine
+            .changes_since(engine.last_sequence().unwrap())
+            .unwrap()
+            .count(),
+        0
+    );
+
+    // 重启之后序列号继续递增
+    let last = engine.last_sequence().unwrap();
+    std::mem::drop(engine);
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(engine2.last_sequence().unwrap(), last);
+    let res5 = engine2.put(get_test_key(5), get_test_value(5)).unwrap();
+    assert_eq!(res5, last + 1);
+
+    // merge 之后被删除的数据不会保留，但序列号不会回退
+    engine2.delete_fast(get_test_key(5)).unwrap();
+    let last = engine2.last_sequence().unwrap();
+    engine2.merge().expect("failed to merge");
+    std::mem::drop(engine2);
+    let engine3 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(engine3.last_sequence().unwrap(), last);
+    let changes3: Vec<_> = engine3.changes_since(0).unwrap().collect();
+    let mut keys: Vec<_> = changes3.iter().map(|change| change.key.clone()).collect();
+    keys.sort();
+    assert_eq!(
+        keys,
+        vec![get_test_key(2), get_test_key(3), get_test_key(4)]
+    );
+
+    // 删除测试的文件夹
+    std::mem::drop(engine3);
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_read_at() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-read-at");
+    opts.data_file_size = 4 * 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    for i in 0..10 {
+        let res = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res.is_ok());
+    }
+
+    // 1.记录当前的写入位置，之后写入的数据跨越多个数据文件
+    let (mut file_id, mut offset) = engine.current_position().unwrap();
+    for i in 10..200 {
+        let res = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res.is_ok());
+    }
+    let (last_file_id, last_offset) = engine.current_position().unwrap();
+    assert!(last_file_id > file_id);
+
+    // 2.从记录的位置开始依次读取之后写入的数据
+    let mut keys = Vec::new();
+    loop {
+        match engine.read_at(file_id, offset) {
+            Ok(read_record) => {
+                let (key, _) = read_record.record().parse_key();
+                assert_eq!(LogRecordType::NORMAL, read_record.record().rec_type());
+                keys.push(Bytes::from(key));
+                offset += read_record.size() as u64;
+            }
+            // 读到文件末尾之后从下一个文件的第一条数据开始读取
+            Err(Errors::ReadDataFileEOF) if file_id < last_file_id => {
+                file_id += 1;
+                offset = DATA_FILE_HEADER_SIZE;
+            }
+            Err(Errors::ReadDataFileEOF) => break,
+            Err(e) => panic!("failed to read record: {:?}", e),
+        }
+    }
+    assert_eq!((last_file_id, last_offset), (file_id, offset));
+    let expected: Vec<_> = (10..200).map(get_test_key).collect();
+    assert_eq!(expected, keys);
+
+    // 3.文件不存在
+    assert_eq!(
+        Errors::DataFileNotFound,
+        engine.read_at(last_file_id + 1, 0).err().unwrap()
+    );
+
+    // 4.没有完整写入的记录
+    let record = LogRecord {
+        key: log_record_key_with_seq(get_test_key(200).to_vec(), NON_TRANSACTION_SEQ_NO),
+        value: get_test_value(200).to_vec(),
+        rec_type: LogRecordType::NORMAL,
+        expire: 0,
+        compression: Compression::None,
+        encrypted: false,
+        sequence: 0,
+        timestamp: 0,
+        keyspace: 0,
+    };
+    let enc_record = record.encode();
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(get_data_file_name(&opts, last_file_id))
+        .unwrap();
+    file.write_all(&enc_record[..enc_record.len() - 5]).unwrap();
+    std::mem::drop(file);
+    assert_eq!(
+        Errors::CorruptedRecord {
+            file_id: last_file_id,
+            offset: last_offset
+        },
+        engine.read_at(last_file_id, last_offset).err().unwrap()
+    );
+
+    // 删除测试的文件夹
+    std::mem::drop(engine);
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_sync_mode() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-sync-mode");
+    opts.data_file_size = 64 * 1024 * 1024;
+    opts.sync_writes = true;
+
+    for (round, sync_mode) in [SyncMode::None, SyncMode::DataOnly, SyncMode::Full]
+        .into_iter()
+        .enumerate()
+    {
+        opts.sync_mode = sync_mode;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        // 替换活跃文件的 IO 实现，统计 sync 的次数
+        let sync_count = Arc::new(AtomicUsize::new(0));
+        let file_id = engine.active_file.read().get_file_id();
+        let io = SyncCountIO {
+            inner: FileIO::new(get_data_file_name(&opts, file_id))
+                .unwrap()
+                .with_sync_mode(sync_mode),
+            sync_count: sync_count.clone(),
+        };
+        let write_off = engine.active_file.read().get_write_off();
+        let data_file = DataFile::with_io_manager(file_id, Box::new(io));
+        data_file.set_write_off(write_off);
+        *engine.active_file.write() = data_file;
+
+        // SyncMode::None 下写入时不持久化，其他方式每次写入都持久化
+        for i in 0..10 {
+            let res = engine.put(get_test_key(round * 10 + i), get_test_value(i));
+            assert!(res.is_ok());
+        }
+        let expected = match sync_mode {
+            SyncMode::None => 0,
+            _ => 10,
+        };
+        assert_eq!(expected, sync_count.load(Ordering::SeqCst));
+
+        // 显式调用 sync 在任何方式下都会持久化
+        assert!(engine.sync().is_ok());
+        assert_eq!(expected + 1, sync_count.load(Ordering::SeqCst));
+        std::mem::drop(engine);
+
+        // 重启之后数据仍然存在
+        let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..10 {
+            assert_eq!(
+                engine2.get(get_test_key(round * 10 + i)).unwrap(),
+                get_test_value(i)
+            );
+        }
+    }
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_engine_read_only_image() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-read-only-image");
+    opts.data_file_size = 32 * 1024;
+    opts.data_file_merge_ratio = 0 as f32;
+
+    // 构造一个 merge 过的数据库，包含 hint 文件和多个数据文件
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    for i in 0..1000 {
+        let res = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res.is_ok());
+    }
+    engine.merge().expect("failed to merge");
+    engine.close().expect("failed to close engine");
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    for i in 0..100 {
+        let res = engine.delete(get_test_key(i));
+        assert!(res.is_ok());
+    }
+    engine.close().expect("failed to close engine");
+    assert!(opts.dir_path.join(HINT_FILE_NAME).is_file());
+
+    // 删除锁文件，并去掉所有文件和数据目录的写权限
+    std::fs::remove_file(opts.dir_path.join(FILE_LOCK_NAME)).unwrap();
+    let mut file_names = Vec::new();
+    for entry in std::fs::read_dir(&opts.dir_path).unwrap() {
+        let path = entry.unwrap().path();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o444)).unwrap();
+        file_names.push(path);
+    }
+    file_names.sort();
+    std::fs::set_permissions(&opts.dir_path, std::fs::Permissions::from_mode(0o555)).unwrap();
+
+    let mut ro_opts = opts.clone();
+    ro_opts.read_only = true;
+    for mmap_at_startup in [false, true] {
+        ro_opts.mmap_at_startup = mmap_at_startup;
+        let engine1 = Engine::open(ro_opts.clone()).expect("failed to open engine");
+        assert_eq!(900, engine1.list_keys().unwrap().len());
+        assert_eq!(
+            Errors::KeyNotFound,
+            engine1.get(get_test_key(1)).err().unwrap()
+        );
+        assert_eq!(get_test_value(500), engine1.get(get_test_key(500)).unwrap());
+        let res1 = engine1.put(get_test_key(1), get_test_value(1));
+        assert_eq!(Errors::ReadOnlyDatabase, res1.err().unwrap());
+        engine1.close().expect("failed to close engine");
+    }
+
+    // 只读模式下不会创建或修改任何文件
+    let mut file_names2: Vec<_> = std::fs::read_dir(&opts.dir_path)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    file_names2.sort();
+    assert_eq!(file_names, file_names2);
+
+    // 删除测试的文件夹
+    std::fs::set_permissions(&opts.dir_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_open_with_sparse_file_ids() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-sparse-file-ids");
+    opts.data_file_size = 64;
+
+    // 每个数据文件只能放下一条数据，写入之后有 0 ~ 6 共 7 个数据文件
+    let value = Bytes::from(vec![b'v'; 24]);
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    for i in 0..7 {
+        let res = engine.put(Bytes::from(format!("key-{:04}", i)), value.clone());
+        assert!(res.is_ok());
+    }
+    assert_eq!(6, engine.active_file.read().get_file_id());
+    engine.close().expect("failed to close engine");
+
+    // 只保留 id 为 2 和 5 的数据文件
+    for file_id in [0, 1, 3, 4, 6] {
+        std::fs::remove_file(get_data_file_name(&opts, file_id)).unwrap();
+    }
+
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(5, engine2.active_file.read().get_file_id());
+    let mut older_ids: Vec<u32> = engine2.older_files.read().keys().copied().collect();
+    older_ids.sort();
+    assert_eq!(vec![2], older_ids);
+    assert_eq!(
+        vec![Bytes::from("key-0002"), Bytes::from("key-0005")],
+        engine2.list_keys().unwrap()
+    );
+    assert_eq!(value, engine2.get(Bytes::from("key-0002")).unwrap());
+    assert_eq!(value, engine2.get(Bytes::from("key-0005")).unwrap());
+
+    // 新写入的数据在最大的 id 之后的文件中
+    let res = engine2.put(Bytes::from("key-new"), value.clone());
+    assert!(res.is_ok());
+    assert_eq!(6, engine2.active_file.read().get_file_id());
+    let pos = engine2.index.get(b"key-new".to_vec()).unwrap();
+    assert_eq!(6, pos.file_id);
+    assert_eq!(value, engine2.get(Bytes::from("key-new")).unwrap());
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_open_with_initial_file_id() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-initial-file-id");
+    opts.initial_file_id = 100;
+
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(100, engine.active_file.read().get_file_id());
+    let res = engine.put(get_test_key(1), get_test_value(1));
+    assert!(res.is_ok());
+    engine.close().expect("failed to close engine");
+    assert!(get_data_file_name(&opts, 100).exists());
+
+    // 已经有数据文件时不再使用 initial_file_id
+    opts.initial_file_id = 0;
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(100, engine2.active_file.read().get_file_id());
+    assert_eq!(get_test_value(1), engine2.get(get_test_key(1)).unwrap());
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_bloom_filters() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-bloom-filters");
+    opts.data_file_size = 64 * 1024;
+    opts.data_file_merge_ratio = 0 as f32;
+    opts.enable_bloom_filters = true;
+
+    // 1.误判率不合法
+    let mut bad_opts = opts.clone();
+    bad_opts.bloom_false_positive_rate = 1.0;
+    let res1 = Engine::open(bad_opts);
+    assert!(matches!(res1, Err(Errors::InvalidBloomFalsePositiveRate)));
+
+    // 2.写满的数据文件都有对应的过滤器文件
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    for i in 0..2000 {
+        let res = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res.is_ok());
+    }
+    let active_fid = engine.active_file.read().get_file_id();
+    assert!(active_fid > 0);
+    for file_id in 0..active_fid {
+        assert!(get_bloom_file_name(&opts, file_id).is_file());
+    }
+    assert!(!get_bloom_file_name(&opts, active_fid).exists());
+    engine.close().expect("failed to close engine");
+
+    // 3.重启之后写入的 key 不会被排除，大部分不存在的 key 会被排除
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    let mut file_ids = vec![engine2.active_file.read().get_file_id()];
+    file_ids.extend(engine2.older_files.read().keys());
+    let bloom_filters = engine2.bloom_filters.as_ref().unwrap();
+    for i in 0..2000 {
+        assert!(bloom_filters.may_contain(&get_test_key(i), &file_ids));
+    }
+    let false_positives = (2000..4000)
+        .filter(|i| bloom_filters.may_contain(&get_test_key(*i), &file_ids))
+        .count();
+    assert!(
+        false_positives < 400,
+        "false positives: {}",
+        false_positives
+    );
+    engine2.close().expect("failed to close engine");
+
+    // 4.后台加载索引时，不存在的 key 直接返回
+    opts.lazy_index = true;
+    let engine3 = Engine::open(opts.clone()).expect("failed to open engine");
+    let res2 = engine3.get(get_test_key(5000));
+    assert!(matches!(res2, Err(Errors::KeyNotFound)));
+    assert_eq!(get_test_value(10), engine3.get(get_test_key(10)).unwrap());
+    for i in 0..1000 {
+        let res = engine3.delete(get_test_key(i));
+        assert!(res.is_ok());
+    }
+    engine3.merge().expect("failed to merge");
+    engine3.close().expect("failed to close engine");
+
+    // 5.merge 之后过滤器仍然和数据文件一致
+    opts.lazy_index = false;
+    let engine4 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(1000, engine4.list_keys().unwrap().len());
+    let mut file_ids = vec![engine4.active_file.read().get_file_id()];
+    file_ids.extend(engine4.older_files.read().keys());
+    let bloom_filters = engine4.bloom_filters.as_ref().unwrap();
+    for i in 1000..2000 {
+        assert!(bloom_filters.may_contain(&get_test_key(i), &file_ids));
+        assert_eq!(get_test_value(i), engine4.get(get_test_key(i)).unwrap());
+    }
+
+    // 6.清空之后删除所有的过滤器文件，内存中的过滤器也被清空
+    assert!(bloom_filters.filter_count() > 0);
+    engine4.clear().expect("failed to clear engine");
+    assert_eq!(0, bloom_filters.filter_count());
+    let bloom_file_count = std::fs::read_dir(&opts.dir_path)
+        .unwrap()
+        .filter(|entry| {
+            let path = entry.as_ref().unwrap().path();
+            path.extension().is_some_and(|ext| ext == "bloom")
+        })
+        .count();
+    assert_eq!(0, bloom_file_count);
+    engine4.close().expect("failed to close engine");
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_open_with_empty_data_file() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-empty-data-file");
+    opts.data_file_size = 64 * 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    for i in 0..1000 {
+        let res = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res.is_ok());
+    }
+    let active_fid = engine.active_file.read().get_file_id();
+    assert!(active_fid > 0);
+    engine.close().expect("failed to close engine");
+
+    // 模拟新建数据文件之后发生崩溃，留下空的数据文件
+    let empty_fid = active_fid + 2;
+    std::fs::File::create(get_data_file_name(&opts, active_fid + 1)).unwrap();
+    std::fs::File::create(get_data_file_name(&opts, empty_fid)).unwrap();
+
+    // id 最大的空文件作为活跃文件，其他的空文件被跳过
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(empty_fid, engine2.active_file.read().get_file_id());
+    assert!(!engine2.older_files.read().contains_key(&(active_fid + 1)));
+    assert_eq!(active_fid + 1, engine2.older_files.read().len() as u32);
+    assert_eq!(1000, engine2.list_keys().unwrap().len());
+    assert_eq!(get_test_value(10), engine2.get(get_test_key(10)).unwrap());
+
+    // 新写入的数据在空的数据文件中
+    let res = engine2.put(get_test_key(1000), get_test_value(1000));
+    assert!(res.is_ok());
+    let pos = engine2.index.get(get_test_key(1000).to_vec()).unwrap();
+    assert_eq!(empty_fid, pos.file_id);
+    engine2.close().expect("failed to close engine");
+
+    let engine3 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(1001, engine3.list_keys().unwrap().len());
+    assert_eq!(
+        get_test_value(1000),
+        engine3.get(get_test_key(1000)).unwrap()
+    );
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_key_count() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-key-count");
+    opts.data_file_size = 64 * 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(0, engine.key_count());
+
+    for i in 0..1000 {
+        let res = engine.put(get_test_key(i), get_test_value(i));
+        assert!(res.is_ok());
+    }
+    // 覆盖写入不会增加 key 的数量
+    for i in 0..100 {
+        let res = engine.put(get_test_key(i), get_test_value(i + 1));
+        assert!(res.is_ok());
+    }
+    for i in 900..1000 {
+        let res = engine.delete(get_test_key(i));
+        assert!(res.is_ok());
+    }
+    assert_eq!(900, engine.key_count());
+    assert_eq!(engine.list_keys().unwrap().len(), engine.key_count());
+    engine.close().expect("failed to close engine");
+
+    // 重启之后数量不变
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(900, engine2.key_count());
+    assert_eq!(engine2.list_keys().unwrap().len(), engine2.key_count());
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_compact_key() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-compact-key");
+    opts.data_file_size = 4 * 1024;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // key 不存在时不做任何操作
+    assert!(engine.compact_key(Bytes::from("absent")).is_ok());
+    assert_eq!(Err(Errors::KeyIsEmpty), engine.compact_key(Bytes::new()));
+
+    // 反复覆盖写入，旧的版本分散在多个数据文件中
+    let mut last_seq = 0;
+    for i in 0..200 {
+        last_seq = engine
+            .put(Bytes::from("hot"), Bytes::from(format!("value-{:060}", i)))
+            .unwrap();
+    }
+    engine
+        .put(Bytes::from("cold"), Bytes::from("cold-value"))
+        .unwrap();
+    let old_pos = engine.locate(Bytes::from("hot")).unwrap().unwrap();
+    let reclaimable = engine.reclaimable_size().unwrap();
+
+    // 重写之后 value 和序列号不变，旧的位置变成可以回收的数据
+    engine.compact_key(Bytes::from("hot")).unwrap();
+    let new_pos = engine.locate(Bytes::from("hot")).unwrap().unwrap();
+    assert!(new_pos.offset() > old_pos.offset() || new_pos.file_id() > old_pos.file_id());
+    assert_eq!(
+        reclaimable + old_pos.size() as u64,
+        engine.reclaimable_size().unwrap()
+    );
+    assert_eq!(
+        Bytes::from(format!("value-{:060}", 199)),
+        engine.get(Bytes::from("hot")).unwrap()
+    );
+
+    // merge 之前旧的数据仍然在数据文件中，变更中同一个序列号只返回一次
+    let changes: Vec<_> = engine.changes_since(last_seq - 1).unwrap().collect();
+    assert_eq!(2, changes.len());
+    assert_eq!(last_seq, changes[0].sequence);
+    assert_eq!(Bytes::from("hot"), changes[0].key);
+    assert_eq!(Bytes::from("cold"), changes[1].key);
+    let hot_changes = engine
+        .changes_since(0)
+        .unwrap()
+        .filter(|change| change.key == Bytes::from("hot"))
+        .count();
+    assert_eq!(200, hot_changes);
+
+    // merge 之后只剩下一条数据
+    engine.merge().unwrap();
+    engine.close().unwrap();
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+    let changes: Vec<_> = engine
+        .changes_since(0)
+        .unwrap()
+        .filter(|change| change.key == Bytes::from("hot"))
+        .collect();
+    assert_eq!(1, changes.len());
+    assert_eq!(last_seq, changes[0].sequence);
+    assert_eq!(
+        Some(Bytes::from(format!("value-{:060}", 199))),
+        changes[0].value
+    );
+    assert_eq!(
+        Bytes::from("cold-value"),
+        engine.get(Bytes::from("cold")).unwrap()
+    );
+
+    // 删除测试的文件夹
+    engine.close().unwrap();
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+fn test_engine_io_factory() {
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-io-factory");
+    opts.data_file_size = 4 * 1024;
+    opts.data_file_merge_ratio = 0 as f32;
+
+    // 所有的文件都保存在内存中，同一个路径返回同一份数据
+    let factory = InMemoryFactory::new();
+    opts.io_factory = Some(Arc::new(factory.clone()));
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    for i in 0..200 {
+        engine.put(get_test_key(i), get_test_value(i)).unwrap();
+    }
+    engine.delete(get_test_key(0)).unwrap();
+    assert_eq!(get_test_value(100), engine.get(get_test_key(100)).unwrap());
+    assert_eq!(
+        Errors::KeyNotFound,
+        engine.get(get_test_key(0)).err().unwrap()
+    );
+    assert_eq!(199, engine.list_keys().unwrap().len());
+    engine.close().unwrap();
+
+    // 数据文件都通过 io_factory 打开，没有写入到磁盘中
+    let data_file_count = |factory: &InMemoryFactory| {
+        factory
+            .file_names()
+            .iter()
+            .filter(|file_name| file_name.extension().is_some_and(|ext| ext == "data"))
+            .count()
+    };
+    assert!(data_file_count(&factory) > 1);
+    assert!(!get_data_file_name(&opts, 0).exists());
+
+    // 1.重新打开之后从 io_factory 中加载数据文件
+    let engine = Engine::open(opts.clone()).expect("failed to reopen engine");
+    assert_eq!(199, engine.list_keys().unwrap().len());
+    assert_eq!(get_test_value(100), engine.get(get_test_key(100)).unwrap());
+    assert_eq!(
+        Errors::KeyNotFound,
+        engine.get(get_test_key(0)).err().unwrap()
+    );
+
+    // 2.merge 的输出以及 merge 完成的标识同样保存在 io_factory 中
+    for i in 1..100 {
+        engine
+            .put(get_test_key(i), Bytes::from("new-value"))
+            .unwrap();
+    }
+    let file_count = data_file_count(&factory);
+    engine.merge().expect("failed to merge");
+    let merge_path = get_merge_path(opts.dir_path.clone());
+    assert!(factory
+        .file_names()
+        .contains(&merge_path.join(MERGE_FINISHED_FILE_NAME)));
+    engine.close().unwrap();
+
+    // 3.重新打开时 merge 生效，旧的数据文件被删除
+    let engine = Engine::open(opts.clone()).expect("failed to reopen engine after merge");
+    assert!(data_file_count(&factory) < file_count);
+    assert!(factory
+        .file_names()
+        .iter()
+        .all(|file_name| !file_name.starts_with(&merge_path)));
+    assert_eq!(199, engine.list_keys().unwrap().len());
+    assert_eq!(
+        Bytes::from("new-value"),
+        engine.get(get_test_key(1)).unwrap()
+    );
+    assert_eq!(get_test_value(150), engine.get(get_test_key(150)).unwrap());
+    assert_eq!(
+        Errors::KeyNotFound,
+        engine.get(get_test_key(0)).err().unwrap()
+    );
+
+    // 4.只支持本地文件的操作返回错误
+    assert_eq!(
+        Errors::IOFactoryUnsupported,
+        engine
+            .backup(PathBuf::from("/tmp/bitcask-rs-io-factory-backup"))
+            .err()
+            .unwrap()
+    );
+    assert_eq!(
+        Errors::IOFactoryUnsupported,
+        engine
+            .save_index_snapshot("/tmp/bitcask-rs-io-factory.snapshot")
+            .err()
+            .unwrap()
+    );
+    engine.close().unwrap();
+    assert_eq!(
+        Errors::IOFactoryUnsupported,
+        Engine::repair(opts.clone()).err().unwrap()
+    );
+    let mut bloom_opts = opts.clone();
+    bloom_opts.enable_bloom_filters = true;
+    assert_eq!(
+        Errors::IOFactoryUnsupported,
+        Engine::open(bloom_opts).err().unwrap()
+    );
+
+    // 删除测试的文件夹
+    std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_engine_preallocate() {
+    use std::os::unix::fs::MetadataExt;
+
+    let mut opts = Options::default();
+    opts.dir_path = PathBuf::from("/tmp/bitcask-rs-preallocate");
+    opts.data_file_size = 1024 * 1024;
+    opts.preallocate = true;
+    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+    // 活跃文件预先分配了 data_file_size 大小的磁盘空间，写入的位置只包括实际写入的数据
+    for i in 0..10 {
+        engine.put(get_test_key(i), get_test_value(i)).unwrap();
+    }
+    let active_meta = std::fs::metadata(get_data_file_name(&opts, 0)).unwrap();
+    assert!(active_meta.blocks() * 512 >= opts.data_file_size);
+    let write_off = engine.active_file.read().get_write_off();
+    assert!(write_off > DATA_FILE_HEADER_SIZE && write_off < opts.data_file_size / 10);
+
+    // 切换活跃文件时截断旧文件中没有使用的空间
+    for i in 10..10000 {
+        engine.put(get_test_key(i), get_test_value(i)).unwrap();
+    }
+    assert!(engine.older_files.read().len() > 0);
+    let older_meta = std::fs::metadata(get_data_file_name(&opts, 0)).unwrap();
+    assert!(older_meta.len() < opts.data_file_size);
+    let active_id = engine.active_file.read().get_file_id();
+    let active_meta = std::fs::metadata(get_data_file_name(&opts, active_id)).unwrap();
+    assert_eq!(opts.data_file_size, active_meta.len());
+
+    // 模拟崩溃，复制还没有截断的数据目录，重启之后从逻辑末尾继续写入
+    let crash_opts = Options {
+        dir_path: PathBuf::from("/tmp/bitcask-rs-preallocate-crash"),
+        ..opts.clone()
+    };
+    std::fs::create_dir_all(&crash_opts.dir_path).unwrap();
+    for entry in std::fs::read_dir(&opts.dir_path).unwrap() {
+        let entry = entry.unwrap();
+        if entry.file_name() != FILE_LOCK_NAME {
+            std::fs::copy(entry.path(), crash_opts.dir_path.join(entry.file_name())).unwrap();
+        }
+    }
+    let crash_engine = Engine::open(crash_opts.clone()).expect("failed to open engine");
+    crash_engine
+        .put(Bytes::from("after-crash"), Bytes::from("v"))
+        .unwrap();
+    crash_engine.close().unwrap();
+    let crash_engine = Engine::open(crash_opts.clone()).expect("failed to open engine");
+    assert_eq!(
+        Bytes::from("v"),
+        crash_engine.get(Bytes::from("after-crash")).unwrap()
+    );
+    assert_eq!(10001, crash_engine.list_keys().unwrap().len());
+    crash_engine.close().unwrap();
+
+    // 关闭时截断活跃文件，文件大小和写入的位置一致
+    let write_off = engine.active_file.read().get_write_off();
+    engine.close().unwrap();
+    let active_meta = std::fs::metadata(get_data_file_name(&opts, active_id)).unwrap();
+    assert_eq!(write_off, active_meta.len());
+
+    // 重启之后数据完整，继续写入的数据可以读取
+    let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(
+        get_test_value(9999),
+        engine2.get(get_test_key(9999)).unwrap()
+    );
+    engine2
+        .put(Bytes::from("after-reopen"), Bytes::from("v"))
+        .unwrap();
+    engine2.close().unwrap();
+    let engine3 = Engine::open(opts.clone()).expect("failed to open engine");
+    assert_eq!(
+        Bytes::from("v"),
+        engine3.get(Bytes::from("after-reopen")).unwrap()
+    );
+    assert_eq!(10001, engine3.list_keys().unwrap().len());
+
     // 删除测试的文件夹
+    engine3.close().unwrap();
     std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    std::fs::remove_dir_all(crash_opts.dir_path).expect("failed to remove path");
 }
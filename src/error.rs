@@ -1,8 +1,8 @@
-use std::result;
+use std::{io, result};
 
 use thiserror::Error;
 
-#[derive(Error, Debug, PartialEq)]
+#[derive(Error, Debug, Clone, PartialEq)]
 pub enum Errors {
     #[error("failed to read from data file")]
     FailedReadFromDataFile,
@@ -37,8 +37,11 @@ pub enum Errors {
     #[error("database data file size must be greater than 0")]
     DataFileSizeTooSmall,
 
-    #[error("failed to create the database directory")]
-    FailedToCreateDatabaseDir,
+    #[error("failed to create the database directory: {kind:?}")]
+    FailedCreateDatabaseDir { kind: io::ErrorKind },
+
+    #[error("the database dir path is not a directory")]
+    DataDirectoryNotDir,
 
     #[error("failed to read the database directory")]
     FailedToReadDatabaseDir,
@@ -52,6 +55,12 @@ pub enum Errors {
     #[error("invalid crc value, log record maybe corrupted")]
     InvalidLogRecordCrc,
 
+    #[error("invalid crc value of key {key:?}, log record maybe corrupted")]
+    InvalidCrc { key: Vec<u8> },
+
+    #[error("the log record at offset {offset} of data file {file_id} is corrupted, it may be partially written")]
+    CorruptedRecord { file_id: u32, offset: u64 },
+
     #[error("exceed the max batch num")]
     ExceedMaxBatchNum,
 
@@ -62,11 +71,17 @@ pub enum Errors {
     UnableToUseWriteBatch,
 
     #[error("the database directory is used by another process")]
-    DatabaseIsUsing,
+    DatabaseIsInUse,
 
     #[error("invalid merge ratio, must between 0 and 1")]
     InvalidMergeRatio,
 
+    #[error("invalid bloom filter false positive rate, must between 0 and 1")]
+    InvalidBloomFalsePositiveRate,
+
+    #[error("invalid data file extension, must not be empty or contain '.' and path separators")]
+    InvalidDataFileExt,
+
     #[error("do not reach the merge ratio")]
     MergeRatioUnreached,
 
@@ -76,8 +91,116 @@ pub enum Errors {
     #[error("failed to copy the database directory")]
     FailedToCopyDirectory,
 
+    #[error("failed to decode the log record pos")]
+    FailedToDecodeLogRecordPos,
+
+    #[error("invalid log record type: {0}")]
+    InvalidLogRecordType(u8),
+
+    #[error("failed to compress the value")]
+    FailedToCompressValue,
+
+    #[error("failed to decompress the value")]
+    FailedToDecompressValue,
+
+    #[error("failed to encrypt the value")]
+    FailedToEncryptValue,
+
+    #[error("failed to decrypt the value, the key is wrong or the data is corrupted")]
+    DecryptionFailed,
+
+    #[error("the key exceeds the max key size")]
+    KeyTooLarge,
+
+    #[error("the value exceeds the max value size")]
+    ValueTooLarge,
+
+    #[error("the database is opened in read only mode")]
+    ReadOnlyDatabase,
+
+    #[error("the value is not an 8-byte integer")]
+    ValueNotInteger,
+
+    #[error("unknown data file format, the file header is invalid")]
+    UnknownFileFormat,
+
+    #[error("the merge is unfinished, the merge finished marker is missing")]
+    UnfinishedMerge,
+
+    #[error("the database has outstanding snapshots")]
+    SnapshotInUse,
+
+    #[error("the prefix index is not enabled or the prefix length does not match")]
+    InvalidPrefix,
+
+    #[error("tombstone tracking is not enabled")]
+    TombstonesNotTracked,
+
+    #[error("the index snapshot is corrupted")]
+    IndexSnapshotCorrupted,
+
+    #[error("failed to load the index in background")]
+    IndexLoadFailed,
+
+    #[error("the keyspace name is empty")]
+    KeyspaceNameIsEmpty,
+
+    #[error("the operation only supports local files and cannot be used with io_factory")]
+    IOFactoryUnsupported,
+
+    #[error("io error: {message}")]
+    Io {
+        kind: io::ErrorKind,
+        message: String,
+    },
+
     #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
     WrongTypeOperation,
 }
 
 pub type Result<T> = result::Result<T, Errors>;
+
+// 将底层的 IO 错误转换为 Errors，保留原始的错误类型和描述信息
+impl From<io::Error> for Errors {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::PermissionDenied => Errors::PermissionDenied,
+            io::ErrorKind::UnexpectedEof => Errors::ReadDataFileEOF,
+            kind => Errors::Io {
+                kind,
+                message: e.to_string(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_from_io_error() {
+        let err1: Errors = io::Error::new(io::ErrorKind::PermissionDenied, "denied").into();
+        assert_eq!(err1, Errors::PermissionDenied);
+
+        let err2: Errors = io::Error::new(io::ErrorKind::UnexpectedEof, "eof").into();
+        assert_eq!(err2, Errors::ReadDataFileEOF);
+
+        let err3: Errors = io::Error::new(io::ErrorKind::Other, "disk failure").into();
+        assert_eq!(
+            err3,
+            Errors::Io {
+                kind: io::ErrorKind::Other,
+                message: "disk failure".to_string(),
+            }
+        );
+        assert_eq!(err3.to_string(), "io error: disk failure");
+    }
+
+    #[test]
+    fn test_errors_as_std_error() {
+        let res: std::result::Result<(), Box<dyn std::error::Error>> =
+            Err(Errors::KeyIsEmpty.into());
+        assert_eq!(res.err().unwrap().to_string(), "the key is empty");
+    }
+}
@@ -0,0 +1,101 @@
+use log::error;
+use parking_lot::RwLock;
+
+use crate::error::Result;
+
+use super::IOManager;
+
+// 带写缓冲的 IO 实现，追加写的数据先写到缓冲中，缓冲写满或者 sync 时再写入文件
+pub struct BufWriterIO {
+    inner: Box<dyn IOManager>, // 实际读写文件的 IO 实现
+    buf: RwLock<Vec<u8>>,      // 还没有写入文件的数据，位于文件末尾之后
+    capacity: usize,           // 缓冲的大小
+}
+
+impl BufWriterIO {
+    pub fn new(inner: Box<dyn IOManager>, capacity: usize) -> Self {
+        BufWriterIO {
+            inner,
+            buf: RwLock::new(Vec::with_capacity(capacity)),
+            capacity,
+        }
+    }
+
+    // 将缓冲中的数据写入文件
+    fn flush(&self, buf: &mut Vec<u8>) -> Result<()> {
+        if !buf.is_empty() {
+            self.inner.write(buf)?;
+            buf.clear();
+        }
+        Ok(())
+    }
+}
+
+impl IOManager for BufWriterIO {
+    fn read(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        // 持有缓冲的读锁，读取的过程中缓冲不会被写入文件
+        let read_guard = self.buf.read();
+        let file_size = self.inner.size();
+
+        // 先读取已经写入文件的部分
+        let mut n = 0;
+        if offset < file_size {
+            n = self.inner.read(buf, offset)?;
+            if n < buf.len() && offset + (n as u64) < file_size {
+                return Ok(n);
+            }
+        }
+
+        // 再从缓冲中读取剩余的部分
+        let buf_off = (offset + n as u64).saturating_sub(file_size) as usize;
+        if buf_off < read_guard.len() {
+            let end = read_guard.len().min(buf_off + buf.len() - n);
+            let val = &read_guard[buf_off..end];
+            buf[n..n + val.len()].copy_from_slice(val);
+            n += val.len();
+        }
+        Ok(n)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize> {
+        let mut write_guard = self.buf.write();
+        // 缓冲放不下，先将缓冲中的数据写入文件
+        if write_guard.len() + buf.len() > self.capacity {
+            self.flush(&mut write_guard)?;
+        }
+        // 超过缓冲大小的数据直接写入文件
+        if buf.len() >= self.capacity {
+            return self.inner.write(buf);
+        }
+        write_guard.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    // 先将缓冲中的数据写入文件再截断
+    fn truncate(&self, len: u64) -> Result<()> {
+        let mut write_guard = self.buf.write();
+        self.flush(&mut write_guard)?;
+        self.inner.truncate(len)
+    }
+
+    fn sync(&self) -> Result<()> {
+        let mut write_guard = self.buf.write();
+        self.flush(&mut write_guard)?;
+        self.inner.sync()
+    }
+
+    fn size(&self) -> u64 {
+        let read_guard = self.buf.read();
+        self.inner.size() + read_guard.len() as u64
+    }
+}
+
+impl Drop for BufWriterIO {
+    fn drop(&mut self) {
+        // 关闭之前将缓冲中剩余的数据写入文件
+        let mut write_guard = self.buf.write();
+        if let Err(e) = self.flush(&mut write_guard) {
+            error!("failed to flush write buffer: {}", e);
+        }
+    }
+}
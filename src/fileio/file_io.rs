@@ -1,12 +1,19 @@
 use std::{
     fs::{File, OpenOptions},
-    io::Write,
-    os::unix::prelude::FileExt,
+    io::{ErrorKind, Write},
     path::PathBuf,
     sync::Arc,
 };
 
-use crate::error::{Errors, Result};
+#[cfg(unix)]
+use std::os::unix::prelude::FileExt;
+#[cfg(windows)]
+use std::os::windows::fs::FileExt;
+
+use crate::{
+    error::{Errors, Result},
+    option::SyncMode,
+};
 use log::error;
 use parking_lot::RwLock;
 
@@ -14,6 +21,7 @@ use super::IOManager;
 
 pub struct FileIO {
     fd: Arc<RwLock<File>>,
+    sync_mode: SyncMode, // sync 时持久化的方式
 }
 
 impl FileIO {
@@ -31,18 +39,71 @@ impl FileIO {
 
         Ok(FileIO {
             fd: Arc::new(RwLock::new(file)),
+            sync_mode: SyncMode::Full,
+        })
+    }
+
+    // 打开文件并预先分配 len 字节的磁盘空间（Unix 下使用 fallocate），文件不以 append 模式打开
+    // 文件末尾是预分配的空间，写入需要通过 write_at 指定逻辑末尾的位置
+    pub fn open_preallocated(filename: PathBuf, len: u64) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(filename)
+            .map_err(|e| {
+                error!("failed to open data file: {}", e);
+                Errors::FailedToOpenDataFile
+            })?;
+        if let Err(e) = fs2::FileExt::allocate(&file, len) {
+            error!("failed to preallocate data file: {}", e);
+            return Err(e.into());
+        }
+
+        Ok(FileIO {
+            fd: Arc::new(RwLock::new(file)),
+            sync_mode: SyncMode::Full,
+        })
+    }
+
+    // 以只读的方式打开已经存在的文件，用于只读文件系统上的数据库，写入会返回错误
+    pub fn open_read_only(filename: PathBuf) -> Result<Self> {
+        let file = OpenOptions::new().read(true).open(filename).map_err(|e| {
+            error!("failed to open data file: {}", e);
+            Errors::FailedToOpenDataFile
+        })?;
+
+        Ok(FileIO {
+            fd: Arc::new(RwLock::new(file)),
+            sync_mode: SyncMode::Full,
         })
     }
+
+    // 设置 sync 时持久化的方式，默认为 SyncMode::Full
+    pub fn with_sync_mode(mut self, sync_mode: SyncMode) -> Self {
+        self.sync_mode = sync_mode;
+        self
+    }
 }
 
 impl IOManager for FileIO {
     fn read(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
         let read_guard = self.fd.read();
-        match read_guard.read_at(buf, offset) {
-            Ok(n) => return Ok(n),
-            Err(e) => {
-                error!("read from data file err: {}", e);
-                return Err(Errors::FailedReadFromDataFile);
+        loop {
+            // windows 下的 seek_read 会移动文件游标，但文件以 append 模式打开，写入始终追加到末尾
+            #[cfg(unix)]
+            let read_res = read_guard.read_at(buf, offset);
+            #[cfg(windows)]
+            let read_res = read_guard.seek_read(buf, offset);
+            match read_res {
+                Ok(n) => return Ok(n),
+                // 被信号中断的读取需要重试
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    error!("read from data file err: {}", e);
+                    return Err(e.into());
+                }
             }
         }
     }
@@ -53,16 +114,45 @@ impl IOManager for FileIO {
             Ok(n) => return Ok(n),
             Err(e) => {
                 error!("write to data file err: {}", e);
-                return Err(Errors::FailedWriteToDataFile);
+                return Err(e.into());
             }
         }
     }
 
+    // 写入整个 buf，失败时返回错误，不会返回只写入了一部分的长度
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        let read_guard = self.fd.read();
+        // write_all_at 会重试被信号中断的写入，无法继续写入时返回 WriteZero
+        #[cfg(unix)]
+        let write_res = read_guard.write_all_at(buf, offset);
+        #[cfg(windows)]
+        let write_res = seek_write_all(&read_guard, buf, offset);
+        if let Err(e) = write_res {
+            error!("write to data file err: {}", e);
+            return Err(e.into());
+        }
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, len: u64) -> Result<()> {
+        let write_guard = self.fd.write();
+        if let Err(e) = write_guard.set_len(len) {
+            error!("failed to truncate data file: {}", e);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
     fn sync(&self) -> Result<()> {
         let read_guard = self.fd.read();
-        if let Err(e) = read_guard.sync_all() {
+        // SyncMode::None 只影响写入时是否持久化，显式调用 sync 时仍然需要落盘
+        let sync_res = match self.sync_mode {
+            SyncMode::DataOnly => read_guard.sync_data(),
+            SyncMode::None | SyncMode::Full => read_guard.sync_all(),
+        };
+        if let Err(e) = sync_res {
             error!("failed to sync data file: {}", e);
-            return Err(Errors::FailedSyncDataFile);
+            return Err(e.into());
         }
         Ok(())
     }
@@ -72,3 +162,20 @@ impl IOManager for FileIO {
         read_guard.metadata().unwrap().len()
     }
 }
+
+// windows 下循环调用 seek_write，直到写入整个 buf
+#[cfg(windows)]
+fn seek_write_all(file: &File, mut buf: &[u8], mut offset: u64) -> std::io::Result<()> {
+    while !buf.is_empty() {
+        match file.seek_write(buf, offset) {
+            Ok(0) => return Err(ErrorKind::WriteZero.into()),
+            Ok(n) => {
+                buf = &buf[n..];
+                offset += n as u64;
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
@@ -1,12 +1,22 @@
 use super::IOManager;
 use crate::error::{Errors, Result};
 use log::error;
-use memmap2::Mmap;
-use parking_lot::Mutex;
-use std::{fs::OpenOptions, path::PathBuf, sync::Arc};
+use memmap2::{MmapMut, MmapOptions};
+use parking_lot::{lock_api::ArcRwLockReadGuard, RawRwLock, RwLock};
+use std::{
+    fs::{File, OpenOptions},
+    ops::{Deref, Range},
+    path::PathBuf,
+    sync::Arc,
+};
+
+// 映射区域扩容时的最小大小
+const MIN_MAP_SIZE: u64 = 4 * 1024;
 
 pub struct MMapIO {
-    map: Arc<Mutex<Mmap>>,
+    fd: File,                    // 被映射的文件，扩容时需要调整其大小
+    map: Arc<RwLock<MmapMut>>,   // 可读写的映射区域，可能比实际写入的数据更大
+    write_off: Arc<RwLock<u64>>, // 实际写入的数据长度
 }
 
 impl MMapIO {
@@ -20,36 +30,158 @@ impl MMapIO {
                 error!("failed to open data file: {}", e);
                 return Errors::FailedToOpenDataFile;
             })?;
-        let map = unsafe { Mmap::map(&file).expect("failed to map the file") };
+        let map = unsafe { MmapMut::map_mut(&file).expect("failed to map the file") };
+        let write_off = map.len() as u64;
 
         Ok(MMapIO {
-            map: Arc::new(Mutex::new(map)),
+            fd: file,
+            map: Arc::new(RwLock::new(map)),
+            write_off: Arc::new(RwLock::new(write_off)),
         })
     }
+
+    // 以只读的方式打开已经存在的文件，使用写时复制的映射，修改不会写回文件
+    pub fn open_read_only(filename: PathBuf) -> Result<Self> {
+        let file = OpenOptions::new().read(true).open(filename).map_err(|e| {
+            error!("failed to open data file: {}", e);
+            Errors::FailedToOpenDataFile
+        })?;
+        let map = match unsafe { MmapOptions::new().map_copy(&file) } {
+            Ok(map) => map,
+            Err(e) => {
+                error!("failed to map data file: {}", e);
+                return Err(e.into());
+            }
+        };
+        let write_off = map.len() as u64;
+
+        Ok(MMapIO {
+            fd: file,
+            map: Arc::new(RwLock::new(map)),
+            write_off: Arc::new(RwLock::new(write_off)),
+        })
+    }
+}
+
+// 映射区域中的一段数据，持有映射区域的读锁，映射区域在此期间不会被重新映射或者释放
+pub struct MappedSlice {
+    map: ArcRwLockReadGuard<RawRwLock, MmapMut>,
+    start: usize,
+    end: usize,
+}
+
+impl MappedSlice {
+    // 截取其中的一部分，range 是相对于当前数据的位置
+    pub fn slice(self, range: Range<usize>) -> MappedSlice {
+        assert!(range.start <= range.end && self.start + range.end <= self.end);
+        MappedSlice {
+            map: self.map,
+            start: self.start + range.start,
+            end: self.start + range.end,
+        }
+    }
+}
+
+impl Deref for MappedSlice {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.map[self.start..self.end]
+    }
 }
 
 impl IOManager for MMapIO {
     fn read(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
-        let map_arr = self.map.lock();
-        let end = offset + buf.len() as u64;
-        if end > map_arr.len() as u64 {
+        let map_arr = self.map.read();
+        let write_off = *self.write_off.read();
+        if offset >= write_off {
             return Err(Errors::ReadDataFileEOF);
         }
+        // 和标准文件 IO 一致，读到文件末尾时只返回剩余的数据
+        let end = write_off.min(offset + buf.len() as u64);
         let val = &map_arr[offset as usize..end as usize];
-        buf.copy_from_slice(val);
+        buf[..val.len()].copy_from_slice(val);
         Ok(val.len())
     }
 
-    fn write(&self, _buf: &[u8]) -> Result<usize> {
-        unimplemented!()
+    fn read_exact(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        let map_arr = self.map.read();
+        let write_off = *self.write_off.read();
+        // 映射区域中的数据是完整的，只需要检查是否超过了写入的数据长度
+        let end = offset + buf.len() as u64;
+        if end > write_off {
+            return Err(Errors::ReadDataFileEOF);
+        }
+        buf.copy_from_slice(&map_arr[offset as usize..end as usize]);
+        Ok(())
+    }
+
+    fn read_mapped(&self, offset: u64, len: usize) -> Result<Option<MappedSlice>> {
+        let map_arr = self.map.read_arc();
+        let end = offset + len as u64;
+        if end > *self.write_off.read() {
+            return Err(Errors::ReadDataFileEOF);
+        }
+        Ok(Some(MappedSlice {
+            map: map_arr,
+            start: offset as usize,
+            end: end as usize,
+        }))
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize> {
+        let mut map_arr = self.map.write();
+        let mut write_off = self.write_off.write();
+        let start = *write_off;
+        let end = start + buf.len() as u64;
+
+        // 映射区域不够写入，则扩大文件后重新映射，按两倍增长避免频繁 remap
+        if end > map_arr.len() as u64 {
+            let new_size = end.max(map_arr.len() as u64 * 2).max(MIN_MAP_SIZE);
+            if let Err(e) = self.fd.set_len(new_size) {
+                error!("failed to extend data file: {}", e);
+                return Err(e.into());
+            }
+            *map_arr = match unsafe { MmapMut::map_mut(&self.fd) } {
+                Ok(map) => map,
+                Err(e) => {
+                    error!("failed to remap data file: {}", e);
+                    return Err(e.into());
+                }
+            };
+        }
+
+        map_arr[start as usize..end as usize].copy_from_slice(buf);
+        *write_off = end;
+        Ok(buf.len())
     }
 
     fn sync(&self) -> Result<()> {
-        unimplemented!()
+        let map_arr = self.map.read();
+        if let Err(e) = map_arr.flush() {
+            error!("failed to sync data file: {}", e);
+            return Err(e.into());
+        }
+        Ok(())
     }
 
     fn size(&self) -> u64 {
-        let map_arr = self.map.lock();
-        map_arr.len() as u64
+        *self.write_off.read()
+    }
+}
+
+impl Drop for MMapIO {
+    fn drop(&mut self) {
+        // 去掉扩容时多分配的空间，保证文件大小和实际写入的数据一致
+        let map_arr = self.map.read();
+        let write_off = *self.write_off.read();
+        if map_arr.len() as u64 > write_off {
+            if let Err(e) = map_arr.flush_async() {
+                error!("failed to flush data file: {}", e);
+            }
+            if let Err(e) = self.fd.set_len(write_off) {
+                error!("failed to truncate data file: {}", e);
+            }
+        }
     }
 }
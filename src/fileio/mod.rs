@@ -1,20 +1,62 @@
+pub mod buf_writer;
 pub mod file_io;
+#[cfg(feature = "mmap")]
 pub mod mmap;
-use std::path::PathBuf;
+pub mod retry;
+#[cfg(test)]
+pub(crate) mod testing;
+use std::path::{Path, PathBuf};
 
+use buf_writer::BufWriterIO;
 use file_io::FileIO;
-use mmap::MMapIO;
+#[cfg(feature = "mmap")]
+use mmap::{MMapIO, MappedSlice};
+use retry::RetryIO;
 
-use crate::{error::Result, option::IOType};
+use crate::{
+    error::{Errors, Result},
+    option::{IOType, SyncMode},
+};
 
 // 抽象 IO 管理接口
 pub trait IOManager: Sync + Send {
     // 从文件的给定位置读取对应的数据
     fn read(&self, buf: &mut [u8], offset: u64) -> Result<usize>;
 
+    // 从文件的给定位置读取数据直到填满 buf，文件中剩余的数据不足时返回 ReadDataFileEOF
+    fn read_exact(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        let mut n = 0;
+        while n < buf.len() {
+            // read 可能只返回部分数据，继续读取剩余的部分
+            match self.read(&mut buf[n..], offset + n as u64)? {
+                0 => return Err(Errors::ReadDataFileEOF),
+                read_bytes => n += read_bytes,
+            }
+        }
+        Ok(())
+    }
+
     // 写入数据到文件
     fn write(&self, buf: &[u8]) -> Result<usize>;
 
+    // 在文件的给定位置写入数据，用于预分配了空间的文件，写入的位置由调用方维护
+    // 默认追加到文件末尾，没有预分配空间的文件末尾就是写入的位置
+    fn write_at(&self, buf: &[u8], _offset: u64) -> Result<usize> {
+        self.write(buf)
+    }
+
+    // 将文件截断到 len 字节，用于释放预分配之后没有使用的空间，默认不做处理
+    fn truncate(&self, _len: u64) -> Result<()> {
+        Ok(())
+    }
+
+    // 不拷贝数据，直接返回映射区域中给定位置的数据，只有 mmap 支持，其他的 IO 返回 None
+    // 返回的数据持有映射区域的读锁，期间写入会被阻塞，只能用于不再写入的文件
+    #[cfg(feature = "mmap")]
+    fn read_mapped(&self, _offset: u64, _len: usize) -> Result<Option<MappedSlice>> {
+        Ok(None)
+    }
+
     // 同步数据
     fn sync(&self) -> Result<()>;
 
@@ -22,10 +64,73 @@ pub trait IOManager: Sync + Send {
     fn size(&self) -> u64;
 }
 
+// 自定义的文件存储，数据文件以及 hint、merge 标识等辅助文件的打开、列出、重命名和删除都通过它完成
+// 数据目录和 merge 目录本身仍然位于本地文件系统中，用于存放文件锁
+pub trait IOFactory: Sync + Send {
+    // 根据文件路径和 IO 类型创建 IO 实现，文件不存在时创建新的文件
+    fn open(&self, file_name: PathBuf, io_type: IOType) -> Box<dyn IOManager>;
+
+    // 列出目录中所有文件的文件名，目录中没有文件时返回空
+    fn list(&self, dir_path: &Path) -> Result<Vec<String>>;
+
+    // 判断文件是否存在
+    fn is_file(&self, file_name: &Path) -> bool;
+
+    // 重命名文件，目标文件已经存在时覆盖
+    fn rename(&self, src: &Path, dest: &Path) -> Result<()>;
+
+    // 删除文件
+    fn remove(&self, file_name: &Path) -> Result<()>;
+}
+
 pub fn new_io_manager(file_name: PathBuf, io_type: IOType) -> Box<dyn IOManager> {
+    new_io_manager_with_sync_mode(file_name, io_type, SyncMode::Full)
+}
+
+// 标准文件 IO 按照 sync_mode 持久化，mmap 不受影响
+pub fn new_io_manager_with_sync_mode(
+    file_name: PathBuf,
+    io_type: IOType,
+    sync_mode: SyncMode,
+) -> Box<dyn IOManager> {
     match io_type {
-        IOType::StandardFIO => Box::new(FileIO::new(file_name).unwrap()),
+        IOType::StandardFIO => Box::new(FileIO::new(file_name).unwrap().with_sync_mode(sync_mode)),
+        #[cfg(feature = "mmap")]
         IOType::MemoryMap => Box::new(MMapIO::new(file_name).unwrap()),
+        // 没有启用 mmap feature 时使用标准文件 IO
+        #[cfg(not(feature = "mmap"))]
+        IOType::MemoryMap => Box::new(FileIO::new(file_name).unwrap().with_sync_mode(sync_mode)),
+        IOType::BufferedFIO(buffer_size) => Box::new(BufWriterIO::new(
+            Box::new(FileIO::new(file_name).unwrap().with_sync_mode(sync_mode)),
+            buffer_size,
+        )),
+    }
+}
+
+// 打开文件并预先分配 len 字节的磁盘空间，之后只能通过 write_at 在逻辑末尾写入
+pub fn new_preallocated_io_manager(
+    file_name: PathBuf,
+    len: u64,
+    sync_mode: SyncMode,
+) -> Result<Box<dyn IOManager>> {
+    let io = FileIO::open_preallocated(file_name, len)?.with_sync_mode(sync_mode);
+    Ok(Box::new(io))
+}
+
+// retry_count 大于 0 时对暂时性的 IO 错误进行重试
+pub fn with_retry(io_manager: Box<dyn IOManager>, retry_count: usize) -> Box<dyn IOManager> {
+    match retry_count {
+        0 => io_manager,
+        _ => Box::new(RetryIO::new(io_manager, retry_count)),
+    }
+}
+
+// 以只读的方式打开已经存在的文件，不会创建新的文件，带写缓冲的 IO 退化为标准文件 IO
+pub fn new_read_only_io_manager(file_name: PathBuf, io_type: IOType) -> Result<Box<dyn IOManager>> {
+    match io_type {
+        #[cfg(feature = "mmap")]
+        IOType::MemoryMap => Ok(Box::new(MMapIO::open_read_only(file_name)?)),
+        _ => Ok(Box::new(FileIO::open_read_only(file_name)?)),
     }
 }
 
@@ -35,7 +140,10 @@ mod tests {
 
     use crate::error::Errors;
 
-    use super::*;
+    use super::{
+        testing::{FaultyIO, InMemoryIO},
+        *,
+    };
 
     fn test_write(io: Box<dyn IOManager>) {
         let res1 = io.write("key-a".as_bytes());
@@ -85,6 +193,74 @@ mod tests {
         assert!(res.is_ok());
     }
 
+    #[test]
+    fn test_file_io_read_after_append() {
+        let path = std::env::temp_dir().join("bitcask-rs-read-after-append.data");
+        let fio = new_io_manager(path.clone(), IOType::StandardFIO);
+
+        let res1 = fio.write(b"key-a");
+        assert!(res1.is_ok());
+
+        // 读取之后再写入，数据仍然需要追加到文件末尾
+        let mut buf1 = [0u8; 5];
+        let read_res1 = fio.read(&mut buf1, 0);
+        assert_eq!(5, read_res1.ok().unwrap());
+        assert_eq!(b"key-a", &buf1);
+
+        let res2 = fio.write(b"key-b");
+        assert!(res2.is_ok());
+
+        let mut buf2 = [0u8; 10];
+        let read_res2 = fio.read(&mut buf2, 0);
+        assert_eq!(10, read_res2.ok().unwrap());
+        assert_eq!(b"key-akey-b", &buf2);
+
+        std::mem::drop(fio);
+        let res = fs::remove_file(path);
+        assert!(res.is_ok());
+    }
+
+    fn test_read_exact(io: Box<dyn IOManager>) {
+        assert!(io.write(b"key-a").is_ok());
+        assert!(io.write(b"key-b").is_ok());
+
+        let mut buf1 = [0u8; 6];
+        assert!(io.read_exact(&mut buf1, 2).is_ok());
+        assert_eq!(b"y-akey", &buf1);
+
+        // 文件中剩余的数据不足以填满 buf
+        let mut buf2 = [0u8; 10];
+        assert_eq!(
+            Errors::ReadDataFileEOF,
+            io.read_exact(&mut buf2, 4).err().unwrap()
+        );
+        assert_eq!(
+            Errors::ReadDataFileEOF,
+            io.read_exact(&mut buf2, 10).err().unwrap()
+        );
+
+        // 空的 buf 不需要读取数据
+        assert!(io.read_exact(&mut [], 10).is_ok());
+    }
+
+    #[test]
+    fn test_file_io_read_exact() {
+        let path = "/tmp/b-read-exact.data";
+        let fio = new_io_manager(PathBuf::from(path), IOType::StandardFIO);
+        test_read_exact(fio);
+        let res = fs::remove_file(path);
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_mmap_read_exact() {
+        let path = "/tmp/mmap-read-exact.data";
+        let mmap_io = new_io_manager(PathBuf::from(path), IOType::MemoryMap);
+        test_read_exact(mmap_io);
+        let res = fs::remove_file(path);
+        assert!(res.is_ok());
+    }
+
     fn test_sync(io: Box<dyn IOManager>) {
         let res1 = io.write("key-a".as_bytes());
         assert!(res1.is_ok());
@@ -107,6 +283,22 @@ mod tests {
         assert!(res.is_ok());
     }
 
+    #[test]
+    fn test_file_io_sync_mode() {
+        let path = "/tmp/c-sync-mode.data";
+        for sync_mode in [SyncMode::None, SyncMode::DataOnly, SyncMode::Full] {
+            let fio =
+                new_io_manager_with_sync_mode(PathBuf::from(path), IOType::StandardFIO, sync_mode);
+            test_sync(fio);
+        }
+
+        // 不同的持久化方式写入的数据都能读取
+        let fio = new_io_manager(PathBuf::from(path), IOType::StandardFIO);
+        assert_eq!(fio.size(), 30);
+        let res = fs::remove_file(path);
+        assert!(res.is_ok());
+    }
+
     fn test_size(io: Box<dyn IOManager>) {
         let size1 = io.size();
         assert_eq!(size1, 0);
@@ -128,6 +320,214 @@ mod tests {
     }
 
     #[test]
+    fn test_in_memory_io() {
+        test_write(Box::new(InMemoryIO::new()));
+        test_read(Box::new(InMemoryIO::new()));
+        test_sync(Box::new(InMemoryIO::new()));
+        test_size(Box::new(InMemoryIO::new()));
+        test_read_exact(Box::new(InMemoryIO::new()));
+
+        // 读到末尾时只返回剩余的数据
+        let io = InMemoryIO::new();
+        let res1 = io.write(b"key-a");
+        assert!(res1.is_ok());
+        let mut buf = [0u8; 10];
+        assert_eq!(3, io.read(&mut buf, 2).unwrap());
+        assert_eq!(b"y-a", &buf[..3]);
+        assert_eq!(0, io.read(&mut buf, 10).unwrap());
+    }
+
+    #[test]
+    fn test_faulty_io() {
+        let io = FaultyIO::new(Box::new(InMemoryIO::new()))
+            .fail_read_at(2)
+            .fail_write_at(3)
+            .fail_sync_at(1);
+
+        assert!(io.write(b"key-a").is_ok());
+        assert!(io.write(b"key-b").is_ok());
+        assert_eq!(
+            Errors::FailedWriteToDataFile,
+            io.write(b"key-c").err().unwrap()
+        );
+        assert!(io.write(b"key-d").is_ok());
+        assert_eq!(15, io.size());
+
+        let mut buf = [0u8; 5];
+        assert!(io.read(&mut buf, 0).is_ok());
+        assert_eq!(
+            Errors::FailedReadFromDataFile,
+            io.read(&mut buf, 5).err().unwrap()
+        );
+        assert!(io.read(&mut buf, 5).is_ok());
+        assert_eq!(b"key-b", &buf);
+
+        assert_eq!(Errors::FailedSyncDataFile, io.sync().err().unwrap());
+        assert!(io.sync().is_ok());
+    }
+
+    #[test]
+    fn test_retry_io() {
+        test_write(Box::new(RetryIO::new(Box::new(InMemoryIO::new()), 3)));
+        test_read(Box::new(RetryIO::new(Box::new(InMemoryIO::new()), 3)));
+        test_read_exact(Box::new(RetryIO::new(Box::new(InMemoryIO::new()), 3)));
+
+        let transient = Errors::Io {
+            kind: std::io::ErrorKind::WouldBlock,
+            message: "resource temporarily unavailable".to_string(),
+        };
+
+        // 暂时性的错误重试之后成功
+        let io = RetryIO::new(
+            Box::new(
+                FaultyIO::new(Box::new(InMemoryIO::new()))
+                    .fail_write_at(1)
+                    .fail_read_at(1)
+                    .fail_with(transient.clone()),
+            ),
+            1,
+        );
+        assert_eq!(5, io.write(b"key-a").unwrap());
+        let mut buf = [0u8; 5];
+        assert_eq!(5, io.read(&mut buf, 0).unwrap());
+        assert_eq!(b"key-a", &buf);
+
+        // 不重试时直接返回错误
+        let io = RetryIO::new(
+            Box::new(
+                FaultyIO::new(Box::new(InMemoryIO::new()))
+                    .fail_write_at(1)
+                    .fail_with(transient.clone()),
+            ),
+            0,
+        );
+        assert_eq!(transient, io.write(b"key-a").err().unwrap());
+
+        // 其他的错误不会重试
+        let io = RetryIO::new(
+            Box::new(FaultyIO::new(Box::new(InMemoryIO::new())).fail_read_at(1)),
+            3,
+        );
+        assert_eq!(
+            Errors::FailedReadFromDataFile,
+            io.read(&mut buf, 0).err().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_buf_writer_io() {
+        test_write(Box::new(BufWriterIO::new(Box::new(InMemoryIO::new()), 8)));
+        test_read(Box::new(BufWriterIO::new(Box::new(InMemoryIO::new()), 8)));
+        test_sync(Box::new(BufWriterIO::new(Box::new(InMemoryIO::new()), 8)));
+        test_size(Box::new(BufWriterIO::new(Box::new(InMemoryIO::new()), 8)));
+        test_read_exact(Box::new(BufWriterIO::new(Box::new(InMemoryIO::new()), 8)));
+
+        let inner = InMemoryIO::new();
+        let io = BufWriterIO::new(Box::new(inner.clone()), 8);
+
+        // 缓冲未写满时数据只在缓冲中，读取时从缓冲中获取
+        assert!(io.write(b"key-a").is_ok());
+        assert_eq!(0, inner.size());
+        assert_eq!(5, io.size());
+        let mut buf1 = [0u8; 5];
+        assert_eq!(5, io.read(&mut buf1, 0).unwrap());
+        assert_eq!(b"key-a", &buf1);
+
+        // 缓冲放不下时先将缓冲写入文件
+        assert!(io.write(b"key-b").is_ok());
+        assert_eq!(5, inner.size());
+        assert_eq!(10, io.size());
+
+        // 读取的数据一部分在文件中，一部分在缓冲中
+        let mut buf2 = [0u8; 10];
+        assert_eq!(10, io.read(&mut buf2, 0).unwrap());
+        assert_eq!(b"key-akey-b", &buf2);
+        let mut buf3 = [0u8; 10];
+        assert_eq!(6, io.read(&mut buf3, 4).unwrap());
+        assert_eq!(b"akey-b", &buf3[..6]);
+        assert_eq!(0, io.read(&mut buf3, 10).unwrap());
+
+        // 超过缓冲大小的数据直接写入文件
+        assert!(io.write(b"key-c-long-value").is_ok());
+        assert_eq!(26, inner.size());
+        assert_eq!(26, io.size());
+
+        // sync 时将缓冲写入文件
+        assert!(io.write(b"key-d").is_ok());
+        assert_eq!(26, inner.size());
+        assert!(io.sync().is_ok());
+        assert_eq!(31, inner.size());
+
+        // 关闭时将缓冲中剩余的数据写入文件
+        assert!(io.write(b"key-e").is_ok());
+        std::mem::drop(io);
+        assert_eq!(36, inner.size());
+        let mut buf4 = [0u8; 5];
+        assert_eq!(5, inner.read(&mut buf4, 31).unwrap());
+        assert_eq!(b"key-e", &buf4);
+    }
+
+    #[test]
+    fn test_mmap_write() {
+        let path = "/tmp/mmap-write.data";
+        let mmap_io = new_io_manager(PathBuf::from(path), IOType::MemoryMap);
+        test_write(mmap_io);
+        let res = fs::remove_file(path);
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_mmap_sync() {
+        let path = "/tmp/mmap-sync.data";
+        let mmap_io = new_io_manager(PathBuf::from(path), IOType::MemoryMap);
+        test_sync(mmap_io);
+        let res = fs::remove_file(path);
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_mmap_size() {
+        let path = "/tmp/mmap-size.data";
+        let mmap_io = new_io_manager(PathBuf::from(path), IOType::MemoryMap);
+        test_size(mmap_io);
+        let res = fs::remove_file(path);
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn test_mmap_write_grow() {
+        let path = PathBuf::from("/tmp/mmap-grow.data");
+        let mmap_io = MMapIO::new(path.clone()).unwrap();
+
+        // 写入的数据超过映射区域的大小，需要扩容
+        let value = vec![1u8; 10 * 1024];
+        for _ in 0..3 {
+            let res = mmap_io.write(&value);
+            assert!(res.is_ok());
+        }
+        assert_eq!(mmap_io.size(), 30 * 1024);
+
+        let mut buf = [0u8; 10];
+        let read_res1 = mmap_io.read(&mut buf, 25 * 1024);
+        assert!(read_res1.is_ok());
+        assert_eq!(buf, [1u8; 10]);
+
+        // 未写入的部分不能被读取
+        let read_res2 = mmap_io.read(&mut buf, 30 * 1024);
+        assert_eq!(read_res2.err().unwrap(), Errors::ReadDataFileEOF);
+
+        // 关闭后文件大小和写入的数据一致
+        std::mem::drop(mmap_io);
+        let fio = FileIO::new(path.clone()).unwrap();
+        assert_eq!(fio.size(), 30 * 1024);
+
+        let remove_res = fs::remove_file(path.clone());
+        assert!(remove_res.is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
     fn test_mmap_read() {
         let path = PathBuf::from("/tmp/mmap-test.data");
 
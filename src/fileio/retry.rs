@@ -0,0 +1,86 @@
+use std::{io::ErrorKind, thread, time::Duration};
+
+use log::warn;
+
+use crate::error::{Errors, Result};
+
+#[cfg(feature = "mmap")]
+use super::mmap::MappedSlice;
+use super::IOManager;
+
+// 两次重试之间最长的等待时间
+const MAX_RETRY_BACKOFF: Duration = Duration::from_millis(64);
+
+// 对暂时性的 IO 错误进行重试的 IO 实现，其余的错误直接返回
+// 网络文件系统上的读写偶尔会被中断或者返回 EAGAIN，稍后重试通常可以成功
+pub struct RetryIO {
+    inner: Box<dyn IOManager>, // 实际读写文件的 IO 实现
+    retry_count: usize,        // 最多重试的次数
+}
+
+impl RetryIO {
+    pub fn new(inner: Box<dyn IOManager>, retry_count: usize) -> Self {
+        RetryIO { inner, retry_count }
+    }
+
+    // 执行 op，遇到暂时性的错误时等待一段时间之后重试，等待的时间每次翻倍
+    fn with_retry<T>(&self, mut op: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut backoff = Duration::from_millis(1);
+        let mut attempt = 0;
+        loop {
+            match op() {
+                Err(e) if is_transient(&e) && attempt < self.retry_count => {
+                    attempt += 1;
+                    warn!("transient io error: {}, retry {}", e, attempt);
+                    thread::sleep(backoff);
+                    backoff = (backoff * 2).min(MAX_RETRY_BACKOFF);
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+// 判断是否是可以重试的暂时性错误
+fn is_transient(e: &Errors) -> bool {
+    matches!(
+        e,
+        Errors::Io {
+            kind: ErrorKind::Interrupted | ErrorKind::WouldBlock | ErrorKind::TimedOut,
+            ..
+        }
+    )
+}
+
+impl IOManager for RetryIO {
+    fn read(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        self.with_retry(|| self.inner.read(buf, offset))
+    }
+
+    // 写入失败时没有写入任何数据，可以直接重试
+    fn write(&self, buf: &[u8]) -> Result<usize> {
+        self.with_retry(|| self.inner.write(buf))
+    }
+
+    fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        self.with_retry(|| self.inner.write_at(buf, offset))
+    }
+
+    fn truncate(&self, len: u64) -> Result<()> {
+        self.inner.truncate(len)
+    }
+
+    #[cfg(feature = "mmap")]
+    fn read_mapped(&self, offset: u64, len: usize) -> Result<Option<MappedSlice>> {
+        self.inner.read_mapped(offset, len)
+    }
+
+    // sync 失败之后文件系统可能已经丢弃了脏页，重试成功也不能保证数据已经持久化，不进行重试
+    fn sync(&self) -> Result<()> {
+        self.inner.sync()
+    }
+
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use parking_lot::{Mutex, RwLock};
+
+use crate::{
+    error::{Errors, Result},
+    option::IOType,
+};
+
+use super::{IOFactory, IOManager};
+
+// 基于内存的 IO 实现，数据保存在 Vec 中，用于测试
+#[derive(Default, Clone)]
+pub struct InMemoryIO {
+    data: Arc<RwLock<Vec<u8>>>,
+}
+
+impl InMemoryIO {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl IOManager for InMemoryIO {
+    fn read(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let read_guard = self.data.read();
+        // 和标准文件 IO 一致，读到末尾时只返回剩余的数据
+        let start = (offset as usize).min(read_guard.len());
+        let end = read_guard.len().min(start + buf.len());
+        let val = &read_guard[start..end];
+        buf[..val.len()].copy_from_slice(val);
+        Ok(val.len())
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize> {
+        let mut write_guard = self.data.write();
+        write_guard.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn size(&self) -> u64 {
+        let read_guard = self.data.read();
+        read_guard.len() as u64
+    }
+}
+
+// 所有的文件都保存在内存中的存储，同一个路径返回同一份数据，用于测试
+#[derive(Default, Clone)]
+pub struct InMemoryFactory {
+    files: Arc<Mutex<HashMap<PathBuf, InMemoryIO>>>,
+}
+
+impl InMemoryFactory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 获取所有文件的路径
+    pub fn file_names(&self) -> Vec<PathBuf> {
+        self.files.lock().keys().cloned().collect()
+    }
+}
+
+impl IOFactory for InMemoryFactory {
+    fn open(&self, file_name: PathBuf, _io_type: IOType) -> Box<dyn IOManager> {
+        Box::new(self.files.lock().entry(file_name).or_default().clone())
+    }
+
+    fn list(&self, dir_path: &Path) -> Result<Vec<String>> {
+        let files = self.files.lock();
+        let file_names = files
+            .keys()
+            .filter(|file_name| file_name.parent() == Some(dir_path))
+            .filter_map(|file_name| file_name.file_name())
+            .map(|file_name| file_name.to_string_lossy().into_owned())
+            .collect();
+        Ok(file_names)
+    }
+
+    fn is_file(&self, file_name: &Path) -> bool {
+        self.files.lock().contains_key(file_name)
+    }
+
+    fn rename(&self, src: &Path, dest: &Path) -> Result<()> {
+        let mut files = self.files.lock();
+        match files.remove(src) {
+            Some(file) => {
+                files.insert(dest.to_path_buf(), file);
+                Ok(())
+            }
+            None => Err(Errors::DataFileNotFound),
+        }
+    }
+
+    fn remove(&self, file_name: &Path) -> Result<()> {
+        self.files.lock().remove(file_name);
+        Ok(())
+    }
+}
+
+// 可以注入错误的 IO 实现，在第 N 次 read/write/sync 时返回错误，其余操作交给内部的 IO 实现
+pub struct FaultyIO {
+    inner: Box<dyn IOManager>,
+    fail_read_at: Option<usize>,  // 第几次 read 失败，从 1 开始计数
+    fail_write_at: Option<usize>, // 第几次 write 失败，从 1 开始计数
+    fail_sync_at: Option<usize>,  // 第几次 sync 失败，从 1 开始计数
+    error: Option<Errors>,        // 失败时返回的错误，为 None 时返回对应操作的默认错误
+    read_count: AtomicUsize,
+    write_count: AtomicUsize,
+    sync_count: AtomicUsize,
+}
+
+impl FaultyIO {
+    pub fn new(inner: Box<dyn IOManager>) -> Self {
+        Self {
+            inner,
+            fail_read_at: None,
+            fail_write_at: None,
+            fail_sync_at: None,
+            error: None,
+            read_count: AtomicUsize::new(0),
+            write_count: AtomicUsize::new(0),
+            sync_count: AtomicUsize::new(0),
+        }
+    }
+
+    // 第 n 次 read 时返回错误
+    pub fn fail_read_at(mut self, n: usize) -> Self {
+        self.fail_read_at = Some(n);
+        self
+    }
+
+    // 第 n 次 write 时返回错误
+    pub fn fail_write_at(mut self, n: usize) -> Self {
+        self.fail_write_at = Some(n);
+        self
+    }
+
+    // 第 n 次 sync 时返回错误
+    pub fn fail_sync_at(mut self, n: usize) -> Self {
+        self.fail_sync_at = Some(n);
+        self
+    }
+
+    // 失败时返回指定的错误
+    pub fn fail_with(mut self, error: Errors) -> Self {
+        self.error = Some(error);
+        self
+    }
+}
+
+// 累加调用次数，判断本次调用是否需要失败
+fn should_fail(count: &AtomicUsize, fail_at: Option<usize>) -> bool {
+    let n = count.fetch_add(1, Ordering::SeqCst) + 1;
+    fail_at == Some(n)
+}
+
+impl IOManager for FaultyIO {
+    fn read(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        if should_fail(&self.read_count, self.fail_read_at) {
+            return Err(self.error.clone().unwrap_or(Errors::FailedReadFromDataFile));
+        }
+        self.inner.read(buf, offset)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize> {
+        if should_fail(&self.write_count, self.fail_write_at) {
+            return Err(self.error.clone().unwrap_or(Errors::FailedWriteToDataFile));
+        }
+        self.inner.write(buf)
+    }
+
+    fn sync(&self) -> Result<()> {
+        if should_fail(&self.sync_count, self.fail_sync_at) {
+            return Err(self.error.clone().unwrap_or(Errors::FailedSyncDataFile));
+        }
+        self.inner.sync()
+    }
+
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{data::LogPosition, option::IteratorOptions};
+use bytes::Bytes;
+use parking_lot::RwLock;
+use std::{collections::BTreeMap, sync::Arc};
+
+use super::{iterator_range, seek_position, sort_by_comparator, Index, IndexIterator};
+
+// BTree 索引，主要封装了标准库中的 BTreeMap 结构
+pub struct BTree<T>
+where
+    T: LogPosition + Send + Sync,
+{
+    tree: Arc<RwLock<BTreeMap<Vec<u8>, T>>>,
+}
+
+impl<T> BTree<T>
+where
+    T: LogPosition + Send + Sync,
+{
+    pub fn new() -> Self {
+        BTree {
+            tree: Arc::new(RwLock::new(BTreeMap::new())),
+        }
+    }
+}
+
+impl<T> Index<T> for BTree<T>
+where
+    T: LogPosition + Send + Sync + Copy + 'static,
+{
+    fn put(&self, key: Vec<u8>, pos: T) -> Option<T> {
+        let mut write_guard = self.tree.write();
+        write_guard.insert(key, pos)
+    }
+
+    fn get(&self, key: Vec<u8>) -> Option<T> {
+        let read_guard = self.tree.read();
+        read_guard.get(&key).copied()
+    }
+
+    fn delete(&self, key: Vec<u8>) -> Option<T> {
+        let mut write_guard = self.tree.write();
+        write_guard.remove(&key)
+    }
+
+    fn clear(&self) {
+        let mut write_guard = self.tree.write();
+        write_guard.clear();
+    }
+
+    fn list_keys(&self) -> crate::error::Result<Vec<Bytes>> {
+        let read_guard = self.tree.read();
+        let mut keys = Vec::with_capacity(read_guard.len());
+        for (k, _) in read_guard.iter() {
+            keys.push(Bytes::copy_from_slice(k));
+        }
+        Ok(keys)
+    }
+
+    fn len(&self) -> usize {
+        self.tree.read().len()
+    }
+
+    fn iterator(&self, options: IteratorOptions) -> Box<dyn IndexIterator<T>> {
+        let read_guard = self.tree.read();
+        let mut items = Vec::new();
+        if let Some(comparator) = &options.comparator {
+            // 使用自定义比较器时，取出所有的数据按照比较器排序
+            for (key, value) in read_guard.iter() {
+                items.push((key.clone(), *value));
+            }
+            sort_by_comparator(&mut items, &options, comparator);
+        } else {
+            // 将 BTree 中配置的 key 范围内的数据存储到数组中
+            if let Some(range) = iterator_range(&options) {
+                for (key, value) in read_guard.range(range) {
+                    items.push((key.clone(), *value));
+                }
+            }
+            if options.reverse {
+                items.reverse();
+            }
+        }
+        Box::new(BTreeIterator {
+            items,
+            curr_index: 0,
+            options,
+        })
+    }
+}
+
+// BTree 索引迭代器
+pub struct BTreeIterator<T>
+where
+    T: LogPosition + Send + Sync,
+{
+    items: Vec<(Vec<u8>, T)>, // 存储 key+索引
+    curr_index: usize,        // 当前遍历的位置下标
+    options: IteratorOptions, // 配置项
+}
+
+impl<T> IndexIterator<T> for BTreeIterator<T>
+where
+    T: LogPosition + Send + Sync,
+{
+    fn rewind(&mut self) {
+        self.curr_index = 0;
+    }
+
+    fn seek(&mut self, key: Vec<u8>) {
+        self.curr_index = seek_position(&self.items, &key, &self.options);
+    }
+
+    fn next(&mut self) -> Option<(&Vec<u8>, &T)> {
+        if self.curr_index >= self.items.len() {
+            return None;
+        }
+        while let Some(item) = self.items.get(self.curr_index) {
+            self.curr_index += 1;
+            if self.options.matches_prefix(&item.0) {
+                return Some((&item.0, &item.1));
+            }
+        }
+        None
+    }
+
+    fn seek_to_last(&mut self) {
+        self.curr_index = self.items.len();
+    }
+
+    fn prev(&mut self) -> Option<(&Vec<u8>, &T)> {
+        while self.curr_index > 0 {
+            self.curr_index -= 1;
+            let item = &self.items[self.curr_index];
+            if self.options.matches_prefix(&item.0) {
+                return Some((&item.0, &item.1));
+            }
+        }
+        None
+    }
+}
@@ -1,14 +1,25 @@
+pub mod btree;
+pub mod prefix;
 pub mod skiplist;
 
-use std::path::PathBuf;
+use std::{
+    cmp::Ordering,
+    ops::Bound::{self, Excluded, Included, Unbounded},
+    path::PathBuf,
+};
 
-use bytes::Bytes;
+use btree::BTree;
+use bytes::{Buf, Bytes, BytesMut};
+use prost::{decode_length_delimiter, encode_length_delimiter};
 use skiplist::SkipList;
 
 use crate::{
-    data::{log_record::LogRecordPos, LogPosition},
-    error::Result,
-    option::{IndexType, IteratorOptions},
+    data::{
+        log_record::{decode_log_record_pos, LogRecordPos},
+        LogPosition,
+    },
+    error::{Errors, Result},
+    option::{Comparator, IndexType, IteratorOptions},
 };
 
 // Index 抽象索引接口，
@@ -25,16 +36,37 @@ where
     // 根据 key 删除对应的索引位置信息
     fn delete(&self, key: Vec<u8>) -> Option<T>;
 
+    // 删除所有的索引位置信息
+    fn clear(&self);
+
     // 获取索引存储的所有的 key
     fn list_keys(&self) -> Result<Vec<Bytes>>;
 
+    // 索引中 key 的数量，不会复制任何 key
+    fn len(&self) -> usize;
+
     // 返回索引迭代器
     fn iterator(&self, options: IteratorOptions) -> Box<dyn IndexIterator<T>>;
+
+    // 按照 key 的顺序编码索引中所有的数据，每条数据依次存储变长的 key 长度、key、位置信息长度以及位置信息
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = BytesMut::new();
+        let mut iter = self.iterator(IteratorOptions::default());
+        while let Some((key, pos)) = iter.next() {
+            let enc_pos = pos.encode();
+            encode_length_delimiter(key.len(), &mut buf).expect("encode key len error");
+            buf.extend_from_slice(key);
+            encode_length_delimiter(enc_pos.len(), &mut buf).expect("encode pos len error");
+            buf.extend_from_slice(&enc_pos);
+        }
+        buf.to_vec()
+    }
 }
 
 pub fn new_indexer<T: LogPosition>(index_type: IndexType, dir_path: PathBuf) -> Box<dyn Index<T>>
 where
     skiplist::SkipList<LogRecordPos>: Index<T>,
+    btree::BTree<LogRecordPos>: Index<T>,
 {
     match index_type {
         IndexType::SkipList => {
@@ -42,6 +74,125 @@ where
             let index = Box::new(skl);
             index
         }
+        IndexType::BTree => Box::new(BTree::<LogRecordPos>::new()),
+    }
+}
+
+// 解码 Index::serialize 编码的索引数据，数据不完整时返回错误
+pub(crate) fn decode_index_entries(mut buf: &[u8]) -> Result<Vec<(Vec<u8>, LogRecordPos)>> {
+    let mut entries = Vec::new();
+    while buf.has_remaining() {
+        let key = read_delimited(&mut buf)?;
+        let pos = decode_log_record_pos(read_delimited(&mut buf)?)?;
+        entries.push((key, pos));
+    }
+    Ok(entries)
+}
+
+// 读取一段以变长长度开头的数据，长度超过剩余的数据时返回错误
+pub(crate) fn read_delimited(buf: &mut &[u8]) -> Result<Vec<u8>> {
+    let len = decode_length_delimiter(&mut *buf).map_err(|_| Errors::IndexSnapshotCorrupted)?;
+    if len > buf.remaining() {
+        return Err(Errors::IndexSnapshotCorrupted);
+    }
+    let data = buf[..len].to_vec();
+    buf.advance(len);
+    Ok(data)
+}
+
+// 遍历的 key 范围，分别为下界和上界
+pub(crate) type KeyRange = (Bound<Vec<u8>>, Bound<Vec<u8>>);
+
+// 根据迭代器配置项计算遍历的 key 范围，返回 None 表示范围为空
+// 正向遍历的范围为 [start, end)，反向遍历的范围为 (end, start]
+pub(crate) fn iterator_range(options: &IteratorOptions) -> Option<KeyRange> {
+    let (lower, upper) = match options.reverse {
+        false => (
+            options.start.clone().map_or(Unbounded, Included),
+            options.end.clone().map_or(Unbounded, Excluded),
+        ),
+        true => (
+            options.end.clone().map_or(Unbounded, Excluded),
+            options.start.clone().map_or(Unbounded, Included),
+        ),
+    };
+    if is_empty_range(&lower, &upper) {
+        return None;
+    }
+    Some((lower, upper))
+}
+
+// 计算大于所有以 prefix 开头的 key 的最小 key，prefix 全部为 0xff 时没有上界
+pub(crate) fn prefix_upper_bound(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut upper = prefix.to_vec();
+    while let Some(last) = upper.pop() {
+        if last < u8::MAX {
+            upper.push(last + 1);
+            return Some(upper);
+        }
+    }
+    None
+}
+
+// 判断上下界之间的范围是否为空，上下界颠倒时 BTreeMap::range 会 panic
+pub(crate) fn is_empty_range(lower: &Bound<Vec<u8>>, upper: &Bound<Vec<u8>>) -> bool {
+    match (lower, upper) {
+        (Included(l), Included(u)) => l > u,
+        (Included(l) | Excluded(l), Included(u) | Excluded(u)) => l >= u,
+        _ => false,
+    }
+}
+
+// 使用自定义比较器时，过滤掉不在遍历范围内的数据，并按照比较器的顺序排序
+pub(crate) fn sort_by_comparator<T>(
+    items: &mut Vec<(Vec<u8>, T)>,
+    options: &IteratorOptions,
+    comparator: &Comparator,
+) {
+    // 正向遍历的范围为 [start, end)，反向遍历的范围为 (end, start]
+    let in_range = |key: &[u8]| {
+        let after_start = options.start.as_ref().is_none_or(|start| {
+            let ord = comparator(key, start);
+            match options.reverse {
+                false => ord != Ordering::Less,
+                true => ord != Ordering::Greater,
+            }
+        });
+        let before_end = options.end.as_ref().is_none_or(|end| {
+            let ord = comparator(key, end);
+            match options.reverse {
+                false => ord == Ordering::Less,
+                true => ord == Ordering::Greater,
+            }
+        });
+        after_start && before_end
+    };
+    items.retain(|(key, _)| in_range(key));
+    items.sort_by(|(x, _), (y, _)| comparator(x, y));
+    if options.reverse {
+        items.reverse();
+    }
+}
+
+// 在有序的数据中查找第一个大于（反向遍历时为小于）等于 key 的位置
+pub(crate) fn seek_position<T>(
+    items: &[(Vec<u8>, T)],
+    key: &[u8],
+    options: &IteratorOptions,
+) -> usize {
+    let result = items.binary_search_by(|(x, _)| {
+        let ord = match &options.comparator {
+            Some(comparator) => comparator(x, key),
+            None => x.as_slice().cmp(key),
+        };
+        match options.reverse {
+            false => ord,
+            true => ord.reverse(),
+        }
+    });
+    match result {
+        Ok(equal_val) => equal_val,
+        Err(insert_val) => insert_val,
     }
 }
 
@@ -57,10 +208,18 @@ where
 
     // Next 跳转到下一个 key，返回 None 则说明迭代完毕
     fn next(&mut self) -> Option<(&Vec<u8>, &T)>;
+
+    // SeekToLast 跳转到最后一个数据之后，之后调用 prev 会返回最后一个数据
+    fn seek_to_last(&mut self);
+
+    // Prev 向前移动并返回前一个 key，在 next 之后调用会返回同一个 key，返回 None 则说明已经到了起点
+    fn prev(&mut self) -> Option<(&Vec<u8>, &T)>;
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use skiplist::SkipList;
 
     use crate::data::log_record::LogRecordPos;
@@ -117,6 +276,7 @@ mod tests {
         let v = res5.unwrap();
         assert_eq!(v.file_id, 1123);
         assert_eq!(v.offset, 1232);
+        assert_eq!(4, index.len());
     }
 
     #[test]
@@ -126,6 +286,13 @@ mod tests {
         test_put(index);
     }
 
+    #[test]
+    fn test_btree_put() {
+        let bt = BTree::new();
+        let index = Box::new(bt);
+        test_put(index);
+    }
+
     fn test_get(index: Box<dyn Index<LogRecordPos>>) {
         let v1 = index.get(b"not exists".to_vec());
         assert!(v1.is_none());
@@ -165,6 +332,13 @@ mod tests {
         test_get(index);
     }
 
+    #[test]
+    fn test_btree_get() {
+        let bt = BTree::new();
+        let index = Box::new(bt);
+        test_get(index);
+    }
+
     fn test_delete(index: Box<dyn Index<LogRecordPos>>) {
         let r1 = index.delete(b"not exists".to_vec());
         assert!(r1.is_none());
@@ -196,6 +370,43 @@ mod tests {
         test_delete(index);
     }
 
+    #[test]
+    fn test_btree_delete() {
+        let bt = BTree::new();
+        let index = Box::new(bt);
+        test_delete(index);
+    }
+
+    fn test_clear(index: Box<dyn Index<LogRecordPos>>) {
+        for key in ["aacd", "bbae", "ddee"] {
+            let res = index.put(
+                key.as_bytes().to_vec(),
+                LogRecordPos {
+                    file_id: 1123,
+                    offset: 1232,
+                    size: 11,
+                },
+            );
+            assert!(res.is_none());
+        }
+
+        index.clear();
+        assert!(index.get(b"aacd".to_vec()).is_none());
+        assert!(index.list_keys().unwrap().is_empty());
+        let mut iter = index.iterator(IteratorOptions::default());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_skl_clear() {
+        test_clear(Box::new(SkipList::new()));
+    }
+
+    #[test]
+    fn test_btree_clear() {
+        test_clear(Box::new(BTree::new()));
+    }
+
     fn test_keys(index: Box<dyn Index<LogRecordPos>>) {
         let keys1 = index.list_keys();
         assert_eq!(keys1.ok().unwrap().len(), 0);
@@ -248,6 +459,13 @@ mod tests {
         test_keys(index);
     }
 
+    #[test]
+    fn test_btree_list_keys() {
+        let bt = BTree::new();
+        let index = Box::new(bt);
+        test_keys(index);
+    }
+
     fn test_iterator(index: Box<dyn Index<LogRecordPos>>) {
         let res1 = index.put(
             "aacd".as_bytes().to_vec(),
@@ -295,10 +513,453 @@ mod tests {
         }
     }
 
+    fn test_iterator_range(index: Box<dyn Index<LogRecordPos>>) {
+        for key in ["aa", "bb", "cc", "dd", "ee"] {
+            let res = index.put(
+                key.as_bytes().to_vec(),
+                LogRecordPos {
+                    file_id: 1123,
+                    offset: 1232,
+                    size: 11,
+                },
+            );
+            assert!(res.is_none());
+        }
+
+        let collect = |opts: IteratorOptions| {
+            let mut iter = index.iterator(opts);
+            let mut keys = Vec::new();
+            while let Some((key, _)) = iter.next() {
+                keys.push(String::from_utf8(key.clone()).unwrap());
+            }
+            keys
+        };
+
+        // 正向遍历，包含 start，不包含 end
+        let mut opts1 = IteratorOptions::default();
+        opts1.start = Some(b"bb".to_vec());
+        opts1.end = Some(b"dd".to_vec());
+        assert_eq!(collect(opts1), vec!["bb", "cc"]);
+
+        // start 和 end 不是已有的 key
+        let mut opts2 = IteratorOptions::default();
+        opts2.start = Some(b"b".to_vec());
+        opts2.end = Some(b"cz".to_vec());
+        assert_eq!(collect(opts2), vec!["bb", "cc"]);
+
+        // 只设置 start 或者 end
+        let mut opts3 = IteratorOptions::default();
+        opts3.start = Some(b"dd".to_vec());
+        assert_eq!(collect(opts3), vec!["dd", "ee"]);
+        let mut opts4 = IteratorOptions::default();
+        opts4.end = Some(b"bb".to_vec());
+        assert_eq!(collect(opts4), vec!["aa"]);
+
+        // 反向遍历，start 为上界（包含），end 为下界（不包含）
+        let mut opts5 = IteratorOptions::default();
+        opts5.reverse = true;
+        opts5.start = Some(b"dd".to_vec());
+        opts5.end = Some(b"bb".to_vec());
+        assert_eq!(collect(opts5), vec!["dd", "cc"]);
+
+        // 范围为空
+        let mut opts6 = IteratorOptions::default();
+        opts6.start = Some(b"dd".to_vec());
+        opts6.end = Some(b"bb".to_vec());
+        assert!(collect(opts6).is_empty());
+        let mut opts7 = IteratorOptions::default();
+        opts7.start = Some(b"cc".to_vec());
+        opts7.end = Some(b"cc".to_vec());
+        assert!(collect(opts7).is_empty());
+
+        // 范围内 seek
+        let mut opts8 = IteratorOptions::default();
+        opts8.start = Some(b"bb".to_vec());
+        opts8.end = Some(b"dd".to_vec());
+        let mut iter = index.iterator(opts8);
+        iter.seek(b"a".to_vec());
+        assert_eq!(iter.next().unwrap().0, &b"bb".to_vec());
+        iter.seek(b"cc".to_vec());
+        assert_eq!(iter.next().unwrap().0, &b"cc".to_vec());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_skl_iterator_range() {
+        let skl = SkipList::new();
+        let index = Box::new(skl);
+        test_iterator_range(index);
+    }
+
+    #[test]
+    fn test_btree_iterator_range() {
+        let bt = BTree::new();
+        let index = Box::new(bt);
+        test_iterator_range(index);
+    }
+
+    fn test_iterator_comparator(index: Box<dyn Index<LogRecordPos>>) {
+        // 有符号整数按照字节序比较时，负数会排在最后面
+        for v in [1i64, -1, 0, 2, -2] {
+            let res = index.put(
+                v.to_be_bytes().to_vec(),
+                LogRecordPos {
+                    file_id: 1123,
+                    offset: 1232,
+                    size: 11,
+                },
+            );
+            assert!(res.is_none());
+        }
+
+        let comparator: Comparator = Arc::new(|a: &[u8], b: &[u8]| {
+            let x = i64::from_be_bytes(a.try_into().unwrap());
+            let y = i64::from_be_bytes(b.try_into().unwrap());
+            x.cmp(&y)
+        });
+        let collect = |iter: &mut Box<dyn IndexIterator<LogRecordPos>>| {
+            let mut values = Vec::new();
+            while let Some((key, _)) = iter.next() {
+                values.push(i64::from_be_bytes(key.as_slice().try_into().unwrap()));
+            }
+            values
+        };
+
+        // 默认按照字节序
+        let mut iter1 = index.iterator(IteratorOptions::default());
+        assert_eq!(vec![0, 1, 2, -2, -1], collect(&mut iter1));
+
+        // 按照数值大小遍历
+        let mut opts2 = IteratorOptions::default();
+        opts2.comparator = Some(comparator.clone());
+        let mut iter2 = index.iterator(opts2);
+        assert_eq!(vec![-2, -1, 0, 1, 2], collect(&mut iter2));
+        iter2.seek((-1i64).to_be_bytes().to_vec());
+        assert_eq!(vec![-1, 0, 1, 2], collect(&mut iter2));
+        iter2.rewind();
+        assert_eq!(vec![-2, -1, 0, 1, 2], collect(&mut iter2));
+
+        // 反向遍历
+        let mut opts3 = IteratorOptions::default();
+        opts3.comparator = Some(comparator.clone());
+        opts3.reverse = true;
+        let mut iter3 = index.iterator(opts3);
+        assert_eq!(vec![2, 1, 0, -1, -2], collect(&mut iter3));
+        iter3.seek((-1i64).to_be_bytes().to_vec());
+        assert_eq!(vec![-1, -2], collect(&mut iter3));
+
+        // 按照比较器确定遍历的范围
+        let mut opts4 = IteratorOptions::default();
+        opts4.comparator = Some(comparator.clone());
+        opts4.start = Some((-1i64).to_be_bytes().to_vec());
+        opts4.end = Some(2i64.to_be_bytes().to_vec());
+        let mut iter4 = index.iterator(opts4);
+        assert_eq!(vec![-1, 0, 1], collect(&mut iter4));
+    }
+
+    #[test]
+    fn test_skl_iterator_comparator() {
+        let skl = SkipList::new();
+        let index = Box::new(skl);
+        test_iterator_comparator(index);
+    }
+
+    #[test]
+    fn test_btree_iterator_comparator() {
+        let bt = BTree::new();
+        let index = Box::new(bt);
+        test_iterator_comparator(index);
+    }
+
+    fn test_serialize(index: Box<dyn Index<LogRecordPos>>) {
+        // 空的索引
+        let skl1 = SkipList::deserialize(&index.serialize()).unwrap();
+        assert!(skl1.list_keys().unwrap().is_empty());
+
+        for (i, key) in ["aa", "bb", "", "cc"].iter().enumerate() {
+            index.put(
+                key.as_bytes().to_vec(),
+                LogRecordPos {
+                    file_id: i as u32,
+                    offset: 1232 + i as u64,
+                    size: 11,
+                },
+            );
+        }
+        let bytes = index.serialize();
+        let skl2 = SkipList::deserialize(&bytes).unwrap();
+        assert_eq!(index.list_keys().unwrap(), skl2.list_keys().unwrap());
+        for key in index.list_keys().unwrap() {
+            let pos1 = index.get(key.to_vec()).unwrap();
+            let pos2 = skl2.get(key.to_vec()).unwrap();
+            assert_eq!(
+                (pos1.file_id, pos1.offset, pos1.size),
+                (pos2.file_id, pos2.offset, pos2.size)
+            );
+        }
+        assert_eq!(bytes, skl2.serialize());
+
+        // 数据不完整
+        let res = SkipList::deserialize(&bytes[..bytes.len() - 1]);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_skl_serialize() {
+        test_serialize(Box::new(SkipList::new()));
+    }
+
+    #[test]
+    fn test_btree_serialize() {
+        test_serialize(Box::new(BTree::new()));
+    }
+
+    #[test]
+    fn test_skl_concurrent_put_get() {
+        // 覆盖已有的 key 时，并发的读取始终可以找到这个 key
+        let skl = SkipList::new();
+        let new_pos = |offset: u64| LogRecordPos {
+            file_id: 1,
+            offset,
+            size: 11,
+        };
+        skl.put(b"key".to_vec(), new_pos(0));
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 1..100000 {
+                    let old_pos = skl.put(b"key".to_vec(), new_pos(i));
+                    assert_eq!(i - 1, old_pos.unwrap().offset);
+                }
+            });
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..100000 {
+                        assert!(skl.get(b"key".to_vec()).is_some());
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_prefix_upper_bound() {
+        assert_eq!(Some(b"ac".to_vec()), prefix_upper_bound(b"ab"));
+        assert_eq!(Some(b"b".to_vec()), prefix_upper_bound(b"a\xff"));
+        assert_eq!(Some(vec![1]), prefix_upper_bound(&[0, 255, 255]));
+        assert_eq!(None, prefix_upper_bound(&[255, 255]));
+        assert_eq!(None, prefix_upper_bound(b""));
+    }
+
+    #[test]
+    fn test_skl_iterator_same_as_btree() {
+        // 跳表的迭代器按需查找，遍历结果需要和 BTree 保持一致
+        let skl = SkipList::new();
+        let bt = BTree::new();
+        for key in ["a", "ab", "abc", "abd", "b", "ba", "bb", "c", "ca"] {
+            let pos = LogRecordPos {
+                file_id: 1,
+                offset: key.len() as u64,
+                size: 11,
+            };
+            skl.put(key.as_bytes().to_vec(), pos);
+            bt.put(key.as_bytes().to_vec(), pos);
+        }
+
+        let collect = |iter: &mut Box<dyn IndexIterator<LogRecordPos>>| {
+            let mut keys = Vec::new();
+            while let Some((key, _)) = iter.next() {
+                keys.push(key.clone());
+            }
+            keys
+        };
+        let collect_prev = |iter: &mut Box<dyn IndexIterator<LogRecordPos>>| {
+            let mut keys = Vec::new();
+            while let Some((key, _)) = iter.prev() {
+                keys.push(key.clone());
+            }
+            keys
+        };
+
+        for reverse in [false, true] {
+            for prefix in ["", "a", "ab", "b", "bz", "c", "d"] {
+                for (start, end) in [(None, None), (Some("ab"), Some("c")), (Some("b"), None)] {
+                    let new_opts = || {
+                        let mut opts = IteratorOptions::default();
+                        opts.reverse = reverse;
+                        opts.prefix = prefix.as_bytes().to_vec();
+                        opts.start = start.map(|s: &str| s.as_bytes().to_vec());
+                        opts.end = end.map(|s: &str| s.as_bytes().to_vec());
+                        opts
+                    };
+                    let mut skl_iter = skl.iterator(new_opts());
+                    let mut bt_iter = bt.iterator(new_opts());
+                    assert_eq!(collect(&mut bt_iter), collect(&mut skl_iter));
+
+                    // seek 之后继续遍历
+                    for seek_key in ["", "ab", "abz", "bb", "z"] {
+                        skl_iter.seek(seek_key.as_bytes().to_vec());
+                        bt_iter.seek(seek_key.as_bytes().to_vec());
+                        assert_eq!(collect(&mut bt_iter), collect(&mut skl_iter));
+                    }
+
+                    // rewind 之后重新遍历
+                    skl_iter.rewind();
+                    bt_iter.rewind();
+                    assert_eq!(collect(&mut bt_iter), collect(&mut skl_iter));
+
+                    // seek_to_last 之后向前遍历
+                    skl_iter.seek_to_last();
+                    bt_iter.seek_to_last();
+                    assert_eq!(collect_prev(&mut bt_iter), collect_prev(&mut skl_iter));
+
+                    // seek 之后向前遍历
+                    for seek_key in ["", "ab", "abz", "bb", "z"] {
+                        skl_iter.seek(seek_key.as_bytes().to_vec());
+                        bt_iter.seek(seek_key.as_bytes().to_vec());
+                        assert_eq!(
+                            bt_iter.next().map(|(key, _)| key.clone()),
+                            skl_iter.next().map(|(key, _)| key.clone())
+                        );
+                        assert_eq!(collect_prev(&mut bt_iter), collect_prev(&mut skl_iter));
+                    }
+                }
+            }
+        }
+    }
+
+    fn test_iterator_prev(index: Box<dyn Index<LogRecordPos>>) {
+        for key in ["a", "b", "c", "d"] {
+            index.put(
+                key.as_bytes().to_vec(),
+                LogRecordPos {
+                    file_id: 1,
+                    offset: 10,
+                    size: 11,
+                },
+            );
+        }
+
+        // next 之后调用 prev 返回同一个数据
+        let mut iter1 = index.iterator(IteratorOptions::default());
+        assert_eq!(iter1.next().unwrap().0, &b"a".to_vec());
+        assert_eq!(iter1.next().unwrap().0, &b"b".to_vec());
+        assert_eq!(iter1.prev().unwrap().0, &b"b".to_vec());
+        assert_eq!(iter1.prev().unwrap().0, &b"a".to_vec());
+        assert!(iter1.prev().is_none());
+        assert_eq!(iter1.next().unwrap().0, &b"a".to_vec());
+
+        // seek_to_last 之后调用 prev 按照降序遍历
+        iter1.seek_to_last();
+        assert!(iter1.next().is_none());
+        let mut keys = Vec::new();
+        while let Some((key, _)) = iter1.prev() {
+            keys.push(String::from_utf8(key.clone()).unwrap());
+        }
+        assert_eq!(keys, vec!["d", "c", "b", "a"]);
+
+        // 反向迭代器的 prev 按照升序遍历
+        let mut iter_opts = IteratorOptions::default();
+        iter_opts.reverse = true;
+        let mut iter2 = index.iterator(iter_opts);
+        iter2.seek_to_last();
+        assert_eq!(iter2.prev().unwrap().0, &b"a".to_vec());
+        assert_eq!(iter2.prev().unwrap().0, &b"b".to_vec());
+        assert_eq!(iter2.next().unwrap().0, &b"b".to_vec());
+        assert_eq!(iter2.next().unwrap().0, &b"a".to_vec());
+    }
+
+    #[test]
+    fn test_skl_iterator_prev() {
+        test_iterator_prev(Box::new(SkipList::new()));
+    }
+
+    #[test]
+    fn test_btree_iterator_prev() {
+        test_iterator_prev(Box::new(BTree::new()));
+    }
+
+    fn test_iterator_reverse_seek(index: Box<dyn Index<LogRecordPos>>) {
+        for key in ["aa", "cc", "ee", "gg"] {
+            index.put(
+                key.as_bytes().to_vec(),
+                LogRecordPos {
+                    file_id: 1,
+                    offset: 10,
+                    size: 11,
+                },
+            );
+        }
+        let collect = |iter: &mut Box<dyn IndexIterator<LogRecordPos>>| {
+            let mut keys = Vec::new();
+            while let Some((key, _)) = iter.next() {
+                keys.push(String::from_utf8(key.clone()).unwrap());
+            }
+            keys
+        };
+
+        // 反向遍历时 seek 到第一个小于等于目标的 key
+        let mut iter_opts = IteratorOptions::default();
+        iter_opts.reverse = true;
+        let mut iter1 = index.iterator(iter_opts);
+        iter1.seek(b"dd".to_vec());
+        assert_eq!(collect(&mut iter1), vec!["cc", "aa"]);
+        iter1.seek(b"cc".to_vec());
+        assert_eq!(collect(&mut iter1), vec!["cc", "aa"]);
+        iter1.seek(b"zz".to_vec());
+        assert_eq!(collect(&mut iter1), vec!["gg", "ee", "cc", "aa"]);
+        iter1.seek(b"a".to_vec());
+        assert!(collect(&mut iter1).is_empty());
+
+        // 使用自定义比较器时同样如此
+        let mut iter_opts2 = IteratorOptions::default();
+        iter_opts2.reverse = true;
+        iter_opts2.comparator = Some(Arc::new(|a: &[u8], b: &[u8]| a.cmp(b)));
+        let mut iter2 = index.iterator(iter_opts2);
+        iter2.seek(b"dd".to_vec());
+        assert_eq!(collect(&mut iter2), vec!["cc", "aa"]);
+        iter2.seek(b"ff".to_vec());
+        assert_eq!(collect(&mut iter2), vec!["ee", "cc", "aa"]);
+        iter2.seek(b"a".to_vec());
+        assert!(collect(&mut iter2).is_empty());
+    }
+
+    #[test]
+    fn test_skl_iterator_reverse_seek() {
+        test_iterator_reverse_seek(Box::new(SkipList::new()));
+    }
+
+    #[test]
+    fn test_btree_iterator_reverse_seek() {
+        test_iterator_reverse_seek(Box::new(BTree::new()));
+    }
+
+    #[test]
+    fn test_seek_position_reverse() {
+        // 反向遍历时数据按照降序排列，插入位置即为第一个小于目标的 key
+        let items = vec![
+            (b"ee".to_vec(), ()),
+            (b"cc".to_vec(), ()),
+            (b"aa".to_vec(), ()),
+        ];
+        let mut opts = IteratorOptions::default();
+        opts.reverse = true;
+        assert_eq!(0, seek_position(&items, b"ff", &opts));
+        assert_eq!(1, seek_position(&items, b"cc", &opts));
+        assert_eq!(1, seek_position(&items, b"dd", &opts));
+        assert_eq!(3, seek_position(&items, b"a", &opts));
+    }
+
     #[test]
     fn test_skl_iterator() {
         let skl = SkipList::new();
         let index = Box::new(skl);
         test_iterator(index);
     }
+
+    #[test]
+    fn test_btree_iterator() {
+        let bt = BTree::new();
+        let index = Box::new(bt);
+        test_iterator(index);
+    }
 }
@@ -0,0 +1,103 @@
+use std::collections::{BTreeSet, HashMap};
+
+use bytes::Bytes;
+use parking_lot::RwLock;
+
+// 前缀索引，按照 key 的前 prefix_len 个字节分组，记录每个前缀下所有 key 的后缀
+// 长度不足 prefix_len 的 key 不会被记录
+pub struct PrefixIndex {
+    prefix_len: usize,
+    map: RwLock<HashMap<Vec<u8>, BTreeSet<Vec<u8>>>>,
+}
+
+impl PrefixIndex {
+    pub fn new(prefix_len: usize) -> Self {
+        PrefixIndex {
+            prefix_len,
+            map: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn prefix_len(&self) -> usize {
+        self.prefix_len
+    }
+
+    // 记录 key，重复记录不会产生影响
+    pub fn put(&self, key: &[u8]) {
+        if key.len() < self.prefix_len {
+            return;
+        }
+        let (prefix, suffix) = key.split_at(self.prefix_len);
+        let mut map = self.map.write();
+        match map.get_mut(prefix) {
+            Some(suffixes) => {
+                suffixes.insert(suffix.to_vec());
+            }
+            None => {
+                map.insert(prefix.to_vec(), BTreeSet::from([suffix.to_vec()]));
+            }
+        }
+    }
+
+    // 删除 key，前缀下没有其他 key 时一并删除前缀
+    pub fn delete(&self, key: &[u8]) {
+        if key.len() < self.prefix_len {
+            return;
+        }
+        let (prefix, suffix) = key.split_at(self.prefix_len);
+        let mut map = self.map.write();
+        if let Some(suffixes) = map.get_mut(prefix) {
+            suffixes.remove(suffix);
+            if suffixes.is_empty() {
+                map.remove(prefix);
+            }
+        }
+    }
+
+    // 按照字节序返回前缀下所有 key 的后缀
+    pub fn list_suffixes(&self, prefix: &[u8]) -> Vec<Bytes> {
+        match self.map.read().get(prefix) {
+            Some(suffixes) => suffixes
+                .iter()
+                .map(|suffix| Bytes::copy_from_slice(suffix))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn clear(&self) {
+        self.map.write().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix_index_put_and_delete() {
+        let index = PrefixIndex::new(3);
+        index.put(b"t01:b");
+        index.put(b"t01:a");
+        index.put(b"t01:a");
+        index.put(b"t02:c");
+        // 长度不足的 key 不会被记录
+        index.put(b"t0");
+
+        let res1 = index.list_suffixes(b"t01");
+        assert_eq!(vec![Bytes::from(":a"), Bytes::from(":b")], res1);
+        let res2 = index.list_suffixes(b"t02");
+        assert_eq!(vec![Bytes::from(":c")], res2);
+        assert!(index.list_suffixes(b"t0").is_empty());
+
+        index.delete(b"t01:a");
+        index.delete(b"t02:c");
+        index.delete(b"t02:x");
+        assert_eq!(vec![Bytes::from(":b")], index.list_suffixes(b"t01"));
+        assert!(index.list_suffixes(b"t02").is_empty());
+        assert!(!index.map.read().contains_key(b"t02".as_slice()));
+
+        index.clear();
+        assert!(index.list_suffixes(b"t01").is_empty());
+    }
+}
@@ -1,16 +1,29 @@
-use crate::{data::LogPosition, option::IteratorOptions};
+use crate::{
+    data::{log_record::LogRecordPos, LogPosition},
+    error::Result,
+    option::IteratorOptions,
+};
 use bytes::Bytes;
 use crossbeam_skiplist::SkipMap;
-use std::sync::Arc;
+use parking_lot::RwLock;
+use std::{
+    ops::Bound::{self, Excluded, Included, Unbounded},
+    sync::Arc,
+};
 
-use super::{Index, IndexIterator};
+use super::{
+    decode_index_entries, is_empty_range, iterator_range, prefix_upper_bound, seek_position,
+    sort_by_comparator, Index, IndexIterator, KeyRange,
+};
 
 // 跳表索引
+// SkipMap 替换已有的 key 时会先删除旧的节点再插入新的节点，期间并发的读取会找不到这个 key，
+// 因此位置信息保存在 RwLock 中，更新已有的 key 时原地修改，不替换节点，并发的读取只需要持有读锁
 pub struct SkipList<T>
 where
     T: LogPosition + Send + Sync + 'static,
 {
-    map: Arc<SkipMap<Vec<u8>, T>>,
+    map: Arc<SkipMap<Vec<u8>, RwLock<T>>>,
 }
 
 impl<T> SkipList<T>
@@ -24,33 +37,60 @@ where
     }
 }
 
+impl SkipList<LogRecordPos> {
+    // 根据 Index::serialize 编码的数据重建跳表索引
+    pub fn deserialize(bytes: &[u8]) -> Result<Self> {
+        let skl = SkipList::new();
+        for (key, pos) in decode_index_entries(bytes)? {
+            skl.map.insert(key, RwLock::new(pos));
+        }
+        Ok(skl)
+    }
+}
+
 impl<T> Index<T> for SkipList<T>
 where
     T: LogPosition + Send + Sync + Copy,
 {
     fn put(&self, key: Vec<u8>, pos: T) -> Option<T> {
-        let mut result = None;
-        if let Some(entry) = self.map.get(&key) {
-            result = Some(*entry.value());
+        loop {
+            let mut inserted = false;
+            let entry = self.map.get_or_insert_with(key.clone(), || {
+                inserted = true;
+                RwLock::new(pos)
+            });
+            if inserted {
+                return None;
+            }
+            let mut value = entry.value().write();
+            // 节点已经被并发的 delete 删除了，重新插入
+            if entry.is_removed() {
+                continue;
+            }
+            let old_pos = *value;
+            *value = pos;
+            return Some(old_pos);
         }
-        self.map.insert(key, pos);
-        result
     }
 
     fn get(&self, key: Vec<u8>) -> Option<T> {
         if let Some(entry) = self.map.get(&key) {
-            return Some(*entry.value());
+            return Some(*entry.value().read());
         }
         None
     }
 
     fn delete(&self, key: Vec<u8>) -> Option<T> {
         if let Some(entry) = self.map.remove(&key) {
-            return Some(*entry.value());
+            return Some(*entry.value().read());
         }
         None
     }
 
+    fn clear(&self) {
+        self.map.clear();
+    }
+
     fn list_keys(&self) -> crate::error::Result<Vec<Bytes>> {
         let mut keys = Vec::with_capacity(self.map.len());
         for e in self.map.iter() {
@@ -59,63 +99,251 @@ where
         Ok(keys)
     }
 
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
     fn iterator(&self, options: IteratorOptions) -> Box<dyn IndexIterator<T>> {
-        let mut items = Vec::with_capacity(self.map.len());
-        for entry in self.map.iter() {
-            items.push((entry.key().clone(), *entry.value()))
-        }
-        if options.reverse {
-            items.reverse();
-        }
+        // 使用自定义比较器时，跳表中的顺序和比较器不一致，需要取出所有的数据重新排序
+        let sorted_items = options.comparator.as_ref().map(|comparator| {
+            let mut items = Vec::with_capacity(self.map.len());
+            for entry in self.map.iter() {
+                items.push((entry.key().clone(), *entry.value().read()));
+            }
+            sort_by_comparator(&mut items, &options, comparator);
+            items
+        });
+
+        // 只记录遍历的范围，迭代时再从跳表中按需查找，不需要预先拷贝所有的数据
+        let range = iterator_range(&options);
         Box::new(SkipListIterator {
-            items,
+            map: self.map.clone(),
+            range,
+            cursor: Unbounded,
+            at_end: false,
+            current: None,
+            sorted_items,
             curr_index: 0,
             options,
         })
     }
 }
 
+// 跳表索引迭代器，每次调用 next 时从跳表中查找下一条数据
 pub struct SkipListIterator<T>
 where
-    T: LogPosition + Send + Sync,
+    T: LogPosition + Send + Sync + 'static,
 {
-    items: Vec<(Vec<u8>, T)>,
-    curr_index: usize,
-    options: IteratorOptions,
+    map: Arc<SkipMap<Vec<u8>, RwLock<T>>>,
+    range: Option<KeyRange>,                 // 遍历的范围，None 表示范围为空
+    cursor: Bound<Vec<u8>>,                  // 下一次查找的起点，正向遍历时为下界，反向遍历时为上界
+    at_end: bool,                            // 是否位于最后一个数据之后
+    current: Option<(Vec<u8>, T)>,           // 当前遍历到的数据
+    sorted_items: Option<Vec<(Vec<u8>, T)>>, // 使用自定义比较器时排好序的数据
+    curr_index: usize,                       // 使用自定义比较器时当前遍历的位置下标
+    options: IteratorOptions,                // 配置项
+}
+
+impl<T> SkipListIterator<T>
+where
+    T: LogPosition + Send + Sync + Copy + 'static,
+{
+    // 结合游标找到下一条数据
+    fn next_entry(&self) -> Option<(Vec<u8>, T)> {
+        if self.at_end {
+            return None;
+        }
+        let (lower, upper) = self.range.as_ref()?;
+        let (lower, upper) = match self.options.reverse {
+            false => (max_lower_bound(lower, &self.cursor), upper.clone()),
+            true => (lower.clone(), min_upper_bound(upper, &self.cursor)),
+        };
+        if is_empty_range(&lower, &upper) {
+            return None;
+        }
+        let mut entries = self.map.range((lower, upper));
+        let entry = match self.options.reverse {
+            false => entries.next(),
+            true => entries.next_back(),
+        }?;
+        let pos = *entry.value().read();
+        Some((entry.key().clone(), pos))
+    }
+
+    // 结合游标找到前一条数据，游标的另一侧即为查找的边界
+    fn prev_entry(&self) -> Option<(Vec<u8>, T)> {
+        let (lower, upper) = self.range.as_ref()?;
+        let bound = match (self.at_end, &self.cursor) {
+            (true, _) => Unbounded,
+            // 位于起点，前面没有数据
+            (false, Unbounded) => return None,
+            (false, Included(key)) => Excluded(key.clone()),
+            (false, Excluded(key)) => Included(key.clone()),
+        };
+        let (lower, upper) = match self.options.reverse {
+            false => (lower.clone(), min_upper_bound(upper, &bound)),
+            true => (max_lower_bound(lower, &bound), upper.clone()),
+        };
+        if is_empty_range(&lower, &upper) {
+            return None;
+        }
+        let mut entries = self.map.range((lower, upper));
+        let entry = match self.options.reverse {
+            false => entries.next_back(),
+            true => entries.next(),
+        }?;
+        let pos = *entry.value().read();
+        Some((entry.key().clone(), pos))
+    }
 }
 
 impl<T> IndexIterator<T> for SkipListIterator<T>
 where
-    T: LogPosition + Send + Sync,
+    T: LogPosition + Send + Sync + Copy + 'static,
 {
     fn rewind(&mut self) {
+        self.cursor = Unbounded;
+        self.at_end = false;
+        self.current = None;
         self.curr_index = 0;
     }
 
     fn seek(&mut self, key: Vec<u8>) {
-        self.curr_index = match self.items.binary_search_by(|(x, _)| {
-            if self.options.reverse {
-                x.cmp(&key).reverse()
-            } else {
-                x.cmp(&key)
-            }
-        }) {
-            Ok(equal_val) => equal_val,
-            Err(insert_val) => insert_val,
-        };
+        if let Some(items) = &self.sorted_items {
+            self.curr_index = seek_position(items, &key, &self.options);
+        }
+        self.cursor = Included(key);
+        self.at_end = false;
+        self.current = None;
     }
 
     fn next(&mut self) -> Option<(&Vec<u8>, &T)> {
-        if self.curr_index >= self.items.len() {
+        if let Some(items) = &self.sorted_items {
+            while let Some(item) = items.get(self.curr_index) {
+                self.curr_index += 1;
+                if self.options.matches_prefix(&item.0) {
+                    return Some((&item.0, &item.1));
+                }
+            }
             return None;
         }
-        while let Some(item) = self.items.get(self.curr_index) {
-            self.curr_index += 1;
-            let prefix = &self.options.prefix;
-            if prefix.is_empty() || item.0.starts_with(&prefix) {
-                return Some((&item.0, &item.1));
+
+        loop {
+            let Some((key, pos)) = self.next_entry() else {
+                self.current = None;
+                return None;
+            };
+            self.cursor = Excluded(key.clone());
+            self.at_end = false;
+
+            if self.options.matches_prefix(&key) {
+                self.current = Some((key, pos));
+                break;
+            }
+            // 带有同一个前缀的 key 是连续的，越过所有的前缀之后不会再有满足条件的 key
+            match self.options.reverse {
+                // 跳到下一个还没有遍历的前缀
+                false => match self.options.prefix_list().filter(|p| **p > key).min() {
+                    Some(prefix) => self.cursor = Included(prefix.clone()),
+                    None => return None,
+                },
+                true => {
+                    if !self.options.prefix_list().any(|p| *p < key) {
+                        return None;
+                    }
+                }
+            }
+        }
+        self.current.as_ref().map(|(key, pos)| (key, pos))
+    }
+
+    fn seek_to_last(&mut self) {
+        if let Some(items) = &self.sorted_items {
+            self.curr_index = items.len();
+        }
+        self.cursor = Unbounded;
+        self.at_end = true;
+        self.current = None;
+    }
+
+    fn prev(&mut self) -> Option<(&Vec<u8>, &T)> {
+        if let Some(items) = &self.sorted_items {
+            while self.curr_index > 0 {
+                self.curr_index -= 1;
+                let item = &items[self.curr_index];
+                if self.options.matches_prefix(&item.0) {
+                    return Some((&item.0, &item.1));
+                }
+            }
+            return None;
+        }
+
+        loop {
+            let Some((key, pos)) = self.prev_entry() else {
+                self.current = None;
+                return None;
+            };
+            // 之后调用 next 会再次返回这条数据
+            self.cursor = Included(key.clone());
+            self.at_end = false;
+
+            if self.options.matches_prefix(&key) {
+                self.current = Some((key, pos));
+                break;
+            }
+            // 带有同一个前缀的 key 是连续的，越过所有的前缀之后不会再有满足条件的 key
+            match self.options.reverse {
+                false => {
+                    let lower: Vec<&Vec<u8>> =
+                        self.options.prefix_list().filter(|p| **p < key).collect();
+                    if lower.is_empty() {
+                        return None;
+                    }
+                    // 跳到还没有遍历的前缀中最大的上界，有前缀没有上界时逐个向前遍历
+                    let uppers: Option<Vec<Vec<u8>>> =
+                        lower.iter().map(|p| prefix_upper_bound(p)).collect();
+                    if let Some(upper) = uppers.and_then(|uppers| uppers.into_iter().max()) {
+                        if key > upper {
+                            self.cursor = Included(upper);
+                        }
+                    }
+                }
+                true => match self.options.prefix_list().filter(|p| **p > key).min() {
+                    Some(prefix) => self.cursor = Excluded(prefix.clone()),
+                    None => return None,
+                },
+            }
+        }
+        self.current.as_ref().map(|(key, pos)| (key, pos))
+    }
+}
+
+// 取两个下界中较大的一个
+fn max_lower_bound(a: &Bound<Vec<u8>>, b: &Bound<Vec<u8>>) -> Bound<Vec<u8>> {
+    match (a, b) {
+        (Unbounded, _) => b.clone(),
+        (_, Unbounded) => a.clone(),
+        (Included(x) | Excluded(x), Included(y) | Excluded(y)) => {
+            if x > y || (x == y && matches!(a, Excluded(_))) {
+                a.clone()
+            } else {
+                b.clone()
+            }
+        }
+    }
+}
+
+// 取两个上界中较小的一个
+fn min_upper_bound(a: &Bound<Vec<u8>>, b: &Bound<Vec<u8>>) -> Bound<Vec<u8>> {
+    match (a, b) {
+        (Unbounded, _) => b.clone(),
+        (_, Unbounded) => a.clone(),
+        (Included(x) | Excluded(x), Included(y) | Excluded(y)) => {
+            if x < y || (x == y && matches!(a, Excluded(_))) {
+                a.clone()
+            } else {
+                b.clone()
             }
         }
-        None
     }
 }
@@ -0,0 +1,366 @@
+use std::{
+    fs::{self, File},
+    path::Path,
+    sync::atomic::Ordering,
+};
+
+use bytes::{Buf, BufMut, BytesMut};
+use log::{info, warn};
+use prost::{
+    encode_length_delimiter,
+    encoding::{decode_varint, encode_varint},
+};
+
+use crate::{
+    data::{data_file::get_data_file_name, log_record::LogRecordPos},
+    db::{Engine, IndexLoader},
+    error::{Errors, Result},
+    index::{read_delimited, skiplist::SkipList, Index},
+    merge::get_merge_path,
+    option::IteratorOptions,
+    util,
+};
+
+// 索引快照的内容
+// 文件格式：活跃文件 id、活跃文件大小、事务序列号、写入序列号、可回收的空间大小依次使用变长编码存储，
+// 之后是变长长度开头的索引数据，以及被删除的 key 的数量和每个 key 及其删除标记所在的数据文件 id，
+// 最后 4 个字节是之前所有内容的 crc32
+struct IndexSnapshot {
+    active_file_id: u32,             // 保存快照时的活跃文件 id
+    active_file_size: u64,           // 保存快照时活跃文件的大小
+    seq_no: usize,                   // 事务序列号
+    sequence: u64,                   // 最大的写入序列号
+    reclaim_size: usize,             // 可以 merge 回收的空间大小
+    index: Vec<u8>,                  // Index::serialize 编码的索引数据
+    tombstones: Vec<(Vec<u8>, u32)>, // 被删除的 key 以及删除标记所在的数据文件 id
+}
+
+impl IndexSnapshot {
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = BytesMut::new();
+        encode_varint(self.active_file_id as u64, &mut buf);
+        encode_varint(self.active_file_size, &mut buf);
+        encode_varint(self.seq_no as u64, &mut buf);
+        encode_varint(self.sequence, &mut buf);
+        encode_varint(self.reclaim_size as u64, &mut buf);
+        encode_length_delimiter(self.index.len(), &mut buf).expect("encode index len error");
+        buf.extend_from_slice(&self.index);
+        encode_varint(self.tombstones.len() as u64, &mut buf);
+        for (key, file_id) in self.tombstones.iter() {
+            encode_length_delimiter(key.len(), &mut buf).expect("encode key len error");
+            buf.extend_from_slice(key);
+            encode_varint(*file_id as u64, &mut buf);
+        }
+        let crc = crc32fast::hash(&buf);
+        buf.put_u32_le(crc);
+        buf.to_vec()
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let crc_len = std::mem::size_of::<u32>();
+        if bytes.len() < crc_len {
+            return Err(Errors::IndexSnapshotCorrupted);
+        }
+        let (mut buf, mut crc_buf) = bytes.split_at(bytes.len() - crc_len);
+        if crc32fast::hash(buf) != crc_buf.get_u32_le() {
+            return Err(Errors::IndexSnapshotCorrupted);
+        }
+
+        let read_varint =
+            |buf: &mut &[u8]| decode_varint(buf).map_err(|_| Errors::IndexSnapshotCorrupted);
+        let active_file_id = read_varint(&mut buf)? as u32;
+        let active_file_size = read_varint(&mut buf)?;
+        let seq_no = read_varint(&mut buf)? as usize;
+        let sequence = read_varint(&mut buf)?;
+        let reclaim_size = read_varint(&mut buf)? as usize;
+        let index = read_delimited(&mut buf)?;
+        let tombstone_num = read_varint(&mut buf)?;
+        let mut tombstones = Vec::new();
+        for _ in 0..tombstone_num {
+            let key = read_delimited(&mut buf)?;
+            let file_id = read_varint(&mut buf)? as u32;
+            tombstones.push((key, file_id));
+        }
+        if buf.has_remaining() {
+            return Err(Errors::IndexSnapshotCorrupted);
+        }
+        Ok(IndexSnapshot {
+            active_file_id,
+            active_file_size,
+            seq_no,
+            sequence,
+            reclaim_size,
+            index,
+            tombstones,
+        })
+    }
+}
+
+impl Engine {
+    /// 将内存索引保存为快照文件，Options::index_snapshot_path 指向这个文件时，打开数据库会直接加载快照，不再遍历数据文件
+    /// 快照中记录了活跃文件的 id 和大小，保存之后数据文件发生了变化时快照失效，打开数据库时会重新遍历数据文件
+    /// merge 正在进行或者 merge 的结果还没有在重新打开数据库时生效时，无法保存快照
+    /// 快照依赖本地数据文件的修改时间判断是否失效，设置了 io_factory 时无法保存
+    pub fn save_index_snapshot<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        if self.options.io_factory.is_some() {
+            return Err(Errors::IOFactoryUnsupported);
+        }
+        self.wait_index_ready()?;
+        // 持有写锁，保存的过程中没有其他的写入，保证快照和数据文件是一致的
+        let _lock = self.write_lock.write();
+        if self.is_merging.load(Ordering::SeqCst)
+            || get_merge_path(self.options.dir_path.clone()).is_dir()
+        {
+            return Err(Errors::MergeInProgress);
+        }
+
+        // 先持久化活跃文件，快照中记录的大小和磁盘上的文件大小保持一致
+        let active_file = self.active_file.read();
+        active_file.sync()?;
+
+        let mut tombstones: Vec<_> = match &self.tombstones {
+            Some(tombstones) => tombstones
+                .read()
+                .iter()
+                .map(|(key, file_id)| (key.clone(), *file_id))
+                .collect(),
+            None => Vec::new(),
+        };
+        tombstones.sort();
+        let snapshot = IndexSnapshot {
+            active_file_id: active_file.get_file_id(),
+            active_file_size: active_file.get_write_off(),
+            seq_no: self.seq_no.load(Ordering::SeqCst),
+            sequence: self.sequence.load(Ordering::SeqCst),
+            reclaim_size: self.reclaim_size.load(Ordering::SeqCst),
+            index: self.index.serialize(),
+            tombstones,
+        };
+
+        // 先写入临时文件再重命名，避免留下不完整的快照
+        let path = path.as_ref();
+        let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+        fs::write(&tmp_path, snapshot.encode())?;
+        File::open(&tmp_path)?.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            util::file::sync_dir(dir)?;
+        }
+        Ok(())
+    }
+}
+
+impl IndexLoader {
+    // 从 Options::index_snapshot_path 加载索引快照，返回 false 时说明没有加载，需要遍历数据文件
+    // 快照不存在、已经损坏或者保存之后数据文件发生了变化时都不会加载
+    pub(crate) fn load_index_from_snapshot(&self) -> Result<bool> {
+        let path = match &self.options.index_snapshot_path {
+            Some(path) if path.is_file() => path,
+            _ => return Ok(false),
+        };
+        let (snapshot, index) = match self.read_snapshot(path) {
+            Ok(Some(res)) => res,
+            Ok(None) => {
+                info!("index snapshot {} is stale, ignore it", path.display());
+                return Ok(false);
+            }
+            Err(e) => {
+                warn!("failed to read index snapshot {}: {}", path.display(), e);
+                return Ok(false);
+            }
+        };
+
+        // 快照已经完整解码，之后再更新内存数据，不会只加载了一部分
+        let mut index_iter = index.iterator(IteratorOptions::default());
+        while let Some((key, pos)) = index_iter.next() {
+            self.index.put(key.clone(), *pos);
+        }
+        if let Some(tombstones) = &self.tombstones {
+            tombstones.write().extend(snapshot.tombstones);
+        }
+        self.reclaim_size
+            .store(snapshot.reclaim_size, Ordering::SeqCst);
+        self.seq_no.store(snapshot.seq_no, Ordering::SeqCst);
+        self.sequence.store(snapshot.sequence, Ordering::SeqCst);
+        self.active_file
+            .read()
+            .set_write_off(snapshot.active_file_size);
+        Ok(true)
+    }
+
+    // 读取并校验快照，快照已经失效时返回 None
+    fn read_snapshot(
+        &self,
+        path: &Path,
+    ) -> Result<Option<(IndexSnapshot, SkipList<LogRecordPos>)>> {
+        let snapshot = IndexSnapshot::decode(&fs::read(path)?)?;
+
+        // 保存快照之后切换过活跃文件，或者活跃文件中写入了新的数据
+        let active_file = self.active_file.read();
+        if self.file_ids.last() != Some(&snapshot.active_file_id)
+            || active_file.file_size() != snapshot.active_file_size
+        {
+            return Ok(None);
+        }
+
+        // 保存快照之后有数据文件被修改过，例如 merge 重写了旧的数据文件
+        let snapshot_mtime = fs::metadata(path)?.modified()?;
+        for file_id in self.file_ids.iter() {
+            let file_name = get_data_file_name(&self.options, *file_id);
+            if fs::metadata(file_name)?.modified()? > snapshot_mtime {
+                return Ok(None);
+            }
+        }
+
+        let index = SkipList::deserialize(&snapshot.index)?;
+        Ok(Some((snapshot, index)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::OpenOptions,
+        io::{Seek, SeekFrom, Write},
+        path::PathBuf,
+    };
+
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::{
+        db::KeyStatus,
+        option::Options,
+        util::rand_kv::{get_test_key, get_test_value},
+    };
+
+    fn test_options(name: &str) -> (Options, PathBuf) {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from(format!("/tmp/bitcask-rs-{}", name));
+        opts.data_file_size = 64 * 1024;
+        let snapshot_path = PathBuf::from(format!("/tmp/bitcask-rs-{}.index", name));
+        opts.index_snapshot_path = Some(snapshot_path.clone());
+        (opts, snapshot_path)
+    }
+
+    #[test]
+    fn test_index_snapshot_encode_decode() {
+        let snapshot = IndexSnapshot {
+            active_file_id: 3,
+            active_file_size: 1024,
+            seq_no: 5,
+            sequence: 100,
+            reclaim_size: 64,
+            index: b"index-data".to_vec(),
+            tombstones: vec![(b"a".to_vec(), 1), (b"bb".to_vec(), 2)],
+        };
+        let enc = snapshot.encode();
+        let dec = IndexSnapshot::decode(&enc).unwrap();
+        assert_eq!(3, dec.active_file_id);
+        assert_eq!(1024, dec.active_file_size);
+        assert_eq!(5, dec.seq_no);
+        assert_eq!(100, dec.sequence);
+        assert_eq!(64, dec.reclaim_size);
+        assert_eq!(b"index-data".to_vec(), dec.index);
+        assert_eq!(
+            vec![(b"a".to_vec(), 1), (b"bb".to_vec(), 2)],
+            dec.tombstones
+        );
+
+        // 内容被修改或者被截断
+        let mut corrupted = enc.clone();
+        corrupted[0] ^= 0xff;
+        assert!(IndexSnapshot::decode(&corrupted).is_err());
+        assert!(IndexSnapshot::decode(&enc[..enc.len() - 1]).is_err());
+        assert!(IndexSnapshot::decode(&[]).is_err());
+    }
+
+    #[test]
+    fn test_open_with_index_snapshot() {
+        let (mut opts, snapshot_path) = test_options("index-snapshot");
+        opts.track_tombstones = true;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..100 {
+            let res = engine.put(get_test_key(i), get_test_value(i));
+            assert!(res.is_ok());
+        }
+        let res1 = engine.delete(get_test_key(1));
+        assert!(res1.is_ok());
+        engine
+            .save_index_snapshot(&snapshot_path)
+            .expect("failed to save index snapshot");
+        let stat1 = engine.stat().unwrap();
+        let pos = engine.locate(get_test_key(0)).unwrap().unwrap();
+        engine.close().expect("failed to close engine");
+
+        // 破坏第一条数据的校验值，并恢复修改时间，遍历数据文件会失败，而加载快照时不会读取
+        let file_name = get_data_file_name(&opts, pos.file_id());
+        let mtime = fs::metadata(&file_name).unwrap().modified().unwrap();
+        let mut file = OpenOptions::new().write(true).open(&file_name).unwrap();
+        let crc_offset = pos.offset() + pos.size() as u64 - 1;
+        file.seek(SeekFrom::Start(crc_offset)).unwrap();
+        file.write_all(&[0xff]).unwrap();
+        file.set_modified(mtime).unwrap();
+        drop(file);
+
+        let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+        let stat2 = engine2.stat().unwrap();
+        assert_eq!(stat1.key_num, stat2.key_num);
+        assert_eq!(stat1.reclaimable_size, stat2.reclaimable_size);
+        assert_eq!(get_test_value(50), engine2.get(get_test_key(50)).unwrap());
+        assert_eq!(
+            KeyStatus::Deleted,
+            engine2.get_status(get_test_key(1)).unwrap()
+        );
+
+        // 加载快照之后可以继续写入
+        let res2 = engine2.put(get_test_key(200), Bytes::from("new-value"));
+        assert!(res2.is_ok());
+        engine2.close().expect("failed to close engine");
+
+        // 写入新的数据之后快照失效，重新遍历数据文件
+        let mut opts2 = opts.clone();
+        opts2.index_snapshot_path = None;
+        assert!(Engine::open(opts2).is_err());
+        assert!(Engine::open(opts.clone()).is_err());
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove path");
+        std::fs::remove_file(snapshot_path).expect("failed to remove snapshot");
+    }
+
+    #[test]
+    fn test_stale_index_snapshot() {
+        let (opts, snapshot_path) = test_options("index-snapshot-stale");
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..10 {
+            let res = engine.put(get_test_key(i), get_test_value(i));
+            assert!(res.is_ok());
+        }
+        engine
+            .save_index_snapshot(&snapshot_path)
+            .expect("failed to save index snapshot");
+
+        // 保存快照之后写入的数据不在快照中，打开时需要遍历数据文件
+        let res1 = engine.put(get_test_key(20), get_test_value(20));
+        assert!(res1.is_ok());
+        engine.close().expect("failed to close engine");
+
+        let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(11, engine2.stat().unwrap().key_num);
+        assert_eq!(get_test_value(20), engine2.get(get_test_key(20)).unwrap());
+        engine2.close().expect("failed to close engine");
+
+        // 损坏的快照同样会被忽略
+        fs::write(&snapshot_path, b"corrupted").unwrap();
+        let engine3 = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(11, engine3.stat().unwrap().key_num);
+        engine3.close().expect("failed to close engine");
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove path");
+        std::fs::remove_file(snapshot_path).expect("failed to remove snapshot");
+    }
+}
@@ -1,47 +1,142 @@
-use std::sync::Arc;
+use std::{ops::ControlFlow, sync::Arc};
 
 use bytes::Bytes;
 use parking_lot::RwLock;
 
 use crate::{
-    data::log_record::LogRecordPos, db::Engine, error::Result, index::IndexIterator,
-    option::IteratorOptions,
+    data::log_record::LogRecordPos,
+    db::Engine,
+    error::{Errors, Result},
+    index::{prefix_upper_bound, IndexIterator},
+    option::{IteratorOptions, ValueFilter},
 };
 
 pub struct Iterator<'a> {
     index_iter: Arc<RwLock<Box<dyn IndexIterator<LogRecordPos>>>>,
     engine: &'a Engine,
+    value_filter: Option<ValueFilter>, // value 的过滤条件
 }
 
 impl Engine {
-    pub fn iter(&self, options: IteratorOptions) -> Iterator {
-        Iterator {
-            index_iter: Arc::new(RwLock::new(self.index.iterator(options))),
-            engine: self,
-        }
+    pub fn iter(&self, options: IteratorOptions) -> Result<Iterator> {
+        self.wait_index_ready()?;
+        let value_filter = options.value_filter.clone();
+        Ok(Iterator::new(
+            self.index.iterator(options),
+            self,
+            value_filter,
+        ))
+    }
+
+    // 返回遍历 key/value 的标准迭代器，读取出错时返回对应的错误，不会中断整个遍历
+    pub fn entries(&self, options: IteratorOptions) -> Result<Entries<'_>> {
+        self.wait_index_ready()?;
+        let value_filter = options.value_filter.clone();
+        Ok(Entries::new(
+            self.index.iterator(options),
+            self,
+            value_filter,
+        ))
+    }
+
+    // 返回遍历 key 和数据位置的标准迭代器，只访问内存索引，不会读取数据文件
+    pub fn index_entries(&self, options: IteratorOptions) -> Result<IndexEntries> {
+        self.wait_index_ready()?;
+        Ok(IndexEntries::new(self.index.iterator(options)))
     }
 
     pub fn list_keys(&self) -> Result<Vec<Bytes>> {
+        self.wait_index_ready()?;
         self.index.list_keys()
     }
 
-    pub fn fold<F>(&self, f: F) -> Result<()>
+    // 返回索引中 key 的数量，不需要像 list_keys 一样复制所有的 key
+    // 只访问内存索引，已经过期但还没有被清理的 key 也会被计算在内，索引在后台加载时返回已经加载的数量
+    pub fn key_count(&self) -> usize {
+        self.index.len()
+    }
+
+    // 统计以 prefix 开头的 key 的数量，只遍历内存索引，不会读取数据文件
+    // 因此已经过期但还没有被清理的 key 也会被计算在内
+    pub fn count_prefix(&self, prefix: &[u8]) -> Result<usize> {
+        self.wait_index_ready()?;
+        // 将遍历范围限制在 prefix 之内，只访问匹配的 key
+        let options = IteratorOptions {
+            prefix: prefix.to_vec(),
+            start: Some(prefix.to_vec()),
+            end: prefix_upper_bound(prefix),
+            ..Default::default()
+        };
+        let mut index_iter = self.index.iterator(options);
+        let mut count = 0;
+        while index_iter.next().is_some() {
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    // 列出以 prefix 开头的所有 key 去掉前缀之后的部分，按照字节序排列
+    // 需要配置 prefix_index_len，并且 prefix 的长度和其一致，只查询内存中的前缀索引，
+    // 因此已经过期但还没有被清理的 key 也会被返回
+    pub fn list_suffixes(&self, prefix: &[u8]) -> Result<Vec<Bytes>> {
+        self.wait_index_ready()?;
+        match &self.prefix_index {
+            Some(prefix_index) if prefix_index.prefix_len() == prefix.len() => {
+                Ok(prefix_index.list_suffixes(prefix))
+            }
+            _ => Err(Errors::InvalidPrefix),
+        }
+    }
+
+    // 按照 key 的顺序遍历所有的数据，闭包返回 ControlFlow::Break 时提前结束
+    pub fn fold<B, F>(&self, init: B, f: F) -> Result<B>
     where
-        Self: Sized,
-        F: Fn(Bytes, Bytes) -> bool,
+        F: FnMut(B, Bytes, Bytes) -> ControlFlow<B, B>,
     {
-        let iter = self.iter(IteratorOptions::default());
+        self.fold_with_options(IteratorOptions::default(), init, f)
+    }
 
-        while let Some((k, v)) = iter.next() {
-            if !f(k, v) {
-                break;
+    // 按照迭代器配置项遍历数据，可以指定反向遍历和 key 前缀
+    pub fn fold_with_options<B, F>(&self, options: IteratorOptions, init: B, mut f: F) -> Result<B>
+    where
+        F: FnMut(B, Bytes, Bytes) -> ControlFlow<B, B>,
+    {
+        self.wait_index_ready()?;
+        let value_filter = options.value_filter.clone();
+        let mut index_iter = self.index.iterator(options);
+        let mut acc = init;
+        while let Some((key, pos)) = index_iter.next() {
+            let value = match self.get_value_by_position(pos) {
+                Ok(value) => value,
+                // 数据已经被删除或者过期，跳过
+                Err(Errors::KeyNotFound) => continue,
+                Err(e) => return Err(e),
+            };
+            if !matches_value_filter(&value_filter, &value) {
+                continue;
             }
+            acc = match f(acc, Bytes::from(key.to_vec()), value) {
+                ControlFlow::Continue(acc) => acc,
+                ControlFlow::Break(acc) => return Ok(acc),
+            };
         }
-        Ok(())
+        Ok(acc)
     }
 }
 
-impl Iterator<'_> {
+impl<'a> Iterator<'a> {
+    pub(crate) fn new(
+        index_iter: Box<dyn IndexIterator<LogRecordPos>>,
+        engine: &'a Engine,
+        value_filter: Option<ValueFilter>,
+    ) -> Self {
+        Iterator {
+            index_iter: Arc::new(RwLock::new(index_iter)),
+            engine,
+            value_filter,
+        }
+    }
+
     // Rewind 重新回到迭代器的起点，即第一个数据
     pub fn rewind(&self) {
         let mut index_iter = self.index_iter.write();
@@ -53,25 +148,122 @@ impl Iterator<'_> {
         index_iter.seek(key);
     }
 
+    // SeekToLast 跳转到最后一个数据之后，之后调用 prev 从最后一个数据开始向前遍历
+    pub fn seek_to_last(&self) {
+        let mut index_iter = self.index_iter.write();
+        index_iter.seek_to_last();
+    }
+
+    // Prev 跳转到前一个 key，返回 None 则说明已经到了起点
+    pub fn prev(&self) -> Option<(Bytes, Bytes)> {
+        let mut index_iter = self.index_iter.write();
+        while let Some(item) = index_iter.prev() {
+            let value = match self.engine.get_value_by_position(item.1) {
+                Ok(value) => value,
+                // 数据已经过期，跳过
+                Err(Errors::KeyNotFound) => continue,
+                Err(e) => panic!("failed to get value from data file: {}", e),
+            };
+            if !matches_value_filter(&self.value_filter, &value) {
+                continue;
+            }
+            return Some((Bytes::from(item.0.to_vec()), value));
+        }
+        None
+    }
+
     // Next 跳转到下一个 key，返回 None 则说明迭代完毕
     pub fn next(&self) -> Option<(Bytes, Bytes)> {
         let mut index_iter = self.index_iter.write();
-        if let Some(item) = index_iter.next() {
-            let value = self
-                .engine
-                .get_value_by_position(item.1)
-                .expect("failed to get value from data file");
+        while let Some(item) = index_iter.next() {
+            let value = match self.engine.get_value_by_position(item.1) {
+                Ok(value) => value,
+                // 数据已经过期，跳过
+                Err(Errors::KeyNotFound) => continue,
+                Err(e) => panic!("failed to get value from data file: {}", e),
+            };
+            if !matches_value_filter(&self.value_filter, &value) {
+                continue;
+            }
             return Some((Bytes::from(item.0.to_vec()), value));
         }
         None
     }
 }
 
+// 遍历 key/value 的迭代器，每次返回一条数据或者读取数据时的错误
+pub struct Entries<'a> {
+    index_iter: Box<dyn IndexIterator<LogRecordPos>>,
+    engine: &'a Engine,
+    value_filter: Option<ValueFilter>, // value 的过滤条件
+}
+
+impl<'a> Entries<'a> {
+    pub(crate) fn new(
+        index_iter: Box<dyn IndexIterator<LogRecordPos>>,
+        engine: &'a Engine,
+        value_filter: Option<ValueFilter>,
+    ) -> Self {
+        Entries {
+            index_iter,
+            engine,
+            value_filter,
+        }
+    }
+}
+
+impl std::iter::Iterator for Entries<'_> {
+    type Item = Result<(Bytes, Bytes)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((key, pos)) = self.index_iter.next() {
+            let key = Bytes::from(key.to_vec());
+            return match self.engine.get_value_by_position(pos) {
+                Ok(value) if !matches_value_filter(&self.value_filter, &value) => continue,
+                Ok(value) => Some(Ok((key, value))),
+                // 数据已经被删除或者过期，跳过
+                Err(Errors::KeyNotFound) => continue,
+                Err(e) => Some(Err(e)),
+            };
+        }
+        None
+    }
+}
+
+// 判断 value 是否满足过滤条件，没有设置过滤条件时全部保留
+fn matches_value_filter(value_filter: &Option<ValueFilter>, value: &[u8]) -> bool {
+    value_filter.as_ref().is_none_or(|filter| filter(value))
+}
+
+// 索引迭代器的标准迭代器适配，每次拷贝一份 key 和数据位置返回
+pub struct IndexEntries {
+    index_iter: Box<dyn IndexIterator<LogRecordPos>>,
+}
+
+impl IndexEntries {
+    pub(crate) fn new(index_iter: Box<dyn IndexIterator<LogRecordPos>>) -> Self {
+        IndexEntries { index_iter }
+    }
+}
+
+impl std::iter::Iterator for IndexEntries {
+    type Item = (Bytes, LogRecordPos);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.index_iter
+            .next()
+            .map(|(key, pos)| (Bytes::copy_from_slice(key), *pos))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
 
-    use crate::{option::Options, util};
+    use crate::{
+        option::{IndexType, Options},
+        util,
+    };
 
     use super::*;
 
@@ -115,13 +307,46 @@ mod tests {
         let put_res4 = engine.put(Bytes::from("ccde"), util::rand_kv::get_test_value(10));
         assert!(put_res4.is_ok());
 
-        engine
-            .fold(|key, value| {
+        // 计算所有 value 的长度之和
+        let total = engine
+            .fold(0, |acc, key, value| {
                 assert!(key.len() > 0);
-                assert!(value.len() > 0);
-                return true;
+                ControlFlow::Continue(acc + value.len())
             })
             .unwrap();
+        assert_eq!(total, util::rand_kv::get_test_value(10).len() * 4);
+
+        // 遍历 2 条数据之后提前结束
+        let keys = engine
+            .fold(Vec::new(), |mut acc, key, _| {
+                acc.push(key);
+                if acc.len() == 2 {
+                    return ControlFlow::Break(acc);
+                }
+                ControlFlow::Continue(acc)
+            })
+            .unwrap();
+        assert_eq!(keys, vec![Bytes::from("aacc"), Bytes::from("bbac")]);
+
+        // 删除的数据不会被遍历，反向遍历
+        let del_res = engine.delete(Bytes::from("eecc"));
+        assert!(del_res.is_ok());
+        let mut iter_opts = IteratorOptions::default();
+        iter_opts.reverse = true;
+        let keys = engine
+            .fold_with_options(iter_opts, Vec::new(), |mut acc, key, _| {
+                acc.push(key);
+                ControlFlow::Continue(acc)
+            })
+            .unwrap();
+        assert_eq!(
+            keys,
+            vec![
+                Bytes::from("ccde"),
+                Bytes::from("bbac"),
+                Bytes::from("aacc")
+            ]
+        );
 
         // 删除测试的文件夹
         std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
@@ -134,14 +359,14 @@ mod tests {
         let engine = Engine::open(opts.clone()).expect("failed to open engine");
 
         // 没有数据的情况
-        let iter1 = engine.iter(IteratorOptions::default());
+        let iter1 = engine.iter(IteratorOptions::default()).unwrap();
         iter1.seek("aa".as_bytes().to_vec());
         assert!(iter1.next().is_none());
 
         // 有一条数据的情况
         let put_res1 = engine.put(Bytes::from("aacc"), util::rand_kv::get_test_value(10));
         assert!(put_res1.is_ok());
-        let iter2 = engine.iter(IteratorOptions::default());
+        let iter2 = engine.iter(IteratorOptions::default()).unwrap();
         iter2.seek("a".as_bytes().to_vec());
         assert!(iter2.next().is_some());
 
@@ -153,7 +378,7 @@ mod tests {
         let put_res4 = engine.put(Bytes::from("ccde"), util::rand_kv::get_test_value(10));
         assert!(put_res4.is_ok());
 
-        let iter3 = engine.iter(IteratorOptions::default());
+        let iter3 = engine.iter(IteratorOptions::default()).unwrap();
         iter3.seek("a".as_bytes().to_vec());
         assert_eq!(Bytes::from("aacc"), iter3.next().unwrap().0);
 
@@ -170,7 +395,7 @@ mod tests {
         // 有一条数据的情况
         let put_res1 = engine.put(Bytes::from("eecc"), util::rand_kv::get_test_value(10));
         assert!(put_res1.is_ok());
-        let iter1 = engine.iter(IteratorOptions::default());
+        let iter1 = engine.iter(IteratorOptions::default()).unwrap();
         assert!(iter1.next().is_some());
         iter1.rewind();
         assert!(iter1.next().is_some());
@@ -186,7 +411,7 @@ mod tests {
 
         let mut iter_opts1 = IteratorOptions::default();
         iter_opts1.reverse = true;
-        let iter2 = engine.iter(iter_opts1);
+        let iter2 = engine.iter(iter_opts1).unwrap();
         while let Some(item) = iter2.next() {
             assert!(item.0.len() > 0);
         }
@@ -195,6 +420,32 @@ mod tests {
         std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
     }
 
+    #[test]
+    fn test_iterator_prev() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-iter-prev");
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for key in ["aade", "bbcc", "ddce", "eecc"] {
+            let put_res = engine.put(Bytes::from(key), util::rand_kv::get_test_value(10));
+            assert!(put_res.is_ok());
+        }
+        let del_res = engine.delete(Bytes::from("ddce"));
+        assert!(del_res.is_ok());
+
+        let iter1 = engine.iter(IteratorOptions::default()).unwrap();
+        iter1.seek_to_last();
+        assert_eq!(iter1.prev().unwrap().0, Bytes::from("eecc"));
+        assert_eq!(iter1.prev().unwrap().0, Bytes::from("bbcc"));
+        assert_eq!(iter1.next().unwrap().0, Bytes::from("bbcc"));
+        assert_eq!(iter1.prev().unwrap().0, Bytes::from("bbcc"));
+        assert_eq!(iter1.prev().unwrap().0, Bytes::from("aade"));
+        assert!(iter1.prev().is_none());
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+
     #[test]
     fn test_iterator_prefix() {
         let mut opts = Options::default();
@@ -214,7 +465,7 @@ mod tests {
 
         let mut iter_opt1 = IteratorOptions::default();
         iter_opt1.prefix = "dd".as_bytes().to_vec();
-        let iter1 = engine.iter(iter_opt1);
+        let iter1 = engine.iter(iter_opt1).unwrap();
         while let Some(item) = iter1.next() {
             assert!(item.0.len() > 0);
         }
@@ -222,4 +473,258 @@ mod tests {
         // 删除测试的文件夹
         std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
     }
+
+    #[test]
+    fn test_iterator_prefixes() {
+        for index_type in [IndexType::SkipList, IndexType::BTree] {
+            let mut opts = Options::default();
+            opts.dir_path = PathBuf::from("/tmp/bitcask-rs-iter-prefixes");
+            opts.index_type = index_type;
+            let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+            for key in [
+                "b:1", "z:2", "a:2", "m:1", "c:1", "z:1", "a:1", "mm", "zz", "a",
+            ] {
+                let put_res = engine.put(Bytes::from(key), util::rand_kv::get_test_value(10));
+                assert!(put_res.is_ok());
+            }
+
+            let mut iter_opts = IteratorOptions::default();
+            iter_opts.prefixes = vec![b"z:".to_vec(), b"a:".to_vec(), b"m:".to_vec()];
+            let iter1 = engine.iter(iter_opts).unwrap();
+            let mut keys1 = Vec::new();
+            while let Some((key, _)) = iter1.next() {
+                keys1.push(key);
+            }
+            assert_eq!(keys1, vec!["a:1", "a:2", "m:1", "z:1", "z:2"]);
+
+            // 反向遍历
+            let mut iter_opts = IteratorOptions::default();
+            iter_opts.prefixes = vec![b"z:".to_vec(), b"a:".to_vec(), b"m:".to_vec()];
+            iter_opts.reverse = true;
+            let iter2 = engine.iter(iter_opts).unwrap();
+            let mut keys2 = Vec::new();
+            while let Some((key, _)) = iter2.next() {
+                keys2.push(key);
+            }
+            assert_eq!(keys2, vec!["z:2", "z:1", "m:1", "a:2", "a:1"]);
+
+            // 从最后一个 key 开始向前遍历
+            let mut iter_opts = IteratorOptions::default();
+            iter_opts.prefixes = vec![b"z:".to_vec(), b"a:".to_vec(), b"m:".to_vec()];
+            let iter3 = engine.iter(iter_opts).unwrap();
+            iter3.seek_to_last();
+            let mut keys3 = Vec::new();
+            while let Some((key, _)) = iter3.prev() {
+                keys3.push(key);
+            }
+            assert_eq!(keys3, vec!["z:2", "z:1", "m:1", "a:2", "a:1"]);
+
+            // prefix 和 prefixes 同时设置
+            let mut iter_opts = IteratorOptions::default();
+            iter_opts.prefix = b"c".to_vec();
+            iter_opts.prefixes = vec![b"mm".to_vec()];
+            let keys4 = engine
+                .index_entries(iter_opts)
+                .unwrap()
+                .map(|(key, _)| key)
+                .collect::<Vec<_>>();
+            assert_eq!(keys4, vec![b"c:1".to_vec(), b"mm".to_vec()]);
+
+            // 删除测试的文件夹
+            std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+        }
+    }
+
+    #[test]
+    fn test_count_prefix() {
+        for index_type in [IndexType::SkipList, IndexType::BTree] {
+            let mut opts = Options::default();
+            opts.dir_path = PathBuf::from("/tmp/bitcask-rs-count-prefix");
+            opts.index_type = index_type;
+            let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+            // 两个租户的数据，以及和前缀相邻的 key
+            for i in 0..30 {
+                let key = format!("tenant:1:{:03}", i);
+                let put_res = engine.put(Bytes::from(key), util::rand_kv::get_test_value(i));
+                assert!(put_res.is_ok());
+            }
+            for i in 0..20 {
+                let key = format!("tenant:2:{:03}", i);
+                let put_res = engine.put(Bytes::from(key), util::rand_kv::get_test_value(i));
+                assert!(put_res.is_ok());
+            }
+            let put_res1 = engine.put(Bytes::from("tenant:10"), util::rand_kv::get_test_value(1));
+            assert!(put_res1.is_ok());
+            let del_res1 = engine.delete(Bytes::from("tenant:2:000"));
+            assert!(del_res1.is_ok());
+
+            assert_eq!(30, engine.count_prefix(b"tenant:1:").unwrap());
+            assert_eq!(19, engine.count_prefix(b"tenant:2:").unwrap());
+            assert_eq!(31, engine.count_prefix(b"tenant:1").unwrap());
+            assert_eq!(0, engine.count_prefix(b"tenant:3:").unwrap());
+            assert_eq!(50, engine.count_prefix(b"").unwrap());
+
+            // 删除测试的文件夹
+            std::mem::drop(engine);
+            std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+        }
+    }
+
+    #[test]
+    fn test_entries() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-entries");
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for i in 0..100 {
+            let put_res = engine.put(
+                util::rand_kv::get_test_key(i),
+                util::rand_kv::get_test_value(i),
+            );
+            assert!(put_res.is_ok());
+        }
+        // 被删除的 key 不会返回
+        for i in 0..10 {
+            let del_res = engine.delete(util::rand_kv::get_test_key(i));
+            assert!(del_res.is_ok());
+        }
+
+        let mut count = 0;
+        for item in engine.entries(IteratorOptions::default()).unwrap() {
+            let (key, value) = item.unwrap();
+            assert_eq!(engine.get(key).unwrap(), value);
+            count += 1;
+        }
+        assert_eq!(90, count);
+
+        // 可以在其他线程中遍历
+        let entries = engine.entries(IteratorOptions::default()).unwrap();
+        let keys = std::thread::scope(|s| {
+            s.spawn(move || entries.map(|item| item.unwrap().0).collect::<Vec<_>>())
+                .join()
+                .unwrap()
+        });
+        assert_eq!(90, keys.len());
+        assert_eq!(util::rand_kv::get_test_key(10), keys[0]);
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+    #[test]
+    fn test_iterator_value_filter() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-iter-value-filter");
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for i in 0..10 {
+            let value = Bytes::from(vec![b'v'; i + 1]);
+            let put_res = engine.put(util::rand_kv::get_test_key(i), value);
+            assert!(put_res.is_ok());
+        }
+        let new_opts = || {
+            let mut iter_opts = IteratorOptions::default();
+            iter_opts.value_filter = Some(Arc::new(|value: &[u8]| value.len() > 6));
+            iter_opts
+        };
+
+        // 只返回 value 长度大于 6 的数据
+        let iter1 = engine.iter(new_opts()).unwrap();
+        let mut keys = Vec::new();
+        while let Some((key, value)) = iter1.next() {
+            assert!(value.len() > 6);
+            keys.push(key);
+        }
+        assert_eq!(
+            vec![
+                util::rand_kv::get_test_key(6),
+                util::rand_kv::get_test_key(7),
+                util::rand_kv::get_test_key(8),
+                util::rand_kv::get_test_key(9),
+            ],
+            keys
+        );
+        iter1.seek_to_last();
+        assert_eq!(util::rand_kv::get_test_key(9), iter1.prev().unwrap().0);
+
+        let entries: Vec<_> = engine
+            .entries(new_opts())
+            .unwrap()
+            .map(|e| e.unwrap())
+            .collect();
+        assert_eq!(4, entries.len());
+        let count = engine
+            .fold_with_options(new_opts(), 0, |acc, _, _| ControlFlow::Continue(acc + 1))
+            .unwrap();
+        assert_eq!(4, count);
+
+        // 删除测试的文件夹
+        std::mem::drop(engine);
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+
+    #[test]
+    fn test_index_entries() {
+        for index_type in [IndexType::SkipList, IndexType::BTree] {
+            let mut opts = Options::default();
+            opts.dir_path = PathBuf::from("/tmp/bitcask-rs-index-entries");
+            opts.index_type = index_type;
+            let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+            for i in 0..100 {
+                let put_res = engine.put(
+                    util::rand_kv::get_test_key(i),
+                    util::rand_kv::get_test_value(i),
+                );
+                assert!(put_res.is_ok());
+            }
+
+            // 返回的数据位置和索引中的一致
+            for (key, pos) in engine.index_entries(IteratorOptions::default()).unwrap() {
+                let index_pos = engine.index.get(key.to_vec()).unwrap();
+                assert_eq!(index_pos.file_id, pos.file_id);
+                assert_eq!(index_pos.offset, pos.offset);
+            }
+
+            // 使用标准迭代器的组合方法
+            let keys1 = engine
+                .index_entries(IteratorOptions::default())
+                .unwrap()
+                .filter(|(key, _)| key.ends_with(b"5"))
+                .map(|(key, _)| key)
+                .take(3)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                vec![
+                    util::rand_kv::get_test_key(5),
+                    util::rand_kv::get_test_key(15),
+                    util::rand_kv::get_test_key(25)
+                ],
+                keys1
+            );
+
+            let mut iter_opts = IteratorOptions::default();
+            iter_opts.reverse = true;
+            let (key2, _) = engine.index_entries(iter_opts).unwrap().next().unwrap();
+            assert_eq!(util::rand_kv::get_test_key(99), key2);
+
+            let total_size: u64 = engine
+                .index_entries(IteratorOptions::default())
+                .unwrap()
+                .map(|(_, pos)| pos.size as u64)
+                .sum();
+            assert!(total_size > 0);
+            assert_eq!(
+                100,
+                engine
+                    .index_entries(IteratorOptions::default())
+                    .unwrap()
+                    .count()
+            );
+
+            // 删除测试的文件夹
+            std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+        }
+    }
 }
@@ -0,0 +1,479 @@
+use std::{
+    collections::HashMap,
+    sync::{atomic::Ordering, Arc},
+};
+
+use bytes::Bytes;
+use log::warn;
+use parking_lot::RwLock;
+
+use crate::{
+    batch::{log_record_key_with_seq, NON_TRANSACTION_SEQ_NO},
+    data::{
+        data_file::{DataFile, KEYSPACES_FILE_NAME},
+        log_record::{LogRecord, LogRecordPos, LogRecordType},
+    },
+    db::Engine,
+    error::{Errors, Result},
+    index::{self, Index},
+    iterator::Iterator,
+    metrics::OpKind,
+    option::{Compression, IteratorOptions, Options},
+    util,
+};
+
+// 单个 keyspace，和其他 keyspace 共享数据文件，但是有独立的内存索引
+pub(crate) struct Keyspace {
+    pub(crate) id: u32,
+    pub(crate) name: String,
+    pub(crate) index: Box<dyn Index<LogRecordPos>>,
+}
+
+impl Keyspace {
+    // 加载索引或者写入数据之后更新内存索引，返回可以 merge 回收的空间大小
+    pub(crate) fn update_index(
+        &self,
+        key: Vec<u8>,
+        rec_type: LogRecordType,
+        pos: LogRecordPos,
+    ) -> usize {
+        match rec_type {
+            LogRecordType::NORMAL => self
+                .index
+                .put(key, pos)
+                .map_or(0, |old_pos| old_pos.size as usize),
+            LogRecordType::DELETED => {
+                let old_size = self
+                    .index
+                    .delete(key)
+                    .map_or(0, |old_pos| old_pos.size as usize);
+                pos.size as usize + old_size
+            }
+            LogRecordType::TXNFINISHED => 0,
+        }
+    }
+}
+
+// 所有已经创建的 keyspace，名称和 id 的对应关系保存在 KEYSPACES_FILE_NAME 文件中
+// 默认的 keyspace 的 id 为 0，使用 Engine 自身的索引，不会记录在文件中
+pub(crate) struct Keyspaces {
+    by_name: RwLock<HashMap<String, Arc<Keyspace>>>,
+    by_id: RwLock<HashMap<u32, Arc<Keyspace>>>,
+}
+
+impl Keyspaces {
+    // 从数据目录中加载已经创建的 keyspace，文件末尾没有完整写入的记录直接忽略
+    pub(crate) fn load(options: &Options) -> Result<Self> {
+        let keyspaces = Keyspaces {
+            by_name: RwLock::new(HashMap::new()),
+            by_id: RwLock::new(HashMap::new()),
+        };
+        if !util::file::is_file(options, &options.dir_path.join(KEYSPACES_FILE_NAME)) {
+            return Ok(keyspaces);
+        }
+
+        let keyspaces_file = DataFile::new_keyspaces_file(options, options.read_only)?;
+        let mut offset = 0;
+        loop {
+            let read_record = match keyspaces_file.read_log_record(offset) {
+                Ok(read_record) => read_record,
+                Err(Errors::ReadDataFileEOF) => break,
+                Err(e) => {
+                    warn!("keyspaces file is corrupted at offset {}: {:?}", offset, e);
+                    break;
+                }
+            };
+            offset += read_record.size as u64;
+
+            let record = read_record.record;
+            let name = String::from_utf8_lossy(&record.key).to_string();
+            let id = String::from_utf8_lossy(&record.value).parse::<u32>();
+            match id {
+                Ok(id) if id > 0 => {
+                    let keyspace = new_keyspace(options, id, &name);
+                    keyspaces.by_name.write().insert(name, keyspace.clone());
+                    keyspaces.by_id.write().insert(id, keyspace);
+                }
+                _ => warn!("skip invalid keyspace record of {}", name),
+            }
+        }
+        Ok(keyspaces)
+    }
+
+    // 获取名称对应的 keyspace，不存在时分配新的 id 并持久化
+    fn get_or_create(&self, options: &Options, name: &str) -> Result<Arc<Keyspace>> {
+        if let Some(keyspace) = self.by_name.read().get(name) {
+            return Ok(keyspace.clone());
+        }
+
+        // 持有写锁，避免同时创建同名的 keyspace
+        let mut by_name = self.by_name.write();
+        if let Some(keyspace) = by_name.get(name) {
+            return Ok(keyspace.clone());
+        }
+        let id = self.by_id.read().keys().max().copied().unwrap_or(0) + 1;
+
+        let is_new_file =
+            !util::file::is_file(options, &options.dir_path.join(KEYSPACES_FILE_NAME));
+        let keyspaces_file = DataFile::new_keyspaces_file(options, false)?;
+        let record = LogRecord {
+            key: name.as_bytes().to_vec(),
+            value: id.to_string().into_bytes(),
+            rec_type: LogRecordType::NORMAL,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
+        };
+        keyspaces_file.write(&record.encode())?;
+        keyspaces_file.sync()?;
+        if is_new_file {
+            util::file::sync_dir(&options.dir_path)?;
+        }
+
+        let keyspace = new_keyspace(options, id, name);
+        by_name.insert(name.to_string(), keyspace.clone());
+        self.by_id.write().insert(id, keyspace.clone());
+        Ok(keyspace)
+    }
+
+    pub(crate) fn get(&self, id: u32) -> Option<Arc<Keyspace>> {
+        self.by_id.read().get(&id).cloned()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.by_id.read().is_empty()
+    }
+
+    // 清空所有 keyspace 的内存索引，keyspace 本身仍然保留
+    pub(crate) fn clear_indexes(&self) {
+        for keyspace in self.by_id.read().values() {
+            keyspace.index.clear();
+        }
+    }
+}
+
+fn new_keyspace(options: &Options, id: u32, name: &str) -> Arc<Keyspace> {
+    Arc::new(Keyspace {
+        id,
+        name: name.to_string(),
+        index: index::new_indexer(options.index_type.clone(), options.dir_path.clone()),
+    })
+}
+
+/// keyspace 的句柄，通过它读写对应 keyspace 中的数据
+/// 不同 keyspace 中的 key 互不影响，数据写入同一组数据文件，merge 时按照各自的索引判断数据是否有效
+/// Engine::snapshot 和 value 缓存只覆盖默认的 keyspace，通过句柄读写的数据不会出现在快照和缓存中
+pub struct KeyspaceHandle<'a> {
+    engine: &'a Engine,
+    keyspace: Arc<Keyspace>,
+}
+
+impl Engine {
+    /// 获取名称对应的 keyspace，不存在时创建，只读模式下不能创建新的 keyspace
+    /// 默认的 keyspace 即 Engine 自身的读写接口，不需要通过句柄访问
+    pub fn keyspace(&self, name: &str) -> Result<KeyspaceHandle<'_>> {
+        if name.is_empty() {
+            return Err(Errors::KeyspaceNameIsEmpty);
+        }
+        if !self.keyspace_exists(name) {
+            self.check_writable()?;
+        }
+        let keyspace = self.keyspaces.get_or_create(&self.options, name)?;
+        Ok(KeyspaceHandle {
+            engine: self,
+            keyspace,
+        })
+    }
+
+    fn keyspace_exists(&self, name: &str) -> bool {
+        self.keyspaces.by_name.read().contains_key(name)
+    }
+}
+
+impl<'a> KeyspaceHandle<'a> {
+    // keyspace 的名称
+    pub fn name(&self) -> &str {
+        &self.keyspace.name
+    }
+
+    /// 存储 key/value 数据，key 不能为空，返回分配的写入序列号
+    pub fn put(&self, key: Bytes, value: Bytes) -> Result<u64> {
+        self.engine
+            .with_metrics(OpKind::Put, || self.put_value(key, value))
+    }
+
+    fn put_value(&self, key: Bytes, value: Bytes) -> Result<u64> {
+        self.engine.check_writable()?;
+        self.engine.wait_index_ready()?;
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+        self.engine.check_key_value_size(&key, &value)?;
+
+        let (value, compression) = self.engine.compress_value(&value)?;
+        let (value, encrypted) = self.engine.encrypt_value(&key, value)?;
+        let record = LogRecord {
+            key: log_record_key_with_seq(key.to_vec(), NON_TRANSACTION_SEQ_NO),
+            value,
+            rec_type: LogRecordType::NORMAL,
+            expire: 0,
+            compression,
+            encrypted,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: self.keyspace.id,
+        };
+        let _lock = self.engine.write_lock.read();
+        self.write_record(key, record)
+    }
+
+    /// 根据 key 获取对应的数据
+    pub fn get(&self, key: Bytes) -> Result<Bytes> {
+        self.engine
+            .with_metrics(OpKind::Get, || self.get_value(key))
+    }
+
+    fn get_value(&self, key: Bytes) -> Result<Bytes> {
+        self.engine.wait_index_ready()?;
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+        match self.keyspace.index.get(key.to_vec()) {
+            Some(pos) => self.engine.get_value_by_position(&pos),
+            None => Err(Errors::KeyNotFound),
+        }
+    }
+
+    /// 根据 key 删除对应的数据，返回删除标记的写入序列号，key 不存在时不写入删除标记，返回 None
+    pub fn delete(&self, key: Bytes) -> Result<Option<u64>> {
+        self.engine
+            .with_metrics(OpKind::Delete, || self.delete_value(key))
+    }
+
+    fn delete_value(&self, key: Bytes) -> Result<Option<u64>> {
+        self.engine.check_writable()?;
+        self.engine.wait_index_ready()?;
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+
+        let _lock = self.engine.write_lock.read();
+        if self.keyspace.index.get(key.to_vec()).is_none() {
+            return Ok(None);
+        }
+        let record = LogRecord {
+            key: log_record_key_with_seq(key.to_vec(), NON_TRANSACTION_SEQ_NO),
+            value: Default::default(),
+            rec_type: LogRecordType::DELETED,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: self.keyspace.id,
+        };
+        self.write_record(key, record).map(Some)
+    }
+
+    // 写入数据并更新 keyspace 的索引，调用方需要持有 write_lock
+    fn write_record(&self, key: Bytes, mut record: LogRecord) -> Result<u64> {
+        let pos = self.engine.append_log_record(&mut record)?;
+        let reclaim_size = self
+            .keyspace
+            .update_index(key.to_vec(), record.rec_type, pos);
+        self.engine
+            .reclaim_size
+            .fetch_add(reclaim_size, Ordering::SeqCst);
+        Ok(record.sequence)
+    }
+
+    /// 判断 key 是否存在，只访问内存索引
+    pub fn contains_key(&self, key: Bytes) -> Result<bool> {
+        self.engine.wait_index_ready()?;
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+        Ok(self.keyspace.index.get(key.to_vec()).is_some())
+    }
+
+    // 获取 keyspace 中所有的 key
+    pub fn list_keys(&self) -> Result<Vec<Bytes>> {
+        self.engine.wait_index_ready()?;
+        self.keyspace.index.list_keys()
+    }
+
+    // 返回 keyspace 中 key 的数量，只访问内存索引
+    pub fn key_count(&self) -> usize {
+        self.keyspace.index.len()
+    }
+
+    // 遍历 keyspace 中的数据，和 Engine::iter 的用法相同
+    pub fn iter(&self, options: IteratorOptions) -> Result<Iterator<'a>> {
+        self.engine.wait_index_ready()?;
+        let value_filter = options.value_filter.clone();
+        Ok(Iterator::new(
+            self.keyspace.index.iterator(options),
+            self.engine,
+            value_filter,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_keyspace_isolation() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-keyspace-isolation");
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        let users = engine.keyspace("users").unwrap();
+        let orders = engine.keyspace("orders").unwrap();
+        assert_eq!("users", users.name());
+        assert_eq!(
+            Err(Errors::KeyspaceNameIsEmpty),
+            engine.keyspace("").map(|_| ())
+        );
+
+        // 同一个 key 在不同的 keyspace 中是不同的数据
+        engine
+            .put(Bytes::from("key"), Bytes::from("default"))
+            .unwrap();
+        users.put(Bytes::from("key"), Bytes::from("user")).unwrap();
+        orders
+            .put(Bytes::from("key"), Bytes::from("order"))
+            .unwrap();
+        orders
+            .put(Bytes::from("other"), Bytes::from("order-2"))
+            .unwrap();
+        assert_eq!(
+            Bytes::from("default"),
+            engine.get(Bytes::from("key")).unwrap()
+        );
+        assert_eq!(Bytes::from("user"), users.get(Bytes::from("key")).unwrap());
+        assert_eq!(
+            Bytes::from("order"),
+            orders.get(Bytes::from("key")).unwrap()
+        );
+        assert_eq!(Err(Errors::KeyNotFound), users.get(Bytes::from("other")));
+        assert_eq!(Err(Errors::KeyIsEmpty), users.contains_key(Bytes::new()));
+        assert!(orders.contains_key(Bytes::from("other")).unwrap());
+        assert_eq!(Err(Errors::KeyNotFound), engine.get(Bytes::from("other")));
+        assert_eq!(1, engine.key_count());
+        assert_eq!(1, users.key_count());
+        assert_eq!(2, orders.key_count());
+
+        // 删除只影响对应的 keyspace
+        assert!(users.delete(Bytes::from("key")).unwrap().is_some());
+        assert!(users.delete(Bytes::from("key")).unwrap().is_none());
+        assert_eq!(Err(Errors::KeyNotFound), users.get(Bytes::from("key")));
+        assert_eq!(
+            Bytes::from("order"),
+            orders.get(Bytes::from("key")).unwrap()
+        );
+        assert_eq!(
+            Bytes::from("default"),
+            engine.get(Bytes::from("key")).unwrap()
+        );
+
+        let iter = orders.iter(IteratorOptions::default()).unwrap();
+        assert_eq!(
+            Some((Bytes::from("key"), Bytes::from("order"))),
+            iter.next()
+        );
+        assert_eq!(
+            Some((Bytes::from("other"), Bytes::from("order-2"))),
+            iter.next()
+        );
+        assert!(iter.next().is_none());
+        drop(iter);
+        drop(users);
+        drop(orders);
+
+        // 重新打开之后从数据文件中恢复每个 keyspace 的索引
+        engine.close().unwrap();
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let users = engine.keyspace("users").unwrap();
+        let orders = engine.keyspace("orders").unwrap();
+        assert_eq!(Err(Errors::KeyNotFound), users.get(Bytes::from("key")));
+        assert_eq!(
+            Bytes::from("order"),
+            orders.get(Bytes::from("key")).unwrap()
+        );
+        assert_eq!(
+            Bytes::from("default"),
+            engine.get(Bytes::from("key")).unwrap()
+        );
+        assert_eq!(1, engine.list_keys().unwrap().len());
+        assert_eq!(2, orders.list_keys().unwrap().len());
+
+        // 新建的 keyspace 使用新的 id
+        let logs = engine.keyspace("logs").unwrap();
+        assert_eq!(3, logs.keyspace.id);
+        drop(users);
+        drop(orders);
+        drop(logs);
+
+        engine.close().unwrap();
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove path");
+    }
+
+    #[test]
+    fn test_keyspace_merge() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-keyspace-merge");
+        opts.data_file_size = 64 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        // 两个 keyspace 中写入相同的 key，并覆盖写入产生可以回收的数据
+        let ks1 = engine.keyspace("ks1").unwrap();
+        let ks2 = engine.keyspace("ks2").unwrap();
+        for round in 0..3 {
+            for i in 0..500 {
+                let key = Bytes::from(format!("key-{:05}", i));
+                ks1.put(key.clone(), Bytes::from(format!("ks1-{}-{}", round, i)))
+                    .unwrap();
+                ks2.put(key, Bytes::from(format!("ks2-{}-{}", round, i)))
+                    .unwrap();
+            }
+        }
+        for i in 0..100 {
+            ks2.delete(Bytes::from(format!("key-{:05}", i))).unwrap();
+        }
+        drop(ks1);
+        drop(ks2);
+        engine.merge().unwrap();
+        engine.close().unwrap();
+
+        // merge 之后从 hint 文件中恢复每个 keyspace 的索引
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let ks1 = engine.keyspace("ks1").unwrap();
+        let ks2 = engine.keyspace("ks2").unwrap();
+        assert_eq!(0, engine.key_count());
+        assert_eq!(500, ks1.key_count());
+        assert_eq!(400, ks2.key_count());
+        for i in 0..500 {
+            let key = Bytes::from(format!("key-{:05}", i));
+            assert_eq!(
+                Bytes::from(format!("ks1-2-{}", i)),
+                ks1.get(key.clone()).unwrap()
+            );
+            match i < 100 {
+                true => assert_eq!(Err(Errors::KeyNotFound), ks2.get(key)),
+                false => assert_eq!(Bytes::from(format!("ks2-2-{}", i)), ks2.get(key).unwrap()),
+            }
+        }
+        drop(ks1);
+        drop(ks2);
+
+        engine.close().unwrap();
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove path");
+    }
+}
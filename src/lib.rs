@@ -1,13 +1,27 @@
+#[cfg(feature = "async")]
+pub mod async_engine;
 pub mod batch;
+mod bloom;
+mod cache;
+pub mod changes;
+pub mod clock;
 mod data;
 pub mod db;
 pub mod error;
 mod fileio;
 mod index;
+pub mod index_snapshot;
 pub mod iterator;
+pub mod keyspace;
 pub mod merge;
+pub mod metrics;
 pub mod option;
+pub mod repair;
+pub mod scan;
+pub mod snapshot;
 mod util;
 
+pub use data::log_record::{LogRecord, LogRecordPos, LogRecordType, ReadLogRecord};
+
 #[cfg(test)]
 mod db_tests;
@@ -1,156 +1,480 @@
-use std::{fs, path::PathBuf, sync::atomic::Ordering};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
 
-use log::error;
+use log::{error, warn};
 
 use crate::{
     batch::{log_record_key_with_seq, parse_log_record_key, NON_TRANSACTION_SEQ_NO},
+    bloom::remove_bloom_file,
     data::{
         data_file::{
-            get_data_file_name, DataFile, DATA_FILE_NAME_SUFFIX, HINT_FILE_NAME,
-            MERGE_FINISHED_FILE_NAME, SEQ_NO_FILE_NAME,
+            get_data_file_name, parse_data_file_id, DataFile, DATA_FILE_HEADER_SIZE,
+            HINT_FILE_NAME, MERGE_CHECKPOINT_FILE_NAME, MERGE_FINISHED_FILE_NAME, SEQ_NO_FILE_NAME,
         },
         log_record::{decode_log_record_pos, LogRecord, LogRecordType},
     },
-    db::{Engine, FILE_LOCK_NAME},
+    db::{older_io_type, Engine, IndexLoader, FILE_LOCK_NAME},
     error::{Errors, Result},
-    option::{IOType, Options},
+    metrics::OpKind,
+    option::{Compression, IOType, Options},
     util,
 };
 
 const MERGE_DIR_NAME: &str = "merge";
 const MERGE_FIN_KEY: &[u8] = "merge.finished".as_bytes();
+const MERGE_CHECKPOINT_KEY: &[u8] = "merge.checkpoint".as_bytes();
+
+// merge 标识的守卫，离开作用域时重置标识，允许下一次 merge
+pub(crate) struct MergeGuard<'a> {
+    is_merging: &'a AtomicBool,
+}
+
+impl Drop for MergeGuard<'_> {
+    fn drop(&mut self) {
+        self.is_merging.store(false, Ordering::SeqCst);
+    }
+}
+
+/// merge 的进度信息
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MergeProgress {
+    // 已经处理完成的数据文件数量
+    pub files_processed: usize,
+    // 需要处理的数据文件总数
+    pub files_total: usize,
+    // 已经重写的有效数据的字节数
+    pub bytes_written: u64,
+}
 
 impl Engine {
     // merge 数据目录，处理无效数据，并生成 hint 索引文件
     pub fn merge(&self) -> Result<()> {
+        self.merge_with_progress(None)
+    }
+
+    // merge 数据目录，每处理完一个数据文件调用一次 progress 汇报进度
+    // 调用 progress 时不持有引擎内部的锁
+    pub fn merge_with_progress(
+        &self,
+        progress: Option<Box<dyn Fn(MergeProgress) + Send>>,
+    ) -> Result<()> {
+        self.with_metrics(OpKind::Merge, || self.merge_files(progress))
+    }
+
+    fn merge_files(&self, progress: Option<Box<dyn Fn(MergeProgress) + Send>>) -> Result<()> {
+        self.check_writable()?;
+        self.wait_index_ready()?;
+
         // 如果是空的数据库则直接返回
         if self.is_empty_engine() {
             return Ok(());
         }
 
         // 如果正在 merge，则直接返回
-        let lock = self.merging_lock.try_lock();
-        if lock.is_none() {
+        let _guard = self.start_merge()?;
+
+        // 判断是否达到了 merge 的比例阈值，以及磁盘空间是否足够
+        self.check_merge_ratio()?;
+
+        let merge_path = get_merge_path(self.options.dir_path.clone());
+        create_merge_dir(&self.options, &merge_path)?;
+
+        // 获取所有需要进行 merge 的数据文件
+        let merge_files = self.rotate_merge_files()?;
+
+        // 打开临时用于 merge 的 bitcask 实例
+        let output_file_size = self.merge_output_file_size(&merge_files);
+        let merge_db = self.open_merge_db(merge_path.clone(), output_file_size)?;
+
+        // 打开 hint 文件存储索引
+        let hint_file = DataFile::new_hint_file(&self.options, merge_path.clone())?;
+        // 依次处理每个数据文件，重写有效的数据，已经过期的数据直接丢弃
+        self.rewrite_data_files(&merge_files, &merge_db, &hint_file, &progress)?;
+
+        // sync 保证持久化
+        merge_db.sync()?;
+        hint_file.sync()?;
+
+        // 拿到最近未参与 merge 的文件 id
+        let non_merge_file_id = merge_files.last().unwrap().get_file_id() + 1;
+        write_merge_fin_file(
+            &self.options,
+            &merge_path,
+            non_merge_file_id,
+            self.sequence.load(Ordering::SeqCst),
+        )?;
+
+        // merge 的结果不包含删除标记，清理参与 merge 的数据文件中的删除标记
+        self.prune_tombstones(non_merge_file_id);
+        Ok(())
+    }
+
+    /// 按照预算分批 merge，每次最多重写大约 max_bytes 的有效数据，剩余的数据留给下一次调用
+    /// 已经处理完成的文件会记录在检查点中，重启之后可以继续 merge
+    /// 返回 true 表示 merge 已经全部完成，重启之后生效
+    pub fn merge_with_budget(&self, max_bytes: u64) -> Result<bool> {
+        self.check_writable()?;
+        self.wait_index_ready()?;
+
+        // 如果是空的数据库则直接返回
+        if self.is_empty_engine() {
+            return Ok(true);
+        }
+
+        // 如果正在 merge，则直接返回
+        let _guard = self.start_merge()?;
+
+        let merge_path = get_merge_path(self.options.dir_path.clone());
+        let (merge_files, non_merge_file_id, output_file_size) =
+            match load_merge_checkpoint(&self.options, &merge_path)? {
+                // 从上一次的检查点继续 merge，输出文件的大小和第一次调用时相同
+                Some((next_file_id, non_merge_file_id, output_file_size)) => {
+                    let mut merge_files = Vec::new();
+                    for file_id in next_file_id..non_merge_file_id {
+                        let file_name = get_data_file_name(&self.options, file_id);
+                        if util::file::is_file(&self.options, &file_name) {
+                            merge_files.push(DataFile::new(
+                                &self.options,
+                                file_id,
+                                IOType::StandardFIO,
+                            )?);
+                        }
+                    }
+                    (merge_files, non_merge_file_id, output_file_size)
+                }
+                // 开始新的 merge，按照所有参与 merge 的文件计算输出文件的大小
+                None => {
+                    self.check_merge_ratio()?;
+                    create_merge_dir(&self.options, &merge_path)?;
+                    let merge_files = self.rotate_merge_files()?;
+                    let non_merge_file_id = merge_files.last().unwrap().get_file_id() + 1;
+                    let output_file_size = self.merge_output_file_size(&merge_files);
+                    (merge_files, non_merge_file_id, output_file_size)
+                }
+            };
+
+        let merge_db = self.open_merge_db(merge_path.clone(), output_file_size)?;
+        let hint_file = DataFile::new_hint_file(&self.options, merge_path.clone())?;
+
+        // 分批处理数据文件，超过预算之后停止，至少处理一批文件
+        // 配置了 merge_pool 时每批文件的数量和线程数相同，同一批文件并发重写
+        let mut bytes_written = 0;
+        let mut next_file_id = non_merge_file_id;
+        for batch in merge_files.chunks(self.merge_batch_size()) {
+            if bytes_written >= max_bytes {
+                next_file_id = batch[0].get_file_id();
+                break;
+            }
+            bytes_written += self.rewrite_data_files(batch, &merge_db, &hint_file, &None)?;
+        }
+
+        // 还有没有处理的文件，记录检查点之后返回
+        if next_file_id < non_merge_file_id {
+            merge_db.sync()?;
+            hint_file.sync()?;
+            write_merge_checkpoint(
+                &self.options,
+                &merge_path,
+                (next_file_id, non_merge_file_id, output_file_size),
+            )?;
+            return Ok(false);
+        }
+
+        // merge 的文件范围在第一次调用时已经确定，之后的写入都在范围之外的文件中
+        // 重启时会在 hint 索引之后加载，因此之前批次中重写的数据不会覆盖更新的数据
+        merge_db.sync()?;
+        hint_file.sync()?;
+
+        util::file::remove_file(&self.options, &merge_path.join(MERGE_CHECKPOINT_FILE_NAME))?;
+        write_merge_fin_file(
+            &self.options,
+            &merge_path,
+            non_merge_file_id,
+            self.sequence.load(Ordering::SeqCst),
+        )?;
+        self.prune_tombstones(non_merge_file_id);
+        Ok(true)
+    }
+
+    // 设置正在 merge 的标识，返回的守卫离开作用域时（包括出错或者 panic）重置标识
+    pub(crate) fn start_merge(&self) -> Result<MergeGuard<'_>> {
+        if self
+            .is_merging
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
             return Err(Errors::MergeInProgress);
         }
+        Ok(MergeGuard {
+            is_merging: &self.is_merging,
+        })
+    }
 
-        // 判断是否达到了 merge 的比例阈值
+    // 判断是否达到了 merge 的比例阈值，以及磁盘剩余空间是否足够容纳 merge 之后的数据
+    // 使用 io_factory 时数据文件不在本地磁盘上，按照所有数据文件的大小计算，不检查磁盘剩余空间
+    fn check_merge_ratio(&self) -> Result<()> {
         let reclaim_size = self.reclaim_size.load(Ordering::SeqCst);
-        let total_size = util::file::dir_disk_size(self.options.dir_path.clone());
+        let total_size = match &self.options.io_factory {
+            Some(_) => {
+                let active_file = self.active_file.read();
+                let older_files = self.older_files.read();
+                let older_size: u64 = older_files.values().map(|file| file.file_size()).sum();
+                active_file.file_size() + older_size
+            }
+            None => util::file::dir_disk_size(self.options.dir_path.clone()),
+        };
         if (reclaim_size as f32 / total_size as f32) < self.options.data_file_merge_ratio {
             return Err(Errors::MergeRatioUnreached);
         }
+        if self.options.io_factory.is_some() {
+            return Ok(());
+        }
 
-        // 判断磁盘剩余空间是否足够容纳 merge 之后的数据
         let available_size = util::file::available_disk_size();
         if total_size - reclaim_size as u64 >= available_size {
             return Err(Errors::MeregeNoEnoughSpace);
         }
+        Ok(())
+    }
 
-        let merge_path = get_merge_path(self.options.dir_path.clone());
-        // 如果目录已经存在，则先删除
-        if merge_path.is_dir() {
-            fs::remove_dir_all(merge_path.clone()).unwrap();
+    // 根据 merge_target_file_count 计算 merge 输出的每个数据文件的大小，有效数据按照文件总大小减去无效数据估算
+    fn merge_output_file_size(&self, merge_files: &[DataFile]) -> u64 {
+        let target_count = self.options.merge_target_file_count as u64;
+        if target_count == 0 {
+            return self.options.data_file_size;
         }
-        // 创建 merge 数据目录
-        if let Err(e) = fs::create_dir_all(merge_path.clone()) {
-            error!("failed to create merge path {}", e);
-            return Err(Errors::FailedToCreateDatabaseDir);
-        }
-
-        // 获取所有需要进行 merge 的数据文件
-        let merge_files = self.rotate_merge_files()?;
+        let total_size: u64 = merge_files.iter().map(|file| file.file_size()).sum();
+        let live_size = total_size.saturating_sub(self.reclaim_size.load(Ordering::SeqCst) as u64);
+        let file_size = live_size.div_ceil(target_count).max(1) + DATA_FILE_HEADER_SIZE;
+        file_size.min(self.options.data_file_size)
+    }
 
-        // 打开临时用于 merge 的 bitcask 实例
+    // 打开临时用于 merge 的 bitcask 实例，data_file_size 为输出的每个数据文件的大小
+    fn open_merge_db(&self, merge_path: PathBuf, data_file_size: u64) -> Result<Engine> {
         let mut merge_db_opts = Options::default();
-        merge_db_opts.dir_path = merge_path.clone();
-        merge_db_opts.data_file_size = self.options.data_file_size;
-        let merge_db = Engine::open(merge_db_opts)?;
+        merge_db_opts.dir_path = merge_path;
+        merge_db_opts.data_file_size = data_file_size;
+        merge_db_opts.data_file_ext = self.options.data_file_ext.clone();
+        merge_db_opts.data_file_id_width = self.options.data_file_id_width;
+        merge_db_opts.checksum = self.options.checksum;
+        merge_db_opts.checksum_enabled = self.options.checksum_enabled;
+        merge_db_opts.io_retry_count = self.options.io_retry_count;
+        merge_db_opts.io_factory = self.options.io_factory.clone();
+        merge_db_opts.enable_bloom_filters = self.options.enable_bloom_filters;
+        merge_db_opts.bloom_false_positive_rate = self.options.bloom_false_positive_rate;
+        Engine::open(merge_db_opts)
+    }
 
-        // 打开 hint 文件存储索引
-        let hint_file = DataFile::new_hint_file(merge_path.clone())?;
-        // 依次处理每个数据文件，重写有效的数据
-        for data_file in merge_files.iter() {
-            let mut offset = 0;
-            loop {
-                let (mut log_record, size) = match data_file.read_log_record(offset) {
-                    Ok(result) => (result.record, result.size),
-                    Err(e) => {
-                        if e == Errors::ReadDataFileEOF {
-                            break;
-                        }
-                        return Err(e);
+    // 重写数据文件中的有效数据，并写入 hint 索引，已经过期的数据直接丢弃，返回重写的字节数
+    fn rewrite_data_file(
+        &self,
+        data_file: &DataFile,
+        merge_db: &Engine,
+        hint_file: &DataFile,
+    ) -> Result<u64> {
+        let now = self.now_millis();
+        let mut bytes_written = 0;
+        let mut records_since_sync = 0;
+        let mut offset = data_file.header_size();
+        loop {
+            let (mut log_record, size) = match data_file.read_log_record(offset) {
+                Ok(result) => (result.record, result.size),
+                Err(e) => {
+                    if e == Errors::ReadDataFileEOF {
+                        break;
                     }
-                };
-
-                // 解码拿到实际的 key
-                let (real_key, _) = parse_log_record_key(log_record.key.clone());
-                if let Some(index_pos) = self.index.get(real_key.clone()) {
-                    // 如果文件 id 和偏移 offset 均相等，则说明是一条有效的数据
-                    if index_pos.file_id == data_file.get_file_id() && index_pos.offset == offset {
-                        // 去除事务的标识
-                        log_record.key =
-                            log_record_key_with_seq(real_key.clone(), NON_TRANSACTION_SEQ_NO);
-                        let log_record_pos = merge_db.append_log_record(&mut log_record)?;
-                        // 写 hint 索引
-                        hint_file.write_hint_record(real_key.clone(), log_record_pos)?;
+                    return Err(e);
+                }
+            };
+
+            // 解码拿到实际的 key，在数据所属的 keyspace 的索引中查找
+            let (real_key, _) = parse_log_record_key(log_record.key.clone());
+            let index_pos = match log_record.keyspace {
+                0 => self.index.get(real_key.clone()),
+                id => self
+                    .keyspaces
+                    .get(id)
+                    .and_then(|keyspace| keyspace.index.get(real_key.clone())),
+            };
+            if let Some(index_pos) = index_pos {
+                // 如果文件 id 和偏移 offset 均相等，则说明是一条有效的数据
+                if index_pos.file_id == data_file.get_file_id()
+                    && index_pos.offset == offset
+                    && !log_record.is_expired(now)
+                {
+                    // 去除事务的标识
+                    log_record.key =
+                        log_record_key_with_seq(real_key.clone(), NON_TRANSACTION_SEQ_NO);
+                    let log_record_pos = merge_db.append_rewritten_log_record(&mut log_record)?;
+                    bytes_written += log_record_pos.size as u64;
+                    // 写 hint 索引
+                    hint_file.write_hint_record(
+                        real_key.clone(),
+                        log_record.keyspace,
+                        log_record_pos,
+                    )?;
+
+                    // 定期持久化 merge 的输出，减少断电时丢失的数据
+                    records_since_sync += 1;
+                    let interval = self.options.merge_sync_interval;
+                    if interval > 0 && records_since_sync >= interval {
+                        merge_db.sync()?;
+                        hint_file.sync()?;
+                        records_since_sync = 0;
                     }
                 }
-                offset += size as u64;
             }
+            offset += size as u64;
         }
+        Ok(bytes_written)
+    }
 
-        // sync 保证持久化
-        merge_db.sync()?;
-        hint_file.sync()?;
+    // 重写一组数据文件，每处理完一个文件汇报一次进度，返回重写的字节数
+    // 配置了 merge_pool 时在线程池中并发重写；在线程池的工作线程中调用 merge 时，等待结果会阻塞这个工作线程，
+    // 线程池的线程都在等待时重写任务无法执行导致死锁，因此这种情况下直接在当前线程中依次重写
+    fn rewrite_data_files(
+        &self,
+        merge_files: &[DataFile],
+        merge_db: &Engine,
+        hint_file: &DataFile,
+        progress: &Option<Box<dyn Fn(MergeProgress) + Send>>,
+    ) -> Result<u64> {
+        #[cfg(feature = "merge-pool")]
+        if let Some(pool) = self.usable_merge_pool() {
+            return self.rewrite_data_files_in_pool(
+                pool,
+                merge_files,
+                merge_db,
+                hint_file,
+                progress,
+            );
+        }
 
-        // 拿到最近未参与 merge 的文件 id
-        let non_merge_file_id = merge_files.last().unwrap().get_file_id() + 1;
-        let merge_fin_file = DataFile::new_merge_fin_file(merge_path.clone())?;
-        let merge_fin_record = LogRecord {
-            key: MERGE_FIN_KEY.to_vec(),
-            value: non_merge_file_id.to_string().into_bytes(),
-            rec_type: LogRecordType::NORMAL,
-        };
-        let enc_record = merge_fin_record.encode();
-        merge_fin_file.write(&enc_record)?;
-        merge_fin_file.sync()?;
+        let mut bytes_written = 0;
+        for (i, data_file) in merge_files.iter().enumerate() {
+            bytes_written += self.rewrite_data_file(data_file, merge_db, hint_file)?;
+
+            // 汇报进度
+            if let Some(progress) = progress {
+                progress(MergeProgress {
+                    files_processed: i + 1,
+                    files_total: merge_files.len(),
+                    bytes_written,
+                });
+            }
+        }
+        Ok(bytes_written)
+    }
 
-        Ok(())
+    // 可以用于 merge 的线程池，当前线程是线程池的工作线程时返回 None
+    #[cfg(feature = "merge-pool")]
+    fn usable_merge_pool(&self) -> Option<&rayon::ThreadPool> {
+        self.options
+            .merge_pool
+            .as_deref()
+            .filter(|pool| pool.current_thread_index().is_none())
+    }
+
+    // 分批 merge 时每批处理的数据文件数量，在线程池中并发重写时和线程数相同
+    fn merge_batch_size(&self) -> usize {
+        #[cfg(feature = "merge-pool")]
+        if let Some(pool) = self.usable_merge_pool() {
+            return pool.current_num_threads().max(1);
+        }
+        1
+    }
+
+    // 将每个数据文件的重写任务提交到线程池中执行，进度回调在当前线程中调用
+    #[cfg(feature = "merge-pool")]
+    fn rewrite_data_files_in_pool(
+        &self,
+        pool: &rayon::ThreadPool,
+        merge_files: &[DataFile],
+        merge_db: &Engine,
+        hint_file: &DataFile,
+        progress: &Option<Box<dyn Fn(MergeProgress) + Send>>,
+    ) -> Result<u64> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        // 当前线程只负责汇总结果，重写任务全部在线程池中执行
+        pool.in_place_scope(|s| {
+            for data_file in merge_files {
+                let tx = tx.clone();
+                s.spawn(move |_| {
+                    let res = self.rewrite_data_file(data_file, merge_db, hint_file);
+                    let _ = tx.send(res);
+                });
+            }
+            drop(tx);
+
+            let mut result = Ok(0);
+            let mut files_processed = 0;
+            let mut bytes_written = 0;
+            for res in rx {
+                match res {
+                    Ok(n) => {
+                        files_processed += 1;
+                        bytes_written += n;
+                        if let Some(progress) = progress {
+                            progress(MergeProgress {
+                                files_processed,
+                                files_total: merge_files.len(),
+                                bytes_written,
+                            });
+                        }
+                    }
+                    // 只保留第一个错误，等待其他任务结束之后返回
+                    Err(e) => {
+                        if result.is_ok() {
+                            result = Err(e);
+                        }
+                    }
+                }
+            }
+            result.map(|_| bytes_written)
+        })
     }
 
     fn is_empty_engine(&self) -> bool {
         let active_file = self.active_file.read();
         let older_files = self.older_files.read();
-        active_file.get_write_off() == 0 && older_files.len() == 0
+        active_file.get_write_off() == active_file.header_size() && older_files.len() == 0
     }
 
     fn rotate_merge_files(&self) -> Result<Vec<DataFile>> {
+        // 写入数据和更新索引时持有 write_lock 的读锁，这里持有写锁，等待正在进行的写入更新完索引
+        // 否则写入的数据所在的文件被切换到 merge 的范围中之后才更新索引，merge 时可能丢弃这条数据，
+        // 事务的数据和提交标识也可能分别位于 merge 范围的两侧
+        let _lock = self.write_lock.write();
+        // 和读写数据时一样，先锁住活跃文件再锁住旧的数据文件，顺序相反时和并发的读取互相等待会导致死锁
+        // 同时持有两个锁切换活跃文件，读取时不会出现数据文件既不是活跃文件也不在旧的数据文件中的情况
+        let mut active_file = self.active_file.write();
+        let mut older_files = self.older_files.write();
+
         // 取出旧的数据文件的 id
         let mut merge_file_ids = Vec::new();
-        let mut older_files = self.older_files.write();
         for fid in older_files.keys() {
             merge_file_ids.push(*fid);
         }
 
         // 设置一个新的活跃文件用于写入
-        let mut active_file = self.active_file.write();
-        // sync 数据文件保证持久性
+        // 截断预分配之后没有使用的空间，sync 数据文件保证持久性
+        active_file.truncate_unused_space()?;
         active_file.sync()?;
+        if let Some(bloom_filters) = &self.bloom_filters {
+            bloom_filters.seal(&self.options, &active_file)?;
+        }
         let active_file_id = active_file.get_file_id();
-        let new_active_file = DataFile::new(
-            self.options.dir_path.clone(),
-            active_file_id + 1,
-            IOType::StandardFIO,
-        )?;
+        let mut new_active_file =
+            DataFile::new(&self.options, active_file_id + 1, self.active_io_type())?;
+        new_active_file.preallocate(&self.options)?;
+        util::file::sync_dir(&self.options.dir_path)?;
         *active_file = new_active_file;
 
         // 加到旧的数据文件当中
-        let old_file = DataFile::new(
-            self.options.dir_path.clone(),
-            active_file_id,
-            IOType::StandardFIO,
-        )?;
+        let old_file = DataFile::new(&self.options, active_file_id, older_io_type(&self.options))?;
         older_files.insert(active_file_id, old_file);
 
         // 加到待 merge 的文件 id 列表中
@@ -161,22 +485,28 @@ impl Engine {
         // 打开所有需要 merge 的数据文件
         let mut merge_files = Vec::new();
         for file_id in merge_file_ids.iter() {
-            let data_file =
-                DataFile::new(self.options.dir_path.clone(), *file_id, IOType::StandardFIO)?;
+            let data_file = DataFile::new(&self.options, *file_id, IOType::StandardFIO)?;
             merge_files.push(data_file);
         }
         Ok(merge_files)
     }
+}
 
+impl IndexLoader {
     /// 从 hint 索引文件中加载索引
-    pub(crate) fn load_index_from_hint_file(&self) -> Result<()> {
+    /// 返回 hint 文件是否完整加载，hint 文件损坏时需要从 merge 后的数据文件中重建索引
+    pub(crate) fn load_index_from_hint_file(&self) -> Result<bool> {
         let hint_file_name = self.options.dir_path.join(HINT_FILE_NAME);
         // 如果 hint 文件不存在则返回
-        if !hint_file_name.is_file() {
-            return Ok(());
+        if !util::file::is_file(&self.options, &hint_file_name) {
+            return Ok(true);
         }
 
-        let hint_file = DataFile::new_hint_file(self.options.dir_path.clone())?;
+        let dir_path = self.options.dir_path.clone();
+        let hint_file = match self.options.read_only {
+            true => DataFile::open_hint_file_read_only(&self.options, dir_path)?,
+            false => DataFile::new_hint_file(&self.options, dir_path)?,
+        };
         let mut offset = 0;
         loop {
             let (log_record, size) = match hint_file.read_log_record(offset) {
@@ -185,106 +515,251 @@ impl Engine {
                     if e == Errors::ReadDataFileEOF {
                         break;
                     }
-                    return Err(e);
+                    // hint 文件已损坏，无法确定后续记录的位置，丢弃已加载的索引
+                    warn!("hint file is corrupted at offset {}: {:?}", offset, e);
+                    for key in self.index.list_keys()? {
+                        self.index.delete(key.to_vec());
+                    }
+                    self.keyspaces.clear_indexes();
+                    return Ok(false);
                 }
             };
-
-            // 解码 value，拿到位置索引信息
-            let log_record_pos = decode_log_record_pos(log_record.value);
-            // 存储到内存索引中
-            self.index.put(log_record.key, log_record_pos);
             offset += size as u64;
+
+            // 解码 value，拿到位置索引信息，无法解码的记录直接跳过
+            let log_record_pos = match decode_log_record_pos(log_record.value) {
+                Ok(pos) => pos,
+                Err(e) => {
+                    warn!("skip invalid hint record: {:?}", e);
+                    continue;
+                }
+            };
+            // 存储到数据所属的 keyspace 的内存索引中
+            match log_record.keyspace {
+                0 => {
+                    self.index.put(log_record.key, log_record_pos);
+                }
+                id => match self.keyspaces.get(id) {
+                    Some(keyspace) => {
+                        keyspace.index.put(log_record.key, log_record_pos);
+                    }
+                    None => warn!("skip hint record of unknown keyspace {}", id),
+                },
+            }
         }
-        Ok(())
+        Ok(true)
     }
 }
 
 // 获取临时的用于 merge 的数据目录
-fn get_merge_path(dir_path: PathBuf) -> PathBuf {
+pub(crate) fn get_merge_path(dir_path: PathBuf) -> PathBuf {
     let file_name = dir_path.file_name().unwrap();
     let merge_name = std::format!("{}-{}", file_name.to_str().unwrap(), MERGE_DIR_NAME);
     let parent = dir_path.parent().unwrap();
     parent.to_path_buf().join(merge_name)
 }
 
+// 创建 merge 数据目录，如果目录已经存在，则先删除
+fn create_merge_dir(options: &Options, merge_path: &Path) -> Result<()> {
+    util::file::remove_dir(options, merge_path)?;
+    if let Err(e) = fs::create_dir_all(merge_path) {
+        error!("failed to create merge path {}", e);
+        return Err(Errors::FailedCreateDatabaseDir { kind: e.kind() });
+    }
+    Ok(())
+}
+
+// 写入标识 merge 完成的文件，记录最近未参与 merge 的文件 id
+// 标识文件是最后写入的，写入之前先持久化 merge 目录，保证标识文件存在时所有 merge 的输出都已经持久化
+// 同时记录 merge 完成时最大的写入序列号，被 merge 的数据文件之后不会再遍历，需要据此恢复序列号
+fn write_merge_fin_file(
+    options: &Options,
+    merge_path: &Path,
+    non_merge_file_id: u32,
+    sequence: u64,
+) -> Result<()> {
+    util::file::sync_dir(merge_path)?;
+    let merge_fin_file = DataFile::new_merge_fin_file(options, merge_path.to_path_buf())?;
+    let merge_fin_record = LogRecord {
+        key: MERGE_FIN_KEY.to_vec(),
+        value: non_merge_file_id.to_string().into_bytes(),
+        rec_type: LogRecordType::NORMAL,
+        expire: 0,
+        compression: Compression::None,
+        encrypted: false,
+        sequence,
+        timestamp: 0,
+        keyspace: 0,
+    };
+    let enc_record = merge_fin_record.encode();
+    merge_fin_file.write(&enc_record)?;
+    merge_fin_file.sync()?;
+    util::file::sync_dir(merge_path)?;
+    Ok(())
+}
+
+// 读取标识 merge 完成的文件，返回最近未参与 merge 的文件 id，即参与 merge 的最大文件 id 加 1
+// 没有标识文件时说明 merge 没有完成，返回 UnfinishedMerge
+fn read_merge_fin_file(options: &Options, merge_path: &Path) -> Result<u32> {
+    if !util::file::is_file(options, &merge_path.join(MERGE_FINISHED_FILE_NAME)) {
+        return Err(Errors::UnfinishedMerge);
+    }
+    let merge_fin_file = DataFile::new_merge_fin_file(options, merge_path.to_path_buf())?;
+    let merge_fin_record = merge_fin_file.read_log_record(0)?;
+    let v = String::from_utf8(merge_fin_record.record.value).unwrap();
+    Ok(v.parse::<u32>().unwrap())
+}
+
+// 写入分批 merge 的检查点，记录下一个需要处理的文件 id、最近未参与 merge 的文件 id 以及 merge 输出的每个数据文件的大小
+fn write_merge_checkpoint(
+    options: &Options,
+    merge_path: &Path,
+    checkpoint: (u32, u32, u64),
+) -> Result<()> {
+    // 检查点之前的 merge 输出需要先持久化
+    util::file::sync_dir(merge_path)?;
+
+    // 先删除旧的检查点，检查点不存在时会重新开始 merge，不会丢失数据
+    util::file::remove_file(options, &merge_path.join(MERGE_CHECKPOINT_FILE_NAME))?;
+    let checkpoint_file = DataFile::new_merge_checkpoint_file(options, merge_path.to_path_buf())?;
+    let record = LogRecord {
+        key: MERGE_CHECKPOINT_KEY.to_vec(),
+        value: format!("{} {} {}", checkpoint.0, checkpoint.1, checkpoint.2).into_bytes(),
+        rec_type: LogRecordType::NORMAL,
+        expire: 0,
+        compression: Compression::None,
+        encrypted: false,
+        sequence: 0,
+        timestamp: 0,
+        keyspace: 0,
+    };
+    checkpoint_file.write(&record.encode())?;
+    checkpoint_file.sync()?;
+    util::file::sync_dir(merge_path)?;
+    Ok(())
+}
+
+// 读取分批 merge 的检查点，没有检查点或者检查点无法解析时返回 None
+fn load_merge_checkpoint(options: &Options, merge_path: &Path) -> Result<Option<(u32, u32, u64)>> {
+    if !util::file::is_file(options, &merge_path.join(MERGE_CHECKPOINT_FILE_NAME)) {
+        return Ok(None);
+    }
+    let checkpoint_file = DataFile::new_merge_checkpoint_file(options, merge_path.to_path_buf())?;
+    let record = match checkpoint_file.read_log_record(0) {
+        Ok(result) => result.record,
+        Err(e) => {
+            warn!("failed to read merge checkpoint: {:?}", e);
+            return Ok(None);
+        }
+    };
+    let v = String::from_utf8(record.value).unwrap_or_default();
+    let mut fields = v.split(' ');
+    let next_file_id = fields.next().map(|id| id.parse::<u32>());
+    let non_merge_file_id = fields.next().map(|id| id.parse::<u32>());
+    // 旧版本的检查点中没有记录输出文件的大小，使用 data_file_size
+    let output_file_size = fields
+        .next()
+        .map_or(Ok(options.data_file_size), |size| size.parse::<u64>());
+    match (next_file_id, non_merge_file_id, output_file_size) {
+        (Some(Ok(next_file_id)), Some(Ok(non_merge_file_id)), Ok(output_file_size)) => {
+            Ok(Some((next_file_id, non_merge_file_id, output_file_size)))
+        }
+        _ => {
+            warn!("invalid merge checkpoint: {}", v);
+            Ok(None)
+        }
+    }
+}
+
+// 删除没有完成的 merge 目录，merge 过程中崩溃或者断电时目录中的数据可能不完整
+// 分批 merge 有检查点时保留已经 merge 的数据，下一次 merge 时继续
+fn remove_unfinished_merge_dir(options: &Options, merge_path: &Path) -> Result<()> {
+    if util::file::is_file(options, &merge_path.join(MERGE_CHECKPOINT_FILE_NAME)) {
+        return Ok(());
+    }
+    warn!(
+        "{}, removing merge dir {:?}",
+        Errors::UnfinishedMerge,
+        merge_path
+    );
+    util::file::remove_dir(options, merge_path)?;
+    Ok(())
+}
+
 // 加载 merge 数据目录
-pub(crate) fn load_merge_files(dir_path: PathBuf) -> Result<()> {
+pub(crate) fn load_merge_files(options: &Options) -> Result<()> {
+    let dir_path = options.dir_path.clone();
     let merge_path = get_merge_path(dir_path.clone());
     // 没有发生过 merge 则直接返回
     if !merge_path.is_dir() {
         return Ok(());
     }
 
-    let dir = match fs::read_dir(merge_path.clone()) {
-        Ok(dir) => dir,
+    let file_names = match util::file::list_file_names(options, &merge_path) {
+        Ok(file_names) => file_names,
         Err(e) => {
             error!("failed to read merge dir: {}", e);
             return Err(Errors::FailedToReadDatabaseDir);
         }
     };
 
-    // 查找是否有标识 merge 完成的文件
+    // 查找 merge 输出的文件
     let mut merge_file_names = Vec::new();
-    let mut merge_finished = false;
-    for file in dir {
-        if let Ok(entry) = file {
-            let file_os_str = entry.file_name();
-            let file_name = file_os_str.to_str().unwrap();
-
-            if file_name.ends_with(MERGE_FINISHED_FILE_NAME) {
-                merge_finished = true;
-            }
-            if file_name.ends_with(SEQ_NO_FILE_NAME) {
-                continue;
-            }
-            if file_name.ends_with(FILE_LOCK_NAME) {
-                continue;
-            }
-            // 数据文件容量为空则跳过
-            let meta = entry.metadata().unwrap();
-            if file_name.ends_with(DATA_FILE_NAME_SUFFIX) && meta.len() == 0 {
-                continue;
-            }
-            merge_file_names.push(entry.file_name());
+    for file_name in file_names {
+        if file_name.ends_with(SEQ_NO_FILE_NAME) {
+            continue;
         }
+        if file_name.ends_with(FILE_LOCK_NAME) {
+            continue;
+        }
+        if file_name.ends_with(MERGE_CHECKPOINT_FILE_NAME) {
+            continue;
+        }
+        // 数据文件容量为空则跳过
+        let file_size = util::file::file_size(options, &merge_path.join(&file_name))?;
+        if parse_data_file_id(options, &file_name).is_some() && file_size == 0 {
+            continue;
+        }
+        merge_file_names.push(file_name);
     }
 
-    // merge 没有完成，直接返回
-    if !merge_finished {
-        fs::remove_dir_all(merge_path.clone()).unwrap();
-        return Ok(());
-    }
-
-    // 打开标识 merge 完成的文件，取出未参与 merge 的文件 id
-    let merge_fin_file = DataFile::new_merge_fin_file(merge_path.clone())?;
-    let merge_fin_record = merge_fin_file.read_log_record(0)?;
-    let v = String::from_utf8(merge_fin_record.record.value).unwrap();
-    let non_merge_fid = v.parse::<u32>().unwrap();
+    // 取出未参与 merge 的文件 id，merge 没有完成则直接返回
+    let non_merge_fid = match read_merge_fin_file(options, &merge_path) {
+        Ok(non_merge_fid) => non_merge_fid,
+        Err(Errors::UnfinishedMerge) => {
+            remove_unfinished_merge_dir(options, &merge_path)?;
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
 
-    // 将旧的数据文件删除
+    // 将旧的数据文件以及它们的布隆过滤器删除
     for file_id in 0..non_merge_fid {
-        let file = get_data_file_name(dir_path.clone(), file_id);
-        if file.is_file() {
-            fs::remove_file(file).unwrap();
-        }
+        util::file::remove_file(options, &get_data_file_name(options, file_id))?;
+        remove_bloom_file(options, file_id)?;
     }
 
     // 将新的数据文件移动到数据目录中
     for file_name in merge_file_names {
-        let src_path = merge_path.join(file_name.clone());
-        let dest_path = dir_path.join(file_name.clone());
-        fs::rename(src_path, dest_path).unwrap();
+        let src_path = merge_path.join(&file_name);
+        let dest_path = dir_path.join(&file_name);
+        util::file::rename_file(options, &src_path, &dest_path)?;
     }
+    // 持久化数据目录之后才能删除 merge 目录，否则断电之后可能同时丢失旧的和新的数据文件
+    util::file::sync_dir(&dir_path)?;
 
     // 最后删除临时 merge 的目录
-    fs::remove_dir_all(merge_path.clone()).unwrap();
+    util::file::remove_dir(options, &merge_path)?;
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::util::rand_kv::{get_test_key, get_test_value};
+    use crate::{
+        option::{Checksum, WriteBatchOptions},
+        util::rand_kv::{get_test_key, get_test_value},
+    };
     use bytes::Bytes;
     use std::{sync::Arc, thread};
 
@@ -469,4 +944,814 @@ mod tests {
         // 删除测试的文件夹
         std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
     }
+
+    #[test]
+    fn test_merge_expired() {
+        // 过期的数据在 merge 之后被清理
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-expired");
+        opts.data_file_size = 32 * 1024 * 1024;
+        opts.data_file_merge_ratio = 0 as f32;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for i in 0..1000 {
+            let put_res = engine.put(get_test_key(i), get_test_value(i));
+            assert!(put_res.is_ok());
+        }
+        for i in 1000..2000 {
+            let put_res = engine.put_with_ttl(
+                get_test_key(i),
+                get_test_value(i),
+                std::time::Duration::from_millis(100),
+            );
+            assert!(put_res.is_ok());
+        }
+        thread::sleep(std::time::Duration::from_millis(200));
+
+        let res1 = engine.merge();
+        assert!(res1.is_ok());
+
+        // 重启校验
+        std::mem::drop(engine);
+
+        let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+        let keys = engine2.list_keys().unwrap();
+        assert_eq!(keys.len(), 1000);
+        for i in 1000..2000 {
+            let get_res = engine2.get(get_test_key(i));
+            assert_eq!(Errors::KeyNotFound, get_res.err().unwrap());
+        }
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+
+    #[test]
+    fn test_merge_progress() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-progress");
+        opts.data_file_size = 64 * 1024;
+        opts.data_file_merge_ratio = 0 as f32;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for i in 0..500 {
+            let put_res = engine.put(get_test_key(i), get_test_value(i));
+            assert!(put_res.is_ok());
+        }
+        for i in 0..250 {
+            let del_res = engine.delete(get_test_key(i));
+            assert!(del_res.is_ok());
+        }
+
+        // 每处理完一个数据文件汇报一次进度
+        let reports = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let reports_clone = reports.clone();
+        let res1 = engine.merge_with_progress(Some(Box::new(move |p| {
+            reports_clone.lock().push(p);
+        })));
+        assert!(res1.is_ok());
+
+        let reports = reports.lock();
+        let files_total = engine.older_files.read().len();
+        assert_eq!(files_total, reports.len());
+        for (i, p) in reports.iter().enumerate() {
+            assert_eq!(i + 1, p.files_processed);
+            assert_eq!(files_total, p.files_total);
+            if i > 0 {
+                assert!(p.bytes_written >= reports[i - 1].bytes_written);
+            }
+        }
+
+        // 重写的数据大小为剩余的有效数据的大小
+        let mut live_size = 0;
+        for i in 250..500 {
+            live_size += engine.locate(get_test_key(i)).unwrap().unwrap().size() as u64;
+        }
+        assert_eq!(live_size, reports.last().unwrap().bytes_written);
+
+        // 删除测试的文件夹，包括还没有生效的 merge 目录
+        std::mem::drop(engine);
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+        std::fs::remove_dir_all(get_merge_path(opts.dir_path)).expect("failed to remove path");
+    }
+
+    #[test]
+    #[cfg(feature = "merge-pool")]
+    fn test_merge_shared_pool() {
+        // 多个实例共享同一个线程池进行 merge
+        let pool = Arc::new(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(2)
+                .build()
+                .expect("failed to build thread pool"),
+        );
+        let mut all_opts = Vec::new();
+        for i in 0..3 {
+            let mut opts = Options::default();
+            opts.dir_path = PathBuf::from(format!("/tmp/bitcask-rs-merge-pool-{}", i));
+            opts.data_file_size = 32 * 1024;
+            opts.data_file_merge_ratio = 0 as f32;
+            opts.merge_pool = Some(pool.clone());
+            all_opts.push(opts);
+        }
+
+        thread::scope(|s| {
+            for opts in all_opts.iter() {
+                s.spawn(move || {
+                    let engine = Engine::open(opts.clone()).expect("failed to open engine");
+                    for i in 0..2000 {
+                        let put_res = engine.put(get_test_key(i), get_test_value(i));
+                        assert!(put_res.is_ok());
+                    }
+                    for i in 0..1000 {
+                        let del_res = engine.delete(get_test_key(i));
+                        assert!(del_res.is_ok());
+                    }
+
+                    // 每个文件处理完成之后汇报一次进度
+                    let reports = Arc::new(parking_lot::Mutex::new(Vec::new()));
+                    let reports_clone = reports.clone();
+                    let merge_res = engine.merge_with_progress(Some(Box::new(move |p| {
+                        reports_clone.lock().push(p);
+                    })));
+                    assert!(merge_res.is_ok());
+                    let reports = reports.lock();
+                    let files_total = engine.older_files.read().len();
+                    assert_eq!(files_total, reports.len());
+                    assert_eq!(files_total, reports.last().unwrap().files_processed);
+                });
+            }
+        });
+
+        // 重启之后校验数据
+        for opts in all_opts.iter() {
+            let engine = Engine::open(opts.clone()).expect("failed to open engine");
+            assert_eq!(1000, engine.list_keys().unwrap().len());
+            for i in 1000..2000 {
+                assert_eq!(get_test_value(i), engine.get(get_test_key(i)).unwrap());
+            }
+            std::mem::drop(engine);
+            std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+        }
+    }
+
+    // 写入测试数据，并删除一部分，使得有可以 merge 的数据
+    fn prepare_merge_data(engine: &Engine) {
+        for i in 0..3000 {
+            let put_res = engine.put(get_test_key(i), get_test_value(i));
+            assert!(put_res.is_ok());
+        }
+        for i in (0..1000).step_by(2) {
+            let del_res = engine.delete(get_test_key(i));
+            assert!(del_res.is_ok());
+        }
+    }
+
+    #[test]
+    fn test_merge_sync_interval() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-sync-interval");
+        opts.data_file_size = 32 * 1024;
+        opts.data_file_merge_ratio = 0 as f32;
+        opts.merge_sync_interval = 100;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        prepare_merge_data(&engine);
+
+        // merge 完成时持久化 merge 目录
+        let count1 = util::file::dir_sync_count();
+        let res1 = engine.merge();
+        assert!(res1.is_ok());
+        assert!(util::file::dir_sync_count() >= count1 + 2);
+        std::mem::drop(engine);
+
+        // 重启之后 merge 生效，数据目录同样被持久化
+        let count2 = util::file::dir_sync_count();
+        let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(util::file::dir_sync_count() > count2);
+        assert!(!get_merge_path(opts.dir_path.clone()).exists());
+        assert_eq!(2500, engine2.list_keys().unwrap().len());
+        for i in 1000..3000 {
+            let res2 = engine2.get(get_test_key(i));
+            assert_eq!(get_test_value(i), res2.unwrap());
+        }
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+
+    // 统计目录中数据文件的个数以及最大的文件大小
+    fn data_file_stats(dir_path: &Path) -> (usize, u64) {
+        let mut count = 0;
+        let mut max_size = 0;
+        for entry in fs::read_dir(dir_path).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_name().to_str().unwrap().ends_with(".data") {
+                count += 1;
+                max_size = max_size.max(entry.metadata().unwrap().len());
+            }
+        }
+        (count, max_size)
+    }
+
+    #[test]
+    fn test_merge_target_file_count() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-target-file-count");
+        opts.data_file_size = 4 * 1024;
+        opts.data_file_merge_ratio = 0 as f32;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        prepare_merge_data(&engine);
+        std::mem::drop(engine);
+        assert!(data_file_stats(&opts.dir_path).0 > 50);
+
+        // 大量的小文件合并为目标个数的文件
+        opts.data_file_size = 64 * 1024 * 1024;
+        opts.merge_target_file_count = 4;
+        let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+        let res1 = engine2.merge();
+        assert!(res1.is_ok());
+        let merge_path = get_merge_path(opts.dir_path.clone());
+        let (count1, _) = data_file_stats(&merge_path);
+        assert!((4..=5).contains(&count1));
+        std::mem::drop(engine2);
+
+        let engine3 = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(2500, engine3.list_keys().unwrap().len());
+        std::mem::drop(engine3);
+
+        // data_file_size 仍然是每个文件大小的上限
+        opts.data_file_size = 32 * 1024;
+        opts.merge_target_file_count = 1;
+        let engine4 = Engine::open(opts.clone()).expect("failed to open engine");
+        let res2 = engine4.merge();
+        assert!(res2.is_ok());
+        let (count2, max_size) = data_file_stats(&merge_path);
+        assert!(count2 > 1);
+        assert!(max_size <= 32 * 1024);
+        std::mem::drop(engine4);
+
+        let engine5 = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 1000..3000 {
+            let res3 = engine5.get(get_test_key(i));
+            assert_eq!(get_test_value(i), res3.unwrap());
+        }
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+
+    #[test]
+    fn test_merge_with_budget_target_file_count() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-budget-target-file-count");
+        opts.data_file_size = 4 * 1024;
+        opts.data_file_merge_ratio = 0 as f32;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        prepare_merge_data(&engine);
+        std::mem::drop(engine);
+
+        // 第一次调用时计算输出文件的大小，记录到检查点中
+        opts.data_file_size = 64 * 1024 * 1024;
+        opts.merge_target_file_count = 4;
+        let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(!engine2.merge_with_budget(32 * 1024).unwrap());
+        let merge_path = get_merge_path(opts.dir_path.clone());
+        let (_, _, output_file_size) = load_merge_checkpoint(&opts, &merge_path).unwrap().unwrap();
+        assert!(output_file_size < opts.data_file_size);
+        std::mem::drop(engine2);
+
+        // 重启之后继续 merge，使用检查点中记录的大小
+        opts.merge_target_file_count = 0;
+        let engine3 = Engine::open(opts.clone()).expect("failed to open engine");
+        while !engine3.merge_with_budget(32 * 1024).unwrap() {}
+        let (count, max_size) = data_file_stats(&merge_path);
+        assert!((4..=5).contains(&count));
+        assert!(max_size <= output_file_size);
+        std::mem::drop(engine3);
+
+        let engine4 = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(2500, engine4.list_keys().unwrap().len());
+        for i in 1000..3000 {
+            assert_eq!(get_test_value(i), engine4.get(get_test_key(i)).unwrap());
+        }
+
+        // 删除测试的文件夹
+        std::mem::drop(engine4);
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+
+    #[test]
+    fn test_merge_unfinished_dir_removed_at_open() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-unfinished");
+        opts.data_file_size = 32 * 1024;
+        opts.data_file_merge_ratio = 0 as f32;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        prepare_merge_data(&engine);
+        std::mem::drop(engine);
+
+        // 模拟 merge 过程中崩溃，merge 目录中只有部分输出，没有完成的标识
+        let merge_path = get_merge_path(opts.dir_path.clone());
+        let mut merge_opts = opts.clone();
+        merge_opts.dir_path = merge_path.clone();
+        let merge_db = Engine::open(merge_opts).expect("failed to open engine");
+        let res1 = merge_db.put(get_test_key(1), Bytes::from("partial merge output"));
+        assert!(res1.is_ok());
+        std::mem::drop(merge_db);
+        let hint_file = DataFile::new_hint_file(&opts, merge_path.clone()).unwrap();
+        assert!(hint_file.sync().is_ok());
+
+        // 启动时删除没有完成的 merge 目录，原来的数据不受影响
+        let res2 = read_merge_fin_file(&opts, &merge_path);
+        assert_eq!(Errors::UnfinishedMerge, res2.err().unwrap());
+        let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(!merge_path.exists());
+        assert_eq!(2500, engine2.list_keys().unwrap().len());
+        let res3 = engine2.get(get_test_key(1));
+        assert_eq!(get_test_value(1), res3.unwrap());
+
+        // 完成的 merge 写入标识之后，启动时使用 merge 之后的数据文件
+        let res4 = engine2.merge();
+        assert!(res4.is_ok());
+        assert!(read_merge_fin_file(&opts, &merge_path).is_ok());
+        std::mem::drop(engine2);
+        let engine3 = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(!merge_path.exists());
+        assert_eq!(2500, engine3.list_keys().unwrap().len());
+        let res5 = engine3.get(get_test_key(1));
+        assert_eq!(get_test_value(1), res5.unwrap());
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+
+    // 在 merge 的过程中更新、删除和新增数据
+    fn update_merge_data(engine: &Engine) {
+        for i in (1..200).step_by(2) {
+            let del_res = engine.delete(get_test_key(i));
+            assert!(del_res.is_ok());
+        }
+        for i in 201..300 {
+            let put_res = engine.put(get_test_key(i), get_test_value(i + 10000));
+            assert!(put_res.is_ok());
+        }
+        for i in 5000..5100 {
+            let put_res = engine.put(get_test_key(i), get_test_value(i));
+            assert!(put_res.is_ok());
+        }
+    }
+
+    #[test]
+    fn test_merge_with_budget() {
+        let mut opts1 = Options::default();
+        opts1.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-budget-full");
+        opts1.data_file_size = 32 * 1024;
+        opts1.data_file_merge_ratio = 0 as f32;
+        let mut opts2 = opts1.clone();
+        opts2.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-budget");
+
+        // 一次完成的 merge
+        let engine1 = Engine::open(opts1.clone()).expect("failed to open engine");
+        prepare_merge_data(&engine1);
+        update_merge_data(&engine1);
+        let res1 = engine1.merge();
+        assert!(res1.is_ok());
+        std::mem::drop(engine1);
+
+        // 分批完成的 merge，两次 merge 之间更新数据
+        let engine2 = Engine::open(opts2.clone()).expect("failed to open engine");
+        prepare_merge_data(&engine2);
+        let res2 = engine2.merge_with_budget(32 * 1024);
+        assert!(!res2.unwrap());
+        update_merge_data(&engine2);
+        let mut calls = 1;
+        while !engine2.merge_with_budget(32 * 1024).unwrap() {
+            calls += 1;
+        }
+        assert!(calls >= 2);
+        std::mem::drop(engine2);
+
+        // 重启之后两个数据库的数据一致
+        let engine1 = Engine::open(opts1.clone()).expect("failed to open engine");
+        let engine2 = Engine::open(opts2.clone()).expect("failed to open engine");
+        let keys1 = engine1.list_keys().unwrap();
+        let keys2 = engine2.list_keys().unwrap();
+        assert_eq!(keys1, keys2);
+        assert_eq!(2549, keys2.len());
+        for key in keys2 {
+            assert_eq!(engine1.get(key.clone()).unwrap(), engine2.get(key).unwrap());
+        }
+        let get_res1 = engine2.get(get_test_key(1));
+        assert_eq!(Errors::KeyNotFound, get_res1.err().unwrap());
+        let get_res2 = engine2.get(get_test_key(201));
+        assert_eq!(get_test_value(10201), get_res2.unwrap());
+
+        // 预算足够时一次完成 merge
+        let res3 = engine1.merge_with_budget(u64::MAX);
+        assert!(res3.unwrap());
+
+        // 删除测试的文件夹，包括还没有生效的 merge 目录
+        std::mem::drop(engine1);
+        std::mem::drop(engine2);
+        std::fs::remove_dir_all(opts1.clone().dir_path).expect("failed to remove path");
+        std::fs::remove_dir_all(get_merge_path(opts1.dir_path)).expect("failed to remove path");
+        std::fs::remove_dir_all(opts2.clone().dir_path).expect("failed to remove path");
+    }
+
+    #[test]
+    #[cfg(feature = "merge-pool")]
+    fn test_merge_pool_budget_and_worker_thread() {
+        let build_pool = |num_threads| {
+            Arc::new(
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(num_threads)
+                    .build()
+                    .expect("failed to build thread pool"),
+            )
+        };
+
+        // 1.分批 merge 同样在线程池中重写数据文件
+        let mut opts1 = Options::default();
+        opts1.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-pool-budget");
+        opts1.data_file_size = 32 * 1024;
+        opts1.data_file_merge_ratio = 0 as f32;
+        opts1.merge_pool = Some(build_pool(2));
+        let engine1 = Engine::open(opts1.clone()).expect("failed to open engine");
+        prepare_merge_data(&engine1);
+        let mut calls = 1;
+        while !engine1.merge_with_budget(32 * 1024).unwrap() {
+            calls += 1;
+        }
+        assert!(calls >= 2);
+        std::mem::drop(engine1);
+        let engine1 = Engine::open(opts1.clone()).expect("failed to open engine");
+        assert_eq!(2500, engine1.list_keys().unwrap().len());
+        for i in 1000..3000 {
+            assert_eq!(get_test_value(i), engine1.get(get_test_key(i)).unwrap());
+        }
+        std::mem::drop(engine1);
+
+        // 2.在线程池唯一的工作线程中调用 merge，不会等待无法执行的重写任务
+        let mut opts2 = opts1.clone();
+        opts2.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-pool-worker");
+        let pool = build_pool(1);
+        opts2.merge_pool = Some(pool.clone());
+        let engine2 = Engine::open(opts2.clone()).expect("failed to open engine");
+        prepare_merge_data(&engine2);
+        let res1 = pool.install(|| engine2.merge());
+        assert!(res1.is_ok());
+        let res2 = pool.install(|| engine2.merge_with_budget(u64::MAX));
+        assert!(res2.is_ok());
+        std::mem::drop(engine2);
+        let engine2 = Engine::open(opts2.clone()).expect("failed to open engine");
+        assert_eq!(2500, engine2.list_keys().unwrap().len());
+        std::mem::drop(engine2);
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts1.clone().dir_path).expect("failed to remove path");
+        std::fs::remove_dir_all(opts2.clone().dir_path).expect("failed to remove path");
+    }
+
+    #[test]
+    fn test_merge_with_budget_restart() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-budget-restart");
+        opts.data_file_size = 32 * 1024;
+        opts.data_file_merge_ratio = 0 as f32;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        prepare_merge_data(&engine);
+        let res1 = engine.merge_with_budget(32 * 1024);
+        assert!(!res1.unwrap());
+        std::mem::drop(engine);
+
+        // 重启之后保留检查点，数据不受影响
+        let merge_path = get_merge_path(opts.dir_path.clone());
+        let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(merge_path.join(MERGE_CHECKPOINT_FILE_NAME).is_file());
+        let (next_file_id, _, output_file_size) =
+            load_merge_checkpoint(&opts, &merge_path).unwrap().unwrap();
+        assert_eq!(opts.data_file_size, output_file_size);
+        assert!(next_file_id > 0);
+        assert_eq!(2500, engine2.list_keys().unwrap().len());
+
+        // 从检查点继续 merge
+        update_merge_data(&engine2);
+        while !engine2.merge_with_budget(32 * 1024).unwrap() {}
+        std::mem::drop(engine2);
+
+        let engine3 = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(!merge_path.is_dir());
+        assert_eq!(2549, engine3.list_keys().unwrap().len());
+        for i in (1..200).step_by(2) {
+            let get_res = engine3.get(get_test_key(i));
+
+            assert_eq!(Errors::KeyNotFound, get_res.err().unwrap());
+        }
+        for i in 201..300 {
+            assert_eq!(
+                get_test_value(i + 10000),
+                engine3.get(get_test_key(i)).unwrap()
+            );
+        }
+        for i in 1000..3000 {
+            assert_eq!(get_test_value(i), engine3.get(get_test_key(i)).unwrap());
+        }
+
+        // 删除测试的文件夹
+        std::mem::drop(engine3);
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+
+    #[test]
+    fn test_merge_corrupted_hint_file() {
+        // hint 文件中无法解码的记录被跳过
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-corrupted-hint");
+        opts.data_file_size = 32 * 1024 * 1024;
+        opts.data_file_merge_ratio = 0 as f32;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for i in 0..1000 {
+            let put_res = engine.put(get_test_key(i), get_test_value(i));
+            assert!(put_res.is_ok());
+        }
+        let res1 = engine.merge();
+        assert!(res1.is_ok());
+        std::mem::drop(engine);
+
+        // 重启之后 merge 的数据会被移动到数据目录，在 hint 文件末尾追加一条无效的记录
+        let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+        std::mem::drop(engine2);
+        let hint_file = DataFile::new_hint_file(&opts, opts.dir_path.clone()).unwrap();
+        let invalid_record = LogRecord {
+            key: get_test_key(1000).to_vec(),
+            value: vec![0xff; 3],
+            rec_type: LogRecordType::NORMAL,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
+        };
+        let res2 = hint_file.write(&invalid_record.encode());
+        assert!(res2.is_ok());
+        std::mem::drop(hint_file);
+
+        let engine3 = Engine::open(opts.clone()).expect("failed to open engine");
+        let keys = engine3.list_keys().unwrap();
+        assert_eq!(keys.len(), 1000);
+        assert_eq!(
+            Errors::KeyNotFound,
+            engine3.get(get_test_key(1000)).err().unwrap()
+        );
+        std::mem::drop(engine3);
+
+        // hint 文件损坏时，从 merge 之后的数据文件中重建索引
+        let hint_path = opts.dir_path.join(HINT_FILE_NAME);
+        let mut content = fs::read(hint_path.clone()).unwrap();
+        let mid = content.len() / 2;
+        content[mid] ^= 0xff;
+        fs::write(hint_path, content).unwrap();
+
+        let engine4 = Engine::open(opts.clone()).expect("failed to open engine");
+        let keys = engine4.list_keys().unwrap();
+        assert_eq!(keys.len(), 1000);
+        for i in 0..1000 {
+            let get_res = engine4.get(get_test_key(i));
+            assert_eq!(get_test_value(i), get_res.unwrap());
+        }
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+
+    #[test]
+    fn test_merge_concurrent() {
+        // 同时只能有一个 merge，merge 的过程中可以继续写入
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-concurrent");
+        opts.data_file_size = 8 * 1024 * 1024;
+        opts.data_file_merge_ratio = 0 as f32;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for i in 0..20000 {
+            let put_res = engine.put(get_test_key(i), get_test_value(i));
+            assert!(put_res.is_ok());
+        }
+
+        let eng = Arc::new(engine);
+        let mut handles = vec![];
+        let eng1 = eng.clone();
+        handles.push(thread::spawn(move || {
+            for i in 0..40000 {
+                let put_res = eng1.put(get_test_key(i), Bytes::from(format!("value-{}", i)));
+                assert!(put_res.is_ok());
+            }
+        }));
+        for _ in 0..2 {
+            let eng2 = eng.clone();
+            handles.push(thread::spawn(move || {
+                for _ in 0..3 {
+                    let merge_res = eng2.merge();
+                    assert!(merge_res.is_ok() || merge_res == Err(Errors::MergeInProgress));
+                }
+            }));
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // merge 结束后标识被重置，可以再次 merge
+        let res1 = eng.merge();
+        assert!(res1.is_ok());
+
+        // 重启校验
+        let engine = Arc::try_unwrap(eng).ok().unwrap();
+        engine.close().expect("failed to close engine");
+        let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+        let keys = engine2.list_keys().unwrap();
+        assert_eq!(keys.len(), 40000);
+        for i in 0..40000 {
+            let get_res = engine2.get(get_test_key(i));
+            assert_eq!(Bytes::from(format!("value-{}", i)), get_res.unwrap());
+        }
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+
+    #[test]
+    fn test_merge_concurrent_write_batch() {
+        // merge 切换活跃文件的同时提交事务，事务的数据不会被 merge 丢弃，重启之后可以正常加载
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-concurrent-batch");
+        opts.data_file_size = 32 * 1024;
+        opts.data_file_merge_ratio = 0 as f32;
+        let engine = Arc::new(Engine::open(opts.clone()).expect("failed to open engine"));
+
+        let eng1 = engine.clone();
+        let handle = thread::spawn(move || {
+            for round in 0..200 {
+                let wb = eng1
+                    .new_write_batch(WriteBatchOptions::default())
+                    .expect("failed to create write batch");
+                for i in 0..20 {
+                    let value = Bytes::from(format!("value-{}-{}", round, i));
+                    assert!(wb.put(get_test_key(i), value).is_ok());
+                }
+                assert!(wb.commit().is_ok());
+            }
+        });
+        for _ in 0..20 {
+            let merge_res = engine.merge();
+            assert!(merge_res.is_ok() || merge_res == Err(Errors::MergeRatioUnreached));
+        }
+        handle.join().unwrap();
+        let res1 = engine.merge();
+        assert!(res1.is_ok());
+
+        // 事务完成的标识之前没有对应的数据，加载时跳过
+        let mut finish_record = LogRecord {
+            key: log_record_key_with_seq(b"txn-fin".to_vec(), 100000),
+            value: Default::default(),
+            rec_type: LogRecordType::TXNFINISHED,
+            expire: 0,
+            compression: Compression::None,
+            encrypted: false,
+            sequence: 0,
+            timestamp: 0,
+            keyspace: 0,
+        };
+        assert!(engine.append_log_record(&mut finish_record).is_ok());
+
+        // 重启校验，每个 key 都是最后一次提交的数据
+        let engine = Arc::try_unwrap(engine).ok().unwrap();
+        engine.close().expect("failed to close engine");
+        let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(20, engine2.list_keys().unwrap().len());
+        for i in 0..20 {
+            let get_res = engine2.get(get_test_key(i));
+            assert_eq!(Bytes::from(format!("value-199-{}", i)), get_res.unwrap());
+        }
+
+        // 删除测试的文件夹
+        engine2.close().expect("failed to close engine");
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+
+    #[test]
+    fn test_merge_concurrent_read() {
+        // merge 切换活跃文件的同时读取和写入，读取到的数据始终有效
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-concurrent-read");
+        opts.data_file_size = 256 * 1024;
+        opts.data_file_merge_ratio = 0 as f32;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for i in 0..2000 {
+            let put_res = engine.put(get_test_key(i), get_test_value(i));
+            assert!(put_res.is_ok());
+        }
+
+        let writing = AtomicBool::new(true);
+        thread::scope(|s| {
+            for t in 0..4 {
+                let engine = &engine;
+                let writing = &writing;
+                s.spawn(move || {
+                    while writing.load(Ordering::SeqCst) {
+                        for i in (t..2000).step_by(4) {
+                            let value = engine.get(get_test_key(i)).unwrap();
+                            assert!(value == get_test_value(i) || value == format!("value-{}", i));
+                        }
+                    }
+                });
+            }
+            s.spawn(|| {
+                for _ in 0..20 {
+                    let merge_res = engine.merge();
+                    assert!(merge_res.is_ok());
+                }
+            });
+            s.spawn(|| {
+                for i in 0..20000 {
+                    let key = get_test_key(i % 2000);
+                    let put_res = engine.put(key, Bytes::from(format!("value-{}", i % 2000)));
+                    assert!(put_res.is_ok());
+                }
+                writing.store(false, Ordering::SeqCst);
+            });
+        });
+
+        // 重启之后 merge 生效，数据仍然是最新的
+        engine.close().expect("failed to close engine");
+        let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..2000 {
+            let get_res = engine2.get(get_test_key(i));
+            assert_eq!(Bytes::from(format!("value-{}", i)), get_res.unwrap());
+        }
+
+        // 删除测试的文件夹
+        std::mem::drop(engine2);
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+
+    #[test]
+    fn test_merge_keeps_checksum() {
+        // merge 输出的数据文件使用配置的校验算法
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-checksum");
+        opts.data_file_size = 64 * 1024;
+        opts.checksum = Checksum::XxHash64;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for _ in 0..3 {
+            for i in 0..200 {
+                let put_res = engine.put(get_test_key(i), get_test_value(i));
+                assert!(put_res.is_ok());
+            }
+        }
+        let res1 = engine.merge();
+        assert!(res1.is_ok());
+        std::mem::drop(engine);
+
+        // 重启之后 merge 的输出移动到数据目录中，文件头部记录的校验算法不变
+        let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+        let non_merge_file_id = engine2.active_file.read().get_file_id();
+        for file_id in 0..non_merge_file_id {
+            if get_data_file_name(&opts, file_id).is_file() {
+                let data_file = DataFile::new(&opts, file_id, IOType::StandardFIO).unwrap();
+                assert_eq!(Checksum::XxHash64, data_file.checksum());
+            }
+        }
+        assert_eq!(get_test_value(10), engine2.get(get_test_key(10)).unwrap());
+
+        // 删除测试的文件夹
+        std::mem::drop(engine2);
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+
+    #[test]
+    fn test_merge_guard_released_on_error() {
+        // merge 出错返回之后，标识被重置
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-guard");
+        opts.data_file_size = 32 * 1024 * 1024;
+        opts.data_file_merge_ratio = 0.9;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for i in 0..1000 {
+            let put_res = engine.put(get_test_key(i), get_test_value(i));
+            assert!(put_res.is_ok());
+        }
+
+        let res1 = engine.merge();
+        assert_eq!(Errors::MergeRatioUnreached, res1.err().unwrap());
+        let res2 = engine.merge();
+        assert_eq!(Errors::MergeRatioUnreached, res2.err().unwrap());
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
 }
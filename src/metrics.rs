@@ -0,0 +1,22 @@
+use std::time::Duration;
+
+use crate::error::Errors;
+
+/// 统计的操作类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpKind {
+    Put,
+    Get,
+    Delete,
+    Merge,
+}
+
+/// 统计操作耗时和错误的接口，例如对接 Prometheus
+/// 引擎在操作完成并释放内部的锁之后再调用，实现中的耗时不会阻塞其他的读写
+pub trait MetricsRecorder: Send + Sync {
+    // 记录一次操作以及耗时，失败的操作同样会记录
+    fn record_op(&self, op: OpKind, latency: Duration);
+
+    // 记录一次操作失败，key 不存在属于正常的结果，不会记录
+    fn incr_error(&self, op: OpKind, error: &Errors);
+}
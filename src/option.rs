@@ -1,33 +1,145 @@
-use std::path::PathBuf;
+use std::{cmp::Ordering, path::PathBuf, sync::Arc};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    fileio::IOFactory,
+    metrics::MetricsRecorder,
+};
 
 #[derive(Clone)]
 pub struct Options {
     // 数据库目录
     pub dir_path: PathBuf,
 
-    // 数据文件大小
+    // 数据文件大小，超过这个大小的单条数据单独存储在一个数据文件中
     pub data_file_size: u64,
 
-    // 是否每次写都持久化
+    // 是否每次写都持久化，持久化的方式由 sync_mode 决定
     pub sync_writes: bool,
 
+    // 数据文件持久化的方式
+    pub sync_mode: SyncMode,
+
     // 累计写到多少字节后进行持久化
     pub bytes_per_sync: usize,
 
+    // 活跃文件写缓冲的大小，为 0 时不使用写缓冲
+    pub write_buffer_size: usize,
+
+    // 读写数据文件遇到暂时性的 IO 错误（被信号中断、EAGAIN、超时）时最多重试的次数，为 0 时不重试
+    // 每次重试之前等待的时间从 1ms 开始翻倍，最长 64ms
+    pub io_retry_count: usize,
+
+    // 保存文件使用的存储，为 None 时按照 IO 类型使用默认的文件 IO
+    // 数据文件以及 hint、merge 标识等辅助文件都通过它打开、列出和删除，可以替换为内存或者远程存储等其他的实现
+    // 数据目录仍然会在本地创建，用于存放文件锁；布隆过滤器、索引快照、备份和修复只支持本地文件，不能同时使用
+    pub io_factory: Option<Arc<dyn IOFactory>>,
+
+    // 是否为活跃文件预先分配 data_file_size 大小的磁盘空间（Unix 下使用 fallocate），减少文件系统的碎片，写入的延迟更稳定
+    // 写入仍然从逻辑末尾开始，切换活跃文件以及关闭数据库时截断没有使用的空间
+    // 使用写缓冲（write_buffer_size 大于 0）或者自定义的 io_factory 时不预分配
+    pub preallocate: bool,
+
     // 索引类型
     pub index_type: IndexType,
 
-    // 是否用 mmap 打开数据库
+    // 是否用 mmap 打开旧的数据文件，活跃文件始终使用标准文件 IO
+    // 没有启用 mmap feature 时使用标准文件 IO 打开
     pub mmap_at_startup: bool,
 
     // 执行数据文件 merge 的阈值
     pub data_file_merge_ratio: f32,
+
+    // value 的压缩算法
+    pub compression: Compression,
+
+    // 小于这个长度的 value 不压缩，每条数据记录实际是否经过压缩
+    pub compression_min_size: usize,
+
+    // 新建的数据文件使用的校验算法，已有的数据文件使用文件头部记录的算法
+    pub checksum: Checksum,
+
+    // 是否为新建的数据文件计算和存储校验值，关闭后由上层保证数据的完整性，可以减少写入的开销
+    // 是否存储校验值记录在每个数据文件的头部，已有的数据文件不受影响
+    pub checksum_enabled: bool,
+
+    // value 的加密配置，为 None 时不加密
+    pub encryption: Option<EncryptionConfig>,
+
+    // key 的最大长度
+    pub max_key_size: usize,
+
+    // value 的最大长度，不能超过 u32::MAX
+    pub max_value_size: usize,
+
+    // 读取数据时是否校验 crc
+    pub verify_checksum_on_read: bool,
+
+    // 是否以只读模式打开数据库
+    pub read_only: bool,
+
+    // value 缓存可以使用的字节数，为 0 时不使用缓存
+    pub value_cache_size: usize,
+
+    // 数据文件的扩展名，不包含 "."
+    pub data_file_ext: String,
+
+    // 数据文件名中文件 id 的宽度，不足时在前面补 0
+    pub data_file_id_width: usize,
+
+    // 数据目录为空时创建的第一个数据文件的 id，已有数据文件时从最大的 id 继续递增
+    pub initial_file_id: u32,
+
+    // merge 时使用的线程池，多个实例可以共享同一个线程池，为 None 时在当前线程中 merge
+    // 在这个线程池的工作线程中调用 merge 时仍然在当前线程中 merge，需要启用 merge-pool feature
+    #[cfg(feature = "merge-pool")]
+    pub merge_pool: Option<Arc<rayon::ThreadPool>>,
+
+    // merge 时每重写多少条数据持久化一次 merge 输出，为 0 时只在 merge 完成时持久化
+    pub merge_sync_interval: usize,
+
+    // merge 输出的数据文件的目标个数，有效数据平均分配到这些文件中，每个文件仍然不超过 data_file_size
+    // 为 0 时按照 data_file_size 写满一个文件之后再写下一个文件
+    pub merge_target_file_count: usize,
+
+    // 统计 put/get/delete/merge 的耗时和错误，为 None 时不统计
+    pub metrics: Option<Arc<dyn MetricsRecorder>>,
+
+    // 按照 key 的前若干个字节建立二级索引，用于快速列出某个前缀下的所有 key，为 None 时不建立
+    pub prefix_index_len: Option<usize>,
+
+    // 是否在内存中记录被删除的 key，开启后 Engine::get_status 可以区分被删除和从未写入的 key
+    // 删除标记随删除操作增长，merge 完成之后清理参与 merge 的数据文件中的删除标记
+    pub track_tombstones: bool,
+
+    // 是否在后台线程中加载索引，开启后 open 不等待索引加载完成，读写操作会等待加载完成
+    pub lazy_index: bool,
+
+    // 索引快照文件的路径，打开数据库时如果快照仍然有效则直接加载，不需要遍历数据文件，为 None 时不使用快照
+    // 快照由 Engine::save_index_snapshot 生成
+    pub index_snapshot_path: Option<PathBuf>,
+
+    // 是否在数据中记录写入时间，开启后每条数据多占用若干个字节，可以通过 Engine::modified_at 获取
+    pub write_timestamp: bool,
+
+    // 获取当前时间的时钟，过期判断和写入时间都使用它，默认使用系统时间
+    pub clock: Arc<dyn Clock>,
+
+    // 是否为每个写满的数据文件建立布隆过滤器，保存在和数据文件同名的 .bloom 文件中
+    // 索引在后台加载（lazy_index）时，过滤器可以直接排除不存在的 key，不需要等待索引加载完成
+    pub enable_bloom_filters: bool,
+
+    // 布隆过滤器期望的误判率，越小占用的空间越大，需要在 0 和 1 之间
+    pub bloom_false_positive_rate: f64,
 }
 
 #[derive(Clone, PartialEq)]
 pub enum IndexType {
     // 跳表索引
     SkipList,
+
+    // BTree 索引
+    BTree,
 }
 
 impl Default for Options {
@@ -36,10 +148,41 @@ impl Default for Options {
             dir_path: std::env::temp_dir().join("bitcask-rs"),
             data_file_size: 256 * 1024 * 1024, // 256MB,
             sync_writes: false,
+            sync_mode: SyncMode::Full,
             bytes_per_sync: 0,
+            write_buffer_size: 0,
+            io_retry_count: 3,
+            io_factory: None,
+            preallocate: false,
             index_type: IndexType::SkipList,
             mmap_at_startup: false,
             data_file_merge_ratio: 0.5,
+            compression: Compression::None,
+            compression_min_size: 0,
+            checksum: Checksum::Crc32,
+            checksum_enabled: true,
+            encryption: None,
+            max_key_size: 64 * 1024,           // 64KB
+            max_value_size: 256 * 1024 * 1024, // 256MB
+            verify_checksum_on_read: true,
+            read_only: false,
+            value_cache_size: 0,
+            data_file_ext: "data".to_string(),
+            data_file_id_width: 9,
+            initial_file_id: 0,
+            #[cfg(feature = "merge-pool")]
+            merge_pool: None,
+            merge_sync_interval: 0,
+            merge_target_file_count: 0,
+            metrics: None,
+            prefix_index_len: None,
+            track_tombstones: false,
+            lazy_index: false,
+            index_snapshot_path: None,
+            write_timestamp: false,
+            clock: Arc::new(SystemClock),
+            enable_bloom_filters: false,
+            bloom_false_positive_rate: 0.01,
         }
     }
 }
@@ -47,14 +190,57 @@ impl Default for Options {
 // 索引迭代器配置项
 pub struct IteratorOptions {
     pub prefix: Vec<u8>,
+
+    // 只遍历以其中任意一个前缀开头的 key，和 prefix 同时设置时满足任意一个即可
+    // prefix 和 prefixes 都为空时遍历所有的 key
+    pub prefixes: Vec<Vec<u8>>,
     pub reverse: bool,
+
+    // 遍历的起始 key（包含），反向遍历时为上界
+    pub start: Option<Vec<u8>>,
+
+    // 遍历的结束 key（不包含），反向遍历时为下界
+    pub end: Option<Vec<u8>>,
+
+    // 自定义 key 的比较器，为 None 时按照字节序比较
+    pub comparator: Option<Comparator>,
+
+    // value 的过滤条件，只返回 value 满足条件的数据，为 None 时不过滤
+    // 需要读取 value 才能判断，只对读取 value 的遍历生效，只遍历索引时会被忽略
+    pub value_filter: Option<ValueFilter>,
+}
+
+// key 的比较器
+pub type Comparator = Arc<dyn Fn(&[u8], &[u8]) -> Ordering + Send + Sync>;
+
+// value 的过滤条件，返回 true 表示保留这条数据
+pub type ValueFilter = Arc<dyn Fn(&[u8]) -> bool + Send + Sync>;
+
+impl IteratorOptions {
+    // 设置的所有前缀，包括 prefix 以及 prefixes
+    pub(crate) fn prefix_list(&self) -> impl Iterator<Item = &Vec<u8>> {
+        std::iter::once(&self.prefix)
+            .filter(|prefix| !prefix.is_empty())
+            .chain(self.prefixes.iter())
+    }
+
+    // 判断 key 是否以任意一个前缀开头，没有设置前缀时全部满足
+    pub(crate) fn matches_prefix(&self, key: &[u8]) -> bool {
+        let mut prefixes = self.prefix_list().peekable();
+        prefixes.peek().is_none() || prefixes.any(|prefix| key.starts_with(prefix))
+    }
 }
 
 impl Default for IteratorOptions {
     fn default() -> Self {
         Self {
             prefix: Default::default(),
+            prefixes: Vec::new(),
             reverse: false,
+            start: None,
+            end: None,
+            comparator: None,
+            value_filter: None,
         }
     }
 }
@@ -77,11 +263,59 @@ impl Default for WriteBatchOptions {
     }
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum IOType {
     // 标准文件 IO
     StandardFIO,
 
-    // 内存文件映射
+    // 内存文件映射，需要启用 mmap feature，否则退化为标准文件 IO
     MemoryMap,
+
+    // 带写缓冲的标准文件 IO，参数为缓冲大小
+    BufferedFIO(usize),
+}
+
+// 数据文件持久化的方式
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SyncMode {
+    // 写入时不持久化，由操作系统决定何时写入磁盘，sync_writes 和 bytes_per_sync 不再生效
+    // 显式调用 sync、切换数据文件以及 merge 时仍然使用 fsync 持久化
+    None,
+
+    // 只持久化数据（fdatasync），不等待文件大小等元数据写入磁盘
+    DataOnly,
+
+    // 持久化数据和元数据（fsync）
+    Full,
+}
+
+// value 的加密配置，使用 AES-256-GCM 加密，key 保持明文并作为附加认证数据参与校验
+#[derive(Clone)]
+pub struct EncryptionConfig {
+    // 32 字节的密钥
+    pub key: [u8; 32],
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Compression {
+    // 不压缩
+    None = 0,
+
+    // lz4 压缩
+    Lz4 = 1,
+
+    // zstd 压缩
+    Zstd = 2,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Checksum {
+    // crc32 校验，占用 4 个字节
+    Crc32 = 0,
+
+    // xxhash64 校验，占用 8 个字节，较大的 value 计算更快
+    XxHash64 = 1,
+
+    // 不计算也不存储校验值，关闭 Options::checksum_enabled 时新建的数据文件使用
+    None = 2,
 }
@@ -0,0 +1,210 @@
+use std::fs;
+
+use fs2::FileExt;
+use log::warn;
+
+use crate::{
+    data::data_file::{get_data_file_name, DataFile, HINT_FILE_NAME, MERGE_FINISHED_FILE_NAME},
+    db::{load_data_files, Engine, FILE_LOCK_NAME},
+    error::{Errors, Result},
+    option::Options,
+    util,
+};
+
+/// 修复数据目录的结果
+#[derive(Debug, Default)]
+pub struct RepairReport {
+    // 每个数据文件的修复结果，按照文件 id 从小到大排列
+    pub files: Vec<RepairedFile>,
+}
+
+/// 单个数据文件的修复结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepairedFile {
+    // 数据文件 id
+    pub file_id: u32,
+    // 保留下来的有效数据条数
+    pub salvaged_records: usize,
+    // 损坏的位置之后被截断的字节数，为 0 时表示文件没有损坏
+    // 损坏的数据之后无法再确定每条数据的边界，因此只统计字节数
+    pub dropped_bytes: u64,
+}
+
+impl RepairReport {
+    /// 所有数据文件中保留下来的有效数据条数
+    pub fn salvaged_records(&self) -> usize {
+        self.files.iter().map(|f| f.salvaged_records).sum()
+    }
+
+    /// 是否有数据文件被截断
+    pub fn is_truncated(&self) -> bool {
+        self.files.iter().any(|f| f.dropped_bytes > 0)
+    }
+}
+
+impl Engine {
+    /// 修复数据目录，需要在数据库没有打开时调用
+    /// 逐条读取每个数据文件，保留第一条损坏的数据之前的有效数据，并在损坏的位置截断文件
+    /// 只能修复本地的数据文件，设置了 io_factory 时返回 IOFactoryUnsupported
+    pub fn repair(opts: Options) -> Result<RepairReport> {
+        if opts.io_factory.is_some() {
+            return Err(Errors::IOFactoryUnsupported);
+        }
+        let dir_path = opts.dir_path.clone();
+        if !dir_path.is_dir() {
+            return Err(Errors::FailedToReadDatabaseDir);
+        }
+
+        // 修复时持有文件锁，数据库已经打开时不能修复
+        let lock_file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(dir_path.join(FILE_LOCK_NAME))?;
+        if lock_file.try_lock_exclusive().is_err() {
+            return Err(Errors::DatabaseIsInUse);
+        }
+
+        let mut report = RepairReport::default();
+        for data_file in load_data_files(&opts)? {
+            let repaired = repair_data_file(&opts, &data_file)?;
+            report.files.push(repaired);
+        }
+
+        // hint 文件中的索引可能指向被截断的数据，删除 hint 文件和 merge 完成的标识，下次启动时从数据文件中加载索引
+        if report.is_truncated() {
+            for file_name in [HINT_FILE_NAME, MERGE_FINISHED_FILE_NAME] {
+                let file_path = dir_path.join(file_name);
+                if file_path.is_file() {
+                    fs::remove_file(file_path)?;
+                }
+            }
+            util::file::sync_dir(&dir_path)?;
+        }
+
+        lock_file.unlock()?;
+        Ok(report)
+    }
+}
+
+// 修复单个数据文件，找到第一条损坏的数据并截断
+fn repair_data_file(opts: &Options, data_file: &DataFile) -> Result<RepairedFile> {
+    let file_id = data_file.get_file_id();
+    let mut salvaged_records = 0;
+    let mut offset = data_file.header_size();
+    let corrupted = loop {
+        match data_file.read_log_record(offset) {
+            Ok(result) => {
+                salvaged_records += 1;
+                offset += result.size as u64;
+            }
+            // 读到文件末尾，文件末尾的空数据不做处理
+            Err(Errors::ReadDataFileEOF) => break false,
+            // 数据损坏，之后的数据都无法读取
+            Err(Errors::InvalidCrc { .. })
+            | Err(Errors::InvalidLogRecordCrc)
+            | Err(Errors::CorruptedRecord { .. })
+            | Err(Errors::InvalidLogRecordType(_)) => break true,
+            Err(e) => return Err(e),
+        }
+    };
+
+    // 在损坏的位置截断文件
+    let mut dropped_bytes = 0;
+    if corrupted {
+        dropped_bytes = data_file.file_size() - offset;
+        warn!(
+            "data file {} is corrupted at offset {}, truncate {} bytes",
+            file_id, offset, dropped_bytes
+        );
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .open(get_data_file_name(opts, file_id))?;
+        file.set_len(offset)?;
+        file.sync_all()?;
+    }
+
+    Ok(RepairedFile {
+        file_id,
+        salvaged_records,
+        dropped_bytes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Seek, SeekFrom, Write},
+        path::PathBuf,
+    };
+
+    use super::*;
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    #[test]
+    fn test_repair_truncate_corrupted_file() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-repair");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..100 {
+            let res = engine.put(get_test_key(i), get_test_value(i));
+            assert!(res.is_ok());
+        }
+        let pos = engine.locate(get_test_key(60)).unwrap().unwrap();
+        engine.close().expect("failed to close engine");
+        let file_size = fs::metadata(get_data_file_name(&opts, 0)).unwrap().len();
+
+        // 数据库打开时不能修复
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let res1 = Engine::repair(opts.clone());
+        assert_eq!(Errors::DatabaseIsInUse, res1.err().unwrap());
+        engine.close().expect("failed to close engine");
+
+        // 没有损坏的数据时不截断
+        let res2 = Engine::repair(opts.clone()).unwrap();
+        assert_eq!(100, res2.salvaged_records());
+        assert!(!res2.is_truncated());
+
+        // 修改第 60 条数据中的一个字节
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .open(get_data_file_name(&opts, 0))
+            .unwrap();
+        file.seek(SeekFrom::Start(pos.offset + 10)).unwrap();
+        file.write_all(b"#").unwrap();
+        drop(file);
+        let res3 = Engine::open(opts.clone());
+        assert!(res3.is_err());
+
+        // 修复之后损坏的数据以及之后的数据都被截断
+        let res4 = Engine::repair(opts.clone()).unwrap();
+        assert_eq!(
+            vec![RepairedFile {
+                file_id: 0,
+                salvaged_records: 60,
+                dropped_bytes: file_size - pos.offset,
+            }],
+            res4.files
+        );
+        let file_size = fs::metadata(get_data_file_name(&opts, 0)).unwrap().len();
+        assert_eq!(pos.offset, file_size);
+
+        // 重新打开之后可以读取保留下来的数据
+        let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(60, engine2.list_keys().unwrap().len());
+        for i in 0..60 {
+            let res5 = engine2.get(get_test_key(i));
+            assert_eq!(get_test_value(i), res5.unwrap());
+        }
+        let res6 = engine2.get(get_test_key(60));
+        assert_eq!(Errors::KeyNotFound, res6.err().unwrap());
+        let res7 = engine2.put(get_test_key(60), get_test_value(60));
+        assert!(res7.is_ok());
+        engine2.close().expect("failed to close engine");
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+}
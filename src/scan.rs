@@ -0,0 +1,346 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use bytes::Bytes;
+
+use crate::{
+    batch::{parse_log_record_key, NON_TRANSACTION_SEQ_NO},
+    data::{
+        data_file::DataFile,
+        log_record::{LogRecord, LogRecordPos, LogRecordType, ReadLogRecord},
+    },
+    db::{load_data_files, Engine},
+    error::{Errors, Result},
+    option::Options,
+};
+
+impl Engine {
+    /// 按照文件 id 从小到大依次读取数据目录中的每一条数据，返回数据所在的文件 id、偏移以及数据本身
+    /// 不使用索引，会返回被覆盖的旧数据、删除标记以及事务完成标识，用于导出数据和排查问题
+    /// 返回的 key 和 value 是数据文件中存储的原始内容，key 以事务序列号开头，可以使用 LogRecord::parse_key 解析
+    /// value 不会解压和解密
+    /// 以只读的方式打开数据文件，不持有文件锁，遇到损坏的数据时返回错误并停止读取
+    pub fn scan_raw(opts: Options) -> Result<impl Iterator<Item = Result<(u32, u64, LogRecord)>>> {
+        if !opts.dir_path.is_dir() {
+            return Err(Errors::FailedToReadDatabaseDir);
+        }
+        let mut read_opts = opts;
+        read_opts.read_only = true;
+        let data_files: BTreeMap<u32, DataFile> = load_data_files(&read_opts)?
+            .into_iter()
+            .map(|data_file| (data_file.get_file_id(), data_file))
+            .collect();
+        // 没有数据文件时直接结束
+        let file_id = data_files.keys().next().copied();
+        let mut cursor = LogCursor::new(OwnedFiles(data_files), file_id.unwrap_or_default(), 0);
+        cursor.finished = file_id.is_none();
+        Ok(cursor.map(|res| res.map(|(pos, log_record)| (pos.file_id, pos.offset, log_record))))
+    }
+
+    /// 从给定的位置开始按照写入的物理顺序读取之后的每一条数据，返回用户写入的 key、value 以及数据的位置，value 为 None 表示删除
+    /// 位置通常来自 current_position，offset 小于文件头部的长度时从文件的第一条数据开始读取
+    /// 事务中的数据在读取到事务完成的标识之后才一起返回，没有提交的事务不会返回
+    /// 读取到活跃文件的写入位置时结束；位置所在的数据文件已经被 merge 删除时返回 DataFileNotFound
+    pub fn scan_from(
+        &self,
+        file_id: u32,
+        offset: u64,
+    ) -> impl Iterator<Item = Result<(Bytes, Option<Bytes>, LogRecordPos)>> + '_ {
+        LogCursor::new(self, file_id, offset)
+            .committed()
+            .filter(|res| !matches!(res, Ok((_, log_record)) if log_record.keyspace != 0))
+            .map(|res| {
+                let (pos, log_record) = res?;
+                let key = Bytes::copy_from_slice(&log_record.key);
+                let value = match log_record.rec_type {
+                    LogRecordType::DELETED => None,
+                    _ => Some(self.decode_value(log_record)?),
+                };
+                Ok((key, value, pos))
+            })
+    }
+}
+
+// 游标读取的数据文件，按照文件 id 从小到大读取
+pub(crate) trait LogSource {
+    // 读取数据文件中给定位置的一条记录，offset 小于文件头部的长度时读取第一条记录，返回实际读取的偏移
+    fn read_at(&self, file_id: u32, offset: u64) -> Result<(u64, ReadLogRecord)>;
+
+    // 读取完一个数据文件之后继续读取的下一个数据文件，文件 id 可能不连续，没有更多的文件时返回 None
+    fn next_file_id(&self, file_id: u32) -> Option<u32>;
+}
+
+// 数据库中的数据文件，每次读取时持有活跃文件和旧的数据文件的锁，读取到活跃文件的写入位置时结束
+impl LogSource for &Engine {
+    fn read_at(&self, file_id: u32, offset: u64) -> Result<(u64, ReadLogRecord)> {
+        let active_file = self.active_file.read();
+        let older_files = self.older_files.read();
+        let data_file = match active_file.get_file_id() == file_id {
+            true => &*active_file,
+            false => older_files.get(&file_id).ok_or(Errors::DataFileNotFound)?,
+        };
+        let offset = offset.max(data_file.header_size());
+        Ok((offset, data_file.read_log_record(offset)?))
+    }
+
+    fn next_file_id(&self, file_id: u32) -> Option<u32> {
+        let active_file = self.active_file.read();
+        let older_files = self.older_files.read();
+        older_files
+            .keys()
+            .copied()
+            .filter(|id| *id > file_id)
+            .min()
+            .or(Some(active_file.get_file_id()))
+            .filter(|id| *id > file_id)
+    }
+}
+
+// 以只读方式打开的数据文件，不属于任何一个数据库实例
+struct OwnedFiles(BTreeMap<u32, DataFile>);
+
+impl LogSource for OwnedFiles {
+    fn read_at(&self, file_id: u32, offset: u64) -> Result<(u64, ReadLogRecord)> {
+        let data_file = self.0.get(&file_id).ok_or(Errors::DataFileNotFound)?;
+        let offset = offset.max(data_file.header_size());
+        Ok((offset, data_file.read_log_record(offset)?))
+    }
+
+    fn next_file_id(&self, file_id: u32) -> Option<u32> {
+        self.0.range(file_id + 1..).next().map(|(id, _)| *id)
+    }
+}
+
+// 按照写入的物理顺序逐条读取数据文件中的原始记录，遇到错误之后停止读取
+pub(crate) struct LogCursor<S> {
+    source: S,
+    file_id: u32,   // 当前读取的数据文件 id
+    offset: u64,    // 当前数据文件中下一条数据的偏移
+    finished: bool, // 是否已经读取完所有的数据文件或者遇到了错误
+}
+
+impl<S: LogSource> LogCursor<S> {
+    pub(crate) fn new(source: S, file_id: u32, offset: u64) -> Self {
+        LogCursor {
+            source,
+            file_id,
+            offset,
+            finished: false,
+        }
+    }
+
+    // 只返回已经提交的数据，key 去掉了事务序列号，事务中的数据在读取到事务完成的标识之后才一起返回
+    pub(crate) fn committed(self) -> CommittedRecords<S> {
+        CommittedRecords {
+            cursor: self,
+            transaction_records: HashMap::new(),
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl<S: LogSource> Iterator for LogCursor<S> {
+    type Item = Result<(LogRecordPos, LogRecord)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            match self.source.read_at(self.file_id, self.offset) {
+                Ok((offset, result)) => {
+                    self.offset = offset + result.size as u64;
+                    let pos = LogRecordPos {
+                        file_id: self.file_id,
+                        offset,
+                        size: result.size as u32,
+                    };
+                    return Some(Ok((pos, result.record)));
+                }
+                // 读取完一个数据文件，继续读取下一个
+                Err(Errors::ReadDataFileEOF) => match self.source.next_file_id(self.file_id) {
+                    Some(file_id) => {
+                        self.file_id = file_id;
+                        self.offset = 0;
+                    }
+                    None => self.finished = true,
+                },
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+// 在 LogCursor 的基础上暂存事务中的数据，只返回已经提交的数据
+pub(crate) struct CommittedRecords<S> {
+    cursor: LogCursor<S>,
+    transaction_records: HashMap<usize, Vec<(LogRecordPos, LogRecord)>>, // 暂存还没有读取到完成标识的事务数据
+    pending: VecDeque<(LogRecordPos, LogRecord)>, // 已经提交的事务中还没有返回的数据
+}
+
+impl<S: LogSource> Iterator for CommittedRecords<S> {
+    type Item = Result<(LogRecordPos, LogRecord)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.pending.pop_front() {
+                return Some(Ok(record));
+            }
+            let (pos, mut log_record) = match self.cursor.next()? {
+                Ok(result) => result,
+                Err(e) => return Some(Err(e)),
+            };
+            let (real_key, seq_no) = parse_log_record_key(log_record.key.clone());
+            log_record.key = real_key;
+            if seq_no == NON_TRANSACTION_SEQ_NO {
+                return Some(Ok((pos, log_record)));
+            } else if log_record.rec_type == LogRecordType::TXNFINISHED {
+                let txn_records = self.transaction_records.remove(&seq_no);
+                self.pending.extend(txn_records.unwrap_or_default());
+            } else {
+                self.transaction_records
+                    .entry(seq_no)
+                    .or_default()
+                    .push((pos, log_record));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::{
+        data::log_record::LogRecordType,
+        option::WriteBatchOptions,
+        util::rand_kv::{get_test_key, get_test_value},
+    };
+
+    #[test]
+    fn test_scan_raw() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-scan-raw");
+        opts.data_file_size = 1024;
+
+        // 数据目录不存在
+        let res1 = Engine::scan_raw(opts.clone());
+        assert_eq!(Errors::FailedToReadDatabaseDir, res1.err().unwrap());
+
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..20 {
+            let res = engine.put(get_test_key(i), get_test_value(i));
+            assert!(res.is_ok());
+        }
+        let res2 = engine.put(get_test_key(1), Bytes::from("new-value"));
+        assert!(res2.is_ok());
+        let res3 = engine.delete(get_test_key(2));
+        assert!(res3.is_ok());
+        let pos = engine.locate(get_test_key(1)).unwrap().unwrap();
+
+        // 数据库打开时同样可以读取，覆盖和删除之前的数据都会返回
+        let records: Vec<_> = Engine::scan_raw(opts.clone())
+            .unwrap()
+            .map(|res| res.unwrap())
+            .collect();
+        assert_eq!(22, records.len());
+        assert!(records
+            .windows(2)
+            .all(|w| (w[0].0, w[0].1) < (w[1].0, w[1].1)));
+
+        let key1: Vec<_> = records
+            .iter()
+            .filter(|(_, _, record)| record.parse_key().0 == get_test_key(1))
+            .collect();
+        assert_eq!(2, key1.len());
+        assert_eq!(get_test_value(1).as_ref(), key1[0].2.value());
+        assert_eq!(b"new-value", key1[1].2.value());
+        assert_eq!((pos.file_id(), pos.offset()), (key1[1].0, key1[1].1));
+
+        let last = &records[21].2;
+        assert_eq!(LogRecordType::DELETED, last.rec_type());
+        assert_eq!((get_test_key(2).to_vec(), 0), last.parse_key());
+        engine.close().expect("failed to close engine");
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+
+    #[test]
+    fn test_scan_from() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-scan-from");
+        opts.data_file_size = 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..20 {
+            let res = engine.put(get_test_key(i), get_test_value(i));
+            assert!(res.is_ok());
+        }
+
+        // 1.从头开始读取所有的数据
+        let entries: Vec<_> = engine.scan_from(0, 0).map(|res| res.unwrap()).collect();
+        assert_eq!(20, entries.len());
+        assert_eq!(get_test_key(0), entries[0].0);
+        assert_eq!(Some(get_test_value(19)), entries[19].1);
+
+        // 2.只返回游标之后写入的数据，跨越多个数据文件
+        let (file_id, offset) = engine.current_position().unwrap();
+        assert_eq!(0, engine.scan_from(file_id, offset).count());
+        for i in 20..60 {
+            let res = engine.put(get_test_key(i), get_test_value(i));
+            assert!(res.is_ok());
+        }
+        let res1 = engine.delete(get_test_key(0));
+        assert!(res1.is_ok());
+        let wb = engine
+            .new_write_batch(WriteBatchOptions::default())
+            .expect("failed to create write batch");
+        assert!(wb.put(get_test_key(100), get_test_value(100)).is_ok());
+        assert!(wb.delete(get_test_key(1)).is_ok());
+        assert!(wb.commit().is_ok());
+        assert!(engine.active_file.read().get_file_id() > file_id);
+
+        let entries: Vec<_> = engine
+            .scan_from(file_id, offset)
+            .map(|res| res.unwrap())
+            .collect();
+        assert_eq!(43, entries.len());
+        for (i, (key, value, _)) in entries[..40].iter().enumerate() {
+            assert_eq!(get_test_key(i + 20), *key);
+            assert_eq!(Some(get_test_value(i + 20)), *value);
+        }
+        assert_eq!(
+            (get_test_key(0), None),
+            (entries[40].0.clone(), entries[40].1.clone())
+        );
+        let txn_keys: Vec<_> = entries[41..].iter().map(|e| e.0.clone()).collect();
+        assert!(txn_keys.contains(&get_test_key(100)));
+        assert!(txn_keys.contains(&get_test_key(1)));
+
+        // 返回的位置按照写入顺序递增，可以直接读取对应的数据
+        assert!(entries[..41]
+            .windows(2)
+            .all(|w| (w[0].2.file_id(), w[0].2.offset()) < (w[1].2.file_id(), w[1].2.offset())));
+        let pos = engine.locate(get_test_key(59)).unwrap().unwrap();
+        assert_eq!(
+            (pos.file_id(), pos.offset()),
+            (entries[39].2.file_id(), entries[39].2.offset())
+        );
+
+        // 3.游标所在的文件不存在
+        let mut iter = engine.scan_from(file_id + 1000, 0);
+        assert_eq!(
+            Errors::DataFileNotFound,
+            iter.next().unwrap().err().unwrap()
+        );
+        assert!(iter.next().is_none());
+        drop(iter);
+        engine.close().expect("failed to close engine");
+
+        // 删除测试的文件夹
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+}
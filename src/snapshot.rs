@@ -0,0 +1,273 @@
+use std::{collections::HashMap, sync::Arc};
+
+use bytes::Bytes;
+use parking_lot::RwLock;
+
+use crate::{
+    data::log_record::LogRecordPos,
+    db::Engine,
+    error::{Errors, Result},
+    index::{btree::BTree, Index},
+    iterator::Entries,
+    option::IteratorOptions,
+};
+
+// 创建快照之后被修改的 key 在第一次修改之前的索引位置，None 表示创建快照时 key 不存在
+pub(crate) type SnapshotOverlay = Arc<RwLock<HashMap<Vec<u8>, Option<LogRecordPos>>>>;
+
+/// 数据库在某一时刻的只读快照
+/// 创建快照时不拷贝内存索引，之后修改索引之前会先在快照中记录 key 原来的位置，
+/// 因此之后的写入、删除以及 merge 都不会影响快照中读取到的数据；快照存在期间被修改的 key 越多，占用的内存越多
+/// 被覆盖或者删除的旧数据仍然保存在数据文件中，merge 之后旧的数据文件也只会在下一次打开数据库时删除，
+/// 而快照借用了引擎实例，存在快照时无法关闭数据库，也无法清空数据库，因此快照引用的数据文件始终可以读取
+pub struct Snapshot<'a> {
+    overlay: SnapshotOverlay, // 创建快照之后被修改的 key 原来的位置
+    engine: &'a Engine,
+}
+
+impl Engine {
+    /// 创建当前数据库的快照，只需要注册快照，不会拷贝内存索引
+    pub fn snapshot(&self) -> Result<Snapshot<'_>> {
+        self.wait_index_ready()?;
+        // 持有写锁注册快照，快照不会包含只更新了一部分索引的批量写入
+        let _lock = self.write_lock.write();
+        let overlay = SnapshotOverlay::default();
+        self.snapshots.write().push(overlay.clone());
+        Ok(Snapshot {
+            overlay,
+            engine: self,
+        })
+    }
+
+    // 更新内存索引，存在快照时先在快照中记录 key 原来的位置
+    pub(crate) fn index_put(&self, key: Vec<u8>, pos: LogRecordPos) -> Option<LogRecordPos> {
+        let snapshots = self.snapshots.read();
+        self.preserve_for_snapshots(&snapshots, &key);
+        self.index.put(key, pos)
+    }
+
+    // 删除内存索引，存在快照时先在快照中记录 key 原来的位置
+    pub(crate) fn index_delete(&self, key: Vec<u8>) -> Option<LogRecordPos> {
+        let snapshots = self.snapshots.read();
+        self.preserve_for_snapshots(&snapshots, &key);
+        self.index.delete(key)
+    }
+
+    // 修改索引之前调用，快照中已经记录过的 key 保留第一次修改之前的位置
+    fn preserve_for_snapshots(&self, snapshots: &[SnapshotOverlay], key: &[u8]) {
+        if snapshots.is_empty() {
+            return;
+        }
+        let pos = self.index.get(key.to_vec());
+        for overlay in snapshots {
+            overlay.write().entry(key.to_vec()).or_insert(pos);
+        }
+    }
+}
+
+impl Drop for Snapshot<'_> {
+    fn drop(&mut self) {
+        self.engine
+            .snapshots
+            .write()
+            .retain(|overlay| !Arc::ptr_eq(overlay, &self.overlay));
+    }
+}
+
+impl<'a> Snapshot<'a> {
+    /// 根据 key 获取快照中对应的数据
+    pub fn get(&self, key: Bytes) -> Result<Bytes> {
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+        // 先读取当前的索引再读取快照中记录的位置，修改索引之前已经记录了原来的位置，不会读到之后写入的数据
+        let current = self.engine.index.get(key.to_vec());
+        let pos = match self.overlay.read().get(key.as_ref()) {
+            Some(pos) => *pos,
+            None => current,
+        };
+        match pos {
+            Some(pos) => self.engine.get_value_by_position(&pos),
+            None => Err(Errors::KeyNotFound),
+        }
+    }
+
+    /// 获取快照中所有的 key
+    pub fn list_keys(&self) -> Result<Vec<Bytes>> {
+        self.materialize().list_keys()
+    }
+
+    /// 遍历快照中的 key/value，读取出错时返回对应的错误
+    /// 遍历之前需要合并当前的索引和快照中记录的位置，得到创建快照时的索引
+    pub fn entries(&self, options: IteratorOptions) -> Entries<'a> {
+        let value_filter = options.value_filter.clone();
+        Entries::new(
+            self.materialize().iterator(options),
+            self.engine,
+            value_filter,
+        )
+    }
+
+    // 合并当前的索引和快照中记录的位置，遍历完当前的索引之后再读取快照中记录的位置，不会漏掉并发的修改
+    fn materialize(&self) -> BTree<LogRecordPos> {
+        let index = BTree::new();
+        let mut index_iter = self.engine.index.iterator(IteratorOptions::default());
+        while let Some((key, pos)) = index_iter.next() {
+            index.put(key.clone(), *pos);
+        }
+        for (key, pos) in self.overlay.read().iter() {
+            match pos {
+                Some(pos) => index.put(key.clone(), *pos),
+                None => index.delete(key.clone()),
+            };
+        }
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{
+        merge::get_merge_path,
+        option::Options,
+        util::rand_kv::{get_test_key, get_test_value},
+    };
+
+    #[test]
+    fn test_snapshot_during_merge() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-snapshot-merge");
+        opts.data_file_size = 32 * 1024;
+        opts.data_file_merge_ratio = 0 as f32;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..1000 {
+            let res = engine.put(get_test_key(i), get_test_value(i));
+            assert!(res.is_ok());
+        }
+
+        let snapshot = engine.snapshot().unwrap();
+
+        // 创建快照之后修改、删除数据并且进行 merge
+        for i in 0..500 {
+            let res1 = engine.put(get_test_key(i), Bytes::from("new value"));
+            assert!(res1.is_ok());
+        }
+        for i in 500..800 {
+            let res2 = engine.delete(get_test_key(i));
+            assert!(res2.is_ok());
+        }
+        let res3 = engine.put(get_test_key(2000), get_test_value(2000));
+        assert!(res3.is_ok());
+        let res4 = engine.merge();
+        assert!(res4.is_ok());
+
+        // 快照中仍然是旧的数据
+        assert_eq!(1000, snapshot.list_keys().unwrap().len());
+        for i in 0..1000 {
+            let res5 = snapshot.get(get_test_key(i));
+            assert_eq!(get_test_value(i), res5.unwrap());
+        }
+        let res6 = snapshot.get(get_test_key(2000));
+        assert_eq!(Errors::KeyNotFound, res6.err().unwrap());
+        let entries = snapshot.entries(IteratorOptions::default());
+        let mut count = 0;
+        for (i, entry) in entries.enumerate() {
+            let (key, value) = entry.unwrap();
+            assert_eq!(get_test_key(i), key);
+            assert_eq!(get_test_value(i), value);
+            count += 1;
+        }
+        assert_eq!(1000, count);
+
+        // 数据库中是新的数据
+        assert_eq!(
+            Bytes::from("new value"),
+            engine.get(get_test_key(0)).unwrap()
+        );
+        assert_eq!(
+            Errors::KeyNotFound,
+            engine.get(get_test_key(600)).err().unwrap()
+        );
+        assert_eq!(701, engine.list_keys().unwrap().len());
+
+        // 删除测试的文件夹
+        std::mem::drop(snapshot);
+        std::mem::drop(engine);
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+        std::fs::remove_dir_all(get_merge_path(opts.dir_path)).expect("failed to remove path");
+    }
+
+    #[test]
+    fn test_snapshot_records_modified_keys() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-snapshot-overlay");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..1000 {
+            let res = engine.put(get_test_key(i), get_test_value(i));
+            assert!(res.is_ok());
+        }
+
+        // 1.创建快照时不拷贝索引，只记录之后被修改的 key
+        let snapshot1 = engine.snapshot().unwrap();
+        assert!(snapshot1.overlay.read().is_empty());
+        for i in 0..10 {
+            let res1 = engine.put(get_test_key(i), Bytes::from("new value"));
+            assert!(res1.is_ok());
+            let res2 = engine.put(get_test_key(i), Bytes::from("newer value"));
+            assert!(res2.is_ok());
+        }
+        for i in 10..15 {
+            let res3 = engine.delete(get_test_key(i));
+            assert!(res3.is_ok());
+        }
+        for i in 1000..1003 {
+            let res4 = engine.put(get_test_key(i), get_test_value(i));
+            assert!(res4.is_ok());
+        }
+        assert_eq!(18, snapshot1.overlay.read().len());
+
+        // 2.之后创建的快照从创建时的状态开始记录
+        let snapshot2 = engine.snapshot().unwrap();
+        let res5 = engine.delete(get_test_key(0));
+        assert!(res5.is_ok());
+        assert_eq!(1, snapshot2.overlay.read().len());
+        assert_eq!(get_test_value(0), snapshot1.get(get_test_key(0)).unwrap());
+        assert_eq!(
+            Bytes::from("newer value"),
+            snapshot2.get(get_test_key(0)).unwrap()
+        );
+        assert_eq!(
+            Errors::KeyNotFound,
+            snapshot1.get(get_test_key(1000)).err().unwrap()
+        );
+        assert_eq!(
+            get_test_value(1000),
+            snapshot2.get(get_test_key(1000)).unwrap()
+        );
+        assert_eq!(1000, snapshot1.list_keys().unwrap().len());
+        assert_eq!(998, snapshot2.list_keys().unwrap().len());
+        let entries: Vec<_> = snapshot1
+            .entries(IteratorOptions::default())
+            .map(|entry| entry.unwrap())
+            .collect();
+        let expected: Vec<_> = (0..1000)
+            .map(|i| (get_test_key(i), get_test_value(i)))
+            .collect();
+        assert_eq!(expected, entries);
+
+        // 3.释放快照之后不再记录
+        std::mem::drop(snapshot1);
+        std::mem::drop(snapshot2);
+        assert!(engine.snapshots.read().is_empty());
+        let res6 = engine.put(get_test_key(1), get_test_value(1));
+        assert!(res6.is_ok());
+
+        // 删除测试的文件夹
+        std::mem::drop(engine);
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove path");
+    }
+}
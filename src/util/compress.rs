@@ -0,0 +1,62 @@
+use log::error;
+
+use crate::{
+    error::{Errors, Result},
+    option::Compression,
+};
+
+// zstd 的压缩级别
+const ZSTD_COMPRESSION_LEVEL: i32 = 3;
+
+// 按照指定的压缩算法压缩数据
+pub fn compress(compression: Compression, data: &[u8]) -> Result<Vec<u8>> {
+    match compression {
+        Compression::None => Ok(data.to_vec()),
+        Compression::Lz4 => Ok(lz4_flex::compress_prepend_size(data)),
+        Compression::Zstd => zstd::encode_all(data, ZSTD_COMPRESSION_LEVEL).map_err(|e| {
+            error!("failed to compress value: {}", e);
+            Errors::FailedToCompressValue
+        }),
+    }
+}
+
+// 按照指定的压缩算法解压数据
+pub fn decompress(compression: Compression, data: Vec<u8>) -> Result<Vec<u8>> {
+    match compression {
+        Compression::None => Ok(data),
+        Compression::Lz4 => lz4_flex::decompress_size_prepended(&data).map_err(|e| {
+            error!("failed to decompress value: {}", e);
+            Errors::FailedToDecompressValue
+        }),
+        Compression::Zstd => zstd::decode_all(data.as_slice()).map_err(|e| {
+            error!("failed to decompress value: {}", e);
+            Errors::FailedToDecompressValue
+        }),
+    }
+}
+
+#[test]
+fn test_compress_and_decompress() {
+    let data = "bitcask-rs-value-value-value-value-value-value-value".repeat(100);
+    for compression in [Compression::None, Compression::Lz4, Compression::Zstd] {
+        let enc = compress(compression, data.as_bytes()).unwrap();
+        if compression != Compression::None {
+            assert!(enc.len() < data.len());
+        }
+        let dec = decompress(compression, enc).unwrap();
+        assert_eq!(data.as_bytes(), dec.as_slice());
+    }
+
+    // 空数据
+    for compression in [Compression::None, Compression::Lz4, Compression::Zstd] {
+        let enc = compress(compression, &[]).unwrap();
+        let dec = decompress(compression, enc).unwrap();
+        assert!(dec.is_empty());
+    }
+
+    // 无效的压缩数据
+    let res1 = decompress(Compression::Zstd, b"invalid compressed data".to_vec());
+    assert_eq!(Errors::FailedToDecompressValue, res1.err().unwrap());
+    let res2 = decompress(Compression::Lz4, b"\x10\x00\x00\x00\xff\xff".to_vec());
+    assert_eq!(Errors::FailedToDecompressValue, res2.err().unwrap());
+}
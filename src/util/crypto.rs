@@ -0,0 +1,79 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use log::error;
+
+use crate::error::{Errors, Result};
+
+// AES-GCM nonce 的长度，存储在加密数据的最前面
+const NONCE_SIZE: usize = 12;
+
+// 使用 AES-256-GCM 加密数据，每次随机生成 nonce，返回 nonce + 密文
+// aad 作为附加认证数据参与校验但不加密，解密时需要传入相同的 aad
+pub fn encrypt(key: &[u8; 32], data: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let payload = Payload { msg: data, aad };
+    let ciphertext = cipher.encrypt(&nonce, payload).map_err(|e| {
+        error!("failed to encrypt value: {}", e);
+        Errors::FailedToEncryptValue
+    })?;
+
+    let mut buf = Vec::with_capacity(NONCE_SIZE + ciphertext.len());
+    buf.extend_from_slice(&nonce);
+    buf.extend_from_slice(&ciphertext);
+    Ok(buf)
+}
+
+// 解密数据，密钥错误、aad 不一致或者数据被篡改时返回 DecryptionFailed
+pub fn decrypt(key: &[u8; 32], data: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    if data.len() < NONCE_SIZE {
+        return Err(Errors::DecryptionFailed);
+    }
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let (nonce, ciphertext) = data.split_at(NONCE_SIZE);
+    cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad,
+            },
+        )
+        .map_err(|_| Errors::DecryptionFailed)
+}
+
+#[test]
+fn test_encrypt_and_decrypt() {
+    let key = [7u8; 32];
+    let data = "bitcask-rs-value-value-value-value-value-value-value".repeat(10);
+    let enc1 = encrypt(&key, data.as_bytes(), b"key").unwrap();
+    assert_ne!(data.as_bytes(), &enc1[NONCE_SIZE..NONCE_SIZE + data.len()]);
+    let dec1 = decrypt(&key, &enc1, b"key").unwrap();
+    assert_eq!(data.as_bytes(), dec1.as_slice());
+
+    // 每次加密使用不同的 nonce
+    let enc2 = encrypt(&key, data.as_bytes(), b"key").unwrap();
+    assert_ne!(enc1, enc2);
+
+    // 密钥错误
+    let res1 = decrypt(&[8u8; 32], &enc1, b"key");
+    assert_eq!(Errors::DecryptionFailed, res1.err().unwrap());
+
+    // aad 不一致
+    let res2 = decrypt(&key, &enc1, b"other-key");
+    assert_eq!(Errors::DecryptionFailed, res2.err().unwrap());
+
+    // 数据被篡改或者长度不足
+    let mut enc3 = enc1.clone();
+    enc3[NONCE_SIZE] ^= 0xFF;
+    assert_eq!(
+        Errors::DecryptionFailed,
+        decrypt(&key, &enc3, b"key").err().unwrap()
+    );
+    assert_eq!(
+        Errors::DecryptionFailed,
+        decrypt(&key, &enc1[..4], b"key").err().unwrap()
+    );
+}
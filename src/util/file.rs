@@ -1,4 +1,18 @@
-use std::{fs, io, path::PathBuf};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::Result,
+    option::{IOType, Options},
+};
+
+#[cfg(test)]
+thread_local! {
+    // 测试中记录当前线程持久化目录的次数
+    static DIR_SYNC_COUNT: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
 
 // 获取磁盘剩余空间容量
 pub fn available_disk_size() -> u64 {
@@ -16,6 +30,25 @@ pub fn dir_disk_size(dir_path: PathBuf) -> u64 {
     0
 }
 
+// 持久化目录，保证目录中新建的文件在断电之后仍然存在
+// 只有 Unix 平台支持打开目录并 fsync，其他平台直接返回
+pub fn sync_dir(dir_path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    fs::File::open(dir_path)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = dir_path;
+
+    #[cfg(test)]
+    DIR_SYNC_COUNT.with(|count| count.set(count.get() + 1));
+    Ok(())
+}
+
+// 获取当前线程持久化目录的次数
+#[cfg(test)]
+pub(crate) fn dir_sync_count() -> usize {
+    DIR_SYNC_COUNT.with(|count| count.get())
+}
+
 // 拷贝数据目录
 pub fn copy_dir(src: PathBuf, dest: PathBuf, exclude: &[&str]) -> io::Result<()> {
     if !dest.exists() {
@@ -40,6 +73,80 @@ pub fn copy_dir(src: PathBuf, dest: PathBuf, exclude: &[&str]) -> io::Result<()>
     Ok(())
 }
 
+// 列出目录中所有文件的文件名，设置了 io_factory 时从它的存储中列出
+pub(crate) fn list_file_names(options: &Options, dir_path: &Path) -> Result<Vec<String>> {
+    if let Some(io_factory) = &options.io_factory {
+        return io_factory.list(dir_path);
+    }
+    let mut file_names = Vec::new();
+    for entry in fs::read_dir(dir_path)? {
+        file_names.push(entry?.file_name().to_string_lossy().into_owned());
+    }
+    Ok(file_names)
+}
+
+// 获取文件的大小
+pub(crate) fn file_size(options: &Options, file_name: &Path) -> Result<u64> {
+    match &options.io_factory {
+        Some(io_factory) => Ok(io_factory
+            .open(file_name.to_path_buf(), IOType::StandardFIO)
+            .size()),
+        None => Ok(fs::metadata(file_name)?.len()),
+    }
+}
+
+// 判断文件是否存在
+pub(crate) fn is_file(options: &Options, file_name: &Path) -> bool {
+    match &options.io_factory {
+        Some(io_factory) => io_factory.is_file(file_name),
+        None => file_name.is_file(),
+    }
+}
+
+// 重命名文件
+pub(crate) fn rename_file(options: &Options, src: &Path, dest: &Path) -> Result<()> {
+    match &options.io_factory {
+        Some(io_factory) => io_factory.rename(src, dest),
+        None => Ok(fs::rename(src, dest)?),
+    }
+}
+
+// 删除文件，文件不存在时不做处理
+pub(crate) fn remove_file(options: &Options, file_name: &Path) -> Result<()> {
+    if !is_file(options, file_name) {
+        return Ok(());
+    }
+    match &options.io_factory {
+        Some(io_factory) => io_factory.remove(file_name),
+        None => Ok(fs::remove_file(file_name)?),
+    }
+}
+
+// 删除目录以及其中的所有文件，设置了 io_factory 时先删除它的存储中的文件
+pub(crate) fn remove_dir(options: &Options, dir_path: &Path) -> Result<()> {
+    if let Some(io_factory) = &options.io_factory {
+        for file_name in io_factory.list(dir_path)? {
+            io_factory.remove(&dir_path.join(file_name))?;
+        }
+    }
+    if dir_path.is_dir() {
+        fs::remove_dir_all(dir_path)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_sync_dir() {
+    let count = dir_sync_count();
+    assert!(sync_dir(&std::env::temp_dir()).is_ok());
+    assert_eq!(count + 1, dir_sync_count());
+
+    // 目录不存在
+    let res = sync_dir(Path::new("/tmp/bitcask-rs-sync-dir-not-exist"));
+    assert!(res.is_err());
+    assert_eq!(count + 1, dir_sync_count());
+}
+
 #[test]
 fn test_available_disk_size() {
     let size = available_disk_size();
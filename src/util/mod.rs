@@ -1,2 +1,5 @@
+pub mod compress;
+pub mod crypto;
 pub mod file;
 pub mod rand_kv;
+pub mod time;
@@ -0,0 +1,17 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// 获取当前的 unix 毫秒时间戳
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[test]
+fn test_now_millis() {
+    let now1 = now_millis();
+    assert!(now1 > 0);
+    let now2 = now_millis();
+    assert!(now2 >= now1);
+}